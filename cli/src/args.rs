@@ -4,21 +4,69 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
-use clap::{Parser, Subcommand};
+use clap::{ArgEnum, Parser, Subcommand};
+use pfp::passwords::{MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH};
 
 /// PfP: Pain-free Passwords, command line edition
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[clap(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "EXIT STATUS:\n    0    Success\n    1    General failure\n    2    Wrong primary password\n    3    Requested password or site doesn't exist\n    4    Storage file is missing, unreadable or not initialized"
+)]
 pub struct Args {
     /// Data storage file path
     #[clap(parse(from_os_str), short = 'c', long)]
     pub storage: Option<std::path::PathBuf>,
+    /// Use the storage file of the named profile in the configuration directory, ignored if a
+    /// storage file path is given
+    #[clap(long, value_name = "NAME", validator = validate_profile)]
+    pub profile: Option<String>,
+    /// Read storage data from stdin instead of a file and write it to stdout when changed, the
+    /// primary password is read from the terminal
+    #[clap(
+        long,
+        conflicts_with_all = &["storage", "profile", "stdin-passwords", "stdin-primary-once"]
+    )]
+    pub stdin_storage: bool,
+    /// Number of primary password attempts before giving up (0 for no limit)
+    #[clap(long, default_value_t = 3, value_name = "COUNT")]
+    pub max_attempts: u32,
+    /// Delay in milliseconds after a wrong primary password
+    #[clap(long, default_value_t = 0, value_name = "MS")]
+    pub retry_delay: u64,
+    /// Open storage files written in a newer format read-only instead of failing
+    #[clap(long)]
+    pub allow_newer: bool,
+    /// Accept storage entries with duplicate fields, using the last value
+    #[clap(long)]
+    pub lenient: bool,
+    /// Ask for the primary password again before showing password values
+    #[clap(long)]
+    pub confirm_show: bool,
+    /// Read the primary password from the first line of stdin, prompt for everything else
+    #[clap(long)]
+    pub stdin_primary_once: bool,
+    /// Take the primary password from the PFP_PRIMARY_PASSWORD environment variable if set (the
+    /// variable is cleared, on Windows its value might stay in the process environment block)
+    #[clap(long)]
+    pub allow_env_password: bool,
     /// Integration tests only: read passwords from stdin
     #[clap(long, hide = true)]
     pub stdin_passwords: bool,
     /// Integration tests only: lock passwords and wait when done
     #[clap(long, hide = true)]
     pub wait: bool,
+    /// Integration tests only: write all allocations and their contents to a file on exit
+    /// (requires a build with logging_alloc enabled)
+    #[clap(parse(from_os_str), long, hide = true, value_name = "PATH")]
+    pub dump_memory_on_exit: Option<std::path::PathBuf>,
+    /// Integration tests only: use this time (seconds since the Unix epoch) instead of the
+    /// system clock
+    #[clap(long, hide = true, value_name = "SECONDS")]
+    pub fixed_time: Option<u64>,
     #[clap(subcommand)]
     pub command: Commands,
 }
@@ -30,6 +78,25 @@ pub enum Commands {
         /// Do not prompt before overwriting data
         #[clap(short = 'y', long)]
         assume_yes: bool,
+        /// Key derivation function for the storage encryption key
+        #[clap(long, arg_enum, default_value = "scrypt", value_name = "ALGORITHM")]
+        kdf: KdfAlgorithm,
+    },
+    /// Changes the primary password while keeping existing passwords
+    ChangePrimary {
+        /// Do not prompt before changing generated passwords
+        #[clap(short = 'y', long)]
+        assume_yes: bool,
+    },
+    /// Changes the key derivation cost for the storage encryption key, re-encrypting all data
+    SetKdf {
+        /// Key derivation function to use
+        #[clap(long, arg_enum, default_value = "scrypt", value_name = "ALGORITHM")]
+        kdf: KdfAlgorithm,
+        /// Number of iterations, for scrypt a power of two (default is the algorithm's
+        /// recommended value)
+        #[clap(short = 'i', long, value_name = "COUNT")]
+        iterations: Option<u64>,
     },
     /// Adds a generated password to the storage
     Add {
@@ -40,9 +107,9 @@ pub enum Commands {
         /// Password revision
         #[clap(short = 'r', long, default_value = "1")]
         revision: String,
-        /// Password length
-        #[clap(short = 'l', long, default_value_t = 16, validator = validate_length)]
-        length: usize,
+        /// Password length [default: 16, adjusted to the site's policy]
+        #[clap(short = 'l', long, validator = validate_length)]
+        length: Option<usize>,
         /// Do not include lower-case letters
         #[clap(short = 'w', long)]
         no_lower: bool,
@@ -55,6 +122,9 @@ pub enum Commands {
         /// Do not include symbols
         #[clap(short = 's', long)]
         no_symbol: bool,
+        /// Use only these symbols instead of the default symbol set
+        #[clap(long, conflicts_with = "no-symbol")]
+        symbols: Option<String>,
         /// Do not prompt before overwriting existing passwords
         #[clap(short = 'y', long)]
         assume_yes: bool,
@@ -71,30 +141,185 @@ pub enum Commands {
         /// Use a recovery code
         #[clap(short = 'c', long)]
         recovery: bool,
+        /// Read the recovery code from this file instead of prompting for it
+        #[clap(
+            parse(from_os_str),
+            long,
+            value_name = "FILE",
+            conflicts_with = "recovery"
+        )]
+        recovery_file: Option<std::path::PathBuf>,
+        /// Store the password verbatim, by default leading and trailing whitespace is removed
+        #[clap(long, conflicts_with_all = &["recovery", "recovery-file"])]
+        no_trim: bool,
+        /// Keep the previous value in the password history when overwriting a stored password
+        #[clap(long)]
+        keep_history: bool,
         /// Do not prompt before overwriting existing passwords
         #[clap(short = 'y', long)]
         assume_yes: bool,
     },
-    /// Removes a password from the storage
+    /// Removes a password from the storage, or all matching passwords of a website
     Remove {
         /// Website name to generate password for
         domain: String,
+        /// User name associated with the account (can be a wildcard pattern)
+        name: String,
+        /// Password revision [default: 1, all revisions if name is a wildcard pattern]
+        #[clap(short = 'r', long)]
+        revision: Option<String>,
+        /// Do not prompt before removing passwords matching a wildcard pattern
+        #[clap(short = 'y', long)]
+        assume_yes: bool,
+    },
+    /// Copies a password to a new name, revision or website
+    Duplicate {
+        /// Website name of the password to be copied
+        domain: String,
         /// User name associated with the account
         name: String,
+        /// User name for the copy
+        new_name: String,
         /// Password revision
         #[clap(short = 'r', long, default_value = "1")]
         revision: String,
+        /// Website name for the copy (if different)
+        #[clap(long)]
+        new_domain: Option<String>,
+        /// Password revision for the copy
+        #[clap(long, default_value = "1")]
+        new_revision: String,
+        /// Do not prompt before overwriting existing passwords
+        #[clap(short = 'y', long)]
+        assume_yes: bool,
+    },
+    /// Moves a password to a different website, generated passwords keep their parameters
+    Move {
+        /// Website name of the password
+        domain: String,
+        /// User name associated with the account
+        name: String,
+        /// New website name
+        new_domain: String,
+        /// Password revision
+        #[clap(short = 'r', long, default_value = "1")]
+        revision: String,
+        /// Do not prompt before changing generated passwords or overwriting existing passwords
+        #[clap(short = 'y', long)]
+        assume_yes: bool,
+    },
+    /// Changes the name or revision of a password, generated passwords keep their parameters
+    Rename {
+        /// Website name of the password
+        domain: String,
+        /// User name associated with the account
+        name: String,
+        /// New user name
+        new_name: String,
+        /// Password revision
+        #[clap(short = 'r', long, default_value = "1")]
+        revision: String,
+        /// New password revision [default: unchanged]
+        #[clap(long)]
+        new_revision: Option<String>,
+        /// Do not prompt before overwriting existing passwords or changing the value of a
+        /// generated password
+        #[clap(short = 'y', long)]
+        assume_yes: bool,
+    },
+    /// Changes the length of a generated password, keeping its other parameters
+    ChangeLength {
+        /// Website name of the password
+        domain: String,
+        /// User name associated with the account
+        name: String,
+        /// New password length
+        #[clap(validator = validate_length)]
+        length: usize,
+        /// Password revision
+        #[clap(short = 'r', long, default_value = "1")]
+        revision: String,
+    },
+    /// Changes the allowed characters of a generated password, keeping its other parameters
+    ChangeCharset {
+        /// Website name of the password
+        domain: String,
+        /// User name associated with the account
+        name: String,
+        /// Password revision
+        #[clap(short = 'r', long, default_value = "1")]
+        revision: String,
+        /// Do not include lower-case letters
+        #[clap(short = 'w', long)]
+        no_lower: bool,
+        /// Do not include upper-case letters
+        #[clap(short = 'u', long)]
+        no_upper: bool,
+        /// Do not include digits
+        #[clap(short = 'd', long)]
+        no_digit: bool,
+        /// Do not include symbols
+        #[clap(short = 's', long)]
+        no_symbol: bool,
+    },
+    /// Replaces a generated password by a new one at the next revision
+    Rotate {
+        /// Website name of the password
+        domain: String,
+        /// User name associated with the account
+        name: String,
+        /// Remove the old password
+        #[clap(long)]
+        replace: bool,
     },
     /// Retrieves a password and displays it
     Show {
-        /// Website name to generate password for
+        /// Website name to generate password for, alternatively a locator like site/name@revision
+        domain: String,
+        /// User name associated with the account (omit if a locator is given)
+        name: Option<String>,
+        /// Password revision [default: 1]
+        #[clap(short = 'r', long)]
+        revision: Option<String>,
+        /// Output the password as a QR code
+        #[clap(short = 'q', long)]
+        qrcode: bool,
+        /// Erase the password from the terminal after this many seconds (terminal scrollback
+        /// might still retain it, copying to clipboard is more secure)
+        #[clap(long, value_name = "SECS")]
+        reveal_after: Option<u64>,
+        /// Copy the password to the clipboard instead of displaying it
+        #[clap(short = 'C', long, conflicts_with_all = &["qrcode", "reveal-after"])]
+        clipboard: bool,
+        /// Clear the clipboard after this many seconds, the command waits until then (press Ctrl-C to
+        /// clear the clipboard early)
+        #[clap(
+            long,
+            value_name = "SECS",
+            default_value = "15",
+            requires = "clipboard"
+        )]
+        clear_after: u64,
+        /// Write the password to this file (on Unix readable only by the current user) instead
+        #[clap(parse(from_os_str), short = 'o', long, value_name = "FILE", conflicts_with_all = &["qrcode", "reveal-after", "clipboard"])]
+        output: Option<std::path::PathBuf>,
+        /// Overwrite the file given by --output if it exists
+        #[clap(long, requires = "output")]
+        force: bool,
+        /// Field to show: the password itself, its notes or the recovery code of a stored password
+        #[clap(long, arg_enum, default_value = "value", value_name = "FIELD")]
+        field: ShowField,
+    },
+    /// Displays the recovery code for a stored password
+    Recovery {
+        /// Website name of the password
         domain: String,
         /// User name associated with the account
         name: String,
         /// Password revision
         #[clap(short = 'r', long, default_value = "1")]
         revision: String,
-        /// Output the password as a QR code
+        /// Output the recovery code as a QR code
         #[clap(short = 'q', long)]
         qrcode: bool,
     },
@@ -110,6 +335,53 @@ pub enum Commands {
         /// Set notes for this password
         #[clap(short = 's', long)]
         set: bool,
+        /// Set notes for this password to the contents of a file, these can span multiple lines
+        #[clap(parse(from_os_str), long, value_name = "FILE", conflicts_with_all = &["set", "set-stdin"])]
+        set_from_file: Option<std::path::PathBuf>,
+        /// Set notes for this password to everything read from stdin until end of input
+        #[clap(long, conflicts_with = "set")]
+        set_stdin: bool,
+        /// Copy the notes to the clipboard instead of displaying them
+        #[clap(long)]
+        clipboard: bool,
+        /// Clear the clipboard after this many seconds, the command waits until then (press Ctrl-C to
+        /// clear the clipboard early)
+        #[clap(
+            long,
+            value_name = "SECS",
+            default_value = "15",
+            requires = "clipboard"
+        )]
+        clear_after: u64,
+    },
+    /// Lists previous values of a stored password or restores one of them
+    History {
+        /// Website name to generate password for
+        domain: String,
+        /// User name associated with the account
+        name: String,
+        /// Password revision
+        #[clap(short = 'r', long, default_value = "1")]
+        revision: String,
+        /// Restore the previous value with this number from the list
+        #[clap(long, value_name = "NUMBER")]
+        restore: Option<usize>,
+    },
+    /// Shows or changes the tags of a password
+    Tag {
+        /// Website name to generate password for
+        domain: String,
+        /// User name associated with the account
+        name: String,
+        /// Password revision
+        #[clap(short = 'r', long, default_value = "1")]
+        revision: String,
+        /// Add this tag (can be specified multiple times)
+        #[clap(short = 'a', long, value_name = "TAG", multiple_occurrences = true)]
+        add: Vec<String>,
+        /// Remove this tag (can be specified multiple times)
+        #[clap(short = 'd', long, value_name = "TAG", multiple_occurrences = true)]
+        remove: Vec<String>,
     },
     /// Lists passwords for a website
     List {
@@ -128,6 +400,107 @@ pub enum Commands {
         /// Show site aliases and password generation parameters
         #[clap(short = 'v', long)]
         verbose: bool,
+        /// Only list passwords of this type
+        #[clap(short = 't', long = "type", arg_enum, value_name = "TYPE")]
+        password_type: Option<PasswordType>,
+        /// Skip this many passwords (counted across all sites, in listing order)
+        #[clap(long, value_name = "COUNT")]
+        offset: Option<usize>,
+        /// List at most this many passwords, applied after --offset
+        #[clap(long, value_name = "COUNT")]
+        limit: Option<usize>,
+        /// Only list passwords carrying this tag
+        #[clap(long, value_name = "TAG")]
+        tag: Option<String>,
+        /// Only list passwords modified on this date (YYYY-MM-DD, UTC) or later
+        #[clap(long, parse(try_from_str = parse_date), value_name = "DATE")]
+        modified_since: Option<u64>,
+        /// With --modified-since, also list passwords without a modification time, e.g. those
+        /// added by the browser extension
+        #[clap(long, requires = "modified-since")]
+        include_undated: bool,
+        /// Print the list as JSON, for use in scripts (always lists all matching passwords)
+        #[clap(long, conflicts_with_all = &["recovery", "offset", "limit"])]
+        json: bool,
+    },
+    /// Searches site names, aliases and user names of all passwords
+    Search {
+        /// Text to look for (case-insensitive)
+        query: String,
+        /// Also search password notes (can be slow)
+        #[clap(long)]
+        notes: bool,
+    },
+    /// Prints all data of a website as JSON, password values only with --include-secrets (all
+    /// websites with --format)
+    Export {
+        /// Website to export (aliases will be resolved)
+        #[clap(long, required_unless_present = "format")]
+        site: Option<String>,
+        /// Include password values, stored passwords and notes in the output
+        #[clap(long)]
+        include_secrets: bool,
+        /// Include generation parameters so that generated passwords can be recreated on import
+        #[clap(long)]
+        include_generated_params: bool,
+        /// Export all data instead of a single website, either as an encrypted backup compatible
+        /// with the PfP browser extension or as plain text
+        #[clap(long, arg_enum, value_name = "FORMAT", conflicts_with_all = &["site", "include-secrets", "include-generated-params"])]
+        format: Option<ExportFormat>,
+        /// Allow --format plain to output decrypted passwords and notes
+        #[clap(long)]
+        allow_plaintext: bool,
+        /// Write the data to this file (on Unix readable only by the current user) instead
+        #[clap(parse(from_os_str), short = 'o', long, value_name = "FILE")]
+        output: Option<std::path::PathBuf>,
+        /// Overwrite the file given by --output if it exists
+        #[clap(long, requires = "output")]
+        force: bool,
+    },
+    /// Prints all passwords as CSV with one row per password
+    ExportCsv {
+        /// Comma-separated list of columns to include
+        #[clap(
+            long,
+            arg_enum,
+            use_value_delimiter = true,
+            default_value = "site,name,revision,type",
+            value_name = "COLUMNS"
+        )]
+        columns: Vec<CsvColumn>,
+        /// Allow the value column, outputting decrypted passwords
+        #[clap(long)]
+        allow_plaintext: bool,
+        /// Write the data to this file (on Unix readable only by the current user) instead
+        #[clap(parse(from_os_str), short = 'o', long, value_name = "FILE")]
+        output: Option<std::path::PathBuf>,
+        /// Overwrite the file given by --output if it exists
+        #[clap(long, requires = "output")]
+        force: bool,
+    },
+    /// Imports website data produced by the export command or a PfP browser extension backup
+    Import {
+        /// File containing the exported data
+        #[clap(parse(from_os_str))]
+        file: std::path::PathBuf,
+        /// Replace existing passwords instead of skipping them (or asking for backups)
+        #[clap(short = 'y', long)]
+        assume_yes: bool,
+    },
+    /// Imports logins from a CSV file exported by another password manager as stored passwords
+    ImportCsv {
+        /// CSV file with a header row
+        #[clap(parse(from_os_str))]
+        file: std::path::PathBuf,
+        /// Name of the column containing the website address
+        #[clap(long, default_value = "url", value_name = "COLUMN")]
+        url_column: String,
+        /// Name of the column containing the user name
+        #[clap(long, default_value = "username", value_name = "COLUMN")]
+        username_column: String,
+        /// Name of the column containing the password
+        #[clap(long, default_value = "password", value_name = "COLUMN")]
+        password_column: String,
     },
     /// Displays, adds or removes website aliases
     Alias {
@@ -139,19 +512,191 @@ pub enum Commands {
         #[clap(short = 'r', long)]
         remove: bool,
     },
+    /// Lists all website aliases
+    ListAliases {
+        /// Group aliases by the site they are an alias for
+        #[clap(short = 'r', long)]
+        reverse: bool,
+    },
+    /// Makes a number of websites aliases for the same site
+    SetAlias {
+        /// Site that the websites should be aliases for
+        #[clap(short = 't', long)]
+        target: String,
+        /// Website names to be made aliases
+        #[clap(required = true)]
+        domains: Vec<String>,
+        /// Move existing passwords of the websites to the target site instead of failing
+        #[clap(long)]
+        migrate: bool,
+        /// Do not prompt before changing generated passwords
+        #[clap(short = 'y', long, requires = "migrate")]
+        assume_yes: bool,
+    },
+    /// Sets the password requirements of a website, used by the add command
+    SetPolicy {
+        /// Website name to set the policy for
+        domain: String,
+        /// Minimal password length
+        #[clap(long, validator = validate_length)]
+        min_length: Option<usize>,
+        /// Maximal password length
+        #[clap(long, validator = validate_length)]
+        max_length: Option<usize>,
+        /// Do not allow lower-case letters
+        #[clap(short = 'w', long)]
+        no_lower: bool,
+        /// Do not allow upper-case letters
+        #[clap(short = 'u', long)]
+        no_upper: bool,
+        /// Do not allow digits
+        #[clap(short = 'd', long)]
+        no_digit: bool,
+        /// Do not allow symbols
+        #[clap(short = 's', long)]
+        no_symbol: bool,
+        /// Characters that passwords must not contain
+        #[clap(short = 'f', long, default_value = "")]
+        forbidden: String,
+        /// Remove the policy, other options are ignored
+        #[clap(long)]
+        remove: bool,
+    },
+    /// Converts generated passwords from early PfP versions to the current format
+    MigrateGenerated {
+        /// Switch to the current password generation algorithm, changing password values
+        #[clap(long)]
+        regenerate: bool,
+    },
+    /// Displays storage metadata, nothing secret is printed
+    Info {
+        /// Unlock storage to display the number of sites and passwords
+        #[clap(short = 'u', long)]
+        unlock: bool,
+    },
+    /// Lists the profiles having a storage file in the configuration directory
+    Profiles,
+    /// Summarizes storage contents: number of sites, aliases and passwords
+    Stats,
+    /// Checks that all storage entries can be decrypted and read
+    Verify,
+    /// Reports the estimated strength of generated passwords
+    Audit {
+        /// Website name to check passwords for (can be a wildcard pattern)
+        #[clap(default_value = "*")]
+        domain: String,
+        /// User name wildcard pattern
+        #[clap(default_value = "*")]
+        name: String,
+        /// Flag passwords with less entropy (in bits) than this
+        #[clap(short = 't', long, default_value = "60", value_name = "BITS")]
+        threshold: f64,
+    },
+    /// Finds passwords sharing the same value
+    Duplicates {
+        /// Compare generated passwords as well (slow, requires deriving their values)
+        #[clap(short = 'g', long)]
+        include_generated: bool,
+    },
+    /// Prints a machine-readable description of all commands and options
+    #[clap(hide = true)]
+    HelpJson,
     /// Open an interactive shell
     Shell {
         /// Command history file path
         #[clap(parse(from_os_str), short = 's', long)]
         history: Option<std::path::PathBuf>,
+        /// Keep generated password values in memory for the duration of the session, making
+        /// repeated retrieval faster
+        #[clap(long)]
+        cache: bool,
     },
 }
 
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PasswordType {
+    Generated,
+    Stored,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShowField {
+    Value,
+    Notes,
+    Recovery,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KdfAlgorithm {
+    Scrypt,
+    Argon2id,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Pfp,
+    Plain,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsvColumn {
+    Site,
+    Name,
+    Revision,
+    Type,
+    Length,
+    Value,
+    Notes,
+}
+
 fn validate_length(arg: &str) -> Result<(), String> {
     if let Ok(length) = arg.parse::<usize>() {
-        if !(4..=24).contains(&length) {
-            return Err("Password length should be between 4 and 24 characters.".to_string());
+        if !(MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&length) {
+            return Err(format!(
+                "Password length should be between {} and {} characters.",
+                MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+            ));
         }
     };
     Ok(())
 }
+
+/// Parses a date in the format YYYY-MM-DD into seconds since the Unix epoch (midnight UTC).
+fn parse_date(arg: &str) -> Result<u64, String> {
+    let error = || "Date should be given in the format YYYY-MM-DD.".to_string();
+    let parts = arg
+        .split('-')
+        .map(|part| part.parse::<u64>().map_err(|_| error()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (year, month, day) = match parts[..] {
+        [year, month, day] if year >= 1970 => (year, month, day),
+        _ => return Err(error()),
+    };
+
+    let leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_days = match month {
+        2 if leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return Err(error()),
+    };
+    if day < 1 || day > month_days {
+        return Err(error());
+    }
+
+    // Day number from civil date, see https://howardhinnant.github.io/date_algorithms.html
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Ok((era * 146097 + day_of_era - 719468) * 86400)
+}
+
+fn validate_profile(arg: &str) -> Result<(), String> {
+    if arg.is_empty() || arg.starts_with('.') || arg.contains(std::path::is_separator) {
+        return Err("Profile name should be a file name without path separators.".to_string());
+    }
+    Ok(())
+}
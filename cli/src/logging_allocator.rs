@@ -7,17 +7,51 @@
 use std::alloc::{GlobalAlloc, Layout, System};
 use std::io::Write;
 
+struct Allocation {
+    ptr: usize,
+    size: usize,
+    freed: bool,
+    backtrace: backtrace::Backtrace,
+}
+
 struct LoggingAllocator {
-    pub allocations: Option<std::sync::Mutex<Vec<(usize, backtrace::Backtrace)>>>,
+    pub allocations: Option<std::sync::Mutex<Vec<Allocation>>>,
 }
 
 impl LoggingAllocator {
-    fn shutdown(&self) {
+    fn shutdown(&self, memory_dump: Option<&std::path::Path>) {
         if let Some(mutex) = &self.allocations {
+            let allocations = mutex.lock().unwrap();
             let mut file =
                 std::fs::File::create(format!("alloc_{}.log", std::process::id())).unwrap();
-            for (ptr, bt) in mutex.lock().unwrap().iter() {
-                write!(file, "Allocation of {:x}:\n{:?}\n", ptr, bt).unwrap();
+            for allocation in allocations.iter() {
+                write!(
+                    file,
+                    "Allocation of {:x}:\n{:?}\n",
+                    allocation.ptr, allocation.backtrace
+                )
+                .unwrap();
+            }
+
+            if let Some(path) = memory_dump {
+                let mut file = std::fs::File::create(path).unwrap();
+                for allocation in allocations.iter() {
+                    write!(
+                        file,
+                        "Allocation of {:x} ({} bytes{}):\n",
+                        allocation.ptr,
+                        allocation.size,
+                        if allocation.freed { ", freed" } else { "" }
+                    )
+                    .unwrap();
+
+                    // Deallocation is a no-op, so the memory of freed allocations is still valid.
+                    let contents = unsafe {
+                        std::slice::from_raw_parts(allocation.ptr as *const u8, allocation.size)
+                    };
+                    file.write_all(contents).unwrap();
+                    file.write_all(b"\n").unwrap();
+                }
             }
         }
     }
@@ -28,13 +62,30 @@ unsafe impl GlobalAlloc for LoggingAllocator {
         let ret = System.alloc(layout);
         if let Some(mutex) = &self.allocations {
             if let Ok(mut allocations) = mutex.try_lock() {
-                allocations.push((ret as usize, backtrace::Backtrace::new()));
+                allocations.push(Allocation {
+                    ptr: ret as usize,
+                    size: layout.size(),
+                    freed: false,
+                    backtrace: backtrace::Backtrace::new(),
+                });
             }
         }
         ret
     }
 
-    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {}
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        if let Some(mutex) = &self.allocations {
+            if let Ok(mut allocations) = mutex.try_lock() {
+                if let Some(allocation) = allocations
+                    .iter_mut()
+                    .rev()
+                    .find(|allocation| allocation.ptr == ptr as usize)
+                {
+                    allocation.freed = true;
+                }
+            }
+        }
+    }
 }
 
 #[global_allocator]
@@ -46,8 +97,8 @@ pub fn init_allocator() {
     }
 }
 
-pub fn shutdown_allocator() {
+pub fn shutdown_allocator(memory_dump: Option<&std::path::Path>) {
     unsafe {
-        ALLOCATOR.shutdown();
+        ALLOCATOR.shutdown(memory_dump);
     }
 }
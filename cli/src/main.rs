@@ -20,22 +20,24 @@ use logging_allocator::{init_allocator, shutdown_allocator};
 fn init_allocator() {}
 
 #[cfg(not(logging_alloc))]
-fn shutdown_allocator() {}
+fn shutdown_allocator(_memory_dump: Option<&std::path::Path>) {}
 
 use args::{Args, Commands};
 use io_streams::StreamWriter;
+use pfp::error::Error;
 use pfp::passwords::Passwords;
-use pfp::storage_io::FileIO;
-use processor::utils::ConvertError;
+use pfp::storage_io::{self, FileIO, StdioIO, StorageIO};
+use processor::utils::{CommandError, ConvertError, DeferInterrupts};
 use std::io::Write;
 
 struct Shutdown {
     wait: bool,
+    memory_dump: Option<std::path::PathBuf>,
 }
 
 impl Shutdown {
-    pub fn new(wait: bool) -> Self {
-        Self { wait }
+    pub fn new(wait: bool, memory_dump: Option<std::path::PathBuf>) -> Self {
+        Self { wait, memory_dump }
     }
 }
 
@@ -51,60 +53,116 @@ impl Drop for Shutdown {
             let mut _input = String::new();
             std::io::stdin().read_line(&mut _input).unwrap();
         }
-        shutdown_allocator();
+        shutdown_allocator(self.memory_dump.as_deref());
     }
 }
 
-fn get_default_storage_path() -> std::path::PathBuf {
-    let app_info = app_dirs2::AppInfo {
-        name: "PfP",
-        author: "Wladimir Palant",
-    };
-    let mut path = app_dirs2::get_app_root(app_dirs2::AppDataType::UserConfig, &app_info).unwrap();
-    path.push("storage.json");
+fn get_default_storage_path(profile: Option<&str>) -> std::path::PathBuf {
+    let mut path = processor::utils::get_config_dir();
+    path.push(format!(
+        "{}.json",
+        profile.unwrap_or(processor::utils::DEFAULT_PROFILE)
+    ));
     path
 }
 
-fn main_inner(args: Args) -> Result<(), String> {
+fn confirm_reset() -> bool {
+    question::Question::new("Changing primary password will remove all existing data. Continue?")
+        .default(question::Answer::NO)
+        .show_defaults()
+        .confirm()
+        == question::Answer::YES
+}
+
+fn run<IO: storage_io::StorageIO>(
+    args: Args,
+    storage_path: &std::path::PathBuf,
+    io: IO,
+) -> Result<(), CommandError> {
+    let mut passwords = Passwords::new(io);
+    passwords.set_lenient(args.lenient);
+    if let Some(time) = args.fixed_time {
+        passwords.set_clock(Box::new(move || time));
+    }
+
+    // Ctrl-C while passwords might be unlocked aborts the command, so that passwords get locked
+    // and the shutdown code runs before exiting.
+    let interrupts = DeferInterrupts::new();
+    processor::process_command(args, storage_path, &mut passwords)?;
+    passwords.close().convert_error()?;
+    interrupts.check_interrupted()
+}
+
+fn main_inner(args: Args) -> Result<(), CommandError> {
+    if args.dump_memory_on_exit.is_some() && !cfg!(logging_alloc) {
+        return Err("Memory dumps are only supported by builds with logging_alloc enabled.".into());
+    }
+
     let storage_path = match &args.storage {
         Some(value) => value.clone(),
-        None => get_default_storage_path(),
+        None => get_default_storage_path(args.profile.as_deref()),
     };
 
-    let io = if let Commands::SetPrimary { assume_yes } = &args.command {
+    if args.stdin_storage && processor::needs_storage(&args.command) {
+        processor::utils::redirect_output_to_stderr();
+        let io = StdioIO::load().convert_error()?;
+        if let Commands::SetPrimary {
+            assume_yes: false, ..
+        } = &args.command
+        {
+            if io.keys().next().is_some() && !confirm_reset() {
+                return Ok(());
+            }
+        }
+        return run(args, &storage_path, io);
+    }
+
+    let io = if !processor::needs_storage(&args.command) {
+        FileIO::new(&storage_path)
+    } else if let Commands::SetPrimary { assume_yes, .. } = &args.command {
         match FileIO::load(&storage_path) {
             Ok(io) => {
-                if !assume_yes {
-                    let allow = question::Question::new(
-                        "Changing primary password will remove all existing data. Continue?",
-                    )
-                    .default(question::Answer::NO)
-                    .show_defaults()
-                    .confirm();
-                    if allow == question::Answer::NO {
-                        return Ok(());
-                    }
+                if !assume_yes && !confirm_reset() {
+                    return Ok(());
                 }
                 io
             }
-            Err(_) => FileIO::new(&storage_path),
+            // Only a missing storage file can be replaced without asking, anything else could
+            // mean overwriting data that is still in use.
+            Err(Error::FileReadFailure { error })
+                if error.kind() == std::io::ErrorKind::NotFound =>
+            {
+                FileIO::new(&storage_path)
+            }
+            Err(error) => return Err(error).convert_error(),
+        }
+    } else if let Commands::Info { .. } = &args.command {
+        match FileIO::load_read_only(&storage_path) {
+            Ok(io) => io,
+            Err(Error::FileReadFailure { .. }) => FileIO::new(&storage_path),
+            Err(error) => return Err(error).convert_error(),
         }
+    } else if args.allow_newer {
+        FileIO::load_allow_newer(&storage_path).convert_error()?
     } else {
         FileIO::load(&storage_path).convert_error()?
     };
 
-    let mut passwords = Passwords::new(io);
-    processor::process_command(args, &storage_path, &mut passwords)
+    run(args, &storage_path, io)
 }
 
 fn main() -> std::process::ExitCode {
     init_allocator();
+    processor::utils::install_interrupt_handler();
 
     let args = <Args as clap::Parser>::parse();
-    let _shutdown = Shutdown::new(args.wait);
+    if args.allow_env_password {
+        processor::utils::take_env_password();
+    }
+    let _shutdown = Shutdown::new(args.wait, args.dump_memory_on_exit.clone());
     if let Err(error) = main_inner(args) {
         eprintln!("{}", error);
-        std::process::ExitCode::FAILURE
+        std::process::ExitCode::from(error.exit_code)
     } else {
         std::process::ExitCode::SUCCESS
     }
@@ -4,16 +4,20 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
-use super::utils::{ensure_unlocked_passwords, ConvertError};
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
 use crate::args::{Args, Commands};
 use pfp::passwords::Passwords;
 use pfp::storage_io;
 use pfp::storage_types::{CharacterSet, CharacterType};
+use secrecy::ExposeSecret;
+use std::io::Write;
+
+const DEFAULT_LENGTH: usize = 16;
 
 pub fn processor<IO: storage_io::StorageIO>(
     args: &Args,
     passwords: &mut Passwords<IO>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     if let Commands::Add {
         domain,
         name,
@@ -23,26 +27,54 @@ pub fn processor<IO: storage_io::StorageIO>(
         no_upper,
         no_digit,
         no_symbol,
+        symbols,
         assume_yes,
     } = &args.command
     {
-        ensure_unlocked_passwords(passwords, args.stdin_passwords)?;
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let policy = passwords.get_policy(domain).convert_error()?;
 
-        let mut charset = CharacterSet::empty();
-        if !no_lower {
-            charset.insert(CharacterType::Lower);
+        let mut charset = policy
+            .as_ref()
+            .map_or_else(CharacterSet::all, |policy| policy.charset());
+        if *no_lower {
+            charset.remove(CharacterType::Lower);
         }
-        if !no_upper {
-            charset.insert(CharacterType::Upper);
+        if *no_upper {
+            charset.remove(CharacterType::Upper);
         }
-        if !no_digit {
-            charset.insert(CharacterType::Digit);
+        if *no_digit {
+            charset.remove(CharacterType::Digit);
         }
-        if !no_symbol {
-            charset.insert(CharacterType::Symbol);
+        if *no_symbol {
+            charset.remove(CharacterType::Symbol);
         }
         if charset.is_empty() {
-            return Err("You need to allow at least one character set.".to_owned());
+            return Err("You need to allow at least one character set.".into());
+        }
+
+        let length = match (length, &policy) {
+            (Some(length), _) => *length,
+            (None, Some(policy)) => policy.clamp_length(DEFAULT_LENGTH),
+            (None, None) => DEFAULT_LENGTH,
+        };
+
+        if let Some(policy) = &policy {
+            if policy.clamp_length(length) != length {
+                return Err(format!(
+                    "Password length {} violates the site's password policy.",
+                    length
+                )
+                .into());
+            }
+
+            let value = passwords
+                .derive_generated(domain, name, revision, length, charset, symbols.as_deref())
+                .convert_error()?;
+            if policy.has_forbidden(value.expose_secret()) {
+                return Err("The generated password contains characters forbidden by the site's password policy, try a different revision.".into());
+            }
         }
 
         if !assume_yes && passwords.has(domain, name, revision).unwrap_or(false) {
@@ -58,9 +90,9 @@ pub fn processor<IO: storage_io::StorageIO>(
         }
 
         passwords
-            .set_generated(domain, name, revision, *length, charset)
+            .set_generated(domain, name, revision, length, charset, symbols.as_deref())
             .convert_error()?;
-        println!("Password added.");
+        writeln!(out(), "Password added.").unwrap();
     }
 
     Ok(())
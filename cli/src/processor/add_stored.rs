@@ -5,25 +5,58 @@
  */
 
 use super::utils::{
-    ensure_unlocked_passwords, prompt_password, prompt_recovery_code, ConvertError,
+    ensure_unlocked_passwords, out, prompt_password, prompt_recovery_code, prompt_secret,
+    CommandError, ConvertError,
 };
 use crate::args::{Args, Commands};
+use pfp::error::Error;
 use pfp::passwords::Passwords;
 use pfp::storage_io;
+use secrecy::{ExposeSecret, SecretString};
+use std::io::Write;
+
+fn read_recovery_file<IO: storage_io::StorageIO>(
+    passwords: &Passwords<IO>,
+    path: &std::path::Path,
+) -> Result<SecretString, CommandError> {
+    let contents = SecretString::new(
+        std::fs::read_to_string(path)
+            .map_err(|error| format!("Failed reading recovery code file ({}).", error))?,
+    );
+    match passwords.decode_recovery_code(contents.expose_secret()) {
+        // Line numbers reported by the decoder are zero-based, people count file lines from one.
+        Err(Error::RecoveryCodeChecksumMismatch { line }) => Err(format!(
+            "Error in recovery code, checksum mismatch in line {} of {}.",
+            line + 1,
+            path.display()
+        )
+        .into()),
+        Err(Error::RecoveryCodeExtraData { line }) => Err(format!(
+            "Error in recovery code, extra data starting with line {} of {}.",
+            line + 1,
+            path.display()
+        )
+        .into()),
+        result => result.convert_error(),
+    }
+}
 
 pub fn processor<IO: storage_io::StorageIO>(
     args: &Args,
     passwords: &mut Passwords<IO>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     if let Commands::AddStored {
         domain,
         name,
         revision,
         recovery,
+        recovery_file,
+        no_trim,
+        keep_history,
         assume_yes,
     } = &args.command
     {
-        ensure_unlocked_passwords(passwords, args.stdin_passwords)?;
+        ensure_unlocked_passwords(passwords, args)?;
 
         if !assume_yes && passwords.has(domain, name, revision).unwrap_or(false) {
             let allow = question::Question::new(
@@ -37,15 +70,21 @@ pub fn processor<IO: storage_io::StorageIO>(
             }
         }
 
-        let password = if *recovery {
+        // Recovery codes reproduce the original value exactly, it must not be trimmed.
+        let password = if let Some(path) = recovery_file {
+            read_recovery_file(passwords, path)?
+        } else if *recovery {
             prompt_recovery_code(passwords)?
+        } else if *no_trim {
+            prompt_secret("Password to be stored: ", args.stdin_passwords)?
         } else {
-            prompt_password("Password to be stored: ", args.stdin_passwords)
+            prompt_password("Password to be stored: ", args.stdin_passwords)?
         };
+        passwords.set_keep_history(*keep_history);
         passwords
             .set_stored(domain, name, revision, password)
             .convert_error()?;
-        println!("Password added.");
+        writeln!(out(), "Password added.").unwrap();
     }
 
     Ok(())
@@ -4,35 +4,38 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
-use super::utils::{ensure_unlocked_passwords, ConvertError};
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
 use crate::args::{Args, Commands};
 use pfp::passwords::Passwords;
 use pfp::storage_io;
+use std::io::Write;
 
 pub fn processor<IO: storage_io::StorageIO>(
     args: &Args,
     passwords: &mut Passwords<IO>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     if let Commands::Alias {
         domain,
         alias_target,
         remove,
     } = &args.command
     {
-        ensure_unlocked_passwords(passwords, args.stdin_passwords)?;
+        ensure_unlocked_passwords(passwords, args)?;
 
         if let Some(target) = alias_target {
             passwords.set_alias(domain, target).convert_error()?;
-            println!("Alias added.");
+            writeln!(out(), "Alias added.").unwrap();
         } else if *remove {
             passwords.remove_alias(domain).convert_error()?;
-            println!("Alias removed.");
+            writeln!(out(), "Alias removed.").unwrap();
         } else {
-            println!(
+            writeln!(
+                out(),
                 "'{}' is an alias for '{}'.",
                 domain,
                 passwords.get_alias(domain).convert_error()?
-            );
+            )
+            .unwrap();
         }
     }
 
@@ -0,0 +1,59 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use pfp::storage_types::Password;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Audit {
+        domain,
+        name,
+        threshold,
+    } = &args.command
+    {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let mut checked = 0;
+        let mut weak = 0;
+        for (_, password) in passwords.find(domain, name) {
+            let entropy = match &password {
+                Password::Generated(password) | Password::LegacyGenerated(password) => {
+                    password.entropy_bits()
+                }
+                // Strength of stored passwords cannot be judged reliably from their characters
+                Password::Stored(_) => continue,
+            };
+
+            checked += 1;
+            if entropy < *threshold {
+                weak += 1;
+                writeln!(out(), "{}: {:.0} bits (weak)", password.id(), entropy).unwrap();
+            } else {
+                writeln!(out(), "{}: {:.0} bits", password.id(), entropy).unwrap();
+            }
+        }
+
+        if checked == 0 {
+            return Err("No matching passwords found.".into());
+        }
+
+        writeln!(out()).unwrap();
+        if weak > 0 {
+            writeln!(out(), "{} password(s) below {} bits.", weak, threshold).unwrap();
+        } else {
+            writeln!(out(), "All passwords have at least {} bits.", threshold).unwrap();
+        }
+    }
+
+    Ok(())
+}
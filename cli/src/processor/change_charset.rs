@@ -0,0 +1,51 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use pfp::storage_types::{CharacterSet, CharacterType};
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::ChangeCharset {
+        domain,
+        name,
+        revision,
+        no_lower,
+        no_upper,
+        no_digit,
+        no_symbol,
+    } = &args.command
+    {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let mut charset = CharacterSet::all();
+        if *no_lower {
+            charset.remove(CharacterType::Lower);
+        }
+        if *no_upper {
+            charset.remove(CharacterType::Upper);
+        }
+        if *no_digit {
+            charset.remove(CharacterType::Digit);
+        }
+        if *no_symbol {
+            charset.remove(CharacterType::Symbol);
+        }
+
+        passwords
+            .set_charset(domain, name, revision, charset)
+            .convert_error()?;
+        writeln!(out(), "Password character set changed.").unwrap();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{
+    ensure_unlocked_passwords, out, prompt_password, CommandError, ConvertError,
+    EXIT_WRONG_PASSWORD,
+};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use pfp::storage_types::Password;
+use secrecy::ExposeSecret;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::ChangePrimary { assume_yes } = &args.command {
+        if passwords.unlocked() {
+            let current = prompt_password("Current primary password: ", args.stdin_passwords)?;
+            if !passwords.verify_primary(&current).convert_error()? {
+                return Err(CommandError::new(
+                    "Wrong primary password.".to_owned(),
+                    EXIT_WRONG_PASSWORD,
+                ));
+            }
+        } else {
+            ensure_unlocked_passwords(passwords, args)?;
+        }
+
+        let has_generated = passwords.list_all().any(|password| {
+            matches!(
+                password,
+                Password::Generated(_) | Password::LegacyGenerated(_)
+            )
+        });
+        if has_generated && !assume_yes {
+            let allow = question::Question::new(
+                "Generated passwords depend on the primary password, their values will change. Continue?",
+            )
+            .default(question::Answer::NO)
+            .show_defaults()
+            .confirm();
+            if allow == question::Answer::NO {
+                return Ok(());
+            }
+        }
+
+        let primary_password = prompt_password("New primary password: ", args.stdin_passwords)?;
+        if primary_password.expose_secret().len() < 6 {
+            return Err("Primary password length should be at least 6 characters.".into());
+        }
+
+        let primary_password2 = prompt_password("Repeat primary password: ", args.stdin_passwords)?;
+        if primary_password.expose_secret() != primary_password2.expose_secret() {
+            return Err("Primary passwords don't match.".into());
+        }
+
+        passwords.change_primary(primary_password).convert_error()?;
+        writeln!(out(), "Primary password changed.").unwrap();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use pfp::storage_types::PasswordId;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Duplicate {
+        domain,
+        name,
+        new_name,
+        revision,
+        new_domain,
+        new_revision,
+        assume_yes,
+    } = &args.command
+    {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let new_domain = new_domain.as_ref().unwrap_or(domain);
+        if !assume_yes
+            && passwords
+                .has(new_domain, new_name, new_revision)
+                .unwrap_or(false)
+        {
+            let allow = question::Question::new(
+                "A password with this domain/name/revision combination already exists. Overwrite?",
+            )
+            .default(question::Answer::NO)
+            .show_defaults()
+            .confirm();
+            if allow == question::Answer::NO {
+                return Ok(());
+            }
+        }
+
+        passwords
+            .duplicate(
+                &PasswordId::new(domain, name, revision),
+                &PasswordId::new(new_domain, new_name, new_revision),
+                true,
+            )
+            .convert_error()?;
+        writeln!(out(), "Password duplicated.").unwrap();
+    }
+
+    Ok(())
+}
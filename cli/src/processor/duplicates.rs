@@ -0,0 +1,38 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Duplicates { include_generated } = &args.command {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let groups = passwords
+            .find_duplicates(*include_generated)
+            .convert_error()?;
+        if groups.is_empty() {
+            writeln!(out(), "No duplicate passwords found.").unwrap();
+            return Ok(());
+        }
+
+        for group in groups {
+            writeln!(out(), "Passwords sharing the same value:").unwrap();
+            for id in group {
+                writeln!(out(), "    {}", id).unwrap();
+            }
+            writeln!(out()).unwrap();
+        }
+    }
+
+    Ok(())
+}
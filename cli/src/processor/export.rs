@@ -0,0 +1,212 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{
+    ensure_unlocked_passwords, out, secret_output, write_secure_file, write_to_secret_vec,
+    CommandError, ConvertError,
+};
+use crate::args::{Args, Commands, ExportFormat};
+use pfp::passwords::{Passwords, SiteExport};
+use pfp::storage_io;
+use pfp::storage_types::{CharacterType, Password, Site};
+use secrecy::{ExposeSecret, SecretString, SecretVec};
+use serde::Serialize;
+use std::io::Write;
+
+// Fields are listed in alphabetical order, matching the JSON format of previous versions.
+
+#[derive(Serialize)]
+struct GeneratedParams<'a> {
+    length: usize,
+    lower: bool,
+    number: bool,
+    symbol: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    symbols: Option<&'a str>,
+    upper: bool,
+}
+
+#[derive(Serialize)]
+struct PasswordData<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generated: Option<GeneratedParams<'a>>,
+    name: &'a str,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "pfp::secret_serialization::option"
+    )]
+    notes: Option<SecretString>,
+    revision: &'a str,
+    #[serde(rename = "type")]
+    password_type: &'static str,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "pfp::secret_serialization::option"
+    )]
+    value: Option<SecretString>,
+}
+
+#[derive(Serialize)]
+struct SiteData<'a> {
+    aliases: &'a [String],
+    passwords: Vec<PasswordData<'a>>,
+    site: &'a Site,
+}
+
+#[derive(Serialize)]
+struct AllData<'a> {
+    sites: Vec<SiteData<'a>>,
+}
+
+fn site_data(
+    export: &SiteExport,
+    include_secrets: bool,
+    include_generated_params: bool,
+) -> SiteData<'_> {
+    let mut entries = Vec::new();
+    for (password, value) in &export.passwords {
+        let password_type = match password {
+            Password::Generated(_) => "generated2",
+            Password::LegacyGenerated(_) => "generated",
+            Password::Stored(_) => "stored",
+        };
+
+        let generated = match password {
+            Password::Generated(generated) | Password::LegacyGenerated(generated)
+                if include_generated_params =>
+            {
+                let charset = generated.charset();
+                Some(GeneratedParams {
+                    length: generated.length(),
+                    lower: charset.contains(CharacterType::Lower),
+                    number: charset.contains(CharacterType::Digit),
+                    symbol: charset.contains(CharacterType::Symbol),
+                    symbols: generated.symbols(),
+                    upper: charset.contains(CharacterType::Upper),
+                })
+            }
+            _ => None,
+        };
+
+        let (value, notes) = if include_secrets {
+            let notes = password.notes();
+            (
+                Some(value.clone()),
+                if notes.expose_secret().is_empty() {
+                    None
+                } else {
+                    Some(notes.clone())
+                },
+            )
+        } else {
+            (None, None)
+        };
+
+        entries.push(PasswordData {
+            generated,
+            name: password.id().name(),
+            notes,
+            revision: password.id().revision(),
+            password_type,
+            value,
+        });
+    }
+
+    SiteData {
+        aliases: &export.aliases,
+        passwords: entries,
+        site: &export.site,
+    }
+}
+
+fn to_json<T: Serialize>(data: &T) -> Result<SecretVec<u8>, CommandError> {
+    write_to_secret_vec(|writer| {
+        serde_json::to_writer_pretty(&mut *writer, data).map_err(|error| error.to_string())?;
+        writer
+            .write_all(b"\n")
+            .map_err(|error| error.to_string().into())
+    })
+}
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Export {
+        site,
+        include_secrets,
+        include_generated_params,
+        format,
+        allow_plaintext,
+        output,
+        force,
+    } = &args.command
+    {
+        if *format == Some(ExportFormat::Plain) {
+            if !allow_plaintext {
+                return Err(
+                    "Plain text export includes all passwords unencrypted, use --allow-plaintext if this is intended.".into(),
+                );
+            }
+
+            let allow = question::Question::new(
+                "All passwords and notes will be exported unencrypted. Continue?",
+            )
+            .default(question::Answer::NO)
+            .show_defaults()
+            .confirm();
+            if allow == question::Answer::NO {
+                return Ok(());
+            }
+        }
+
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let contents = match (format, site) {
+            (Some(ExportFormat::Pfp), _) => {
+                let backup = SecretString::new(passwords.backup().convert_error()?);
+                SecretVec::new([backup.expose_secret().as_bytes(), b"\n"].concat())
+            }
+            (Some(ExportFormat::Plain), _) => {
+                let mut names = passwords
+                    .list_sites("*")
+                    .filter(|site| site.alias().is_none())
+                    .map(|site| site.name().to_string())
+                    .collect::<Vec<String>>();
+                names.sort();
+
+                let mut exports = Vec::new();
+                for name in names {
+                    exports.push(passwords.export_site(&name).convert_error()?);
+                }
+                to_json(&AllData {
+                    sites: exports
+                        .iter()
+                        .map(|export| site_data(export, true, true))
+                        .collect(),
+                })?
+            }
+            (None, Some(site)) => {
+                let export = passwords.export_site(site).convert_error()?;
+                to_json(&site_data(
+                    &export,
+                    *include_secrets,
+                    *include_generated_params,
+                ))?
+            }
+            (None, None) => unreachable!("Either site or format should be present"),
+        };
+
+        if let Some(path) = output {
+            write_secure_file(path, *force, contents.expose_secret())?;
+            writeln!(out(), "Data written to {}.", path.display()).unwrap();
+        } else {
+            secret_output().write_all(contents.expose_secret()).unwrap();
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{
+    ensure_unlocked_passwords, out, secret_output, write_secure_file, write_to_secret_vec,
+    CommandError, ConvertError,
+};
+use crate::args::{Args, Commands, CsvColumn};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use pfp::storage_types::Password;
+use secrecy::{ExposeSecret, SecretString};
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::ExportCsv {
+        columns,
+        allow_plaintext,
+        output,
+        force,
+    } = &args.command
+    {
+        if columns.contains(&CsvColumn::Value) && !allow_plaintext {
+            return Err(
+                "The value column includes passwords unencrypted, use --allow-plaintext if this is intended.".into(),
+            );
+        }
+
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let mut list = passwords.list_all().collect::<Vec<Password>>();
+        list.sort_by(|a, b| {
+            (a.id().site(), a.id().name(), a.id().revision()).cmp(&(
+                b.id().site(),
+                b.id().name(),
+                b.id().revision(),
+            ))
+        });
+
+        let mut records = vec![columns
+            .iter()
+            .map(|column| {
+                SecretString::new(
+                    match column {
+                        CsvColumn::Site => "site",
+                        CsvColumn::Name => "name",
+                        CsvColumn::Revision => "revision",
+                        CsvColumn::Type => "type",
+                        CsvColumn::Length => "length",
+                        CsvColumn::Value => "value",
+                        CsvColumn::Notes => "notes",
+                    }
+                    .to_string(),
+                )
+            })
+            .collect::<Vec<_>>()];
+
+        for password in list {
+            let id = password.id();
+            let value = if columns.contains(&CsvColumn::Value) {
+                passwords
+                    .get(id.site(), id.name(), id.revision())
+                    .convert_error()?
+            } else {
+                SecretString::new(String::new())
+            };
+
+            let mut record = Vec::new();
+            for column in columns {
+                record.push(match column {
+                    CsvColumn::Site => id.site().to_string(),
+                    CsvColumn::Name => id.name().to_string(),
+                    CsvColumn::Revision => {
+                        if id.revision().is_empty() {
+                            "1".to_string()
+                        } else {
+                            id.revision().to_string()
+                        }
+                    }
+                    CsvColumn::Type => match &password {
+                        Password::Generated(_) => "generated",
+                        Password::LegacyGenerated(_) => "legacy generated",
+                        Password::Stored(_) => "stored",
+                    }
+                    .to_string(),
+                    CsvColumn::Length => match &password {
+                        Password::Generated(generated) | Password::LegacyGenerated(generated) => {
+                            generated.length().to_string()
+                        }
+                        Password::Stored(_) => String::new(),
+                    },
+                    CsvColumn::Value => value.expose_secret().clone(),
+                    CsvColumn::Notes => password.notes().expose_secret().clone(),
+                });
+            }
+            records.push(record.into_iter().map(SecretString::new).collect());
+        }
+
+        let contents = write_to_secret_vec(|output| {
+            // The CSV writer's own buffer is never zeroed, keep it at the minimal size. Two bytes are
+            // required for escaped quotes which cannot be split.
+            let mut writer = csv::WriterBuilder::new()
+                .buffer_capacity(2)
+                .from_writer(output);
+            for record in &records {
+                writer
+                    .write_record(record.iter().map(|field| field.expose_secret()))
+                    .map_err(|error| format!("Failed writing CSV data ({}).", error))?;
+            }
+            writer
+                .flush()
+                .map_err(|error| format!("Failed writing CSV data ({}).", error).into())
+        })?;
+        if let Some(path) = output {
+            write_secure_file(path, *force, contents.expose_secret())?;
+            writeln!(out(), "Data written to {}.", path.display()).unwrap();
+        } else {
+            secret_output().write_all(contents.expose_secret()).unwrap();
+        }
+    }
+
+    Ok(())
+}
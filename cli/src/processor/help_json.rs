@@ -0,0 +1,66 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{out, CommandError};
+use crate::args::Args;
+use clap::CommandFactory;
+use serde_json::{json, Value};
+use std::io::Write;
+
+fn describe_arg(arg: &clap::Arg) -> Value {
+    json!({
+        "name": arg.get_id(),
+        "long": arg.get_long(),
+        "short": arg.get_short().map(|short| short.to_string()),
+        "value_name": arg.get_value_names().and_then(|names| names.first()),
+        "help": arg.get_help(),
+        "positional": arg.is_positional(),
+        "takes_value": arg.is_takes_value_set(),
+        "required": arg.is_required_set(),
+        "multiple": arg.is_multiple_values_set() || arg.is_multiple_occurrences_set(),
+        "default": arg
+            .get_default_values()
+            .iter()
+            .map(|value| value.to_string_lossy())
+            .collect::<Vec<_>>(),
+        "possible_values": arg
+            .get_possible_values()
+            .unwrap_or_default()
+            .iter()
+            .map(|value| value.get_name())
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn describe_command(command: &clap::Command) -> Value {
+    json!({
+        "name": command.get_name(),
+        "about": command.get_about(),
+        "args": command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .map(describe_arg)
+            .collect::<Vec<_>>(),
+        "subcommands": command
+            .get_subcommands()
+            .filter(|subcommand| !subcommand.is_hide_set())
+            .map(describe_command)
+            .collect::<Vec<_>>(),
+    })
+}
+
+pub fn processor() -> Result<(), CommandError> {
+    let mut command = Args::command();
+    command.build();
+    writeln!(
+        out(),
+        "{}",
+        serde_json::to_string_pretty(&describe_command(&command))
+            .map_err(|error| error.to_string())?
+    )
+    .unwrap();
+    Ok(())
+}
@@ -0,0 +1,61 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, format_timestamp, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::History {
+        domain,
+        name,
+        revision,
+        restore,
+    } = &args.command
+    {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let history = passwords.history(domain, name, revision).convert_error()?;
+
+        if let Some(number) = restore {
+            if *number == 0 || *number > history.len() {
+                return Err(format!(
+                    "There is no previous value with number {} for this password.",
+                    number
+                )
+                .into());
+            }
+            passwords
+                .restore_history(domain, name, revision, number - 1)
+                .convert_error()?;
+            writeln!(
+                out(),
+                "Previous value restored, the current value has been added to the history."
+            )
+            .unwrap();
+        } else if history.is_empty() {
+            writeln!(out(), "No previous values recorded for this password.").unwrap();
+        } else {
+            writeln!(out(), "Previous values of this password, replaced at:").unwrap();
+            for (index, entry) in history.iter().enumerate() {
+                writeln!(
+                    out(),
+                    "    {}. {}",
+                    index + 1,
+                    format_timestamp(entry.timestamp())
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,264 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::error::Error;
+use pfp::passwords::Passwords;
+use pfp::secret_deserializer::from_secret_slice;
+use pfp::storage_io;
+use pfp::storage_types::{CharacterSet, CharacterType, Site};
+use secrecy::{ExposeSecret, SecretString};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::io::Write;
+
+const INVALID_DATA: &str = "Import file doesn't contain exported website data.";
+
+#[derive(Deserialize)]
+struct Header {
+    application: Option<String>,
+    sites: Option<IgnoredAny>,
+}
+
+/// Data of all websites as written by `export --format plain`.
+#[derive(Deserialize)]
+struct AllImport<'a> {
+    #[serde(borrow)]
+    sites: Vec<&'a RawValue>,
+}
+
+/// Website data as written by `export --site`. Password entries are kept raw, so that each of
+/// them can be deserialized without leaving plaintext copies behind.
+#[derive(Deserialize)]
+struct SiteImport<'a> {
+    site: Site,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(borrow)]
+    passwords: Vec<&'a RawValue>,
+}
+
+#[derive(Deserialize)]
+struct PasswordImport {
+    #[serde(rename = "type")]
+    password_type: Option<String>,
+    name: String,
+    revision: String,
+    generated: Option<Value>,
+    #[serde(default, with = "pfp::secret_serialization::option")]
+    value: Option<SecretString>,
+    #[serde(default, with = "pfp::secret_serialization::option")]
+    notes: Option<SecretString>,
+}
+
+fn parse_generated(params: &Value) -> Option<(usize, CharacterSet, Option<&str>)> {
+    let length = params.get("length")?.as_u64()? as usize;
+    let mut charset = CharacterSet::empty();
+    for (key, character_type) in [
+        ("lower", CharacterType::Lower),
+        ("upper", CharacterType::Upper),
+        ("number", CharacterType::Digit),
+        ("symbol", CharacterType::Symbol),
+    ] {
+        if params.get(key)?.as_bool()? {
+            charset.insert(character_type);
+        }
+    }
+    let symbols = params.get("symbols").and_then(Value::as_str);
+    Some((length, charset, symbols))
+}
+
+fn import_backup<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+    file: &std::path::Path,
+    assume_yes: bool,
+) -> Result<(), CommandError> {
+    ensure_unlocked_passwords(passwords, args)?;
+
+    let backup_io = storage_io::FileIO::load_read_only(file).convert_error()?;
+    if backup_io.newer_format() && !args.allow_newer {
+        return Err(Error::NewerStorageFormat {
+            format: backup_io.format(),
+        })
+        .convert_error();
+    }
+    let mut backup = Passwords::new(backup_io);
+    match backup.unlock_from(passwords) {
+        Err(Error::DecryptionFailure) => {
+            return Err("Backup was created with a different primary password.".into());
+        }
+        result => result.convert_error()?,
+    }
+
+    let entries = backup.export_all().convert_error()?;
+
+    let mut skip_all = false;
+    let mut skipped = 0;
+    let imported = passwords
+        .import_entries(entries, |password| {
+            if assume_yes {
+                return true;
+            }
+
+            if !skip_all {
+                let id = password.id();
+                let answer = question::Question::new(&format!(
+                    "Password {} for {} (revision: {}) already exists. Keep existing, overwrite or skip all existing? (k/o/s)",
+                    id.name(),
+                    id.site(),
+                    if id.revision().is_empty() { "1" } else { id.revision() }
+                ))
+                .acceptable(vec!["k", "o", "s"])
+                .until_acceptable()
+                .ask();
+                match answer {
+                    Some(question::Answer::RESPONSE(response)) if response == "o" => return true,
+                    Some(question::Answer::RESPONSE(response)) if response == "s" => {
+                        skip_all = true
+                    }
+                    _ => {}
+                }
+            }
+            skipped += 1;
+            false
+        })
+        .convert_error()?;
+
+    writeln!(
+        out(),
+        "{} password(s) imported, {} skipped (already present).",
+        imported,
+        skipped
+    )
+    .unwrap();
+    Ok(())
+}
+
+fn import_site<IO: storage_io::StorageIO>(
+    passwords: &mut Passwords<IO>,
+    data: &SiteImport,
+    assume_yes: bool,
+) -> Result<(usize, usize), CommandError> {
+    let site_entry = &data.site;
+    let site = site_entry.name();
+    let mut imported = 0;
+    let mut skipped = 0;
+    for entry in &data.passwords {
+        let entry: PasswordImport =
+            from_secret_slice(entry.get().as_bytes(), false).map_err(|_| INVALID_DATA)?;
+        let name = entry.name.as_str();
+        let revision = entry.revision.as_str();
+
+        if !assume_yes && passwords.has(site, name, revision).unwrap_or(false) {
+            skipped += 1;
+            continue;
+        }
+
+        // Only the current generation algorithm can be recreated, legacy generated passwords
+        // have to be imported by value.
+        let generated = match entry.password_type.as_deref() {
+            Some("generated2") => entry.generated.as_ref().and_then(parse_generated),
+            _ => None,
+        };
+        if let Some((length, charset, symbols)) = generated {
+            passwords
+                .set_generated(site, name, revision, length, charset, symbols)
+                .convert_error()?;
+        } else if let Some(value) = entry.value {
+            passwords
+                .set_stored(site, name, revision, value)
+                .convert_error()?;
+        } else {
+            skipped += 1;
+            continue;
+        }
+
+        if let Some(notes) = entry.notes {
+            passwords
+                .set_notes(site, name, revision, notes)
+                .convert_error()?;
+        }
+        imported += 1;
+    }
+
+    if let Some(policy) = site_entry.policy() {
+        passwords
+            .set_policy(site, Some(policy.clone()))
+            .convert_error()?;
+    }
+
+    for alias in &data.aliases {
+        passwords.set_alias(alias, site).convert_error()?;
+    }
+
+    Ok((imported, skipped))
+}
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Import { file, assume_yes } = &args.command {
+        // Site exports can contain password values and notes, keep these out of regular memory
+        let contents = SecretString::new(
+            std::fs::read_to_string(file)
+                .map_err(|error| format!("Failed reading import file ({}).", error))?,
+        );
+        let contents = contents.expose_secret();
+
+        let header = match serde_json::from_str::<Header>(contents) {
+            Ok(header) => header,
+            Err(error) if error.is_data() => Header {
+                application: None,
+                sites: None,
+            },
+            Err(error) => return Err(format!("Failed parsing import file ({}).", error).into()),
+        };
+        if header.application.as_deref() == Some("pfp") {
+            return import_backup(args, passwords, file, *assume_yes);
+        }
+
+        let sites = if header.sites.is_some() {
+            let data: AllImport =
+                from_secret_slice(contents.as_bytes(), false).map_err(|_| INVALID_DATA)?;
+            data.sites
+                .iter()
+                .map(|site| from_secret_slice(site.get().as_bytes(), false))
+                .collect::<Result<Vec<SiteImport>, _>>()
+                .map_err(|_| INVALID_DATA)?
+        } else {
+            vec![from_secret_slice(contents.as_bytes(), false).map_err(|_| INVALID_DATA)?]
+        };
+
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let (imported, skipped) = passwords
+            .batch(|passwords| -> Result<(usize, usize), CommandError> {
+                let (mut imported, mut skipped) = (0, 0);
+                for site in &sites {
+                    let (site_imported, site_skipped) = import_site(passwords, site, *assume_yes)?;
+                    imported += site_imported;
+                    skipped += site_skipped;
+                }
+                Ok((imported, skipped))
+            })
+            .convert_error()??;
+
+        writeln!(
+            out(),
+            "{} password(s) imported, {} skipped (already present or missing data).",
+            imported,
+            skipped
+        )
+        .unwrap();
+    }
+
+    Ok(())
+}
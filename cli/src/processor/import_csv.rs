@@ -0,0 +1,172 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::{url_host, Passwords};
+use pfp::storage_io;
+use secrecy::{ExposeSecret, SecretString, SecretVec};
+use std::io::Write;
+
+fn site_from_url(url: &str) -> Option<String> {
+    // Browsers might export addresses without a scheme
+    let url = url.trim();
+    let host = if url.contains("://") {
+        url_host(url)?
+    } else {
+        url_host(&format!("https://{}", url))?
+    };
+    Some(host.strip_prefix("www.").unwrap_or(&host).to_string())
+}
+
+/// A CSV record that is zeroed before being reused and when dropped. Its buffer is allocated for the
+/// entire file, so that it never grows and leaves partial copies of the data behind.
+struct SecretRecord(csv::ByteRecord);
+
+impl SecretRecord {
+    fn new(size: usize, fields: usize) -> Self {
+        Self(csv::ByteRecord::with_capacity(size, fields))
+    }
+
+    fn clear(&mut self) {
+        // There is no direct access to the record buffer, overwrite it with a field of zeros
+        let len = self.0.as_slice().len();
+        self.0.clear();
+        self.0.push_field(&vec![0; len]);
+        self.0.clear();
+    }
+}
+
+impl Drop for SecretRecord {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+fn column_index(headers: &csv::StringRecord, name: &str) -> Result<usize, CommandError> {
+    headers
+        .iter()
+        .position(|header| header.trim() == name)
+        .ok_or_else(|| format!("Column {} not found in CSV file.", name).into())
+}
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::ImportCsv {
+        file,
+        url_column,
+        username_column,
+        password_column,
+    } = &args.command
+    {
+        let contents = SecretVec::new(
+            std::fs::read(file)
+                .map_err(|error| format!("Failed reading import file ({}).", error))?,
+        );
+
+        // The reader's own buffer is never zeroed, keep it at the minimal size
+        let mut reader = csv::ReaderBuilder::new()
+            .buffer_capacity(1)
+            .from_reader(contents.expose_secret().as_slice());
+        let headers = reader
+            .headers()
+            .map_err(|error| format!("Failed parsing import file ({}).", error))?
+            .clone();
+        let url_index = column_index(&headers, url_column)?;
+        let username_index = column_index(&headers, username_column)?;
+        let password_index = column_index(&headers, password_column)?;
+
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let mut imported = 0;
+        let mut empty = 0;
+        let mut existing = 0;
+        let mut malformed = Vec::new();
+        passwords
+            .batch(|passwords| -> Result<(), CommandError> {
+                let mut record = SecretRecord::new(contents.expose_secret().len(), headers.len());
+                loop {
+                    record.clear();
+                    match reader.read_byte_record(&mut record.0) {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        Err(error) => {
+                            let line = error.position().map_or(0, |position| position.line());
+                            let message = match error.kind() {
+                                csv::ErrorKind::UnequalLengths {
+                                    expected_len, len, ..
+                                } => format!("expected {} fields, found {}", expected_len, len),
+                                _ => error.to_string(),
+                            };
+                            malformed.push(format!("Line {}: {}", line, message));
+                            continue;
+                        }
+                    }
+                    let line = record.0.position().map_or(0, |position| position.line());
+
+                    let field = |index| {
+                        std::str::from_utf8(record.0.get(index).unwrap_or_default())
+                            .map_err(|_| format!("Line {}: invalid UTF-8 data", line))
+                    };
+                    let (password, url, name) = match (
+                        field(password_index),
+                        field(url_index),
+                        field(username_index),
+                    ) {
+                        (Ok(password), Ok(url), Ok(name)) => (password, url, name),
+                        (Err(error), _, _) | (_, Err(error), _) | (_, _, Err(error)) => {
+                            malformed.push(error);
+                            continue;
+                        }
+                    };
+
+                    if password.is_empty() {
+                        empty += 1;
+                        continue;
+                    }
+                    let password = SecretString::new(password.to_string());
+
+                    let site = match site_from_url(url) {
+                        Some(site) => site,
+                        None => {
+                            malformed.push(format!("Line {}: no valid website address", line));
+                            continue;
+                        }
+                    };
+
+                    if passwords.has(&site, name, "1").convert_error()? {
+                        existing += 1;
+                        continue;
+                    }
+                    passwords
+                        .set_stored(&site, name, "1", password)
+                        .convert_error()?;
+                    imported += 1;
+                }
+                Ok(())
+            })
+            .convert_error()??;
+
+        writeln!(
+            out(),
+            "{} password(s) imported, {} skipped (empty password), {} skipped (already present).",
+            imported,
+            empty,
+            existing
+        )
+        .unwrap();
+        if !malformed.is_empty() {
+            writeln!(out(), "The following rows could not be imported:").unwrap();
+            for error in malformed {
+                writeln!(out(), "    {}", error).unwrap();
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    storage_path: &std::path::Path,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Info { unlock } = &args.command {
+        if args.stdin_storage {
+            writeln!(out(), "Storage path: stdin").unwrap();
+        } else {
+            writeln!(out(), "Storage path: {}", storage_path.display()).unwrap();
+        }
+        if !passwords.initialized() {
+            writeln!(out(), "Initialized: no").unwrap();
+            return Ok(());
+        }
+        writeln!(out(), "Initialized: yes").unwrap();
+
+        if let Some(format) = passwords.io().format_version() {
+            writeln!(out(), "Format version: {}", format).unwrap();
+        }
+        let kdf_params = passwords.kdf_params().convert_error()?;
+        writeln!(out(), "Key derivation: {}", kdf_params).unwrap();
+        writeln!(
+            out(),
+            "Salt length: {} bytes",
+            passwords.salt_length().convert_error()?
+        )
+        .unwrap();
+
+        if *unlock {
+            ensure_unlocked_passwords(passwords, args)?;
+
+            let statistics = passwords.statistics().convert_error()?;
+            writeln!(out(), "Sites: {}", statistics.sites).unwrap();
+            writeln!(out(), "Aliases: {}", statistics.aliases).unwrap();
+            writeln!(
+                out(),
+                "Passwords: {} ({} generated, {} stored)",
+                statistics.generated + statistics.stored,
+                statistics.generated,
+                statistics.stored
+            )
+            .unwrap();
+        }
+    }
+
+    Ok(())
+}
@@ -4,28 +4,129 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
-use super::utils::{ensure_unlocked_passwords, ConvertError};
-use crate::args::{Args, Commands};
-use io_streams::StreamWriter;
+use super::utils::{
+    confirm_primary_password, ensure_unlocked_passwords, format_timestamp, out, secret_output,
+    CommandError, ConvertError, DeferInterrupts,
+};
+use crate::args::{Args, Commands, PasswordType};
 use pfp::passwords::Passwords;
 use pfp::storage_io;
 use pfp::storage_types::{CharacterType, Password, Site};
-use secrecy::ExposeSecret;
+use secrecy::{ExposeSecret, SecretString};
+use serde::Serialize;
 use std::io::Write;
 
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    site: &'a str,
+    name: &'a str,
+    revision: &'a str,
+    #[serde(rename = "type")]
+    password_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    charset: Option<Vec<&'static str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<u64>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "pfp::secret_serialization::option"
+    )]
+    notes: Option<SecretString>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "pfp::secret_serialization::option"
+    )]
+    value: Option<SecretString>,
+}
+
+impl<'a> JsonEntry<'a> {
+    fn new(
+        site: &'a str,
+        password: &'a Password,
+        notes: bool,
+        value: Option<SecretString>,
+    ) -> Self {
+        let mut entry = Self {
+            site,
+            name: password.id().name(),
+            revision: password.id().revision(),
+            password_type: "stored",
+            length: None,
+            charset: None,
+            created: password.created(),
+            modified: password.modified(),
+            tags: password.tags(),
+            notes: None,
+            value,
+        };
+
+        if let Password::Generated(generated) | Password::LegacyGenerated(generated) = password {
+            entry.password_type = match password {
+                Password::LegacyGenerated(_) => "legacy generated",
+                _ => "generated",
+            };
+            entry.length = Some(generated.length());
+            entry.charset = Some(
+                [
+                    (CharacterType::Lower, "lower"),
+                    (CharacterType::Upper, "upper"),
+                    (CharacterType::Digit, "number"),
+                    (CharacterType::Symbol, "symbol"),
+                ]
+                .into_iter()
+                .filter(|(chartype, _)| generated.charset().contains(*chartype))
+                .map(|(_, name)| name)
+                .collect(),
+            );
+        }
+
+        if notes && !password.notes().expose_secret().is_empty() {
+            entry.notes = Some(password.notes().clone());
+        }
+        entry
+    }
+}
+
 pub fn processor<IO: storage_io::StorageIO>(
     args: &Args,
     passwords: &mut Passwords<IO>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
+    // Site entries are resolved repeatedly while listing, only decrypt each of them once
+    passwords.set_site_cache(true);
+    let result = list(args, passwords);
+    passwords.set_site_cache(false);
+    result
+}
+
+fn list<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
     if let Commands::List {
         domain,
         name,
         show,
         recovery,
         verbose,
+        password_type,
+        offset,
+        limit,
+        tag,
+        modified_since,
+        include_undated,
+        json,
     } = &args.command
     {
-        ensure_unlocked_passwords(passwords, args.stdin_passwords)?;
+        ensure_unlocked_passwords(passwords, args)?;
+        if *show || *recovery {
+            confirm_primary_password(passwords, args)?;
+        }
 
         let mut empty_sites = Vec::new();
 
@@ -55,9 +156,44 @@ pub fn processor<IO: storage_io::StorageIO>(
             None => true,
         });
 
+        // When listing all sites, retrieve all passwords in one go instead of site by site
+        let mut all_passwords = if domain == "*" {
+            let matcher = wildmatch::WildMatch::new(name);
+            let mut map = std::collections::HashMap::<String, Vec<Password>>::new();
+            for password in passwords.list_all() {
+                if matcher.matches(password.id().name()) {
+                    map.entry(password.id().site().to_string())
+                        .or_default()
+                        .push(password);
+                }
+            }
+            Some(map)
+        } else {
+            None
+        };
+
+        // Deriving passwords for --show can take a while, allow aborting it cleanly
+        let interrupts = DeferInterrupts::new();
+
+        // JSON output goes through an unbuffered writer, so that password values aren't copied
+        let mut json_writer = if *json {
+            std::io::stdout().flush().unwrap();
+            let mut writer = secret_output();
+            writer.write_all(b"[").unwrap();
+            Some(writer)
+        } else {
+            None
+        };
+
+        let mut skip = offset.unwrap_or(0);
+        let mut remaining = limit.unwrap_or(usize::MAX);
         let mut found = false;
         for site in sites {
-            let mut list = passwords.list(site.name(), name).collect::<Vec<Password>>();
+            interrupts.check_interrupted()?;
+            let mut list = match &mut all_passwords {
+                Some(map) => map.remove(site.name()).unwrap_or_default(),
+                None => passwords.list(site.name(), name).collect::<Vec<Password>>(),
+            };
             if list.is_empty() {
                 if name == "*" {
                     empty_sites.push(site.name().to_string());
@@ -65,35 +201,113 @@ pub fn processor<IO: storage_io::StorageIO>(
                 continue;
             }
 
-            found = true;
-            println!("Passwords for {}:", site.name());
-            if *verbose {
-                if let Some(aliased) = aliases.get(site.name()) {
-                    println!("    Aliases: {}", aliased.join(",\n             "));
+            if let Some(password_type) = password_type {
+                list.retain(|password| match password {
+                    Password::Generated(_) | Password::LegacyGenerated(_) => {
+                        *password_type == PasswordType::Generated
+                    }
+                    Password::Stored(_) => *password_type == PasswordType::Stored,
+                });
+                if list.is_empty() {
+                    continue;
+                }
+            }
+
+            if let Some(tag) = tag {
+                list.retain(|password| password.has_tag(tag));
+                if list.is_empty() {
+                    continue;
+                }
+            }
+
+            if let Some(since) = modified_since {
+                list.retain(|password| match password.modified() {
+                    Some(modified) => modified >= *since,
+                    None => *include_undated,
+                });
+                if list.is_empty() {
+                    continue;
                 }
             }
 
             list.sort_by_key(|password| {
                 password.id().name().to_string() + " " + password.id().revision()
             });
+
+            if skip >= list.len() {
+                skip -= list.len();
+                continue;
+            }
+            list.drain(..skip);
+            skip = 0;
+            list.truncate(remaining);
+            remaining -= list.len();
+            if list.is_empty() {
+                continue;
+            }
+
+            if let Some(writer) = &mut json_writer {
+                for password in list {
+                    interrupts.check_interrupted()?;
+
+                    let value = if *show {
+                        let id = password.id();
+                        Some(
+                            passwords
+                                .get(site.name(), id.name(), id.revision())
+                                .convert_error()?,
+                        )
+                    } else {
+                        None
+                    };
+                    if found {
+                        writer.write_all(b",").unwrap();
+                    }
+                    found = true;
+                    serde_json::to_writer(
+                        &mut *writer,
+                        &JsonEntry::new(site.name(), &password, *verbose, value),
+                    )
+                    .map_err(|error| error.to_string())?;
+                }
+                continue;
+            }
+
+            found = true;
+            writeln!(out(), "Passwords for {}:", site.name()).unwrap();
+            if *verbose {
+                if let Some(aliased) = aliases.get(site.name()) {
+                    writeln!(out(), "    Aliases: {}", aliased.join(",\n             ")).unwrap();
+                }
+            }
+
             for password in list {
+                interrupts.check_interrupted()?;
+
                 let name = password.id().name().to_owned();
                 let revision = password.id().revision().to_owned();
                 let password_type = match &password {
                     Password::Generated(_) => "generated",
+                    Password::LegacyGenerated(_) => "legacy generated",
                     Password::Stored(_) => "stored",
                 };
                 if !revision.is_empty() {
-                    println!("    {} ({}, revision: {})", name, password_type, revision);
+                    writeln!(
+                        out(),
+                        "    {} ({}, revision: {})",
+                        name,
+                        password_type,
+                        revision
+                    )
+                    .unwrap();
                 } else {
-                    println!("    {} ({})", name, password_type);
+                    writeln!(out(), "    {} ({})", name, password_type).unwrap();
                 }
 
                 if *show {
-                    print!("        ");
+                    write!(out(), "        ").unwrap();
                     std::io::stdout().flush().unwrap();
-                    StreamWriter::stdout()
-                        .unwrap()
+                    secret_output()
                         .write_all(
                             passwords
                                 .get(site.name(), &name, &revision)
@@ -102,18 +316,18 @@ pub fn processor<IO: storage_io::StorageIO>(
                                 .as_bytes(),
                         )
                         .unwrap();
-                    println!();
+                    writeln!(out()).unwrap();
                 }
 
                 if *recovery {
                     if let Password::Stored(password) = &password {
-                        println!("        Recovery code:");
+                        writeln!(out(), "        Recovery code:").unwrap();
                         for line in passwords
                             .get_recovery_code(password)
                             .convert_error()?
                             .split('\n')
                         {
-                            println!("        {}", line);
+                            writeln!(out(), "        {}", line).unwrap();
                         }
                     }
                 }
@@ -121,17 +335,18 @@ pub fn processor<IO: storage_io::StorageIO>(
                 if *verbose {
                     let notes = password.notes();
                     if !notes.expose_secret().is_empty() {
-                        print!("        Notes: ");
+                        write!(out(), "        Notes: ").unwrap();
                         std::io::stdout().flush().unwrap();
-                        StreamWriter::stdout()
-                            .unwrap()
+                        secret_output()
                             .write_all(notes.expose_secret().as_bytes())
                             .unwrap();
-                        println!();
+                        writeln!(out()).unwrap();
                     }
 
-                    if let Password::Generated(password) = &password {
-                        println!("        Length: {}", password.length());
+                    if let Password::Generated(password) | Password::LegacyGenerated(password) =
+                        &password
+                    {
+                        writeln!(out(), "        Length: {}", password.length()).unwrap();
 
                         let mut chars = Vec::new();
                         if password.charset().contains(CharacterType::Lower) {
@@ -144,9 +359,23 @@ pub fn processor<IO: storage_io::StorageIO>(
                             chars.push("789");
                         }
                         if password.charset().contains(CharacterType::Symbol) {
-                            chars.push("+^;");
+                            chars.push(password.symbols().unwrap_or("+^;"));
+                        }
+                        writeln!(out(), "        Allowed characters: {}", chars.join(" ")).unwrap();
+                    }
+
+                    if !password.tags().is_empty() {
+                        writeln!(out(), "        Tags: {}", password.tags().join(", ")).unwrap();
+                    }
+
+                    if let Some(created) = password.created() {
+                        writeln!(out(), "        Created: {}", format_timestamp(created)).unwrap();
+                    }
+                    if let Some(modified) = password.modified() {
+                        if password.created() != Some(modified) {
+                            writeln!(out(), "        Modified: {}", format_timestamp(modified))
+                                .unwrap();
                         }
-                        println!("        Allowed characters: {}", chars.join(" "));
                     }
                 }
             }
@@ -154,8 +383,26 @@ pub fn processor<IO: storage_io::StorageIO>(
 
         passwords.remove_sites(&empty_sites).convert_error()?;
 
-        if !found {
-            println!("No matching passwords found.");
+        if let Some(writer) = &mut json_writer {
+            writer.write_all(b"]\n").unwrap();
+        } else if !found {
+            writeln!(out(), "No matching passwords found.").unwrap();
+
+            // Without any filters, nothing found means that the storage is empty
+            if domain == "*"
+                && name == "*"
+                && password_type.is_none()
+                && tag.is_none()
+                && modified_since.is_none()
+                && offset.unwrap_or(0) == 0
+                && *limit != Some(0)
+            {
+                writeln!(
+                    out(),
+                    "No passwords stored yet. Maybe use add or add-stored subcommand to add one?"
+                )
+                .unwrap();
+            }
         }
     }
 
@@ -0,0 +1,52 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::ListAliases { reverse } = &args.command {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let mut aliases = BTreeMap::<String, Vec<String>>::new();
+        for site in passwords.list_sites("*") {
+            if let Some(target) = site.alias() {
+                aliases
+                    .entry(target.to_string())
+                    .or_default()
+                    .push(site.name().to_string());
+            }
+        }
+
+        if aliases.is_empty() {
+            writeln!(out(), "No aliases found.").unwrap();
+            return Ok(());
+        }
+
+        for (target, mut sites) in aliases {
+            sites.sort();
+            if *reverse {
+                writeln!(out(), "Aliases for {}:", target).unwrap();
+                for site in sites {
+                    writeln!(out(), "    {}", site).unwrap();
+                }
+            } else {
+                for site in sites {
+                    writeln!(out(), "{} -> {}", site, target).unwrap();
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::MigrateGenerated { regenerate } = &args.command {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        if *regenerate {
+            let allow = question::Question::new(
+                "Regenerating will change the values of all legacy passwords. Continue?",
+            )
+            .default(question::Answer::NO)
+            .show_defaults()
+            .confirm();
+            if allow == question::Answer::NO {
+                return Ok(());
+            }
+        }
+
+        let count = passwords.migrate_generated(*regenerate).convert_error()?;
+        if count == 0 {
+            writeln!(out(), "No legacy generated passwords found.").unwrap();
+        } else if *regenerate {
+            writeln!(
+                out(),
+                "Converted {} legacy password(s), these need to be changed on the websites now.",
+                count
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                out(),
+                "Converted {} legacy password(s) to stored passwords.",
+                count
+            )
+            .unwrap();
+        }
+    }
+
+    Ok(())
+}
@@ -7,32 +7,92 @@
 mod add;
 mod add_stored;
 mod alias;
+mod audit;
+mod change_charset;
+mod change_length;
+mod change_primary;
+mod duplicate;
+mod duplicates;
+mod export;
+mod export_csv;
+mod help_json;
+mod history;
+mod import;
+mod import_csv;
+mod info;
 mod list;
+mod list_aliases;
+mod migrate_generated;
+mod move_password;
 mod notes;
+mod profiles;
+mod recovery;
 mod remove;
+mod rename;
+mod rotate;
+mod search;
+mod set_alias;
+mod set_kdf;
+mod set_policy;
 mod set_primary;
 mod shell;
 mod show;
+mod stats;
+mod tag;
 pub mod utils;
+mod verify;
 
 use crate::args::{Args, Commands};
 use pfp::passwords::Passwords;
 use pfp::storage_io;
+use utils::CommandError;
+
+/// Checks whether a command needs the storage file to be loaded.
+pub fn needs_storage(command: &Commands) -> bool {
+    !matches!(command, Commands::HelpJson | Commands::Profiles)
+}
 
 pub fn process_command<IO: storage_io::StorageIO>(
     args: Args,
     storage_path: &std::path::PathBuf,
     passwords: &mut Passwords<IO>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     match &args.command {
         Commands::SetPrimary { .. } => set_primary::processor(&args, storage_path, passwords),
+        Commands::ChangePrimary { .. } => change_primary::processor(&args, passwords),
+        Commands::SetKdf { .. } => set_kdf::processor(&args, passwords),
         Commands::Add { .. } => add::processor(&args, passwords),
         Commands::AddStored { .. } => add_stored::processor(&args, passwords),
         Commands::Remove { .. } => remove::processor(&args, passwords),
+        Commands::Duplicate { .. } => duplicate::processor(&args, passwords),
+        Commands::Move { .. } => move_password::processor(&args, passwords),
+        Commands::Rename { .. } => rename::processor(&args, passwords),
+        Commands::ChangeLength { .. } => change_length::processor(&args, passwords),
+        Commands::ChangeCharset { .. } => change_charset::processor(&args, passwords),
+        Commands::Rotate { .. } => rotate::processor(&args, passwords),
         Commands::Show { .. } => show::processor(&args, passwords),
+        Commands::Recovery { .. } => recovery::processor(&args, passwords),
         Commands::Notes { .. } => notes::processor(&args, passwords),
+        Commands::History { .. } => history::processor(&args, passwords),
+        Commands::Tag { .. } => tag::processor(&args, passwords),
         Commands::List { .. } => list::processor(&args, passwords),
+        Commands::ListAliases { .. } => list_aliases::processor(&args, passwords),
+        Commands::Search { .. } => search::processor(&args, passwords),
+        Commands::Export { .. } => export::processor(&args, passwords),
+        Commands::ExportCsv { .. } => export_csv::processor(&args, passwords),
+        Commands::Import { .. } => import::processor(&args, passwords),
+        Commands::ImportCsv { .. } => import_csv::processor(&args, passwords),
         Commands::Alias { .. } => alias::processor(&args, passwords),
+        Commands::SetAlias { .. } => set_alias::processor(&args, passwords),
+        Commands::SetPolicy { .. } => set_policy::processor(&args, passwords),
+        Commands::MigrateGenerated { .. } => migrate_generated::processor(&args, passwords),
+        Commands::Info { .. } => info::processor(&args, storage_path, passwords),
+        Commands::Profiles => profiles::processor(),
+        Commands::Stats => stats::processor(&args, passwords),
+        Commands::Verify => verify::processor(&args, passwords),
+        Commands::Audit { .. } => audit::processor(&args, passwords),
+        Commands::Duplicates { .. } => duplicates::processor(&args, passwords),
+        Commands::HelpJson => help_json::processor(),
         Commands::Shell { .. } => shell::processor(&args, storage_path, passwords),
     }
 }
@@ -4,52 +4,90 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
-use super::utils::{ensure_unlocked_passwords, prompt_secret_text, ConvertError};
+use super::utils::{
+    copy_to_clipboard, ensure_unlocked_passwords, out, prompt_secret_text, read_secret_file,
+    read_secret_text, secret_output, CommandError, ConvertError,
+};
 use crate::args::{Args, Commands};
-use io_streams::StreamWriter;
 use pfp::passwords::Passwords;
 use pfp::storage_io;
-use secrecy::ExposeSecret;
+use secrecy::{ExposeSecret, SecretString};
 use std::io::Write;
 
 pub fn processor<IO: storage_io::StorageIO>(
     args: &Args,
     passwords: &mut Passwords<IO>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     if let Commands::Notes {
         domain,
         name,
         revision,
         set,
+        set_from_file,
+        set_stdin,
+        clipboard,
+        clear_after,
     } = &args.command
     {
-        ensure_unlocked_passwords(passwords, args.stdin_passwords)?;
+        ensure_unlocked_passwords(passwords, args)?;
+
+        // Non-interactive input replaces the notes without displaying the old ones first.
+        let new_notes = if let Some(path) = set_from_file {
+            Some(read_secret_file(path)?)
+        } else if *set_stdin {
+            Some(read_secret_text()?)
+        } else {
+            None
+        };
+        if let Some(notes) = new_notes {
+            return store_notes(passwords, domain, name, revision, notes);
+        }
 
         let notes = passwords
             .get_notes(domain, name, revision)
             .convert_error()?;
         if notes.expose_secret().is_empty() {
-            println!("Currently no notes are stored for this password.");
+            writeln!(out(), "Currently no notes are stored for this password.").unwrap();
+        } else if *clipboard {
+            copy_to_clipboard(
+                &notes,
+                &format!(
+                    "Copied to clipboard, it will be cleared in {} seconds.",
+                    clear_after
+                ),
+                std::time::Duration::from_secs(*clear_after),
+            )?;
         } else {
-            let mut stdout = StreamWriter::stdout().unwrap();
+            let mut stdout = secret_output();
             stdout.write_all(b"Notes for this password: ").unwrap();
             stdout.write_all(notes.expose_secret().as_bytes()).unwrap();
             stdout.write_all(b"\n").unwrap();
         }
 
         if *set {
-            let notes = prompt_secret_text("Please enter new notes to be stored:");
-            let removing = notes.expose_secret().is_empty();
-            passwords
-                .set_notes(domain, name, revision, notes)
-                .convert_error()?;
-            if removing {
-                println!("Notes removed.");
-            } else {
-                println!("Notes stored.");
-            }
+            let notes = prompt_secret_text("Please enter new notes to be stored:")?;
+            store_notes(passwords, domain, name, revision, notes)?;
         }
     }
 
     Ok(())
 }
+
+fn store_notes<IO: storage_io::StorageIO>(
+    passwords: &mut Passwords<IO>,
+    domain: &str,
+    name: &str,
+    revision: &str,
+    notes: SecretString,
+) -> Result<(), CommandError> {
+    let removing = notes.expose_secret().is_empty();
+    passwords
+        .set_notes(domain, name, revision, notes)
+        .convert_error()?;
+    if removing {
+        writeln!(out(), "Notes removed.").unwrap();
+    } else {
+        writeln!(out(), "Notes stored.").unwrap();
+    }
+    Ok(())
+}
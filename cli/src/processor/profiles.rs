@@ -0,0 +1,41 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{get_config_dir, out, CommandError, DEFAULT_PROFILE};
+use std::io::Write;
+
+pub fn processor() -> Result<(), CommandError> {
+    let mut profiles = match std::fs::read_dir(get_config_dir()) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension() == Some("json".as_ref()))
+            .filter_map(|path| {
+                path.file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .map(|stem| stem.to_string())
+            })
+            .collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+    profiles.sort();
+
+    if profiles.is_empty() {
+        writeln!(out(), "No profiles found.").unwrap();
+        return Ok(());
+    }
+
+    writeln!(out(), "Profiles:").unwrap();
+    for profile in profiles {
+        if profile == DEFAULT_PROFILE {
+            writeln!(out(), "    {} (default)", profile).unwrap();
+        } else {
+            writeln!(out(), "    {}", profile).unwrap();
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, get_recovery_code, out, write_qrcode, CommandError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use pfp::storage_types::PasswordId;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Recovery {
+        domain,
+        name,
+        revision,
+        qrcode,
+    } = &args.command
+    {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let code = get_recovery_code(passwords, &PasswordId::new(domain, name, revision))?;
+        writeln!(out(), "Recovery code:").unwrap();
+        if *qrcode {
+            write_qrcode(&mut std::io::stdout(), &code)?;
+        } else {
+            writeln!(out(), "{}", code).unwrap();
+        }
+    }
+
+    Ok(())
+}
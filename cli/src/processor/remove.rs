@@ -4,25 +4,81 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
-use super::utils::{ensure_unlocked_passwords, ConvertError};
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
 use crate::args::{Args, Commands};
 use pfp::passwords::Passwords;
 use pfp::storage_io;
+use pfp::storage_types::PasswordId;
+use std::io::Write;
+
+fn is_pattern(name: &str) -> bool {
+    name.contains(['*', '?'])
+}
 
 pub fn processor<IO: storage_io::StorageIO>(
     args: &Args,
     passwords: &mut Passwords<IO>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     if let Commands::Remove {
         domain,
         name,
         revision,
+        assume_yes,
     } = &args.command
     {
-        ensure_unlocked_passwords(passwords, args.stdin_passwords)?;
+        ensure_unlocked_passwords(passwords, args)?;
+
+        if !is_pattern(name) {
+            let revision = revision.as_deref().unwrap_or("1");
+            passwords.remove(domain, name, revision).convert_error()?;
+            writeln!(out(), "Password removed.").unwrap();
+            return Ok(());
+        }
+
+        let revision = revision.as_deref().map(|revision| {
+            PasswordId::new(domain, name, revision)
+                .revision()
+                .to_owned()
+        });
+        let ids = passwords
+            .list(domain, name)
+            .map(|password| password.id().clone())
+            .filter(|id| match &revision {
+                Some(revision) => id.revision() == revision,
+                None => true,
+            })
+            .collect::<Vec<_>>();
+        if ids.is_empty() {
+            return Err("No matching passwords found.".into());
+        }
+
+        if !assume_yes {
+            writeln!(out(), "The following passwords will be removed:").unwrap();
+            for id in &ids {
+                if !id.revision().is_empty() {
+                    writeln!(out(), "    {} (revision: {})", id.name(), id.revision()).unwrap();
+                } else {
+                    writeln!(out(), "    {}", id.name()).unwrap();
+                }
+            }
+
+            let allow = question::Question::new("Remove these passwords?")
+                .default(question::Answer::NO)
+                .show_defaults()
+                .confirm();
+            if allow == question::Answer::NO {
+                return Ok(());
+            }
+        }
 
-        passwords.remove(domain, name, revision).convert_error()?;
-        println!("Password removed.");
+        passwords
+            .batch(|passwords| {
+                ids.iter()
+                    .try_for_each(|id| passwords.remove(id.site(), id.name(), id.revision()))
+            })
+            .and_then(|result| result)
+            .convert_error()?;
+        writeln!(out(), "{} password(s) removed.", ids.len()).unwrap();
     }
 
     Ok(())
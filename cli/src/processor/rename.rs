@@ -0,0 +1,80 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use pfp::storage_types::{Password, PasswordId};
+use std::io::Write;
+
+fn confirm(message: &str) -> bool {
+    question::Question::new(message)
+        .default(question::Answer::NO)
+        .show_defaults()
+        .confirm()
+        == question::Answer::YES
+}
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Rename {
+        domain,
+        name,
+        new_name,
+        revision,
+        new_revision,
+        assume_yes,
+    } = &args.command
+    {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let new_revision = new_revision.as_ref().unwrap_or(revision);
+        if !assume_yes {
+            let id = PasswordId::new(domain, name, revision);
+            let generated = passwords
+                .list(domain, name)
+                .filter(|password| {
+                    password.id().name() == id.name() && password.id().revision() == id.revision()
+                })
+                .any(|password| {
+                    matches!(
+                        password,
+                        Password::Generated(_) | Password::LegacyGenerated(_)
+                    )
+                });
+            if generated
+                && (name != new_name || revision != new_revision)
+                && !confirm(
+                    "This is a generated password, its value will change when renamed. Continue?",
+                )
+            {
+                return Ok(());
+            }
+
+            if passwords.has(domain, name, revision).unwrap_or(false)
+                && passwords
+                    .has(domain, new_name, new_revision)
+                    .unwrap_or(false)
+                && (name != new_name || revision != new_revision)
+                && !confirm(
+                    "A password with this domain/name/revision combination already exists. Overwrite?",
+                )
+            {
+                return Ok(());
+            }
+        }
+
+        passwords
+            .rename(domain, name, revision, new_name, new_revision, true)
+            .convert_error()?;
+        writeln!(out(), "Password renamed.").unwrap();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, secret_output, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use secrecy::ExposeSecret;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Rotate {
+        domain,
+        name,
+        replace,
+    } = &args.command
+    {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let revision = passwords.rotate(domain, name, *replace).convert_error()?;
+        writeln!(out(), "Password rotated, new revision is {}.", revision).unwrap();
+
+        let password = passwords.get(domain, name, &revision).convert_error()?;
+        let mut stdout = secret_output();
+        stdout
+            .write_all(password.expose_secret().as_bytes())
+            .unwrap();
+        stdout.write_all(b"\n").unwrap();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use pfp::storage_types::{Password, Site};
+use secrecy::{ExposeSecret, SecretString};
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Search { query, notes } = &args.command {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let query = query.to_lowercase();
+
+        // Collect sites along with the names of their aliases, an alias matching the query
+        // makes the site it is an alias for match.
+        let mut sites = std::collections::BTreeMap::new();
+        for site in passwords.list_sites("*").collect::<Vec<Site>>() {
+            match site.alias() {
+                Some(alias) => sites
+                    .entry(alias.to_owned())
+                    .or_insert_with(Vec::new)
+                    .push(site.name().to_owned()),
+                None => {
+                    sites.entry(site.name().to_owned()).or_insert_with(Vec::new);
+                }
+            }
+        }
+
+        let mut found = false;
+        for (site, aliases) in sites {
+            let site_matches = site.to_lowercase().contains(&query)
+                || aliases
+                    .iter()
+                    .any(|alias| alias.to_lowercase().contains(&query));
+
+            let mut list = passwords
+                .list(&site, "*")
+                .filter(|password| {
+                    site_matches
+                        || password.id().name().to_lowercase().contains(&query)
+                        || (*notes
+                            && SecretString::new(password.notes().expose_secret().to_lowercase())
+                                .expose_secret()
+                                .contains(&query))
+                })
+                .collect::<Vec<Password>>();
+            if list.is_empty() {
+                continue;
+            }
+
+            list.sort_by_key(|password| {
+                password.id().name().to_string() + " " + password.id().revision()
+            });
+
+            found = true;
+            writeln!(out(), "Passwords for {}:", site).unwrap();
+            for password in list {
+                let name = password.id().name();
+                let revision = password.id().revision();
+                let password_type = match &password {
+                    Password::Generated(_) => "generated",
+                    Password::LegacyGenerated(_) => "legacy generated",
+                    Password::Stored(_) => "stored",
+                };
+                if !revision.is_empty() {
+                    writeln!(
+                        out(),
+                        "    {} ({}, revision: {})",
+                        name,
+                        password_type,
+                        revision
+                    )
+                    .unwrap();
+                } else {
+                    writeln!(out(), "    {} ({})", name, password_type).unwrap();
+                }
+            }
+        }
+
+        if !found {
+            writeln!(out(), "No matching passwords found.").unwrap();
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use pfp::storage_types::Password;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::SetAlias {
+        target,
+        domains,
+        migrate,
+        assume_yes,
+    } = &args.command
+    {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let domains = domains
+            .iter()
+            .map(|domain| domain.as_str())
+            .collect::<Vec<&str>>();
+
+        if !migrate {
+            passwords.set_aliases(&domains, target).convert_error()?;
+            for domain in domains {
+                writeln!(out(), "'{}' is now an alias for '{}'.", domain, target).unwrap();
+            }
+            return Ok(());
+        }
+
+        if !assume_yes {
+            let generated = domains
+                .iter()
+                .filter(|domain| {
+                    passwords
+                        .site_info(domain)
+                        .map(|site| site.alias().is_none())
+                        .unwrap_or(false)
+                })
+                .flat_map(|domain| passwords.list(domain, "*"))
+                .any(|password| {
+                    matches!(
+                        password,
+                        Password::Generated(_) | Password::LegacyGenerated(_)
+                    )
+                });
+            if generated {
+                let allow = question::Question::new(
+                    "Generated passwords will change their values when moved to a different website. Continue?",
+                )
+                .default(question::Answer::NO)
+                .show_defaults()
+                .confirm();
+                if allow == question::Answer::NO {
+                    return Ok(());
+                }
+            }
+        }
+
+        for domain in domains {
+            let count = passwords
+                .set_alias_migrating(domain, target)
+                .convert_error()?;
+            writeln!(
+                out(),
+                "'{}' is now an alias for '{}', {} password(s) moved.",
+                domain,
+                target,
+                count
+            )
+            .unwrap();
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,26 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, get_kdf_params, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::SetKdf { kdf, iterations } = &args.command {
+        let kdf_params = get_kdf_params(*kdf, *iterations)?;
+
+        ensure_unlocked_passwords(passwords, args)?;
+        passwords.set_kdf_params(kdf_params).convert_error()?;
+        writeln!(out(), "Key derivation parameters changed.").unwrap();
+    }
+
+    Ok(())
+}
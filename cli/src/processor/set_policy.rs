@@ -0,0 +1,67 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use pfp::storage_types::{CharacterSet, CharacterType, PasswordPolicy};
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::SetPolicy {
+        domain,
+        min_length,
+        max_length,
+        no_lower,
+        no_upper,
+        no_digit,
+        no_symbol,
+        forbidden,
+        remove,
+    } = &args.command
+    {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        if *remove {
+            passwords.set_policy(domain, None).convert_error()?;
+            writeln!(out(), "Password policy removed.").unwrap();
+            return Ok(());
+        }
+
+        if let (Some(min_length), Some(max_length)) = (min_length, max_length) {
+            if min_length > max_length {
+                return Err("Minimal length cannot exceed maximal length.".into());
+            }
+        }
+
+        let mut charset = CharacterSet::empty();
+        if !no_lower {
+            charset.insert(CharacterType::Lower);
+        }
+        if !no_upper {
+            charset.insert(CharacterType::Upper);
+        }
+        if !no_digit {
+            charset.insert(CharacterType::Digit);
+        }
+        if !no_symbol {
+            charset.insert(CharacterType::Symbol);
+        }
+        if charset.is_empty() {
+            return Err("You need to allow at least one character set.".into());
+        }
+
+        let policy = PasswordPolicy::new(*min_length, *max_length, charset, forbidden);
+        passwords.set_policy(domain, Some(policy)).convert_error()?;
+        writeln!(out(), "Password policy set.").unwrap();
+    }
+
+    Ok(())
+}
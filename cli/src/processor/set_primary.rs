@@ -4,32 +4,47 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
-use super::utils::{prompt_password, ConvertError};
-use crate::args::Args;
+use super::utils::{get_kdf_params, out, prompt_password, CommandError, ConvertError};
+use crate::args::{Args, Commands};
 use pfp::passwords::Passwords;
 use pfp::storage_io;
+use pfp::storage_types::KdfParams;
 use secrecy::ExposeSecret;
+use std::io::Write;
 
 pub fn processor<IO: storage_io::StorageIO>(
     args: &Args,
     storage_path: &std::path::Path,
     passwords: &mut Passwords<IO>,
-) -> Result<(), String> {
-    let primary_password = prompt_password("New primary password: ", args.stdin_passwords);
+) -> Result<(), CommandError> {
+    let primary_password = prompt_password("New primary password: ", args.stdin_passwords)?;
     if primary_password.expose_secret().len() < 6 {
-        return Err("Primary password length should be at least 6 characters.".to_owned());
+        return Err("Primary password length should be at least 6 characters.".into());
     }
 
-    let primary_password2 = prompt_password("Repeat primary password: ", args.stdin_passwords);
+    let primary_password2 = prompt_password("Repeat primary password: ", args.stdin_passwords)?;
     if primary_password.expose_secret() != primary_password2.expose_secret() {
-        return Err("Primary passwords don't match.".to_owned());
+        return Err("Primary passwords don't match.".into());
     }
 
-    passwords.reset(primary_password).convert_error()?;
-    println!(
-        "New primary password set for {}.",
-        storage_path.to_string_lossy()
-    );
+    let kdf_params = match &args.command {
+        Commands::SetPrimary { kdf, .. } => get_kdf_params(*kdf, None)?,
+        _ => KdfParams::default(),
+    };
+
+    passwords
+        .reset_with_kdf_params(primary_password, kdf_params)
+        .convert_error()?;
+    if args.stdin_storage {
+        writeln!(out(), "New primary password set.").unwrap();
+    } else {
+        writeln!(
+            out(),
+            "New primary password set for {}.",
+            storage_path.to_string_lossy()
+        )
+        .unwrap();
+    }
 
     Ok(())
 }
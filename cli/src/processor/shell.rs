@@ -4,6 +4,7 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
+use super::utils::{check_interrupted, get_config_dir, out, CommandError, ConvertError};
 use crate::args::{Args, Commands};
 use clap::{CommandFactory, FromArgMatches};
 use pfp::passwords::Passwords;
@@ -12,11 +13,7 @@ use rustyline::error::ReadlineError;
 use std::io::Write;
 
 fn get_default_history_path() -> std::path::PathBuf {
-    let app_info = app_dirs2::AppInfo {
-        name: "PfP",
-        author: "Wladimir Palant",
-    };
-    let mut path = app_dirs2::get_app_root(app_dirs2::AppDataType::UserConfig, &app_info).unwrap();
+    let mut path = get_config_dir();
     path.push("history.txt");
     path
 }
@@ -25,14 +22,19 @@ pub fn processor<IO: storage_io::StorageIO>(
     args: &Args,
     storage_path: &std::path::PathBuf,
     passwords: &mut Passwords<IO>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     let history_path = match &args.command {
         Commands::Shell {
             history: Some(value),
+            ..
         } => value.clone(),
         _ => get_default_history_path(),
     };
 
+    if let Commands::Shell { cache: true, .. } = &args.command {
+        passwords.set_derivation_cache(true);
+    }
+
     let mut editor = rustyline::Editor::<()>::new();
     if let Err(error) = editor.load_history(&history_path) {
         eprintln!(
@@ -42,7 +44,7 @@ pub fn processor<IO: storage_io::StorageIO>(
         );
     }
 
-    println!("Enter a command or type 'help' for a list of commands. Enter 'help <command>' for detailed information on a command.");
+    writeln!(out(), "Enter a command or type 'help' for a list of commands. Enter 'help <command>' for detailed information on a command.").unwrap();
     std::io::stdout().flush().unwrap();
     loop {
         match editor.readline("pfp> ") {
@@ -72,6 +74,14 @@ pub fn processor<IO: storage_io::StorageIO>(
                         .about("Exits the shell"))
                     .subcommand(clap::Command::new("lock")
                         .about("Locks passwords, so that the next operation will ask for the primary password again"))
+                    .subcommand(clap::Command::new("begin")
+                        .about("Starts a transaction, changes will not be saved until the transaction is committed"))
+                    .subcommand(clap::Command::new("commit")
+                        .about("Saves all changes made since the transaction started"))
+                    .subcommand(clap::Command::new("rollback")
+                        .about("Discards all changes made since the transaction started, reloading storage file"))
+                    .subcommand(clap::Command::new("reload")
+                        .about("Discards all unsaved changes, reloading storage file"))
                     .mut_subcommand("shell", |subcmd| subcmd.hide(true))
                     .mut_subcommand("set-primary", |subcmd| subcmd.hide(true))
                     .help_template("COMMANDS:\n{subcommands}");
@@ -87,7 +97,31 @@ pub fn processor<IO: storage_io::StorageIO>(
                 }
                 if let Some(("lock", _)) = matches.subcommand() {
                     passwords.lock();
-                    println!("Passwords locked.");
+                    writeln!(out(), "Passwords locked.").unwrap();
+                    continue;
+                }
+                if let Some(("begin", _)) = matches.subcommand() {
+                    print_errors!(passwords.begin_transaction().convert_error());
+                    writeln!(
+                        out(),
+                        "Transaction started, changes will not be saved until committed."
+                    )
+                    .unwrap();
+                    continue;
+                }
+                if let Some(("commit", _)) = matches.subcommand() {
+                    print_errors!(passwords.commit_transaction().convert_error());
+                    writeln!(out(), "Changes saved.").unwrap();
+                    continue;
+                }
+                if let Some(("rollback", _)) = matches.subcommand() {
+                    print_errors!(passwords.rollback_transaction().convert_error());
+                    writeln!(out(), "Changes discarded.").unwrap();
+                    continue;
+                }
+                if let Some(("reload", _)) = matches.subcommand() {
+                    print_errors!(passwords.reload().convert_error());
+                    writeln!(out(), "Storage file reloaded.").unwrap();
                     continue;
                 }
                 if let Some(("shell", _)) = matches.subcommand() {
@@ -101,8 +135,15 @@ pub fn processor<IO: storage_io::StorageIO>(
 
                 let mut new_args = print_errors!(Args::from_arg_matches(&matches));
                 new_args.stdin_passwords = args.stdin_passwords;
+                new_args.stdin_primary_once = args.stdin_primary_once;
+                new_args.lenient = args.lenient;
+                new_args.confirm_show |= args.confirm_show;
+                new_args.max_attempts = args.max_attempts;
+                new_args.retry_delay = args.retry_delay;
 
-                print_errors!(super::process_command(new_args, storage_path, passwords));
+                // Ctrl-C only aborts the current command, discard it after reporting
+                let result = super::process_command(new_args, storage_path, passwords);
+                print_errors!(result.and(check_interrupted()));
                 std::io::stdout().flush().unwrap();
             }
             Err(ReadlineError::Interrupted) => {}
@@ -115,6 +156,11 @@ pub fn processor<IO: storage_io::StorageIO>(
         }
     }
 
+    if passwords.in_transaction() {
+        passwords.rollback_transaction().convert_error()?;
+        writeln!(out(), "Uncommitted changes discarded.").unwrap();
+    }
+
     if let Err(error) = editor.save_history(&history_path) {
         eprintln!(
             "Failed saving history to {} ({}).",
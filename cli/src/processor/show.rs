@@ -4,56 +4,99 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
-use super::utils::{ensure_unlocked_passwords, ConvertError};
-use crate::args::{Args, Commands};
-use io_streams::StreamWriter;
+use super::utils::{
+    confirm_primary_password, copy_to_clipboard, ensure_unlocked_passwords, get_password_id,
+    get_recovery_code, out, secret_output, sleep_interruptible, write_qrcode, write_secure_file,
+    CommandError, ConvertError,
+};
+use crate::args::{Args, Commands, ShowField};
 use pfp::passwords::Passwords;
 use pfp::storage_io;
-use secrecy::ExposeSecret;
-use std::io::Write;
+use secrecy::{ExposeSecret, SecretString, SecretVec};
+use std::io::{IsTerminal, Write};
 
 pub fn processor<IO: storage_io::StorageIO>(
     args: &Args,
     passwords: &mut Passwords<IO>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     if let Commands::Show {
         domain,
         name,
         revision,
         qrcode,
+        reveal_after,
+        clipboard,
+        clear_after,
+        output,
+        force,
+        field,
     } = &args.command
     {
-        ensure_unlocked_passwords(passwords, args.stdin_passwords)?;
+        let id = get_password_id(domain, name, revision)?;
+        ensure_unlocked_passwords(passwords, args)?;
+        confirm_primary_password(passwords, args)?;
 
-        let password = passwords.get(domain, name, revision).convert_error()?;
-        let mut stdout = StreamWriter::stdout().unwrap();
-        stdout.write_all(b"Password retrieved.").unwrap();
+        let (label, value) = match field {
+            ShowField::Value => (
+                "Password",
+                passwords
+                    .get(id.site(), id.name(), id.revision())
+                    .convert_error()?,
+            ),
+            ShowField::Notes => (
+                "Notes",
+                passwords
+                    .get_notes(id.site(), id.name(), id.revision())
+                    .convert_error()?,
+            ),
+            ShowField::Recovery => (
+                "Recovery code",
+                SecretString::new(get_recovery_code(passwords, &id)?),
+            ),
+        };
+
+        if let Some(path) = output {
+            let contents = SecretVec::new([value.expose_secret().as_bytes(), b"\n"].concat());
+            write_secure_file(path, *force, contents.expose_secret())?;
+            writeln!(out(), "{} written to {}.", label, path.display()).unwrap();
+            return Ok(());
+        }
+
+        if *clipboard {
+            return copy_to_clipboard(
+                &value,
+                &format!("{} copied to clipboard.", label),
+                std::time::Duration::from_secs(*clear_after),
+            );
+        }
+
+        let mut stdout = secret_output();
+        stdout
+            .write_all(format!("{} retrieved.", label).as_bytes())
+            .unwrap();
+        let mut lines = 0;
         if *qrcode {
-            const BLOCKS: [&str; 4] = [" ", "\u{2580}", "\u{2584}", "\u{2588}"];
-
-            match qrcodegen::QrCode::encode_text(
-                password.expose_secret(),
-                qrcodegen::QrCodeEcc::Low,
-            ) {
-                Ok(qr) => {
-                    for y in (0..qr.size()).step_by(2) {
-                        for x in 0..qr.size() {
-                            let index = if qr.get_module(x, y) { 1 } else { 0 }
-                                | if qr.get_module(x, y + 1) { 2 } else { 0 };
-                            stdout.write_all(BLOCKS[index].as_bytes()).unwrap();
-                        }
-                        stdout.write_all(b"\n").unwrap();
-                    }
-                }
-                Err(error) => {
-                    return Err(format!("Error generating QR code: {}", error));
-                }
-            }
+            lines += write_qrcode(&mut stdout, value.expose_secret())?;
         } else {
-            stdout
-                .write_all(password.expose_secret().as_bytes())
-                .unwrap();
+            stdout.write_all(value.expose_secret().as_bytes()).unwrap();
             stdout.write_all(b"\n").unwrap();
+            lines += value.expose_secret().split('\n').count();
+        }
+
+        if let Some(delay) = reveal_after {
+            if std::io::stdout().is_terminal() {
+                stdout.flush().unwrap();
+                sleep_interruptible(std::time::Duration::from_secs(*delay));
+
+                // Move the cursor to the start of the first line written and erase everything
+                // from there on.
+                stdout
+                    .write_all(format!("\x1b[{}F\x1b[J", lines).as_bytes())
+                    .unwrap();
+                stdout
+                    .write_all(format!("{} hidden.\n", label).as_bytes())
+                    .unwrap();
+            }
         }
     }
 
@@ -0,0 +1,42 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Stats = &args.command {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let statistics = passwords.statistics().convert_error()?;
+        writeln!(out(), "Sites: {}", statistics.sites).unwrap();
+        writeln!(out(), "Aliases: {}", statistics.aliases).unwrap();
+        writeln!(
+            out(),
+            "Passwords: {} ({} generated, {} stored)",
+            statistics.generated + statistics.stored,
+            statistics.generated,
+            statistics.stored
+        )
+        .unwrap();
+        writeln!(out(), "Passwords with notes: {}", statistics.with_notes).unwrap();
+
+        if !statistics.lengths.is_empty() {
+            writeln!(out(), "Generated password lengths:").unwrap();
+            for (length, count) in &statistics.lengths {
+                writeln!(out(), "    {}: {}", length, count).unwrap();
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -4,13 +4,166 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
+use crate::args::{Args, KdfAlgorithm};
 use io_streams::{StreamReader, StreamWriter};
 use pfp::error::Error;
-use pfp::passwords::Passwords;
+use pfp::passwords::{Passwords, MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH};
 use pfp::recovery_codes;
 use pfp::storage_io;
+use pfp::storage_types::{KdfParams, Password, PasswordId};
+use secrecy::zeroize::Zeroize;
 use secrecy::{ExposeSecret, SecretString, SecretVec};
 use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+static INTERRUPTS_DEFERRED: AtomicUsize = AtomicUsize::new(0);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static OUTPUT_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Sends all command output to stderr, used when stdout receives the storage data.
+pub fn redirect_output_to_stderr() {
+    OUTPUT_TO_STDERR.store(true, Ordering::SeqCst);
+}
+
+/// Checks whether command output should go to stderr rather than stdout.
+pub fn output_to_stderr() -> bool {
+    OUTPUT_TO_STDERR.load(Ordering::SeqCst)
+}
+
+/// Returns the writer for regular command output. This is stdout unless output has been
+/// redirected to stderr.
+pub fn out() -> Box<dyn Write> {
+    if output_to_stderr() {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    }
+}
+
+/// Returns an unbuffered writer for command output containing secrets, so that these aren't
+/// copied into buffers. This is stdout unless output has been redirected to stderr.
+pub fn secret_output() -> Box<dyn Write> {
+    if output_to_stderr() {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(StreamWriter::stdout().unwrap())
+    }
+}
+
+/// Installs a Ctrl-C handler. Outside of sections marked with `DeferInterrupts` the process exits
+/// immediately like it would without a handler. Within such sections the first Ctrl-C only sets
+/// a flag, so that the operation can be aborted in an orderly way: passwords get locked and
+/// secrets zeroed out before exiting. A second Ctrl-C exits immediately.
+pub fn install_interrupt_handler() {
+    let _ = ctrlc::set_handler(|| {
+        if INTERRUPTS_DEFERRED.load(Ordering::SeqCst) == 0
+            || INTERRUPTED.swap(true, Ordering::SeqCst)
+        {
+            std::process::exit(130);
+        }
+    });
+}
+
+/// Reports an error if Ctrl-C was pressed since the last check.
+pub fn check_interrupted() -> Result<(), CommandError> {
+    if INTERRUPTED.swap(false, Ordering::SeqCst) {
+        Err("Interrupted.".into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Waits for the given time, returning early if Ctrl-C is pressed.
+pub fn sleep_interruptible(duration: std::time::Duration) {
+    const STEP: std::time::Duration = std::time::Duration::from_millis(50);
+
+    let start = std::time::Instant::now();
+    while !INTERRUPTED.load(Ordering::SeqCst) {
+        let elapsed = start.elapsed();
+        if elapsed >= duration {
+            break;
+        }
+        std::thread::sleep(STEP.min(duration - elapsed));
+    }
+}
+
+/// Marks a section where Ctrl-C is handled by calling `check_interrupted()`. Sections can be
+/// nested, pending interrupts are discarded once the outermost section ends.
+pub struct DeferInterrupts;
+
+impl DeferInterrupts {
+    pub fn new() -> Self {
+        if INTERRUPTS_DEFERRED.fetch_add(1, Ordering::SeqCst) == 0 {
+            INTERRUPTED.store(false, Ordering::SeqCst);
+        }
+        Self
+    }
+
+    pub fn check_interrupted(&self) -> Result<(), CommandError> {
+        check_interrupted()
+    }
+}
+
+impl Drop for DeferInterrupts {
+    fn drop(&mut self) {
+        if INTERRUPTS_DEFERRED.fetch_sub(1, Ordering::SeqCst) == 1 {
+            INTERRUPTED.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Exit code for failures that don't have a more specific code
+pub const EXIT_FAILURE: u8 = 1;
+/// Exit code when the primary password is wrong
+pub const EXIT_WRONG_PASSWORD: u8 = 2;
+/// Exit code when the requested password or site doesn't exist
+pub const EXIT_NOT_FOUND: u8 = 3;
+/// Exit code when the storage file is missing, unreadable or not initialized
+pub const EXIT_NO_STORAGE: u8 = 4;
+
+/// An error reported by a command: the message to be displayed and the process exit code.
+#[derive(Debug)]
+pub struct CommandError {
+    pub message: String,
+    pub exit_code: u8,
+}
+
+impl CommandError {
+    pub fn new(message: String, exit_code: u8) -> Self {
+        Self { message, exit_code }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::new(message, EXIT_FAILURE)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        Self::new(message.to_string(), EXIT_FAILURE)
+    }
+}
+
+impl From<Error> for CommandError {
+    fn from(error: Error) -> Self {
+        let exit_code = match error {
+            Error::DecryptionFailure => EXIT_WRONG_PASSWORD,
+            Error::KeyMissing => EXIT_NOT_FOUND,
+            Error::StorageNotInitialized | Error::FileReadFailure { .. } => EXIT_NO_STORAGE,
+            _ => EXIT_FAILURE,
+        };
+        Self::new(format_error(&error), exit_code)
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
 
 fn format_error(error: &Error) -> String {
     match error {
@@ -22,23 +175,93 @@ fn format_error(error: &Error) -> String {
             error
         ),
         Error::FileWriteFailure { error } => format!("Failed writing storage file ({}).", error),
+        Error::IoFailure { error } => format!("I/O operation failed ({}).", error),
+        Error::DatabaseFailure { error } => format!("Database access failed ({}).", error),
         Error::StorageNotInitialized => {
             "Storage is missing data. Maybe use set-primary subcommand first?".to_string()
         }
         Error::UnexpectedStorageFormat => "Unexpected storage file format.".to_string(),
+        Error::NewerStorageFormat { format } => format!(
+            "Storage file uses format version {} which is only supported by newer versions of this application, please upgrade. Use --allow-newer to access it read-only.",
+            format
+        ),
+        Error::ReadOnlyStorage => {
+            "Storage file uses a newer format and was opened read-only, changes cannot be saved."
+                .to_string()
+        }
+        Error::StorageLocked => {
+            "Storage file is in use by another pfp instance (maybe a running shell?), close it first."
+                .to_string()
+        }
+        Error::StorageChangedExternally => {
+            "Storage file was modified by another application, changes cannot be saved. Use the reload command in a shell to discard your changes and load the current data."
+                .to_string()
+        }
+        Error::ReloadUnsupported => {
+            "This storage cannot discard unsaved changes, rollback and reload aren't possible."
+                .to_string()
+        }
+        Error::TransactionInProgress => "A transaction is already in progress.".to_string(),
+        Error::NoTransaction => "No transaction in progress.".to_string(),
         Error::PasswordsLocked => "Passwords are locked.".to_string(),
         Error::KeyMissing => "No such value in storage.".to_string(),
+        Error::PasswordExists => {
+            "A password with this domain/name/revision combination already exists.".to_string()
+        }
+        Error::NotGeneratedPassword => {
+            "This operation is only possible for generated passwords.".to_string()
+        }
+        Error::NotStoredPassword => {
+            "This operation is only possible for stored passwords.".to_string()
+        }
+        Error::InvalidTag => "Tags cannot be empty.".to_string(),
+        Error::InvalidLength { length } => format!(
+            "Password length {} is invalid, it should be between {} and {} characters.",
+            length, MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+        ),
+        Error::InvalidKdfParams => format!(
+            "Unsupported key derivation parameters, the number of iterations should be a power of two between {} and {} for scrypt and between 1 and {} for Argon2id.",
+            KdfParams::MIN_SCRYPT_COST,
+            KdfParams::MAX_SCRYPT_COST,
+            KdfParams::MAX_ARGON2_ITERATIONS
+        ),
+        Error::EmptyCharacterSet => "You need to allow at least one character set.".to_string(),
+        Error::InvalidSymbols => {
+            "Symbols should be distinct ASCII punctuation characters.".to_string()
+        }
+        Error::InvalidLocator => {
+            "Invalid password locator, expected format is site/name@revision.".to_string()
+        }
         Error::UnexpectedData => "Unexpected JSON data in storage.".to_string(),
-        Error::InvalidCiphertext => "Corrupt ciphertext data in storage.".to_string(),
+        Error::InvalidCiphertext => {
+            "Storage appears to be corrupt, invalid ciphertext data.".to_string()
+        }
         Error::InvalidBase64 { error } => format!("Corrupt Base64 data in storage ({}).", error),
         Error::InvalidJson { error } => format!("Corrupt JSON data in storage ({}).", error),
         Error::InvalidUtf8 { error } => format!("Corrupt UTF-8 data in storage ({}).", error),
         Error::DecryptionFailure => "Decryption failure, wrong primary password?".to_string(),
         Error::NoSuchAlias => "Site is not an alias.".to_string(),
         Error::AliasToSelf => "Cannot make a site an alias for itself.".to_string(),
+        Error::AliasCycle => {
+            "Site aliases refer to each other in a cycle, storage data is inconsistent.".to_string()
+        }
         Error::SiteHasPasswords => {
             "Site has passwords, remove before making it an alias.".to_string()
         }
+        Error::AliasFailures { failures } => {
+            let mut message = "No aliases were added, the following sites failed:".to_string();
+            for (site, error) in failures {
+                message.push_str(&format!("\n    {}: {}", site, format_error(error)));
+            }
+            message
+        }
+        Error::UnreadableEntries { failures } => {
+            let mut message = "The following storage entries could not be read:".to_string();
+            for (key, error) in failures {
+                message.push_str(&format!("\n    {}: {}", key, format_error(error)));
+            }
+            message
+        }
         Error::RecoveryCodeExtraData { line } => format!(
             "Error in recovery code, extra data starting with line {}.",
             line
@@ -52,86 +275,285 @@ fn format_error(error: &Error) -> String {
             "Wrong recovery code version, generated by a newer application version?".to_string()
         }
         Error::RecoveryCodeInsufficientData => "Not enough data in the recovery code.".to_string(),
+        Error::RecoveryCodeTooLong => "Recovery code is too long.".to_string(),
     }
 }
 
 pub trait ConvertError<T> {
-    fn convert_error(self) -> Result<T, String>;
+    fn convert_error(self) -> Result<T, CommandError>;
 }
 
 impl<T> ConvertError<T> for Result<T, Error> {
-    fn convert_error(self) -> Result<T, String> {
-        match self {
-            Ok(value) => Ok(value),
-            Err(error) => Err(format_error(&error)),
+    fn convert_error(self) -> Result<T, CommandError> {
+        self.map_err(CommandError::from)
+    }
+}
+
+/// Name of the profile used when no profile is specified explicitly.
+pub const DEFAULT_PROFILE: &str = "storage";
+
+/// Determines the directory holding configuration data, including profile storage files.
+pub fn get_config_dir() -> std::path::PathBuf {
+    let app_info = app_dirs2::AppInfo {
+        name: "PfP",
+        author: "Wladimir Palant",
+    };
+    app_dirs2::get_app_root(app_dirs2::AppDataType::UserConfig, &app_info).unwrap()
+}
+
+pub fn get_password_id(
+    domain: &str,
+    name: &Option<String>,
+    revision: &Option<String>,
+) -> Result<PasswordId, CommandError> {
+    match name {
+        Some(name) => Ok(PasswordId::new(
+            domain,
+            name,
+            revision.as_deref().unwrap_or("1"),
+        )),
+        None => {
+            if revision.is_some() {
+                return Err(
+                    "Password revision cannot be specified along with a password locator.".into(),
+                );
+            }
+            PasswordId::parse(domain).convert_error()
+        }
+    }
+}
+
+static STDIN_PRIMARY_CONSUMED: AtomicBool = AtomicBool::new(false);
+static ENV_PRIMARY_PASSWORD: Mutex<Option<SecretString>> = Mutex::new(None);
+
+/// Environment variable consulted for the primary password with `--allow-env-password`
+pub const PRIMARY_PASSWORD_VAR: &str = "PFP_PRIMARY_PASSWORD";
+
+/// Removes the primary password variable from the environment, keeping its value for the first
+/// unlock attempt.
+///
+/// On Unix the original variable value is overwritten in place first, so that it no longer shows
+/// up in the process environment (e.g. `/proc/<pid>/environ` on Linux). On Windows the value might
+/// remain in the process environment block.
+pub fn take_env_password() {
+    if let Some(value) = std::env::var_os(PRIMARY_PASSWORD_VAR) {
+        #[cfg(unix)]
+        clear_env_value(PRIMARY_PASSWORD_VAR);
+        std::env::remove_var(PRIMARY_PASSWORD_VAR);
+        if let Ok(value) = value.into_string() {
+            *ENV_PRIMARY_PASSWORD.lock().unwrap() = Some(SecretString::new(value));
         }
     }
 }
 
-pub fn prompt_secret_text(prompt: &str) -> SecretString {
-    StreamWriter::stdout()
-        .unwrap()
-        .write_all(prompt.as_bytes())
-        .unwrap();
+#[cfg(unix)]
+fn clear_env_value(name: &str) {
+    let name = std::ffi::CString::new(name).expect("Variable name shouldn't contain zero bytes");
+
+    // getenv() returns a pointer into the original environment string, it stays valid until the
+    // environment is modified.
+    unsafe {
+        let value = libc::getenv(name.as_ptr());
+        if !value.is_null() {
+            std::ptr::write_bytes(value, 0, libc::strlen(value));
+        }
+    }
+}
+
+pub fn prompt_secret_text(prompt: &str) -> Result<SecretString, CommandError> {
+    secret_output().write_all(prompt.as_bytes()).unwrap();
+
+    let text = read_secret_line()?;
+    check_interrupted()?;
+    Ok(text)
+}
+
+fn read_secret_line() -> Result<SecretString, CommandError> {
+    read_secret_stdin(true)
+}
+
+fn strip_line_break(text: &SecretString) -> SecretString {
+    let text = text.expose_secret();
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    SecretString::new(text.strip_suffix('\r').unwrap_or(text).to_owned())
+}
 
+/// Reads secret text from stdin until the end of input, a single trailing line break is removed.
+pub fn read_secret_text() -> Result<SecretString, CommandError> {
+    Ok(strip_line_break(&read_secret_stdin(false)?))
+}
+
+/// Reads secret text from a file, a single trailing line break is removed.
+pub fn read_secret_file(path: &std::path::Path) -> Result<SecretString, CommandError> {
+    let contents = SecretString::new(
+        std::fs::read_to_string(path)
+            .map_err(|error| format!("Failed reading file {} ({}).", path.display(), error))?,
+    );
+    Ok(strip_line_break(&contents))
+}
+
+fn read_secret_stdin(single_line: bool) -> Result<SecretString, CommandError> {
     let mut byte_buffer = [0];
     let mut buffer = Vec::with_capacity(1024);
     let mut stdin = StreamReader::stdin().unwrap();
     while let Ok(1) = stdin.read(&mut byte_buffer) {
-        if byte_buffer[0] == b'\n' {
+        if single_line && byte_buffer[0] == b'\n' {
             break;
         }
+        if buffer.len() == buffer.capacity() {
+            // Grow manually so that no copy of the secret is left behind in freed memory.
+            let mut new_buffer = Vec::with_capacity(buffer.capacity() * 2);
+            new_buffer.extend_from_slice(&buffer);
+            buffer.zeroize();
+            buffer = new_buffer;
+        }
         buffer.push(byte_buffer[0]);
     }
 
     let input = SecretVec::new(buffer);
-    SecretString::new(
-        std::str::from_utf8(input.expose_secret().as_slice())
-            .unwrap()
-            .to_owned(),
-    )
+    let text = std::str::from_utf8(input.expose_secret().as_slice())
+        .map_err(|error| format!("Entered text isn't valid UTF-8 ({}).", error))?;
+    Ok(SecretString::new(text.to_owned()))
 }
 
-pub fn prompt_password(prompt: &str, stdin_passwords: bool) -> SecretString {
-    let secret = if stdin_passwords {
+pub fn prompt_secret(prompt: &str, stdin_passwords: bool) -> Result<SecretString, CommandError> {
+    if stdin_passwords {
         prompt_secret_text(prompt)
     } else {
-        SecretString::new(rpassword::prompt_password(prompt).unwrap())
-    };
-    SecretString::new(secret.expose_secret().trim().to_owned())
+        let secret = SecretString::new(
+            rpassword::prompt_password(prompt)
+                .map_err(|error| format!("Failed reading password ({}).", error))?,
+        );
+        check_interrupted()?;
+        Ok(secret)
+    }
+}
+
+/// Prompts for a password, leading and trailing whitespace is removed from the value.
+pub fn prompt_password(prompt: &str, stdin_passwords: bool) -> Result<SecretString, CommandError> {
+    let secret = prompt_secret(prompt, stdin_passwords)?;
+    Ok(SecretString::new(secret.expose_secret().trim().to_owned()))
+}
+
+/// Determines the key derivation parameters for an algorithm, using its recommended parameters
+/// unless a number of iterations is given.
+pub fn get_kdf_params(
+    algorithm: KdfAlgorithm,
+    iterations: Option<u64>,
+) -> Result<KdfParams, CommandError> {
+    match (algorithm, iterations) {
+        (KdfAlgorithm::Scrypt, None) => Ok(KdfParams::default()),
+        (KdfAlgorithm::Scrypt, Some(iterations)) => KdfParams::scrypt(iterations).convert_error(),
+        (KdfAlgorithm::Argon2id, None) => Ok(KdfParams::argon2id_default()),
+        (KdfAlgorithm::Argon2id, Some(iterations)) => KdfParams::argon2id(
+            KdfParams::DEFAULT_ARGON2_MEMORY,
+            u32::try_from(iterations).unwrap_or(u32::MAX),
+            KdfParams::DEFAULT_ARGON2_PARALLELISM,
+        )
+        .convert_error(),
+    }
 }
 
 pub fn ensure_unlocked_passwords<IO: storage_io::StorageIO>(
     passwords: &mut Passwords<IO>,
-    stdin_passwords: bool,
-) -> Result<(), String> {
+    args: &Args,
+) -> Result<(), CommandError> {
     if !passwords.initialized() {
-        return Err(format_error(&Error::StorageNotInitialized));
+        return Err(Error::StorageNotInitialized.into());
     }
 
+    let mut attempts = 0;
     while !passwords.unlocked() {
-        let primary_password = prompt_password("Your primary password: ", stdin_passwords);
+        if args.max_attempts > 0 && attempts >= args.max_attempts {
+            return Err(CommandError::new(
+                "Too many failed attempts, giving up.".to_owned(),
+                EXIT_WRONG_PASSWORD,
+            ));
+        }
+        attempts += 1;
+
+        let env_password = ENV_PRIMARY_PASSWORD.lock().unwrap().take();
+        let primary_password = if let Some(primary_password) = env_password {
+            primary_password
+        } else if args.stdin_primary_once && !STDIN_PRIMARY_CONSUMED.swap(true, Ordering::Relaxed) {
+            SecretString::new(read_secret_line()?.expose_secret().trim().to_owned())
+        } else {
+            prompt_password("Your primary password: ", args.stdin_passwords)?
+        };
         if primary_password.expose_secret().len() < 6 {
             eprintln!("Primary password length should be at least 6 characters.");
         } else {
-            passwords
-                .unlock(primary_password)
-                .unwrap_or_else(|error| eprintln!("{}", format_error(&error)));
+            match passwords.unlock(primary_password) {
+                Ok(()) => {}
+                Err(Error::DecryptionFailure) => {
+                    eprintln!("{}", format_error(&Error::DecryptionFailure));
+                    if args.retry_delay > 0 {
+                        std::thread::sleep(std::time::Duration::from_millis(args.retry_delay));
+                    }
+                }
+                Err(error) => return Err(error.into()),
+            }
         }
     }
     Ok(())
 }
 
+pub fn confirm_primary_password<IO: storage_io::StorageIO>(
+    passwords: &Passwords<IO>,
+    args: &Args,
+) -> Result<(), CommandError> {
+    if !args.confirm_show {
+        return Ok(());
+    }
+
+    let primary_password =
+        prompt_password("Confirm your primary password: ", args.stdin_passwords)?;
+    if passwords
+        .verify_primary(&primary_password)
+        .convert_error()?
+    {
+        Ok(())
+    } else {
+        Err(CommandError::new(
+            "Wrong primary password, not showing password values.".to_owned(),
+            EXIT_WRONG_PASSWORD,
+        ))
+    }
+}
+
+/// Retrieves the recovery code for a stored password, generated passwords produce an error.
+pub fn get_recovery_code<IO: storage_io::StorageIO>(
+    passwords: &Passwords<IO>,
+    id: &PasswordId,
+) -> Result<String, CommandError> {
+    let password = passwords
+        .list(id.site(), id.name())
+        .find(|password| {
+            password.id().name() == id.name() && password.id().revision() == id.revision()
+        })
+        .ok_or(Error::KeyMissing)
+        .convert_error()?;
+
+    let password = match password {
+        Password::Stored(password) => password,
+        Password::Generated(_) | Password::LegacyGenerated(_) => {
+            return Err("Recovery codes are only available for stored passwords.".into());
+        }
+    };
+
+    passwords.get_recovery_code(&password).convert_error()
+}
+
 pub fn prompt_recovery_code<IO: storage_io::StorageIO>(
     passwords: &Passwords<IO>,
-) -> Result<SecretString, String> {
+) -> Result<SecretString, CommandError> {
     let mut accepted = String::new();
     loop {
         if let Some(question::Answer::RESPONSE(line)) =
             question::Question::new("Next line of your recovery code (empty line to abort):").ask()
         {
             if line.is_empty() {
-                return Err(String::new());
+                return Err(String::new().into());
             }
 
             let code = String::from(&accepted) + &line;
@@ -150,7 +572,7 @@ pub fn prompt_recovery_code<IO: storage_io::StorageIO>(
                         if accept == question::Answer::YES {
                             return passwords.decode_recovery_code(&accepted).convert_error();
                         } else {
-                            return Err(String::new());
+                            return Err(String::new().into());
                         }
                     }
                     Error::RecoveryCodeChecksumMismatch { line } => {
@@ -168,10 +590,172 @@ pub fn prompt_recovery_code<IO: storage_io::StorageIO>(
                         eprintln!("Line accepted. The recovery code is still incomplete, please enter more data.\n");
                     }
                     unknown_error => {
-                        return Err(format_error(&unknown_error));
+                        return Err(format_error(&unknown_error).into());
                     }
                 },
             }
         }
     }
 }
+
+/// Runs `write` twice, first to determine the size of the output and then to write it into a
+/// buffer allocated with that size. Growing the buffer while writing would leave partial copies of
+/// secret data behind in freed memory.
+pub fn write_to_secret_vec<F>(mut write: F) -> Result<SecretVec<u8>, CommandError>
+where
+    F: FnMut(&mut dyn Write) -> Result<(), CommandError>,
+{
+    struct Counter(usize);
+
+    impl Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    write(&mut counter)?;
+    let mut buffer = Vec::with_capacity(counter.0);
+    if let Err(error) = write(&mut buffer) {
+        buffer.zeroize();
+        return Err(error);
+    }
+    Ok(SecretVec::new(buffer))
+}
+
+/// Writes secret data to a file that only the current user can read. On Unix the file is created
+/// with permissions 0600. On Windows it gets the default permissions of its directory, and a
+/// warning is printed since other users might be able to read it.
+///
+/// An existing file is only overwritten if `force` is set, its permissions are restricted then.
+pub fn write_secure_file(
+    path: &std::path::Path,
+    force: bool,
+    contents: &[u8],
+) -> Result<(), CommandError> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path).map_err(|error| {
+        if error.kind() == std::io::ErrorKind::AlreadyExists {
+            format!(
+                "File {} already exists, use --force to overwrite it.",
+                path.display()
+            )
+        } else {
+            format!("Failed creating file {} ({}).", path.display(), error)
+        }
+    })?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .map_err(|error| format!("Failed restricting file permissions ({}).", error))?;
+    }
+    #[cfg(windows)]
+    eprintln!(
+        "Warning: file permissions aren't restricted on Windows, {} might be readable by other users.",
+        path.display()
+    );
+
+    file.write_all(contents)
+        .map_err(|error| format!("Failed writing file {} ({}).", path.display(), error).into())
+}
+
+pub fn copy_to_clipboard(
+    text: &SecretString,
+    message: &str,
+    clear_after: std::time::Duration,
+) -> Result<(), CommandError> {
+    let mut clipboard = arboard::Clipboard::new()
+        .map_err(|error| format!("Failed accessing clipboard ({}).", error))?;
+    clipboard
+        .set_text(text.expose_secret().as_str())
+        .map_err(|error| format!("Failed copying to clipboard ({}).", error))?;
+
+    // Clipboard contents are owned by this process on some platforms and disappear once it
+    // exits, so the clipboard is cleared from here rather than by a separate process. This blocks
+    // the command (and the shell) until the clipboard is cleared, Ctrl-C ends the wait early.
+    writeln!(out(), "{}", message).unwrap();
+    std::io::stdout().flush().unwrap();
+    sleep_interruptible(clear_after);
+
+    // Only clear the clipboard if nothing else was copied in the meantime.
+    let current = clipboard.get_text().map(SecretString::new);
+    if let Ok(current) = current {
+        if current.expose_secret() == text.expose_secret() {
+            clipboard
+                .clear()
+                .map_err(|error| format!("Failed clearing clipboard ({}).", error))?;
+            writeln!(out(), "Clipboard cleared.").unwrap();
+        }
+    }
+    Ok(())
+}
+
+/// Renders `text` as a QR code, two rows of modules per line of output. Returns the number of
+/// lines written.
+pub fn write_qrcode(output: &mut impl Write, text: &str) -> Result<usize, CommandError> {
+    const BLOCKS: [&str; 4] = [" ", "\u{2580}", "\u{2584}", "\u{2588}"];
+
+    let qr = qrcodegen::QrCode::encode_text(text, qrcodegen::QrCodeEcc::Low)
+        .map_err(|error| format!("Error generating QR code: {}", error))?;
+
+    let mut lines = 0;
+    for y in (0..qr.size()).step_by(2) {
+        for x in 0..qr.size() {
+            let index = if qr.get_module(x, y) { 1 } else { 0 }
+                | if qr.get_module(x, y + 1) { 2 } else { 0 };
+            output.write_all(BLOCKS[index].as_bytes()).unwrap();
+        }
+        output.write_all(b"\n").unwrap();
+        lines += 1;
+    }
+    Ok(lines)
+}
+
+/// Formats a timestamp in seconds since the Unix epoch as UTC date and time.
+pub fn format_timestamp(timestamp: u64) -> String {
+    // Civil date from day number, see https://howardhinnant.github.io/date_algorithms.html
+    let days = timestamp / 86400 + 719468;
+    let seconds = timestamp % 86400;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,25 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::utils::{ensure_unlocked_passwords, out, CommandError, ConvertError};
+use crate::args::{Args, Commands};
+use pfp::passwords::Passwords;
+use pfp::storage_io;
+use std::io::Write;
+
+pub fn processor<IO: storage_io::StorageIO>(
+    args: &Args,
+    passwords: &mut Passwords<IO>,
+) -> Result<(), CommandError> {
+    if let Commands::Verify = &args.command {
+        ensure_unlocked_passwords(passwords, args)?;
+
+        let count = passwords.verify_entries().convert_error()?;
+        writeln!(out(), "All {} storage entries can be read.", count).unwrap();
+    }
+
+    Ok(())
+}
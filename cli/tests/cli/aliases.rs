@@ -101,3 +101,204 @@ fn add_remove() {
         session.expect_str("Password retrieved");
     }
 }
+
+#[test]
+fn set_multiple() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["add", "example.net", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "set-alias",
+                "--target",
+                "example.com",
+                "example.info",
+                "example.net",
+                "www.example.com",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+No aliases were added, the following sites failed:
+    example.net: Site has passwords, remove before making it an alias.
+    www.example.com: Cannot make a site an alias for itself.
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(&["alias", "example.info"], Some(PRIMARY_PASSWORD));
+        session.expect_str("is not an alias");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "set-alias",
+                "-t",
+                "www.example.com",
+                "example.info",
+                "example.org",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("'example.info' is now an alias for 'www.example.com'.");
+        session.expect_str("'example.org' is now an alias for 'www.example.com'.");
+    }
+
+    {
+        let mut session = setup.run(&["alias", "example.org"], Some(PRIMARY_PASSWORD));
+        session.expect_str("'example.org' is an alias for 'example.com'");
+    }
+}
+
+#[test]
+fn set_migrating() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["add", "example.net", "blabber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["add", "example.info", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["set-alias", "--migrate", "-t", "example.com", "example.net"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("will change their values");
+        session.send_line("n");
+    }
+
+    {
+        let mut session = setup.run(&["alias", "example.net"], Some(PRIMARY_PASSWORD));
+        session.expect_str("is not an alias");
+    }
+
+    {
+        let mut session = setup.run(
+            &["set-alias", "--migrate", "-t", "example.com", "example.net"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("will change their values");
+        session.send_line("y");
+        session.expect_str("'example.net' is now an alias for 'example.com', 1 password(s) moved.");
+    }
+
+    {
+        let mut session = setup.run(&["alias", "example.net"], Some(PRIMARY_PASSWORD));
+        session.expect_str("'example.net' is an alias for 'example.com'");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com", "blabber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "set-alias",
+                "--migrate",
+                "-y",
+                "-t",
+                "example.com",
+                "example.info",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("A password with this domain/name/revision combination already exists");
+    }
+
+    {
+        let mut session = setup.run(&["alias", "example.info"], Some(PRIMARY_PASSWORD));
+        session.expect_str("is not an alias");
+    }
+}
+
+#[test]
+fn list() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["list-aliases"], Some(PRIMARY_PASSWORD));
+        session.expect_str("No aliases found.");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "set-alias",
+                "-t",
+                "example.com",
+                "example.org",
+                "example.net",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("'example.net' is now an alias for 'example.com'.");
+    }
+
+    {
+        let mut session = setup.run(
+            &["set-alias", "-t", "example.info", "www.example.de"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("'www.example.de' is now an alias for 'example.info'.");
+    }
+
+    {
+        let mut session = setup.run(&["list-aliases"], Some(PRIMARY_PASSWORD));
+        session.expect_str(
+            "
+example.net -> example.com
+example.org -> example.com
+example.de -> example.info
+"
+            .trim_start(),
+        );
+    }
+
+    {
+        let mut session = setup.run(&["list-aliases", "--reverse"], Some(PRIMARY_PASSWORD));
+        session.expect_str(
+            "
+Aliases for example.com:
+    example.net
+    example.org
+Aliases for example.info:
+    example.de
+"
+            .trim_start(),
+        );
+    }
+}
@@ -9,8 +9,12 @@ use std::io::Write;
 
 use memmem::{Searcher, TwoWaySearcher};
 
+/// Clock value passed to the application, timestamps are displayed as 2009-02-13 23:31:30 UTC.
+const FIXED_TIME: &str = "1234567890";
+
 pub struct Setup {
     storage_file: tempfile::TempPath,
+    config_dir: Option<tempfile::TempDir>,
     secrets: Vec<Vec<u8>>,
 }
 
@@ -20,18 +24,32 @@ impl Setup {
             storage_file: tempfile::NamedTempFile::new()
                 .expect("Creating a temporary file should succeed")
                 .into_temp_path(),
+            config_dir: None,
             secrets: Vec::new(),
         };
         std::fs::remove_file(&setup.storage_file).expect("Temporary file should be removed");
         setup
     }
 
+    /// Creates a setup where the storage file isn't passed explicitly, the application uses a
+    /// temporary configuration directory instead.
+    pub fn with_config_dir() -> Self {
+        let mut setup = Self::new();
+        setup.config_dir =
+            Some(tempfile::tempdir().expect("Creating a temporary directory should succeed"));
+        setup
+    }
+
     pub fn set_secrets(&mut self, secrets: &[&[u8]]) {
         for &secret in secrets.iter() {
             self.secrets.push(Vec::from(secret));
         }
     }
 
+    pub fn get_file_data(&self) -> String {
+        std::fs::read_to_string(&self.storage_file).expect("Reading temporary file should succeed")
+    }
+
     pub fn set_file_data(&self, data: &str) {
         std::fs::write(&self.storage_file, data.as_bytes())
             .expect("Writing to temporary file should succeed");
@@ -41,27 +59,59 @@ impl Setup {
         &self,
         args: &[impl AsRef<std::ffi::OsStr>],
         primary_password: Option<&str>,
+    ) -> Session {
+        self.run_with_env(args, &[], primary_password)
+    }
+
+    pub fn run_with_env(
+        &self,
+        args: &[impl AsRef<std::ffi::OsStr>],
+        env: &[(&str, &str)],
+        primary_password: Option<&str>,
     ) -> Session {
         let binary = env!("CARGO_BIN_EXE_pfp-cli");
 
-        let process = subprocess::Exec::cmd(binary)
-            .args(&[
-                "--stdin-passwords".as_ref(),
-                "-c".as_ref(),
-                self.storage_file.as_os_str(),
-            ])
+        // Builds with logging allocator can dump all allocations on exit, check these as well
+        let memory_dump = if cfg!(logging_alloc) && self.secrets.len() > 0 {
+            Some(
+                tempfile::NamedTempFile::new()
+                    .expect("Creating a temporary file should succeed")
+                    .into_temp_path(),
+            )
+        } else {
+            None
+        };
+
+        let mut process = subprocess::Exec::cmd(binary)
+            .arg("--stdin-passwords")
+            .args(&["--fixed-time", FIXED_TIME]);
+        process = match &self.config_dir {
+            Some(config_dir) => process.env("XDG_CONFIG_HOME", config_dir.path()),
+            None => process.args(&["-c".as_ref(), self.storage_file.as_os_str()]),
+        };
+        let process = process
             .args(if self.secrets.len() > 0 {
                 &["--wait"]
             } else {
                 &[]
             })
+            .args(&match &memory_dump {
+                Some(path) => vec!["--dump-memory-on-exit".as_ref(), path.as_os_str()],
+                None => vec![],
+            })
             .args(args)
+            .env_extend(env)
             .stdin(subprocess::Redirection::Pipe)
             .stdout(subprocess::Redirection::Pipe)
             .stderr(subprocess::Redirection::Merge)
             .popen()
             .expect("Running binary should succeed");
-        let mut session = Session::new(process, args[0].as_ref() == "shell", &self.secrets);
+        let mut session = Session::new(
+            process,
+            args[0].as_ref() == "shell",
+            &self.secrets,
+            memory_dump,
+        );
 
         if let Some(primary_password) = primary_password {
             session.expect_str("Your primary password:");
@@ -86,16 +136,23 @@ pub struct Session {
     process: subprocess::Popen,
     is_shell: bool,
     secrets: Vec<Vec<u8>>,
+    memory_dump: Option<tempfile::TempPath>,
 }
 
 impl Session {
     const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
 
-    fn new(process: subprocess::Popen, is_shell: bool, secrets: &Vec<Vec<u8>>) -> Self {
+    fn new(
+        process: subprocess::Popen,
+        is_shell: bool,
+        secrets: &Vec<Vec<u8>>,
+        memory_dump: Option<tempfile::TempPath>,
+    ) -> Self {
         Self {
             process,
             is_shell,
             secrets: secrets.clone(),
+            memory_dump,
         }
     }
 
@@ -138,6 +195,44 @@ impl Session {
             .expect("Failed sending terminating newline to process input");
     }
 
+    pub fn send_bytes(&mut self, bytes: &[u8]) {
+        let mut stdin = self
+            .process
+            .stdin
+            .as_ref()
+            .expect("Process should have stdin");
+        stdin
+            .write_all(bytes)
+            .expect("Failed sending bytes to process input");
+    }
+
+    /// Reads the environment of the running process as reported by the system.
+    #[cfg(target_os = "linux")]
+    pub fn read_environ(&self) -> Vec<u8> {
+        let pid = self.process.pid().expect("Process should still be running");
+        std::fs::read(format!("/proc/{}/environ", pid))
+            .expect("Reading process environment should succeed")
+    }
+
+    /// Sends SIGINT to the process, like pressing Ctrl-C would.
+    #[cfg(unix)]
+    pub fn interrupt(&mut self) {
+        let pid = self.process.pid().expect("Process should still be running");
+        std::process::Command::new("kill")
+            .args(["-INT", &pid.to_string()])
+            .status()
+            .expect("Sending signal should succeed");
+
+        // Give the signal handler a chance to run
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+
+    /// Closes process input, the process will see the end of input. Secrets cannot be checked
+    /// afterwards because the process won't wait for input before exiting.
+    pub fn close_stdin(&mut self) {
+        self.process.stdin.take();
+    }
+
     pub fn read_to(&mut self, pattern: &str) -> String {
         let start = std::time::Instant::now();
         let mut stdout = self
@@ -234,6 +329,44 @@ impl Session {
         self.send_line("");
     }
 
+    pub fn check_memory_dump(&mut self) {
+        let path = match &self.memory_dump {
+            Some(path) => path,
+            None => return,
+        };
+
+        self.process
+            .wait_timeout(Self::TIMEOUT)
+            .expect("Waiting for process should succeed")
+            .expect("Process should terminate");
+        let data = std::fs::read(path).expect("Reading memory dump should succeed");
+
+        let mut secret_found = false;
+        for secret in self.secrets.iter() {
+            if let Some(pos) = TwoWaySearcher::new(secret).search_in(&data) {
+                secret_found = true;
+                eprintln!("Secret found in memory dump at offset {:0>16X}", pos);
+                dump_memory(&data, 0, pos);
+                break;
+            }
+        }
+
+        if !std::thread::panicking() {
+            assert!(!secret_found);
+        }
+    }
+
+    pub fn wait_exit_code(&mut self) -> u32 {
+        match self
+            .process
+            .wait_timeout(Self::TIMEOUT)
+            .expect("Waiting for process should succeed")
+        {
+            Some(subprocess::ExitStatus::Exited(code)) => code,
+            status => panic!("Unexpected process status {:?}", status),
+        }
+    }
+
     pub fn kill(&mut self) {
         self.process.kill().expect("App should terminate");
     }
@@ -246,6 +379,7 @@ impl Drop for Session {
         }
         if self.secrets.len() > 0 {
             self.check_secrets();
+            self.check_memory_dump();
         }
     }
 }
@@ -256,7 +390,7 @@ fn dump_memory(memory: &[u8], offset: usize, pos: usize) {
     const POSTFIX_CHUNKS: usize = 16;
 
     let normalized_pos = pos & !(CHUNK_SIZE - 1);
-    let start = std::cmp::max(normalized_pos - PREFIX_CHUNKS * CHUNK_SIZE, 0);
+    let start = normalized_pos.saturating_sub(PREFIX_CHUNKS * CHUNK_SIZE);
     let end = std::cmp::min(normalized_pos + POSTFIX_CHUNKS * CHUNK_SIZE, memory.len());
     let mut current_pos = start;
     for chunk in memory[start..end].chunks(CHUNK_SIZE) {
@@ -0,0 +1,65 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use crate::common::Setup;
+
+const PRIMARY_PASSWORD: &str = "foobar";
+const ANOTHER_PRIMARY_PASSWORD: &str = "asdfyxcv";
+
+#[test]
+fn success() {
+    let setup = Setup::new();
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+    session.expect_str("Password added");
+    assert_eq!(session.wait_exit_code(), 0);
+}
+
+#[test]
+fn general_failure() {
+    let setup = Setup::new();
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let mut session = setup.run(
+        &["add", "example.com", "blubber", "-w", "-u", "-d", "-s"],
+        Some(PRIMARY_PASSWORD),
+    );
+    session.expect_str("at least one character set");
+    assert_eq!(session.wait_exit_code(), 1);
+}
+
+#[test]
+fn wrong_primary_password() {
+    let setup = Setup::new();
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let mut session = setup.run(
+        &["--max-attempts", "1", "list"],
+        Some(ANOTHER_PRIMARY_PASSWORD),
+    );
+    session.expect_str("Too many failed attempts");
+    assert_eq!(session.wait_exit_code(), 2);
+}
+
+#[test]
+fn not_found() {
+    let setup = Setup::new();
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let mut session = setup.run(&["show", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+    session.expect_str("No such value");
+    assert_eq!(session.wait_exit_code(), 3);
+}
+
+#[test]
+fn no_storage() {
+    let setup = Setup::new();
+
+    let mut session = setup.run(&["list"], None);
+    session.expect_str("Failed reading storage file");
+    assert_eq!(session.wait_exit_code(), 4);
+}
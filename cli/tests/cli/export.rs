@@ -0,0 +1,451 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use crate::common::Setup;
+
+const PRIMARY_PASSWORD: &str = "foobar";
+const SECRETS: &[&[u8]] = &[PRIMARY_PASSWORD.as_bytes()];
+const IMPORTED_VALUE: &str = "imported-stored-value";
+const IMPORTED_NOTES: &str = "imported \"quoted\" notes";
+const EXPORTED_VALUE: &str = "exported-stored-value";
+const EXPORTED_NOTES: &str = "exported \"quoted\" notes";
+
+#[test]
+fn export_site() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.set_secrets(&[EXPORTED_VALUE.as_bytes(), EXPORTED_NOTES.as_bytes()]);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let mut session = setup.run(&["shell"], None);
+    session.expect_str("Enter a command");
+
+    session.send_line("export --site example.com");
+    session.expect_str("Your primary password:");
+    session.send_line(PRIMARY_PASSWORD);
+    session.expect_str("No such value");
+
+    session.send_line("add example.com blubber");
+    session.expect_str("Password added");
+
+    session.send_line("alias example.info example.com");
+    session.expect_str("Alias added");
+
+    session.send_line("export --site example.info");
+    let output = session.read_to("\n}\n");
+    assert!(output.contains("\"example.info\""));
+    assert!(output.contains("\"name\": \"blubber\""));
+    assert!(output.contains("\"site\": \"example.com\""));
+    assert!(!output.contains("\"value\""));
+    assert!(!output.contains("\"generated\""));
+
+    session.send_line("export --site example.com --include-secrets");
+    let output = session.read_to("\n}\n");
+    assert!(output.contains("\"name\": \"blubber\""));
+    assert!(output.contains("\"value\": \""));
+
+    session.send_line("export --site example.com --include-generated-params");
+    let output = session.read_to("\n}\n");
+    assert!(output.contains("\"generated\": {"));
+    assert!(output.contains("\"length\": 16"));
+
+    session.send_line("add-stored example.com blabber");
+    session.expect_str("Password to be stored");
+    session.send_line(EXPORTED_VALUE);
+    session.expect_str("Password added");
+
+    session.send_line("notes example.com blabber -s");
+    session.expect_str("enter new notes");
+    session.send_line(EXPORTED_NOTES);
+    session.expect_str("Notes stored");
+
+    session.send_line("export --site example.com --include-secrets");
+    let output = session.read_to("\n}\n");
+    assert!(output.contains(&format!("\"value\": \"{}\"", EXPORTED_VALUE)));
+    assert!(output.contains("\"notes\": \"exported \\\"quoted\\\" notes\""));
+
+    let export_dir = tempfile::tempdir().expect("Creating a temporary directory should succeed");
+    let export_file = export_dir.path().join("export.json");
+    session.send_line(&format!(
+        "export --site example.com --include-secrets -o {}",
+        export_file.display()
+    ));
+    session.expect_str("Data written to");
+    let contents =
+        std::fs::read_to_string(&export_file).expect("Reading export file should succeed");
+    assert!(contents.contains(&format!("\"value\": \"{}\"", EXPORTED_VALUE)));
+
+    session.send_line("list");
+    session.expect_str("blubber");
+}
+
+#[test]
+fn export_all() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let mut session = setup.run(&["shell"], None);
+    session.expect_str("Enter a command");
+
+    session.send_line("add example.com blubber");
+    session.expect_str("Your primary password:");
+    session.send_line(PRIMARY_PASSWORD);
+    session.expect_str("Password added");
+
+    session.send_line("alias example.info example.com");
+    session.expect_str("Alias added");
+
+    session.send_line("export --format pfp");
+    session.expect_str("{\"application\":\"pfp\",\"format\":3,\"data\":{");
+    session.expect_str("\"hmac-secret\":\"");
+
+    session.send_line("export --format plain");
+    session.expect_str("use --allow-plaintext");
+
+    session.send_line("export --format plain --allow-plaintext");
+    session.expect_str("exported unencrypted");
+    session.send_line("y");
+    let output = session.read_to("\n}\n");
+    assert!(output.contains("\"sites\": ["));
+    assert!(output.contains("\"example.info\""));
+    assert!(output.contains("\"name\": \"blubber\""));
+    assert!(output.contains("\"value\": \""));
+    assert!(output.contains("\"length\": 16"));
+    let json = &output[output.find('{').expect("Output should contain JSON")..];
+
+    session.send_line("list");
+    session.expect_str("blubber");
+    drop(session);
+
+    // Plain exports can be imported again
+    let export_file = tempfile::NamedTempFile::new()
+        .expect("Creating a temporary file should succeed")
+        .into_temp_path();
+    std::fs::write(&export_file, json).expect("Writing export file should succeed");
+
+    let mut target = Setup::new();
+    target.set_secrets(SECRETS);
+    target.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = target.run(
+            &["import".as_ref(), export_file.as_os_str()],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("1 password(s) imported, 0 skipped");
+    }
+
+    {
+        let mut session = target.run(&["list", "-v", "example.info"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Aliases: example.info");
+        session.expect_str("blubber (generated)");
+        session.expect_str("Length: 16");
+    }
+}
+
+#[test]
+fn import() {
+    let mut source = Setup::new();
+    source.set_secrets(SECRETS);
+    source.initialize(PRIMARY_PASSWORD);
+
+    let mut session = source.run(&["shell"], None);
+    session.expect_str("Enter a command");
+
+    session.send_line("add example.com blubber --length 12 --no-symbol");
+    session.expect_str("Your primary password:");
+    session.send_line(PRIMARY_PASSWORD);
+    session.expect_str("Password added");
+
+    session.send_line("add example.com blobber --length 8 --symbols=ab");
+    session.expect_str("Symbols should be distinct ASCII punctuation characters.");
+
+    session.send_line("add example.com blobber --length 8 --symbols=-_");
+    session.expect_str("Password added");
+
+    session.send_line("add-stored example.com blabber");
+    session.expect_str("Password to be stored");
+    session.send_line(IMPORTED_VALUE);
+    session.expect_str("Password added");
+
+    session.send_line("notes example.com blabber -s");
+    session.expect_str("enter new notes");
+    session.send_line(IMPORTED_NOTES);
+    session.expect_str("Notes stored");
+
+    session.send_line("alias example.info example.com");
+    session.expect_str("Alias added");
+
+    session.send_line("export --site example.com --include-secrets --include-generated-params");
+    let output = session.read_to("\n}\n");
+    let json = &output[output.find('{').expect("Output should contain JSON")..];
+
+    session.send_line("list");
+    session.expect_str("blubber");
+    drop(session);
+
+    let export_file = tempfile::NamedTempFile::new()
+        .expect("Creating a temporary file should succeed")
+        .into_temp_path();
+    std::fs::write(&export_file, json).expect("Writing export file should succeed");
+
+    // Imported values and notes shouldn't be left behind in memory
+    let mut target = Setup::new();
+    target.set_secrets(SECRETS);
+    target.set_secrets(&[IMPORTED_VALUE.as_bytes(), IMPORTED_NOTES.as_bytes()]);
+    target.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = target.run(
+            &["import".as_ref(), export_file.as_os_str()],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("3 password(s) imported, 0 skipped");
+    }
+
+    {
+        let mut session = target.run(
+            &["import".as_ref(), export_file.as_os_str()],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("0 password(s) imported, 3 skipped");
+    }
+
+    {
+        let mut session = target.run(&["list", "-v", "example.info"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Aliases: example.info");
+        session.expect_str("blabber (stored)");
+        session.expect_str("blobber (generated)");
+        session.expect_str("Length: 8");
+        session.expect_str("Allowed characters: abc ABC 789 -_");
+        session.expect_str("blubber (generated)");
+        session.expect_str("Length: 12");
+        session.expect_str("Allowed characters: abc ABC 789");
+    }
+
+    {
+        let mut session = target.run(&["show", "example.com", "blabber"], Some(PRIMARY_PASSWORD));
+        session.expect_str(IMPORTED_VALUE);
+    }
+
+    {
+        let mut session = target.run(&["notes", "example.com", "blabber"], Some(PRIMARY_PASSWORD));
+        session.expect_str(IMPORTED_NOTES);
+    }
+}
+
+#[test]
+fn import_backup() {
+    let mut source = Setup::new();
+    source.set_secrets(SECRETS);
+    source.initialize(PRIMARY_PASSWORD);
+
+    let backup_dir = tempfile::tempdir().expect("Creating a temporary directory should succeed");
+    let backup_file = backup_dir.path().join("backup.json");
+
+    {
+        let mut session = source.run(&["shell"], None);
+        session.expect_str("Enter a command");
+
+        session.send_line("add-stored example.com blabber");
+        session.expect_str("Your primary password:");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("Password to be stored");
+        session.send_line("asdf");
+        session.expect_str("Password added");
+
+        session.send_line("add-stored example.com blubber");
+        session.expect_str("Password to be stored");
+        session.send_line("yxcv");
+        session.expect_str("Password added");
+
+        session.send_line("alias example.info example.com");
+        session.expect_str("Alias added");
+
+        session.send_line(&format!("export --format pfp -o {}", backup_file.display()));
+        session.expect_str("Data written to");
+    }
+
+    {
+        let mut target = Setup::new();
+        target.set_secrets(SECRETS);
+        target.initialize("different");
+
+        let mut session = target.run(
+            &["import".as_ref(), backup_file.as_os_str()],
+            Some("different"),
+        );
+        session.expect_str("Backup was created with a different primary password.");
+    }
+
+    let mut target = Setup::new();
+    target.set_secrets(SECRETS);
+    target.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = target.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line("existing");
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = target.run(
+            &["import".as_ref(), backup_file.as_os_str()],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password blabber for example.com (revision: 1) already exists.");
+        session.send_line("k");
+        session.expect_str("1 password(s) imported, 1 skipped");
+    }
+
+    {
+        let mut session = target.run(&["show", "example.info", "blabber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), "existing");
+    }
+
+    {
+        let mut session = target.run(
+            &["import".as_ref(), backup_file.as_os_str(), "-y".as_ref()],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("2 password(s) imported, 0 skipped");
+    }
+
+    {
+        let mut session = target.run(&["show", "example.com", "blabber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), "asdf");
+    }
+}
+
+#[test]
+fn import_csv() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.set_secrets(&[IMPORTED_VALUE.as_bytes()]);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let csv_file = tempfile::NamedTempFile::new()
+        .expect("Creating a temporary file should succeed")
+        .into_temp_path();
+    std::fs::write(
+        &csv_file,
+        format!(
+            "name,url,username,password
+Example,https://www.Example.com/login?x=1,blubber,{}
+Example again,http://user@example.com:8080,blabber,yxcv
+No password,https://example.net/,blubber,
+Broken,https://example.org/
+No address,,blubber,qwer
+Duplicate,example.com,blubber,zxcv
+Local,[::1]:8080/admin,root,rtyu
+",
+            IMPORTED_VALUE
+        ),
+    )
+    .expect("Writing CSV file should succeed");
+
+    {
+        let mut session = setup.run(
+            &["import-csv".as_ref(), csv_file.as_os_str()],
+            Some(PRIMARY_PASSWORD),
+        );
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+3 password(s) imported, 1 skipped (empty password), 1 skipped (already present).
+The following rows could not be imported:
+    Line 5: expected 4 fields, found 2
+    Line 6: no valid website address
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for [::1]:
+    root (stored)
+Passwords for example.com:
+    blabber (stored)
+    blubber (stored)
+"
+            .trim()
+        );
+    }
+}
+
+#[test]
+fn export_csv() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.set_secrets(&[EXPORTED_VALUE.as_bytes(), EXPORTED_NOTES.as_bytes()]);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let mut session = setup.run(&["shell"], None);
+    session.expect_str("Enter a command");
+
+    session.send_line("add example.com blubber --length 12");
+    session.expect_str("Your primary password:");
+    session.send_line(PRIMARY_PASSWORD);
+    session.expect_str("Password added");
+
+    session.send_line("add-stored example.info blabber -r 2");
+    session.expect_str("Password to be stored");
+    session.send_line(EXPORTED_VALUE);
+    session.expect_str("Password added");
+
+    session.send_line("notes example.info blabber -r 2 -s");
+    session.expect_str("enter new notes");
+    session.send_line(EXPORTED_NOTES);
+    session.expect_str("Notes stored");
+
+    session.send_line("export-csv");
+    session.expect_str(
+        "site,name,revision,type
+example.com,blubber,1,generated
+example.info,blabber,2,stored
+",
+    );
+
+    session.send_line("export-csv --columns name,length,notes");
+    session.expect_str(
+        "name,length,notes
+blubber,12,
+blabber,,\"exported \"\"quoted\"\" notes\"
+",
+    );
+
+    session.send_line("export-csv --columns site,value");
+    session.expect_str("use --allow-plaintext");
+
+    session.send_line("export-csv --columns site,value --allow-plaintext");
+    session.expect_str(&format!("example.info,{}\n", EXPORTED_VALUE));
+
+    let export_dir = tempfile::tempdir().expect("Creating a temporary directory should succeed");
+    let export_file = export_dir.path().join("export.csv");
+    session.send_line(&format!(
+        "export-csv --columns site,name,value,notes --allow-plaintext -o {}",
+        export_file.display()
+    ));
+    session.expect_str("Data written to");
+    let contents =
+        std::fs::read_to_string(&export_file).expect("Reading export file should succeed");
+    assert!(contents.starts_with("site,name,value,notes\nexample.com,blubber,"));
+    assert!(contents.ends_with(&format!(
+        "\nexample.info,blabber,{},\"exported \"\"quoted\"\" notes\"\n",
+        EXPORTED_VALUE
+    )));
+}
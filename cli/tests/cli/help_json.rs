@@ -0,0 +1,22 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use crate::common::Setup;
+
+#[test]
+fn help_json() {
+    // Storage file doesn't exist, this command shouldn't need it
+    let setup = Setup::new();
+    let mut session = setup.run(&["help-json"], None);
+
+    session.expect_str(r#""about": "PfP: Pain-free Passwords, command line edition""#);
+    session.expect_str(r#""long": "type""#);
+    session.expect_str(r#""possible_values": ["#);
+    session.expect_str(r#""generated","#);
+    session.expect_str(r#""stored""#);
+    session.expect_str(r#""name": "list""#);
+    session.expect_str(r#""name": "shell""#);
+}
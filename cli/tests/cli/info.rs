@@ -0,0 +1,185 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use crate::common::Setup;
+
+const PRIMARY_PASSWORD: &str = "foobar";
+const SECRETS: &[&[u8]] = &[PRIMARY_PASSWORD.as_bytes()];
+
+#[test]
+fn uninitialized() {
+    let setup = Setup::new();
+    let mut session = setup.run(&["info"], None);
+    session.expect_str("Initialized: no");
+}
+
+#[test]
+fn info() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["alias", "example.org", "example.com"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Alias added");
+    }
+
+    {
+        let mut session = setup.run(&["info"], None);
+        session.expect_str("Initialized: yes");
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Format version: 3
+Key derivation: scrypt (N = 32768, r = 8, p = 1)
+Salt length: 16 bytes
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(&["info", "-u"], None);
+        session.expect_str("Salt length: 16 bytes");
+        session.expect_str("Your primary password:");
+        session.send_line(PRIMARY_PASSWORD);
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Sites: 1
+Aliases: 1
+Passwords: 1 (1 generated, 0 stored)
+"
+            .trim()
+        );
+    }
+}
+
+#[test]
+fn stats() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["add", "example.net", "blabber", "-l", "12"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["alias", "example.org", "example.com"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Alias added");
+    }
+
+    {
+        let mut session = setup.run(&["stats"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Sites: 2
+Aliases: 1
+Passwords: 2 (2 generated, 0 stored)
+Passwords with notes: 0
+Generated password lengths:
+    12: 1
+    16: 1
+"
+            .trim()
+        );
+    }
+}
+
+#[test]
+fn set_kdf() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line("asdf");
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["set-kdf", "-i", "5000"], None);
+        session.expect_str("power of two");
+    }
+
+    {
+        let mut session = setup.run(&["set-kdf", "-i", "1024"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Key derivation parameters changed");
+    }
+
+    {
+        let mut session = setup.run(&["info"], None);
+        session.expect_str("Key derivation: scrypt (N = 1024, r = 8, p = 1)");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved");
+        session.expect_str("asdf");
+    }
+}
+
+#[test]
+fn argon2id() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+
+    {
+        let mut session = setup.run(&["set-primary", "--kdf", "argon2id"], None);
+        session.expect_str("New primary password:");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("Repeat primary password:");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("primary password set");
+    }
+
+    {
+        let mut session = setup.run(&["info"], None);
+        session.expect_str("Key derivation: argon2id (m = 65536 KiB, t = 3, p = 1)");
+    }
+
+    {
+        let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+        session.expect_str("No matching passwords");
+    }
+
+    {
+        let mut session = setup.run(&["set-kdf"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Key derivation parameters changed");
+    }
+
+    {
+        let mut session = setup.run(&["info"], None);
+        session.expect_str("Key derivation: scrypt (N = 32768, r = 8, p = 1)");
+    }
+}
@@ -49,6 +49,142 @@ fn success() {
     session.expect_str("No matching passwords");
 }
 
+#[test]
+fn too_many_attempts() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let mut session = setup.run(
+        &["--max-attempts", "2", "--retry-delay", "10", "list"],
+        Some(ANOTHER_PRIMARY_PASSWORD),
+    );
+    session.expect_str("Decryption failure");
+    session.expect_str("Your primary password");
+    session.send_line(ANOTHER_PRIMARY_PASSWORD);
+    session.expect_str("Decryption failure");
+    session.expect_str("Too many failed attempts");
+}
+
+#[test]
+fn stdin_primary_once() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["--stdin-primary-once", "list"], None);
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("No matching passwords");
+    }
+
+    {
+        // Only the first line is taken from stdin without prompting
+        let mut session = setup.run(&["--stdin-primary-once", "list"], None);
+        session.send_line(ANOTHER_PRIMARY_PASSWORD);
+        session.expect_str("Decryption failure");
+        session.expect_str("Your primary password");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("No matching passwords");
+    }
+}
+
+#[test]
+fn env_password() {
+    let setup = Setup::new();
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run_with_env(
+            &["--allow-env-password", "list"],
+            &[("PFP_PRIMARY_PASSWORD", PRIMARY_PASSWORD)],
+            None,
+        );
+        session.expect_str("No matching passwords");
+    }
+
+    {
+        // Variable is ignored without the flag
+        let mut session = setup.run_with_env(
+            &["list"],
+            &[("PFP_PRIMARY_PASSWORD", PRIMARY_PASSWORD)],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("No matching passwords");
+    }
+
+    {
+        // Wrong password in the variable falls back to prompting
+        let mut session = setup.run_with_env(
+            &["--allow-env-password", "list"],
+            &[("PFP_PRIMARY_PASSWORD", ANOTHER_PRIMARY_PASSWORD)],
+            None,
+        );
+        session.expect_str("Decryption failure");
+        session.expect_str("Your primary password");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("No matching passwords");
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // The password shouldn't stay in the process environment
+        let mut session = setup.run_with_env(
+            &["--allow-env-password", "shell"],
+            &[("PFP_PRIMARY_PASSWORD", PRIMARY_PASSWORD)],
+            None,
+        );
+        session.expect_str("Enter a command");
+        session.send_line("list");
+        session.expect_str("No matching passwords");
+
+        let environ = session.read_environ();
+        session.send_line("exit");
+        assert!(!environ
+            .windows(PRIMARY_PASSWORD.len())
+            .any(|window| window == PRIMARY_PASSWORD.as_bytes()));
+    }
+}
+
+#[test]
+fn stdin_storage() {
+    let setup = Setup::new();
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let run = |args: &[&str], input: String| {
+        subprocess::Exec::cmd(env!("CARGO_BIN_EXE_pfp-cli"))
+            .args(&["--stdin-storage", "--allow-env-password"])
+            .args(args)
+            .env("PFP_PRIMARY_PASSWORD", PRIMARY_PASSWORD)
+            .stdin(input.into_bytes())
+            .stdout(subprocess::Redirection::Pipe)
+            .stderr(subprocess::Redirection::Pipe)
+            .capture()
+            .expect("Running binary should succeed")
+    };
+
+    let result = run(&["add", "example.com", "blubber"], setup.get_file_data());
+    assert!(result.exit_status.success());
+    assert!(result.stderr_str().contains("Password added"));
+
+    // Output consists of exactly one storage document which can be read in again
+    let data = result.stdout_str();
+    assert_eq!(data.matches("\"application\"").count(), 1);
+    let result = run(&["list"], data.clone());
+    assert!(result.exit_status.success());
+    assert!(result.stderr_str().contains("blubber (generated)"));
+    assert_eq!(result.stdout_str(), "");
+
+    let result = run(&["info"], data.clone());
+    assert!(result.exit_status.success());
+    assert!(result.stderr_str().contains("Storage path: stdin"));
+    assert!(result.stderr_str().contains("Format version: 3"));
+
+    setup.set_file_data(&data);
+    let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+    session.expect_str("blubber (generated)");
+}
+
 #[test]
 fn reinitialization_aborted() {
     let mut setup = Setup::new();
@@ -110,3 +246,84 @@ fn reinitialization_noninteractive() {
         session.expect_str("No matching passwords");
     }
 }
+
+#[test]
+fn change_primary() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line("stored value");
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["change-primary"], Some(PRIMARY_PASSWORD));
+        session.expect_str("New primary password:");
+        session.send_line(ANOTHER_PRIMARY_PASSWORD);
+        session.expect_str("Repeat primary password:");
+        session.send_line(ANOTHER_PRIMARY_PASSWORD);
+        session.expect_str("Primary password changed.");
+    }
+
+    {
+        let mut session = setup.run(&["--max-attempts", "1", "list"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Decryption failure");
+        session.expect_str("Too many failed attempts");
+    }
+
+    {
+        let mut session = setup.run(
+            &["show", "example.com", "blubber"],
+            Some(ANOTHER_PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), "stored value");
+    }
+
+    {
+        let mut session = setup.run(
+            &["add", "example.com", "blabber"],
+            Some(ANOTHER_PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password added");
+
+        session = setup.run(&["change-primary"], Some(ANOTHER_PRIMARY_PASSWORD));
+        session.expect_str("their values will change");
+        session.send_line("n");
+    }
+
+    {
+        let mut session = setup.run(&["shell"], None);
+        session.expect_str("Enter a command");
+        session.send_line("list");
+        session.expect_str("Your primary password:");
+        session.send_line(ANOTHER_PRIMARY_PASSWORD);
+        session.expect_str("blubber (stored)");
+
+        session.send_line("change-primary -y");
+        session.expect_str("Current primary password:");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("Wrong primary password.");
+
+        session.send_line("change-primary -y");
+        session.expect_str("Current primary password:");
+        session.send_line(ANOTHER_PRIMARY_PASSWORD);
+        session.expect_str("New primary password:");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("Repeat primary password:");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("Primary password changed.");
+    }
+
+    {
+        let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+        session.expect_str("blubber (stored)");
+    }
+}
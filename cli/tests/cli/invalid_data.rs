@@ -50,7 +50,7 @@ fn wrong_application() {
 #[test]
 fn wrong_format() {
     let setup = Setup::new();
-    setup.set_file_data(r#"{"application":"pfp","format":8}"#);
+    setup.set_file_data(r#"{"application":"pfp","format":2}"#);
     let mut session = setup.run(&["list"], None);
 
     session.expect_str("Corrupt JSON data");
@@ -58,6 +58,38 @@ fn wrong_format() {
     session.expect_str("expected 3");
 }
 
+#[test]
+fn newer_format() {
+    let setup = Setup::new();
+    setup.set_file_data(
+        r#"{"application":"pfp","format":4,"data":{"salt":"Y2Jh","hmac-secret":"YWJjZGVmZ2hpamts_Nosk0g9vPYtLPn9QzyFXLQ/1ZuAHVw=="},"something":2}"#,
+    );
+
+    {
+        let mut session = setup.run(&["list"], None);
+        session.expect_str("format version 4");
+        session.expect_str("please upgrade");
+    }
+
+    {
+        let mut session = setup.run(&["set-primary", "-y"], None);
+        session.expect_str("format version 4");
+    }
+
+    {
+        let mut session = setup.run(&["--allow-newer", "list"], Some("foobar"));
+        session.expect_str("No matching passwords found.");
+    }
+
+    {
+        let mut session = setup.run(
+            &["--allow-newer", "add", "example.com", "test"],
+            Some("foobar"),
+        );
+        session.expect_str("opened read-only");
+    }
+}
+
 #[test]
 fn wrong_format_type() {
     let setup = Setup::new();
@@ -157,3 +189,57 @@ fn complete_data() {
     );
     setup.run(&["list"], Some("asdf")).kill();
 }
+
+#[test]
+fn corrupt_hmac_secret() {
+    let setup = Setup::new();
+    setup.set_file_data(
+        r#"{"application":"pfp","format":3,"data":{"salt":"Y2Jh","hmac-secret":"YWJj_YWJj"}}"#,
+    );
+    let mut session = setup.run(&["list"], Some("asdfyxcv"));
+
+    session.expect_str("Storage appears to be corrupt");
+}
+
+#[test]
+fn unreadable_entries() {
+    let setup = Setup::new();
+    // Contains a legacy generated password example.com/legacy, primary password foobar
+    let data = r#"
+        "salt":"Y2Jh",
+        "hmac-secret":"YWJjZGVmZ2hpamts_Nosk0g9vPYtLPn9QzyFXLQ/1ZuAHVw==",
+        "site:fRTOldDD+lTwIBS8G+eUkrIzvNsfdGRSWQXrXqszDHM=":"YWJjZGVmZ2hpamts_b/AA8REorsFjuwlGDYB+KVw/fqoHPv2Ehc7sBIYqhR+ygcsd/t4=",
+        "site:fRTOldDD+lTwIBS8G+eUkrIzvNsfdGRSWQXrXqszDHM=:qWhfZOFEdqACexKf3dxNh+lGAaN/LCf6xELNB7I9Ts8=":"YWJjZGVmZ2hpamts_b/AH4RUorsFjuRRJBYJzOBc4M+sHMG+8u1ZPdAegGgJ0WRr0DnFfptHwCQdRh4sfyWuuGm0+fQIJSFaVrprvKt0Z3jZz1OmtAJyvMwQiOARVxSYbhStzi6fdhdfNvxjQpCuvvxVdq7CVNk6GnjNZTtaNQdFaQYFqX0yjiKgwLCeI3/Jo1PMzdycTfv8IVUC9clyEESkKQV4JzNZyCnMts9Vzgg3/dZGrUMPGmA==""#;
+
+    setup.set_file_data(&format!(
+        r#"{{"application":"pfp","format":3,"data":{{{}}}}}"#,
+        data
+    ));
+
+    {
+        let mut session = setup.run(&["verify"], Some("foobar"));
+        session.expect_str("All 2 storage entries can be read.");
+        assert_eq!(session.wait_exit_code(), 0);
+    }
+
+    setup.set_file_data(&format!(
+        r#"{{"application":"pfp","format":3,"data":{{{},
+            "site:abc":"garbage",
+            "site:fRTOldDD+lTwIBS8G+eUkrIzvNsfdGRSWQXrXqszDHM=:xyz":"YWJjZGVmZ2hpamts_YWJjZGVmZ2hpamtsbW5vcHFyc3R1dnd4"
+        }}}}"#,
+        data
+    ));
+
+    {
+        let mut session = setup.run(&["verify"], Some("foobar"));
+        session.expect_str(
+            "
+The following storage entries could not be read:
+    site:abc: Storage appears to be corrupt, invalid ciphertext data.
+    site:fRTOldDD+lTwIBS8G+eUkrIzvNsfdGRSWQXrXqszDHM=:xyz: Decryption failure, wrong primary password?
+"
+            .trim(),
+        );
+        assert_eq!(session.wait_exit_code(), 1);
+    }
+}
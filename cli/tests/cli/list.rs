@@ -26,6 +26,25 @@ fn uninitialized() {
     session.expect_str("Failed reading storage file");
 }
 
+#[test]
+fn empty() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+        session.expect_str("No matching passwords found.");
+        session.expect_str("No passwords stored yet.");
+    }
+
+    {
+        let mut session = setup.run(&["list", "example.com"], Some(PRIMARY_PASSWORD));
+        session.expect_str("No matching passwords found.");
+        assert!(!session.read_to_empty_line().contains("stored yet"));
+    }
+}
+
 #[test]
 fn list() {
     let mut setup = Setup::new();
@@ -141,23 +160,28 @@ Passwords for example.com:
             .to_string()
                 + ANOTHER_STORED_PASSWORD
                 + "
+        Created: 2009-02-13 23:31:30 UTC
     blubber (generated)
         SUDJjn&%:nBe}cr8
         Length: 16
         Allowed characters: abc ABC 789 +^;
+        Created: 2009-02-13 23:31:30 UTC
     blubber (generated, revision: 2)
         &>?DR
         Length: 5
         Allowed characters: ABC +^;
+        Created: 2009-02-13 23:31:30 UTC
     blubber (generated, revision: 8)
         8svhxq86pwfc87qwvx9g
         Notes: Now some notes stored here
         Length: 20
         Allowed characters: abc 789
+        Created: 2009-02-13 23:31:30 UTC
 Passwords for example.net:
     blabber (stored)
         " + STORED_PASSWORD
                 + "
+        Created: 2009-02-13 23:31:30 UTC
 ")
             .trim()
         );
@@ -170,6 +194,7 @@ Passwords for example.net:
             "
 Passwords for example.net:
     blabber (stored)
+        Created: 2009-02-13 23:31:30 UTC
 "
             .trim()
         );
@@ -189,13 +214,16 @@ Passwords for example.com:
     blubber (generated)
         Length: 16
         Allowed characters: abc ABC 789 +^;
+        Created: 2009-02-13 23:31:30 UTC
     blubber (generated, revision: 2)
         Length: 5
         Allowed characters: ABC +^;
+        Created: 2009-02-13 23:31:30 UTC
     blubber (generated, revision: 8)
         Notes: Now some notes stored here
         Length: 20
         Allowed characters: abc 789
+        Created: 2009-02-13 23:31:30 UTC
 "
             .trim()
         );
@@ -213,6 +241,7 @@ Passwords for example.com:
 Passwords for example.com:
     Aliases: example.info
     blabber (stored, revision: another)
+        Created: 2009-02-13 23:31:30 UTC
 "
             .trim()
         );
@@ -227,10 +256,375 @@ Passwords for example.com:
     Aliases: example.info,
              example.org
     blabber (stored, revision: another)
+        Created: 2009-02-13 23:31:30 UTC
+Passwords for example.net:
+    blabber (stored)
+        Created: 2009-02-13 23:31:30 UTC
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(&["list", "--type", "stored"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
+    blabber (stored, revision: another)
+Passwords for example.net:
+    blabber (stored)
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(&["list", "-t", "generated"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
+    blubber (generated)
+    blubber (generated, revision: 2)
+    blubber (generated, revision: 8)
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(
+            &["list", "--type", "generated", "example.net"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("No matching passwords found.");
+    }
+
+    {
+        let mut session = setup.run(
+            &["list", "--offset", "2", "--limit", "3"],
+            Some(PRIMARY_PASSWORD),
+        );
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
+    blubber (generated, revision: 2)
+    blubber (generated, revision: 8)
+Passwords for example.net:
+    blabber (stored)
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(&["list", "--offset", "10"], Some(PRIMARY_PASSWORD));
+        session.expect_str("No matching passwords found.");
+    }
+
+    {
+        // Sites with passwords of the other type shouldn't be considered empty
+        let mut session = setup.run(&["list", "example.net"], Some(PRIMARY_PASSWORD));
+        session.expect_str("blabber (stored)");
+    }
+}
+
+#[test]
+fn search() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+
+        session = setup.run(
+            &["add-stored", "example.net", "Blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(ANOTHER_STORED_PASSWORD);
+        session.expect_str("Password added");
+
+        session = setup.run(
+            &["notes", "example.net", "Blubber", "-s"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("enter new notes");
+        session.send_line("Now some notes stored here");
+        session.expect_str("Notes stored");
+
+        session = setup.run(
+            &["set-alias", "--target", "example.com", "example.info"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("is now an alias");
+    }
+
+    {
+        let mut session = setup.run(&["search", "BLUB"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
 Passwords for example.net:
+    Blubber (stored)
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(&["search", "info"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
     blabber (stored)
 "
             .trim()
         );
     }
+
+    {
+        let mut session = setup.run(&["search", "notes"], Some(PRIMARY_PASSWORD));
+        session.expect_str("No matching passwords found.");
+
+        session = setup.run(&["search", "notes", "--notes"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Passwords for example.net:");
+        session.expect_str("Blubber (stored)");
+    }
+}
+
+#[test]
+fn json() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["list", "--json"], Some(PRIMARY_PASSWORD));
+        assert_eq!(session.read_to_empty_line().trim(), "[]");
+    }
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.net", "blabber", "-r", "2"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["notes", "example.net", "blabber", "-r", "2", "-s"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("enter new notes");
+        session.send_line("Now some notes stored here");
+        session.expect_str("Notes stored");
+    }
+
+    {
+        let mut session = setup.run(&["list", "--json"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            r#"[{"site":"example.com","name":"blubber","revision":"","type":"generated","length":16,"charset":["lower","upper","number","symbol"],"created":1234567890,"modified":1234567890},{"site":"example.net","name":"blabber","revision":"2","type":"stored","created":1234567890,"modified":1234567890}]"#
+        );
+    }
+
+    {
+        let mut session = setup.run(
+            &["list", "--json", "--show", "-v", "example.net"],
+            Some(PRIMARY_PASSWORD),
+        );
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            r#"[{"site":"example.net","name":"blabber","revision":"2","type":"stored","created":1234567890,"modified":1234567890,"notes":"Now some notes stored here","value":"asdf"}]"#
+        );
+    }
+
+    {
+        // Pagination isn't available for JSON output, scripts always get everything
+        let setup = Setup::new();
+        let mut session = setup.run(&["list", "--json", "--limit", "1"], None);
+        session.expect_str("cannot be used with");
+
+        session = setup.run(&["list", "--json", "--offset", "1"], None);
+        session.expect_str("cannot be used with");
+    }
+}
+
+#[test]
+fn tags() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    for name in ["blabber", "blubber"] {
+        let mut session = setup.run(&["add", "example.com", name], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "tag",
+                "example.com",
+                "blubber",
+                "-a",
+                "work",
+                "--add",
+                "mail",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Tags: mail, work");
+    }
+
+    {
+        let mut session = setup.run(
+            &["tag", "example.com", "blabber", "--remove", "work"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("This password has no tags.");
+    }
+
+    {
+        let mut session = setup.run(
+            &["tag", "example.com", "blabber", "--add", ""],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Tags cannot be empty.");
+    }
+
+    {
+        let mut session = setup.run(&["list", "--tag", "work"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
+    blubber (generated)
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(
+            &["list", "-v", "--tag", "mail", "*.com", "bl?bber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
+    blubber (generated)
+        Length: 16
+        Allowed characters: abc ABC 789 +^;
+        Tags: mail, work
+        Created: 2009-02-13 23:31:30 UTC
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(
+            &["list", "--tag", "work", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("No matching passwords found.");
+    }
+
+    {
+        let mut session = setup.run(&["list", "--json", "--tag", "work"], Some(PRIMARY_PASSWORD));
+        session.expect_str(r#""tags":["mail","work"]"#);
+    }
+}
+
+#[test]
+fn modified_since() {
+    let mut setup = Setup::new();
+    setup.set_secrets(&[PRIMARY_PASSWORD.as_bytes()]);
+
+    // Entries written by the browser extension, these have no timestamps
+    setup.set_file_data(
+        r#"{"application":"pfp","format":3,"data":{
+            "salt":"Y2Jh",
+            "hmac-secret":"YWJjZGVmZ2hpamts_Nosk0g9vPYtLPn9QzyFXLQ/1ZuAHVw==",
+            "site:fRTOldDD+lTwIBS8G+eUkrIzvNsfdGRSWQXrXqszDHM=":"YWJjZGVmZ2hpamts_b/AA8REorsFjuwlGDYB+KVw/fqoHPv2Ehc7sBIYqhR+ygcsd/t4=",
+            "site:fRTOldDD+lTwIBS8G+eUkrIzvNsfdGRSWQXrXqszDHM=:/uudghlPp4TDZPtfZFPj6nJs/zMDAE2AqVfz6Hu8N9I=":"YWJjZGVmZ2hpamts_b/AH4RUorsFjuRRJBYJzOBc4I+UJYXWhqhFXbEC9Aw5pRRO/Q31d6d/+RQhdj8wH0SWpEXk/ZkVXSAjSqpbqKsEek2JzzOetQNutMR4tblZGzTsPxWZogaKazYGFvg+J43L9ugBf7PjDfk+Rx3QbGdWaScEdCdciXlv6z/drMjyK0b8+kKgrdjdaIT7NuJwpEiZxzMngRiqPqZI="
+        }}"#,
+    );
+
+    {
+        let mut session = setup.run(&["add", "example.net", "test"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["list", "--modified-since", "2009-02-13"],
+            Some(PRIMARY_PASSWORD),
+        );
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.net:
+    test (generated)
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "list",
+                "--modified-since",
+                "2009-02-13",
+                "--include-undated",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
+    blubber (generated)
+Passwords for example.net:
+    test (generated)
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(
+            &["list", "--modified-since", "2009-02-14"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("No matching passwords found.");
+    }
+
+    {
+        let setup = Setup::new();
+        let mut session = setup.run(&["list", "--modified-since", "2009-02-30"], None);
+        session.expect_str("format YYYY-MM-DD");
+    }
 }
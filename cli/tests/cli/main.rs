@@ -12,8 +12,14 @@
 
 mod aliases;
 mod common;
+mod exit_codes;
+mod export;
+mod help_json;
+mod info;
 mod initialization;
 mod invalid_data;
 mod list;
 mod passwords;
+mod policies;
+mod profiles;
 mod shell;
@@ -107,16 +107,21 @@ fn add() {
             "
 Passwords for example.com:
     blabber (stored)
+        Created: 2009-02-13 23:31:30 UTC
     blabber (stored, revision: another)
+        Created: 2009-02-13 23:31:30 UTC
     blubber (generated)
         Length: 16
         Allowed characters: abc ABC 789 +^;
+        Created: 2009-02-13 23:31:30 UTC
     blubber (generated, revision: 2)
         Length: 5
         Allowed characters: ABC +^;
+        Created: 2009-02-13 23:31:30 UTC
     blubber (generated, revision: 8)
         Length: 20
         Allowed characters: abc 789
+        Created: 2009-02-13 23:31:30 UTC
 "
             .trim()
         );
@@ -204,12 +209,58 @@ Passwords for example.com:
     blubber (generated)
         Length: 16
         Allowed characters: abc ABC 789 +^;
+        Created: 2009-02-13 23:31:30 UTC
 "
             .trim()
         );
     }
 }
 
+#[test]
+fn stored_whitespace() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line("  trimmed  ");
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blubber", "--no-trim"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line("  verbatim  ");
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com", "blabber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(
+            session.read_to_empty_line().trim_end_matches('\n'),
+            "trimmed"
+        );
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(
+            session.read_to_empty_line().trim_end_matches('\n'),
+            "  verbatim  "
+        );
+    }
+}
+
 #[test]
 fn overwrite_aborted_stored() {
     let mut setup = Setup::new();
@@ -279,6 +330,7 @@ Passwords for example.com:
     blubber (generated)
         Length: 8
         Allowed characters: ABC 789 +^;
+        Created: 2009-02-13 23:31:30 UTC
 "
             .trim()
         );
@@ -359,6 +411,7 @@ Passwords for example.com:
     blubber (generated)
         Length: 8
         Allowed characters: ABC 789 +^;
+        Created: 2009-02-13 23:31:30 UTC
 "
             .trim()
         );
@@ -398,6 +451,92 @@ fn overwrite_noninteractive_stored() {
     }
 }
 
+#[test]
+fn history() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["history", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("No previous values recorded for this password.");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "add-stored",
+                "-y",
+                "--keep-history",
+                "example.com",
+                "blabber",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(ANOTHER_STORED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["history", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Previous values of this password, replaced at:\n");
+        assert_eq!(
+            session.read_to_empty_line().trim_end(),
+            "    1. 2009-02-13 23:31:30 UTC"
+        );
+    }
+
+    {
+        let mut session = setup.run(
+            &["history", "example.com", "blabber", "--restore", "2"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("There is no previous value with number 2 for this password.");
+    }
+
+    {
+        let mut session = setup.run(
+            &["history", "example.com", "blabber", "--restore", "1"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Previous value restored");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com", "blabber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), STORED_PASSWORD);
+    }
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+
+        session = setup.run(
+            &["history", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("This operation is only possible for stored passwords.");
+    }
+}
+
 #[test]
 fn remove() {
     let mut setup = Setup::new();
@@ -444,6 +583,70 @@ fn remove() {
     }
 }
 
+#[test]
+fn remove_pattern() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+
+        session = setup.run(
+            &["add", "example.com", "blubber", "-r", "2"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password added");
+
+        session = setup.run(&["add", "example.com", "blabber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+
+        session = setup.run(&["add", "example.com", "test"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["remove", "example.com", "x*"], Some(PRIMARY_PASSWORD));
+        session.expect_str("No matching passwords found.");
+
+        session = setup.run(
+            &["remove", "example.com", "bl?bber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("The following passwords will be removed:");
+        session.expect_str("Remove these passwords?");
+        session.send_line("n");
+
+        session = setup.run(
+            &["remove", "example.com", "bl*", "-r", "2"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("blubber (revision: 2)");
+        session.expect_str("Remove these passwords?");
+        session.send_line("y");
+        session.expect_str("1 password(s) removed.");
+
+        session = setup.run(
+            &["remove", "example.com", "bl*", "-y"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("2 password(s) removed.");
+    }
+
+    {
+        let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
+    test (generated)
+"
+            .trim()
+        );
+    }
+}
+
 #[test]
 fn recovery_codes() {
     let mut setup = Setup::new();
@@ -495,7 +698,7 @@ fn recovery_codes() {
 }
 
 #[test]
-fn show_qrcode() {
+fn recovery_file() {
     let mut setup = Setup::new();
     setup.set_secrets(SECRETS);
     setup.initialize(PRIMARY_PASSWORD);
@@ -510,100 +713,1191 @@ fn show_qrcode() {
         session.expect_str("Password added");
     }
 
+    let recovery_code = {
+        let mut session = setup.run(&["list", "-r"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Recovery code:");
+        session.read_to_empty_line()
+    };
+
+    let dir = tempfile::tempdir().expect("Creating a temporary directory should succeed");
+    let path = dir.path().join("recovery.txt");
+    let path_str = path.to_str().expect("Path should be valid UTF-8");
+
+    let mut lines: Vec<String> = recovery_code
+        .trim()
+        .split('\n')
+        .map(|line| line.trim().to_owned())
+        .collect();
+    let corrupted = lines[1].replacen(|c: char| c.is_ascii_alphanumeric(), "?", 1);
+    let original = std::mem::replace(&mut lines[1], corrupted);
+    std::fs::write(&path, lines.join("\n")).expect("Writing recovery code should succeed");
     {
         let mut session = setup.run(
-            &["show", "-q", "example.com", "blabber"],
+            &[
+                "add-stored",
+                "--recovery-file",
+                path_str,
+                "example.net",
+                "test",
+            ],
             Some(PRIMARY_PASSWORD),
         );
-        session.expect_str("Password retrieved.");
-        assert_eq!(
-            session.read_to_empty_line().trim(),
-            "
-█▀▀▀▀▀█ █  ▄  █▀▀▀▀▀█\n
-█ ███ █ ▀▄▄▀▀ █ ███ █\n
-█ ▀▀▀ █ ▄▀█ █ █ ▀▀▀ █\n
-▀▀▀▀▀▀▀ █▄█ █ ▀▀▀▀▀▀▀\n
-  ███▄▀ ▀ ▄ ████  ███\n
- ▄▀▄ ▄▀▀▀▀▀▀▄▄ ▄██▄█ \n
-▀   ▀ ▀▀█ ▄ ▀▄▀ ▀▄▄ ▄\n
-█▀▀▀▀▀█   ▀▀▀ █▀ ▄█▀ \n
-█ ███ █ ███▄  ▀█ ▄▄▀ \n
-█ ▀▀▀ █ ▀▀▄ █▄█▄█ ▄  \n
-▀▀▀▀▀▀▀     ▀ ▀ ▀ ▀▀ \n
-"
-            .replace("\n\n", "\n")
-            .trim()
+        session.expect_str("checksum mismatch in line 2 of");
+    }
+
+    lines[1] = original;
+    std::fs::write(&path, lines.join("\n")).expect("Writing recovery code should succeed");
+    {
+        let mut session = setup.run(
+            &[
+                "add-stored",
+                "--recovery-file",
+                path_str,
+                "example.net",
+                "test",
+            ],
+            Some(PRIMARY_PASSWORD),
         );
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.net", "test"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), STORED_PASSWORD);
     }
 }
 
 #[test]
-fn notes() {
+fn recovery_command() {
     let mut setup = Setup::new();
     setup.set_secrets(SECRETS);
     setup.initialize(PRIMARY_PASSWORD);
 
     {
-        let mut session = setup.run(
-            &[
-                "add",
-                "example.com",
-                "blubber",
-                "-r",
-                "2",
-                "--no-lower",
-                "--no-digit",
-                "--length",
-                "5",
-            ],
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+
+        session = setup.run(
+            &["add-stored", "example.com", "blabber"],
             Some(PRIMARY_PASSWORD),
         );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
         session.expect_str("Password added");
     }
 
     {
         let mut session = setup.run(
-            &["notes", "example.com", "blubber", "-r", "2"],
+            &["recovery", "example.com", "blubber"],
             Some(PRIMARY_PASSWORD),
         );
-        session.expect_str("no notes are stored");
-    }
+        session.expect_str("Recovery codes are only available for stored passwords.");
 
-    {
-        let mut session = setup.run(
-            &["notes", "example.com", "blubber", "-r", "2", "-s"],
+        session = setup.run(
+            &["recovery", "example.com", "blabber", "-r", "2"],
             Some(PRIMARY_PASSWORD),
         );
-        session.expect_str("no notes are stored");
-        session.expect_str("enter new notes");
-        session.send_line("hi there!");
-        session.expect_str("Notes stored");
-    }
+        session.expect_str("No such value");
 
-    {
-        let mut session = setup.run(
-            &["notes", "example.com", "blubber", "-r", "2"],
+        session = setup.run(
+            &["recovery", "-q", "example.com", "blabber"],
             Some(PRIMARY_PASSWORD),
         );
-        session.expect_str("Notes for this password: hi there!");
+        session.expect_str("Recovery code:");
+        assert!(session
+            .read_to_empty_line()
+            .trim()
+            .starts_with("\u{2588}\u{2580}"));
     }
 
-    {
+    let recovery_code = {
         let mut session = setup.run(
-            &["notes", "example.com", "blubber", "-r", "2", "-s"],
+            &["recovery", "example.com", "blabber"],
             Some(PRIMARY_PASSWORD),
         );
-        session.expect_str("Notes for this password: hi there!");
-        session.expect_str("enter new notes");
-        session.send_line("");
-        session.expect_str("Notes removed");
-    }
+        session.expect_str("Recovery code:");
+        session.read_to_empty_line()
+    };
 
     {
         let mut session = setup.run(
-            &["notes", "example.com", "blubber", "-r", "2"],
+            &["add-stored", "-c", "example.net", "test"],
             Some(PRIMARY_PASSWORD),
         );
-        session.expect_str("no notes are stored");
+        for line in recovery_code.trim().split('\n') {
+            session.expect_str("line of your recovery code");
+            session.send_line(line);
+        }
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.net", "test"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), STORED_PASSWORD);
+    }
+}
+
+#[test]
+fn confirm_show() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["--confirm-show", "show", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Confirm your primary password:");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), STORED_PASSWORD);
+    }
+
+    {
+        let mut session = setup.run(
+            &["--confirm-show", "list", "--show"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Confirm your primary password:");
+        session.send_line("foobaz");
+        session.expect_str("Wrong primary password");
+    }
+}
+
+#[test]
+fn show_output() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    let dir = tempfile::tempdir().expect("Creating a temporary directory should succeed");
+    let path = dir.path().join("password.txt");
+    let path_str = path.to_str().expect("Path should be valid UTF-8");
+
+    {
+        let mut session = setup.run(
+            &["show", "example.com", "blabber", "--output", path_str],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password written to");
+    }
+    assert_eq!(
+        std::fs::read_to_string(&path).expect("Reading output file should succeed"),
+        format!("{}\n", STORED_PASSWORD)
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = std::fs::metadata(&path).expect("Output file should exist");
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+
+    {
+        let mut session = setup.run(
+            &["show", "example.com", "blabber", "--output", path_str],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("already exists");
+    }
+
+    std::fs::write(&path, "").expect("Writing output file should succeed");
+    {
+        let mut session = setup.run(
+            &["show", "example.com", "blabber", "-o", path_str, "--force"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password written to");
+    }
+    assert_eq!(
+        std::fs::read_to_string(&path).expect("Reading output file should succeed"),
+        format!("{}\n", STORED_PASSWORD)
+    );
+}
+
+#[test]
+fn show_qrcode() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["show", "-q", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password retrieved.");
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+█▀▀▀▀▀█ █  ▄  █▀▀▀▀▀█\n
+█ ███ █ ▀▄▄▀▀ █ ███ █\n
+█ ▀▀▀ █ ▄▀█ █ █ ▀▀▀ █\n
+▀▀▀▀▀▀▀ █▄█ █ ▀▀▀▀▀▀▀\n
+  ███▄▀ ▀ ▄ ████  ███\n
+ ▄▀▄ ▄▀▀▀▀▀▀▄▄ ▄██▄█ \n
+▀   ▀ ▀▀█ ▄ ▀▄▀ ▀▄▄ ▄\n
+█▀▀▀▀▀█   ▀▀▀ █▀ ▄█▀ \n
+█ ███ █ ███▄  ▀█ ▄▄▀ \n
+█ ▀▀▀ █ ▀▀▄ █▄█▄█ ▄  \n
+▀▀▀▀▀▀▀     ▀ ▀ ▀ ▀▀ \n
+"
+            .replace("\n\n", "\n")
+            .trim()
+        );
+    }
+}
+
+#[test]
+fn show_field() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+
+        session = setup.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+
+        session = setup.run(
+            &["notes", "example.com", "blubber", "-s"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("enter new notes");
+        session.send_line("hi there!");
+        session.expect_str("Notes stored");
+    }
+
+    {
+        let mut session = setup.run(
+            &["show", "--field", "notes", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Notes retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), "hi there!");
+
+        session = setup.run(
+            &["show", "--field", "value", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), STORED_PASSWORD);
+
+        session = setup.run(
+            &["show", "--field", "recovery", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Recovery codes are only available for stored passwords.");
+    }
+
+    let recovery_code = {
+        let mut session = setup.run(
+            &["show", "--field", "recovery", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Recovery code retrieved.");
+        session.read_to_empty_line()
+    };
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "-c", "example.net", "test"],
+            Some(PRIMARY_PASSWORD),
+        );
+        for line in recovery_code.trim().split('\n') {
+            session.expect_str("line of your recovery code");
+            session.send_line(line);
+        }
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.net", "test"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), STORED_PASSWORD);
+    }
+}
+
+#[test]
+fn notes() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &[
+                "add",
+                "example.com",
+                "blubber",
+                "-r",
+                "2",
+                "--no-lower",
+                "--no-digit",
+                "--length",
+                "5",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["notes", "example.com", "blubber", "-r", "2"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("no notes are stored");
+    }
+
+    {
+        let mut session = setup.run(
+            &["notes", "example.com", "blubber", "-r", "2", "--clipboard"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("no notes are stored");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "notes",
+                "example.com",
+                "blubber",
+                "-r",
+                "2",
+                "--clipboard",
+                "--clear-after",
+                "5",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("no notes are stored");
+    }
+
+    {
+        // --clear-after is only accepted along with --clipboard
+        let setup = Setup::new();
+        let mut session = setup.run(
+            &["notes", "example.com", "blubber", "--clear-after", "5"],
+            None,
+        );
+        session.expect_str("--clipboard");
+    }
+
+    {
+        let mut session = setup.run(
+            &["notes", "example.com", "blubber", "-r", "2", "-s"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("no notes are stored");
+        session.expect_str("enter new notes");
+        session.send_line("hi there!");
+        session.expect_str("Notes stored");
+    }
+
+    {
+        let mut session = setup.run(
+            &["notes", "example.com", "blubber", "-r", "2"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Notes for this password: hi there!");
+    }
+
+    {
+        let mut session = setup.run(
+            &["notes", "example.com", "blubber", "-r", "2", "-s"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Notes for this password: hi there!");
+        session.expect_str("enter new notes");
+        session.send_line("");
+        session.expect_str("Notes removed");
+    }
+
+    {
+        let mut session = setup.run(
+            &["notes", "example.com", "blubber", "-r", "2", "-s"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("enter new notes");
+        session.send_bytes(b"caf\xe9\n");
+        session.expect_str("isn't valid UTF-8");
+    }
+
+    {
+        let mut session = setup.run(
+            &["notes", "example.com", "blubber", "-r", "2"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("no notes are stored");
+    }
+}
+
+#[test]
+fn notes_non_interactive() {
+    let setup = Setup::new();
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    let dir = tempfile::tempdir().expect("Creating a temporary directory should succeed");
+    let path = dir.path().join("notes.txt");
+    let path_str = path.to_str().expect("Path should be valid UTF-8");
+    std::fs::write(&path, "first line\nsecond line\n").expect("Writing notes file should succeed");
+
+    {
+        let mut session = setup.run(
+            &[
+                "notes",
+                "example.com",
+                "blubber",
+                "--set-from-file",
+                path_str,
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Notes stored");
+
+        session = setup.run(
+            &["show", "--field", "notes", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Notes retrieved.first line\nsecond line\n");
+    }
+
+    {
+        let mut session = setup.run(
+            &["notes", "example.com", "blubber", "--set-stdin"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.send_bytes(b"one\ntwo\nthree\n");
+        session.close_stdin();
+        session.expect_str("Notes stored");
+
+        session = setup.run(
+            &["show", "--field", "notes", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Notes retrieved.one\ntwo\nthree\n");
+    }
+
+    std::fs::write(&path, "").expect("Writing notes file should succeed");
+    {
+        let mut session = setup.run(
+            &[
+                "notes",
+                "example.com",
+                "blubber",
+                "--set-from-file",
+                path_str,
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Notes removed");
+
+        session = setup.run(&["notes", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("no notes are stored");
+    }
+
+    {
+        let missing = dir.path().join("missing.txt");
+        let mut session = setup.run(
+            &[
+                "notes",
+                "example.com",
+                "blubber",
+                "--set-from-file",
+                missing.to_str().expect("Path should be valid UTF-8"),
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Failed reading file");
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn notes_interrupted() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["notes", "example.com", "blubber", "-s"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("enter new notes");
+        session.interrupt();
+        session.send_line("hi there!");
+        session.expect_str("Interrupted.");
+    }
+
+    {
+        let mut session = setup.run(&["notes", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("no notes are stored");
+    }
+}
+
+#[test]
+fn duplicate() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "duplicate",
+                "example.com",
+                "blabber",
+                "blabber",
+                "--new-revision",
+                "2",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password duplicated");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "duplicate",
+                "example.com",
+                "blabber",
+                "blubber",
+                "-r",
+                "2",
+                "--new-domain",
+                "example.net",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password duplicated");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "duplicate",
+                "example.com",
+                "blabber",
+                "blubber",
+                "--new-domain",
+                "example.net",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("already exists");
+        session.send_line("n");
+    }
+
+    {
+        let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
+    blabber (stored)
+    blabber (stored, revision: 2)
+Passwords for example.net:
+    blubber (stored)
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.net", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), STORED_PASSWORD);
+    }
+}
+
+#[test]
+fn rename() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+
+        session = setup.run(
+            &["add-stored", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(ANOTHER_STORED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["rename", "example.com", "blabber", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("already exists");
+        session.send_line("n");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "rename",
+                "example.com",
+                "blabber",
+                "blabbel",
+                "--new-revision",
+                "2",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password renamed");
+    }
+
+    {
+        let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
+    blabbel (stored, revision: 2)
+    blubber (stored)
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(
+            &["show", "example.com", "blabbel", "-r", "2"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), STORED_PASSWORD);
+    }
+
+    {
+        let mut session = setup.run(&["add", "example.com", "generated"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+
+        session = setup.run(
+            &["rename", "example.com", "generated", "renamed"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("its value will change");
+        session.send_line("n");
+
+        session = setup.run(
+            &["rename", "example.com", "generated", "renamed", "-y"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password renamed");
+    }
+}
+
+#[test]
+fn move_password() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+
+        session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["move", "example.com", "blubber", "example.net"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("its value will change");
+        session.send_line("n");
+    }
+
+    {
+        let mut session = setup.run(
+            &["move", "example.com", "blabber", "example.net"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password moved");
+    }
+
+    {
+        let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
+    blubber (generated)
+Passwords for example.net:
+    blabber (stored)
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.net", "blabber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), STORED_PASSWORD);
+    }
+}
+
+#[test]
+fn change_length() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add", "example.com", "blubber", "--no-symbol"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password added");
+
+        session = setup.run(
+            &["add-stored", "example.com", "blabber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["change-length", "example.com", "blabber", "8"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("only possible for generated passwords");
+
+        session = setup.run(
+            &["change-length", "example.com", "blubber", "8", "-r", "2"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("No such value");
+
+        session = setup.run(
+            &["change-length", "example.com", "blubber", "8"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password length changed.");
+    }
+
+    {
+        let mut session = setup.run(
+            &["list", "-v", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("blubber (generated)");
+        session.expect_str("Length: 8");
+        session.expect_str("Allowed characters: abc ABC 789");
+    }
+}
+
+#[test]
+fn change_charset() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add", "example.com", "blubber", "--length", "8"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "change-charset",
+                "example.com",
+                "blubber",
+                "-w",
+                "-u",
+                "-d",
+                "-s",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("You need to allow at least one character set.");
+
+        session = setup.run(
+            &["change-charset", "example.com", "blubber", "-w", "-s"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password character set changed.");
+    }
+
+    {
+        let mut session = setup.run(
+            &["list", "-v", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("blubber (generated)");
+        session.expect_str("Length: 8");
+        session.expect_str("Allowed characters: ABC 789");
+    }
+}
+
+#[test]
+fn show_locator() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blabber", "-r", "2"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com/blabber@2"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), STORED_PASSWORD);
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com/blabber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("No such value");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com"], None);
+        session.expect_str("Invalid password locator");
+    }
+}
+
+#[test]
+fn migrate_generated() {
+    let setup = Setup::new();
+    // Contains a legacy generated password example.com/legacy, primary password foobar
+    setup.set_file_data(
+        r#"{"application":"pfp","format":3,"data":{
+            "salt":"Y2Jh",
+            "hmac-secret":"YWJjZGVmZ2hpamts_Nosk0g9vPYtLPn9QzyFXLQ/1ZuAHVw==",
+            "site:fRTOldDD+lTwIBS8G+eUkrIzvNsfdGRSWQXrXqszDHM=":"YWJjZGVmZ2hpamts_b/AA8REorsFjuwlGDYB+KVw/fqoHPv2Ehc7sBIYqhR+ygcsd/t4=",
+            "site:fRTOldDD+lTwIBS8G+eUkrIzvNsfdGRSWQXrXqszDHM=:qWhfZOFEdqACexKf3dxNh+lGAaN/LCf6xELNB7I9Ts8=":"YWJjZGVmZ2hpamts_b/AH4RUorsFjuRRJBYJzOBc4M+sHMG+8u1ZPdAegGgJ0WRr0DnFfptHwCQdRh4sfyWuuGm0+fQIJSFaVrprvKt0Z3jZz1OmtAJyvMwQiOARVxSYbhStzi6fdhdfNvxjQpCuvvxVdq7CVNk6GnjNZTtaNQdFaQYFqX0yjiKgwLCeI3/Jo1PMzdycTfv8IVUC9clyEESkKQV4JzNZyCnMts9Vzgg3/dZGrUMPGmA=="
+        }}"#,
+    );
+
+    {
+        let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+        session.expect_str("legacy (legacy generated)");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com", "legacy"], Some(PRIMARY_PASSWORD));
+        session.expect_str("E24gxYNZA3pv");
+    }
+
+    {
+        let mut session = setup.run(&["migrate-generated"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Converted 1 legacy password(s) to stored passwords.");
+    }
+
+    {
+        let mut session = setup.run(&["list", "-v"], Some(PRIMARY_PASSWORD));
+        session.expect_str("legacy (stored)");
+        session.expect_str("Notes: old one");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com", "legacy"], Some(PRIMARY_PASSWORD));
+        session.expect_str("E24gxYNZA3pv");
+    }
+
+    {
+        let mut session = setup.run(&["migrate-generated"], Some(PRIMARY_PASSWORD));
+        session.expect_str("No legacy generated passwords found.");
+    }
+}
+
+#[test]
+fn rotate() {
+    let mut setup = Setup::new();
+    setup.set_secrets(&[PRIMARY_PASSWORD.as_bytes()]);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let mut session = setup.run(&["shell"], None);
+    session.expect_str("Enter a command");
+
+    session.send_line("rotate example.com blubber");
+    session.expect_str("Your primary password:");
+    session.send_line(PRIMARY_PASSWORD);
+    session.expect_str("No such value");
+
+    session.send_line("add example.com blubber");
+    session.expect_str("Password added");
+
+    session.send_line("rotate example.com blubber");
+    session.expect_str("Password rotated, new revision is 2.");
+
+    session.send_line("rotate example.com blubber --replace");
+    session.expect_str("Password rotated, new revision is 3.");
+
+    session.send_line("add-stored example.com blabber");
+    session.expect_str("Password to be stored");
+    session.send_line(STORED_PASSWORD);
+    session.expect_str("Password added");
+
+    session.send_line("rotate example.com blabber");
+    session.expect_str("only possible for generated passwords");
+
+    session.send_line("list");
+    session.expect_str("blabber (stored)");
+    session.expect_str("blubber (generated)");
+    session.expect_str("blubber (generated, revision: 3)");
+}
+
+#[test]
+fn duplicates() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    for (domain, name, value) in [
+        ("example.com", "blabber", STORED_PASSWORD),
+        ("example.net", "blabber", ANOTHER_STORED_PASSWORD),
+        ("example.org", "test", STORED_PASSWORD),
+    ] {
+        let mut session = setup.run(&["add-stored", domain, name], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password to be stored");
+        session.send_line(value);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["duplicates"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords sharing the same value:
+    example.com/blabber
+    example.org/test
+"
+            .trim()
+        );
+    }
+
+    {
+        let mut session = setup.run(&["remove", "example.org", "test"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password removed");
+    }
+
+    {
+        let mut session = setup.run(&["duplicates", "-g"], Some(PRIMARY_PASSWORD));
+        session.expect_str("No duplicate passwords found.");
+    }
+}
+
+#[test]
+fn audit() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["add", "example.net", "blabber", "-l", "6", "-w", "-u", "-s"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.org", "test"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(STORED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["audit"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+example.com/blubber: 102 bits
+example.net/blabber: 18 bits (weak)
+"
+            .trim()
+        );
+        session.expect_str("1 password(s) below 60 bits.");
+    }
+
+    {
+        let mut session = setup.run(&["audit", "-t", "10"], Some(PRIMARY_PASSWORD));
+        session.expect_str("All passwords have at least 10 bits.");
+    }
+
+    {
+        let mut session = setup.run(&["audit", "example.org"], Some(PRIMARY_PASSWORD));
+        session.expect_str("No matching passwords found.");
+    }
+}
+
+#[test]
+fn escaped_values() {
+    const ESCAPED_PASSWORD: &str = "qu\"ote\\slash";
+    const ESCAPED_NOTES: &str = "tab\there";
+
+    let mut setup = Setup::new();
+    setup.set_secrets(&[
+        PRIMARY_PASSWORD.as_bytes(),
+        ESCAPED_PASSWORD.as_bytes(),
+        ESCAPED_NOTES.as_bytes(),
+    ]);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add-stored", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password to be stored");
+        session.send_line(ESCAPED_PASSWORD);
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["notes", "example.com", "blubber", "-s"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("enter new notes");
+        session.send_line(ESCAPED_NOTES);
+        session.expect_str("Notes stored");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), ESCAPED_PASSWORD);
+    }
+
+    {
+        let mut session = setup.run(&["notes", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Notes for this password: ");
+        assert_eq!(session.read_to("\n").trim_end(), ESCAPED_NOTES);
+    }
+}
+
+#[test]
+fn generated_max_length() {
+    const GENERATED_PASSWORD: &str = "SUDJjn&%:nBe}crH#A@T@Hw9";
+
+    let mut setup = Setup::new();
+    setup.set_secrets(&[PRIMARY_PASSWORD.as_bytes(), GENERATED_PASSWORD.as_bytes()]);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &["add", "example.com", "blubber", "--length", "24"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["show", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password retrieved.");
+        assert_eq!(session.read_to_empty_line().trim(), GENERATED_PASSWORD);
     }
 }
@@ -0,0 +1,105 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use crate::common::Setup;
+
+const PRIMARY_PASSWORD: &str = "foobar";
+const SECRETS: &[&[u8]] = &[PRIMARY_PASSWORD.as_bytes()];
+
+#[test]
+fn defaults_and_validation() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    {
+        let mut session = setup.run(
+            &[
+                "set-policy",
+                "example.com",
+                "--min-length",
+                "12",
+                "--max-length",
+                "8",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Minimal length cannot exceed maximal length");
+    }
+
+    {
+        let mut session = setup.run(
+            &[
+                "set-policy",
+                "example.com",
+                "--min-length",
+                "8",
+                "--max-length",
+                "12",
+                "--no-symbol",
+            ],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password policy set");
+    }
+
+    {
+        let mut session = setup.run(&["add", "example.com", "blubber"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(
+            &["add", "example.com", "blabber", "--length", "16"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password length 16 violates the site's password policy");
+    }
+
+    {
+        let mut session = setup.run(&["list", "-v", "example.com"], Some(PRIMARY_PASSWORD));
+        session.expect_str("blubber");
+        session.expect_str("Length: 12");
+        session.expect_str("Allowed characters: abc ABC 789");
+    }
+
+    {
+        let mut session = setup.run(
+            &["set-policy", "example.com", "--remove"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password policy removed");
+    }
+
+    {
+        let mut session = setup.run(
+            &["add", "example.com", "blabber", "--length", "16"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password added");
+    }
+}
+
+#[test]
+fn forbidden_characters() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let mut session = setup.run(&["shell"], None);
+    session.expect_str("Enter a command");
+
+    session.send_line("set-policy example.com --forbidden ABCDEFGHIJKLMNOPQRSTUVWXYZ");
+    session.expect_str("Your primary password:");
+    session.send_line(PRIMARY_PASSWORD);
+    session.expect_str("Password policy set");
+
+    session.send_line("add example.com blubber");
+    session.expect_str("contains characters forbidden by the site's password policy");
+
+    session.send_line("add example.com blubber --no-upper");
+    session.expect_str("Password added");
+}
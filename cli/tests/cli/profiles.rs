@@ -0,0 +1,85 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use crate::common::Setup;
+
+const PRIMARY_PASSWORD: &str = "foobar";
+
+#[test]
+fn profiles() {
+    let setup = Setup::with_config_dir();
+
+    {
+        let mut session = setup.run(&["profiles"], None);
+        session.expect_str("No profiles found.");
+    }
+
+    for profile in ["work", "personal"] {
+        let mut session = setup.run(&["--profile", profile, "set-primary"], None);
+        session.expect_str("New primary password:");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("Repeat primary password:");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str(&format!("{}.json", profile));
+    }
+
+    {
+        let mut session = setup.run(
+            &["--profile", "work", "add", "example.com", "blubber"],
+            Some(PRIMARY_PASSWORD),
+        );
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["--profile", "work", "list"], Some(PRIMARY_PASSWORD));
+        session.expect_str("blubber");
+    }
+
+    {
+        let mut session = setup.run(&["--profile", "personal", "stats"], Some(PRIMARY_PASSWORD));
+        session.expect_str("Passwords: 0");
+    }
+
+    {
+        let mut session = setup.run(&["info"], None);
+        session.expect_str("storage.json");
+        session.expect_str("Initialized: no");
+    }
+
+    {
+        let mut session = setup.run(&["profiles"], None);
+        session.expect_str("Profiles:\n    personal\n    work\n");
+    }
+}
+
+#[test]
+fn storage_wins() {
+    let setup = Setup::with_config_dir();
+    let storage = tempfile::NamedTempFile::new()
+        .expect("Creating a temporary file should succeed")
+        .into_temp_path();
+    std::fs::remove_file(&storage).expect("Temporary file should be removed");
+
+    let mut session = setup.run(
+        &[
+            "--profile".as_ref(),
+            "work".as_ref(),
+            "-c".as_ref(),
+            storage.as_os_str(),
+            "info".as_ref(),
+        ],
+        None,
+    );
+    session.expect_str(&format!("Storage path: {}", storage.display()));
+}
+
+#[test]
+fn invalid_name() {
+    let setup = Setup::with_config_dir();
+    let mut session = setup.run(&["--profile", "../work", "info"], None);
+    session.expect_str("Profile name should be a file name without path separators.");
+}
@@ -100,6 +100,7 @@ fn shell() {
 Passwords for example.com:
     Aliases: example.org
     blabber whatever (stored, revision: 2)
+        Created: 2009-02-13 23:31:30 UTC
     blubber (generated)
         Notes: "
             .to_string()
@@ -117,3 +118,206 @@ Passwords for example.com:
     session.send_line("shell");
     session.expect_str("cannot run a shell");
 }
+
+#[test]
+fn transactions() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let history_file = tempfile::NamedTempFile::new()
+        .expect("Creating a temporary file should succeed")
+        .into_temp_path();
+
+    {
+        let mut session = setup.run(
+            &["shell".as_ref(), "-s".as_ref(), history_file.as_os_str()],
+            None,
+        );
+        session.expect_str("Enter a command");
+
+        session.send_line("commit");
+        session.expect_str("No transaction in progress");
+
+        session.send_line("begin");
+        session.expect_str("Transaction started");
+
+        session.send_line("begin");
+        session.expect_str("already in progress");
+
+        session.send_line("add example.com blubber");
+        session.expect_str("Your primary password:");
+        session.send_line(PRIMARY_PASSWORD);
+        session.expect_str("Password added");
+
+        session.send_line("rollback");
+        session.expect_str("Changes discarded");
+
+        session.send_line("list");
+        session.expect_str("No matching passwords found");
+
+        session.send_line("begin");
+        session.expect_str("Transaction started");
+
+        session.send_line("add example.com blabber");
+        session.expect_str("Password added");
+
+        session.send_line("commit");
+        session.expect_str("Changes saved");
+
+        session.send_line("begin");
+        session.expect_str("Transaction started");
+
+        session.send_line("add example.com blubber");
+        session.expect_str("Password added");
+    }
+
+    {
+        let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+        assert_eq!(
+            session.read_to_empty_line().trim(),
+            "
+Passwords for example.com:
+    blabber (generated)
+"
+            .trim()
+        );
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn interrupt() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let history_file = tempfile::NamedTempFile::new()
+        .expect("Creating a temporary file should succeed")
+        .into_temp_path();
+
+    let mut session = setup.run(
+        &["shell".as_ref(), "-s".as_ref(), history_file.as_os_str()],
+        None,
+    );
+    session.expect_str("Enter a command");
+
+    session.send_line("add example.com blubber");
+    session.expect_str("Your primary password:");
+    session.send_line(PRIMARY_PASSWORD);
+    session.expect_str("Password added");
+
+    session.send_line("notes example.com blubber -s");
+    session.expect_str("enter new notes");
+    session.interrupt();
+    session.send_line(PASSWORD_NOTES);
+    session.expect_str("Interrupted.");
+
+    // The shell keeps running, only the command was aborted
+    session.send_line("notes example.com blubber");
+    session.expect_str("no notes are stored");
+}
+
+#[test]
+fn locking() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let history_file = tempfile::NamedTempFile::new()
+        .expect("Creating a temporary file should succeed")
+        .into_temp_path();
+
+    let mut shell = setup.run(
+        &["shell".as_ref(), "-s".as_ref(), history_file.as_os_str()],
+        None,
+    );
+    shell.expect_str("Enter a command");
+
+    {
+        let mut session = setup.run(&["list"], None);
+        session.expect_str("in use by another pfp instance");
+    }
+
+    {
+        let mut session = setup.run(&["info"], None);
+        session.expect_str("Initialized: yes");
+    }
+
+    shell.send_line("add example.com blubber");
+    shell.expect_str("Your primary password:");
+    shell.send_line(PRIMARY_PASSWORD);
+    shell.expect_str("Password added");
+    drop(shell);
+
+    let mut session = setup.run(&["list"], Some(PRIMARY_PASSWORD));
+    session.expect_str("blubber (generated)");
+}
+
+#[test]
+fn locking_set_primary() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+    let data = setup.get_file_data();
+
+    let history_file = tempfile::NamedTempFile::new()
+        .expect("Creating a temporary file should succeed")
+        .into_temp_path();
+
+    let mut shell = setup.run(
+        &["shell".as_ref(), "-s".as_ref(), history_file.as_os_str()],
+        None,
+    );
+    shell.expect_str("Enter a command");
+
+    {
+        let mut session = setup.run(&["set-primary"], None);
+        session.expect_str("in use by another pfp instance");
+    }
+
+    {
+        let mut session = setup.run(&["set-primary", "-y"], None);
+        session.expect_str("in use by another pfp instance");
+    }
+
+    drop(shell);
+    assert_eq!(setup.get_file_data(), data);
+}
+
+#[test]
+fn external_modification() {
+    let mut setup = Setup::new();
+    setup.set_secrets(SECRETS);
+    setup.initialize(PRIMARY_PASSWORD);
+
+    let history_file = tempfile::NamedTempFile::new()
+        .expect("Creating a temporary file should succeed")
+        .into_temp_path();
+
+    let mut session = setup.run(
+        &["shell".as_ref(), "-s".as_ref(), history_file.as_os_str()],
+        None,
+    );
+    session.expect_str("Enter a command");
+
+    session.send_line("add example.com blubber");
+    session.expect_str("Your primary password:");
+    session.send_line(PRIMARY_PASSWORD);
+    session.expect_str("Password added");
+
+    let data = setup.get_file_data();
+    setup.set_file_data(&(data + "\n"));
+
+    session.send_line("add example.com blabber");
+    session.expect_str("modified by another application");
+
+    session.send_line("reload");
+    session.expect_str("Storage file reloaded");
+
+    session.send_line("list");
+    session.expect_str("blubber (generated)");
+
+    session.send_line("add example.com blabber");
+    session.expect_str("Password added");
+}
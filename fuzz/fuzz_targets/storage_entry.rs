@@ -0,0 +1,16 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pfp::storage_types::{Password, Site};
+
+fuzz_target!(|data: &[u8]| {
+    // Decrypted storage entries are parsed like this, errors are fine but panics aren't
+    let _ = serde_json::from_slice::<Password>(data);
+    let _ = serde_json::from_slice::<Site>(data);
+});
@@ -0,0 +1,17 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pfp::storage_io::FileIO;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(contents) = std::str::from_utf8(data) {
+        // Any input has to produce a result, errors are fine but panics aren't
+        let _ = FileIO::parse(std::path::Path::new("storage.json"), contents);
+    }
+});
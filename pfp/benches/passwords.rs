@@ -0,0 +1,156 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+//! Benchmarks for key derivation, password generation and bulk retrieval.
+//!
+//! The number of passwords in the synthetic storage used by the `unlock_list_show` benchmark can
+//! be changed via the `PFP_BENCH_ENTRIES` environment variable (default is 20). The
+//! `list_site_cache` benchmark always uses a storage with 1000 sites.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use pfp::passwords::{get_encryption_key, Passwords};
+use pfp::storage_io::FileIO;
+use pfp::storage_types::CharacterSet;
+use secrecy::SecretString;
+
+const PRIMARY_PASSWORD: &str = "my primary password";
+
+fn primary_pass() -> SecretString {
+    SecretString::new(PRIMARY_PASSWORD.to_owned())
+}
+
+fn entry_count() -> usize {
+    std::env::var("PFP_BENCH_ENTRIES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(20)
+}
+
+fn temp_file() -> tempfile::TempPath {
+    tempfile::NamedTempFile::new()
+        .expect("Creating temporary file should succeed")
+        .into_temp_path()
+}
+
+fn create_storage(file: &tempfile::TempPath, entries: usize) -> Passwords<FileIO> {
+    let mut passwords = Passwords::new(FileIO::new(file));
+    passwords
+        .reset(primary_pass())
+        .expect("Initialization should succeed");
+    passwords
+        .begin_transaction()
+        .expect("Starting transaction should succeed");
+    for i in 0..entries {
+        passwords
+            .set_generated(
+                &format!("example{}.com", i / 2),
+                &format!("user{}", i % 2),
+                "1",
+                16,
+                CharacterSet::all(),
+                None,
+            )
+            .expect("Adding password should succeed");
+    }
+    passwords
+        .commit_transaction()
+        .expect("Committing transaction should succeed");
+    passwords
+}
+
+fn encryption_key(c: &mut Criterion) {
+    let primary_password = primary_pass();
+    c.bench_function("get_encryption_key", |b| {
+        b.iter(|| get_encryption_key(&primary_password, b"abcdefghijklmnop"))
+    });
+}
+
+fn derive_password(c: &mut Criterion) {
+    let file = temp_file();
+    let passwords = create_storage(&file, 1);
+    c.bench_function("derive_password", |b| {
+        b.iter(|| {
+            passwords
+                .get("example0.com", "user0", "1")
+                .expect("Retrieving password should succeed")
+        })
+    });
+}
+
+fn unlock_list_show(c: &mut Criterion) {
+    let file = temp_file();
+    let mut passwords = create_storage(&file, entry_count());
+    let mut group = c.benchmark_group("bulk");
+    group.sample_size(10);
+    group.bench_function("unlock_list_show", |b| {
+        b.iter(|| {
+            passwords.lock();
+            passwords
+                .unlock(primary_pass())
+                .expect("Unlocking should succeed");
+            for site in passwords.list_sites("*") {
+                for password in passwords.list(site.name(), "*") {
+                    let id = password.id();
+                    passwords
+                        .get(id.site(), id.name(), id.revision())
+                        .expect("Retrieving password should succeed");
+                }
+            }
+        })
+    });
+    group.finish();
+}
+
+fn list_site_cache(c: &mut Criterion) {
+    const SITES: usize = 1000;
+
+    let file = temp_file();
+    let mut passwords = create_storage(&file, 0);
+    passwords
+        .begin_transaction()
+        .expect("Starting transaction should succeed");
+    for i in 0..SITES {
+        passwords
+            .set_stored(
+                &format!("example{}.com", i),
+                "user",
+                "1",
+                SecretString::new("password".to_owned()),
+            )
+            .expect("Adding password should succeed");
+    }
+    passwords
+        .commit_transaction()
+        .expect("Committing transaction should succeed");
+
+    let mut group = c.benchmark_group("list_site_cache");
+    group.sample_size(10);
+    for cache in [false, true] {
+        passwords.set_site_cache(cache);
+        group.bench_function(if cache { "cached" } else { "uncached" }, |b| {
+            b.iter(|| {
+                // Every call resolves the site, so site entries are decrypted repeatedly unless cached
+                for site in passwords.list_sites("*") {
+                    assert!(passwords.get_alias(site.name()).is_err());
+                    passwords
+                        .get_notes(site.name(), "user", "1")
+                        .expect("Retrieving notes should succeed");
+                }
+                passwords.set_site_cache(cache);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    encryption_key,
+    derive_password,
+    unlock_list_show,
+    list_site_cache
+);
+criterion_main!(benches);
@@ -5,15 +5,19 @@
  */
 
 use crate::error::Error;
-use crate::storage_types::{CharacterSet, CharacterType};
+use crate::storage_types::{CharacterSet, CharacterType, KdfParams};
 use aes_gcm::aead::{Aead, NewAead};
 use hmac::Mac;
 use rand::Rng;
 use scrypt::scrypt;
+use secrecy::zeroize::Zeroize;
 use secrecy::{ExposeSecret, SecretString, SecretVec};
+use unicode_normalization::UnicodeNormalization;
 
 const AES_KEY_SIZE: usize = 256;
 const AES_NONCE_SIZE: usize = 96;
+const AES_TAG_SIZE: usize = 128;
+const LEGACY_PBKDF2_ROUNDS: u32 = 262144;
 
 // I, l, O, 0, 1 excluded because of potential confusion. ", ', \ excluded
 // because of common bugs in web interfaces (magic quotes).
@@ -22,21 +26,47 @@ const CHARS_UPPER: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ";
 const CHARS_DIGIT: &[u8] = b"23456789";
 const CHARS_SYMBOL: &[u8] = b"!#$%&()*+,-./:;<=>?@[]^_{|}~";
 
-const CHARS_MAPPING: [(CharacterType, &[u8]); 4] = [
-    (CharacterType::Lower, CHARS_LOWER),
-    (CharacterType::Upper, CHARS_UPPER),
-    (CharacterType::Digit, CHARS_DIGIT),
-    (CharacterType::Symbol, CHARS_SYMBOL),
-];
+fn chars_mapping(symbols: Option<&str>) -> [(CharacterType, &[u8]); 4] {
+    [
+        (CharacterType::Lower, CHARS_LOWER),
+        (CharacterType::Upper, CHARS_UPPER),
+        (CharacterType::Digit, CHARS_DIGIT),
+        (
+            CharacterType::Symbol,
+            symbols.map_or(CHARS_SYMBOL, |symbols| symbols.as_bytes()),
+        ),
+    ]
+}
+
+/// Checks whether `symbols` can replace the default symbol set: it has to consist of distinct
+/// ASCII punctuation characters.
+pub fn valid_symbols(symbols: &str) -> bool {
+    !symbols.is_empty()
+        && symbols.bytes().enumerate().all(|(i, byte)| {
+            byte.is_ascii_punctuation() && !symbols.as_bytes()[..i].contains(&byte)
+        })
+}
 
 // Our Base32 variant follows RFC 4648 but uses a custom alphabet to remove
 // ambiguous characters: 0, 1, O, I.
 pub const BASE32_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
 
+pub const SCRYPT_LOG_N: u8 = 15;
+pub const SCRYPT_R: u32 = 8;
+pub const SCRYPT_P: u32 = 1;
+
 pub fn derive_bits(password: &SecretString, salt: &[u8], size: usize) -> SecretVec<u8> {
-    let params = scrypt::Params::new(15, 8, 1).unwrap();
-    let mut bytes: Vec<u8> = Vec::new();
-    bytes.resize(size, 0);
+    derive_bits_scrypt(password, salt, SCRYPT_LOG_N, size)
+}
+
+fn derive_bits_scrypt(
+    password: &SecretString,
+    salt: &[u8],
+    log_n: u8,
+    size: usize,
+) -> SecretVec<u8> {
+    let params = scrypt::Params::new(log_n, SCRYPT_R, SCRYPT_P).unwrap();
+    let mut bytes = vec![0; size];
     scrypt(
         password.expose_secret().as_bytes(),
         salt,
@@ -44,30 +74,101 @@ pub fn derive_bits(password: &SecretString, salt: &[u8], size: usize) -> SecretV
         bytes.as_mut_slice(),
     )
     .unwrap();
+    clear_stack();
     SecretVec::new(bytes)
 }
 
+/// Overwrites the stack area previously used by key derivation. HMAC copies its key into a
+/// block buffer on the stack without zeroing it afterwards, and PBKDF2 uses the password as key.
+#[inline(never)]
+fn clear_stack() {
+    let mut buffer = [0u8; 16384];
+    buffer.zeroize();
+}
+
+// Not inlined: Argon2 state would otherwise enlarge the stack frame of derive_key() and keep
+// scrypt's stack remnants of the primary password from being overwritten.
+#[inline(never)]
+fn derive_bits_argon2id(
+    password: &SecretString,
+    salt: &[u8],
+    memory: u32,
+    iterations: u32,
+    parallelism: u32,
+    size: usize,
+) -> Result<SecretVec<u8>, Error> {
+    let params = argon2::Params::new(memory, iterations, parallelism, Some(size))
+        .or(Err(Error::InvalidKdfParams))?;
+    let argon2 = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut bytes = vec![0; size];
+    argon2
+        .hash_password_into(
+            password.expose_secret().as_bytes(),
+            salt,
+            bytes.as_mut_slice(),
+        )
+        .or(Err(Error::InvalidKdfParams))?;
+    Ok(SecretVec::new(bytes))
+}
+
 pub fn derive_password(
     primary_password: &SecretString,
     salt: &str,
     length: usize,
     charset: CharacterSet,
+    symbols: Option<&str>,
 ) -> SecretString {
     let bytes = derive_bits(primary_password, salt.as_bytes(), length);
-    to_password(bytes, charset)
+    to_password(bytes, charset, symbols)
+}
+
+pub fn derive_password_legacy(
+    primary_password: &SecretString,
+    salt: &str,
+    length: usize,
+    charset: CharacterSet,
+    symbols: Option<&str>,
+) -> SecretString {
+    let mut bytes = vec![0; length];
+    pbkdf2::pbkdf2::<hmac::Hmac<sha1::Sha1>>(
+        primary_password.expose_secret().as_bytes(),
+        salt.as_bytes(),
+        LEGACY_PBKDF2_ROUNDS,
+        bytes.as_mut_slice(),
+    );
+    clear_stack();
+    to_password(SecretVec::new(bytes), charset, symbols)
 }
 
-fn to_password(bytes: SecretVec<u8>, charset: CharacterSet) -> SecretString {
+/// Number of characters that a password can be generated from with the given character set and
+/// symbols.
+pub fn charset_size(charset: CharacterSet, symbols: Option<&str>) -> usize {
+    chars_mapping(symbols)
+        .iter()
+        .filter(|(chartype, _)| charset.contains(*chartype))
+        .map(|(_, chars)| chars.len())
+        .sum()
+}
+
+/// Maps derived bytes to password characters. Each byte selects a character from the allowed
+/// character types. Once the remaining positions are only sufficient for the character types that
+/// didn't occur yet, the choice is restricted to these. So the result always contains at least one
+/// character of each type in `charset`, provided that it is long enough, and it depends on the
+/// input bytes only.
+fn to_password(bytes: SecretVec<u8>, charset: CharacterSet, symbols: Option<&str>) -> SecretString {
+    // The buffer is allocated with its final size upfront and turned into a string without
+    // copying, so that no partial copies of the password are left behind in memory.
     let len = bytes.expose_secret().len();
-    let mut result = String::with_capacity(len);
+    let mut result = Vec::with_capacity(len);
     let mut seen = CharacterSet::empty();
+    let mapping = chars_mapping(symbols);
     for (i, &byte) in bytes.expose_secret().iter().enumerate() {
         let allowed = if charset.len() - seen.len() >= len - i {
             charset - seen
         } else {
             charset
         };
-        let num_chars = CHARS_MAPPING.iter().fold(0, |acc, (chartype, chars)| {
+        let num_chars = mapping.iter().fold(0, |acc, (chartype, chars)| {
             if allowed.contains(*chartype) {
                 acc + chars.len()
             } else {
@@ -76,10 +177,10 @@ fn to_password(bytes: SecretVec<u8>, charset: CharacterSet) -> SecretString {
         });
 
         let mut index = usize::from(byte) % num_chars;
-        for (chartype, chars) in CHARS_MAPPING {
+        for (chartype, chars) in mapping {
             if allowed.contains(chartype) {
                 if index < chars.len() {
-                    result.push(chars[index] as char);
+                    result.push(chars[index]);
                     seen.insert(chartype);
                     break;
                 }
@@ -87,11 +188,45 @@ fn to_password(bytes: SecretVec<u8>, charset: CharacterSet) -> SecretString {
             }
         }
     }
-    SecretString::new(result)
+
+    match String::from_utf8(result) {
+        Ok(password) => SecretString::new(password),
+        Err(error) => {
+            error.into_bytes().zeroize();
+            panic!("Generated password should only contain ASCII characters");
+        }
+    }
+}
+
+pub fn normalize_password(password: &SecretString) -> SecretString {
+    SecretString::new(password.expose_secret().nfc().collect())
 }
 
-pub fn derive_key(primary_password: &SecretString, salt: &[u8]) -> SecretVec<u8> {
-    derive_bits(primary_password, salt, AES_KEY_SIZE / 8)
+pub fn derive_key(
+    primary_password: &SecretString,
+    salt: &[u8],
+    params: &KdfParams,
+) -> Result<SecretVec<u8>, Error> {
+    match params {
+        KdfParams::Scrypt { cost } => Ok(derive_bits_scrypt(
+            primary_password,
+            salt,
+            cost.trailing_zeros() as u8,
+            AES_KEY_SIZE / 8,
+        )),
+        KdfParams::Argon2id {
+            memory,
+            iterations,
+            parallelism,
+        } => derive_bits_argon2id(
+            primary_password,
+            salt,
+            *memory,
+            *iterations,
+            *parallelism,
+            AES_KEY_SIZE / 8,
+        ),
+    }
 }
 
 #[cfg(not(test))]
@@ -125,11 +260,15 @@ pub fn decrypt_data(value: &str, encryption_key: &SecretVec<u8>) -> Result<Secre
 
     let (nonce_base64, ciphertext_base64) =
         value.split_once('_').ok_or(Error::InvalidCiphertext)?;
-    let nonce_data =
-        base64::decode(nonce_base64).map_err(|error| Error::InvalidBase64 { error })?;
+    let nonce_data = base64::decode(nonce_base64)?;
+    if nonce_data.len() != AES_NONCE_SIZE / 8 {
+        return Err(Error::InvalidCiphertext);
+    }
     let nonce = aes_gcm::Nonce::from_slice(&nonce_data);
-    let ciphertext =
-        base64::decode(ciphertext_base64).map_err(|error| Error::InvalidBase64 { error })?;
+    let ciphertext = base64::decode(ciphertext_base64)?;
+    if ciphertext.len() < AES_TAG_SIZE / 8 {
+        return Err(Error::InvalidCiphertext);
+    }
     Ok(SecretVec::new(
         cipher
             .decrypt(nonce, ciphertext.as_slice())
@@ -144,6 +283,18 @@ pub fn get_digest(hmac_secret: &SecretVec<u8>, data: &str) -> String {
     base64::encode(result)
 }
 
+/// Calculates a digest of a secret value. Unlike `get_digest()` this makes sure that no part of the
+/// value is left behind in the hash function's block buffer, the result is only meant for
+/// in-memory comparisons however.
+pub fn get_secret_digest(hmac_secret: &SecretVec<u8>, data: &SecretString) -> String {
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(hmac_secret.expose_secret()).unwrap();
+    mac.update(data.expose_secret().as_bytes());
+    // A full block of zeros overwrites whatever remained of the data in the block buffer
+    mac.update(&[0; 64]);
+    let result = mac.finalize().into_bytes();
+    base64::encode(result)
+}
+
 pub fn base32_encode(input: &[u8]) -> Result<Vec<u8>, Error> {
     if input.len() % 5 != 0 {
         return Err(Error::UnexpectedData);
@@ -250,6 +401,134 @@ mod tests {
         return result;
     }
 
+    fn to_hex(bytes: &[u8]) -> String {
+        let mut result = String::new();
+        for byte in bytes {
+            write!(&mut result, "{:02x}", byte).expect("Converting bytes to hex should succeed");
+        }
+        result
+    }
+
+    #[test]
+    fn test_derive_key() {
+        let password = SecretString::new("foobar".to_owned());
+
+        // Key used by existing storage, has to stay unchanged
+        let key = derive_key(&password, b"cba", &KdfParams::default())
+            .expect("Key derivation should succeed");
+        assert_eq!(
+            to_hex(key.expose_secret()),
+            "9b4f2d1737b6c257f75049518c844987b5de401b3a87048b262d9b40aef8b0e2"
+        );
+
+        let params = KdfParams::argon2id(8192, 2, 1).expect("Parameters should be valid");
+        let key = derive_key(&password, b"abcdefghijklmnop", &params)
+            .expect("Key derivation should succeed");
+        assert_eq!(
+            to_hex(key.expose_secret()),
+            "8189e8ebabb08ec20d4623dd40088e6824b2c5f202337131c16933568bcd2fb6"
+        );
+
+        // Argon2 requires at least 8 bytes of salt
+        assert!(matches!(
+            derive_key(&password, b"cba", &params),
+            Err(Error::InvalidKdfParams)
+        ));
+    }
+
+    #[test]
+    fn test_to_password() {
+        let password = |bytes: &[u8], charset: CharacterSet, symbols: Option<&str>| {
+            to_password(SecretVec::new(bytes.to_vec()), charset, symbols)
+                .expose_secret()
+                .to_owned()
+        };
+
+        // Existing generated passwords depend on this mapping, it has to stay unchanged
+        assert_eq!(password(&[0; 4], CharacterSet::all(), None), "aA2!");
+        assert_eq!(password(&[0; 8], CharacterSet::all(), None), "aaaaaA2!");
+        assert_eq!(
+            password(
+                &[17, 250, 3, 96, 128, 42, 77, 201],
+                CharacterSet::all(),
+                None
+            ),
+            "uedr2W_R"
+        );
+        assert_eq!(
+            password(
+                &[17, 250, 3, 96, 128, 42, 77, 201],
+                CharacterType::Digit | CharacterType::Symbol,
+                Some("-_")
+            ),
+            "9258-493"
+        );
+
+        // Each character type is always present
+        let mappings = chars_mapping(None);
+        for charset in (1..16u8).map(|bits| {
+            mappings
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| bits & (1 << i) != 0)
+                .map(|(_, (chartype, _))| *chartype)
+                .collect::<CharacterSet>()
+        }) {
+            for length in 4..=24 {
+                for seed in 0..64usize {
+                    let bytes = (0..length)
+                        .map(|i| ((seed * 131 + i * 37) % 256) as u8)
+                        .collect::<Vec<u8>>();
+                    let value = password(&bytes, charset, None);
+                    assert_eq!(value.len(), length);
+                    for (chartype, chars) in mappings {
+                        assert_eq!(
+                            value.bytes().any(|byte| chars.contains(&byte)),
+                            charset.contains(chartype),
+                            "{:?} in {}",
+                            chartype,
+                            value
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decrypt_data() {
+        let key = SecretVec::new(vec![1u8; AES_KEY_SIZE / 8]);
+        let other_key = SecretVec::new(vec![2u8; AES_KEY_SIZE / 8]);
+        let encrypted = encrypt_data(&SecretVec::new(b"hi there".to_vec()), &key);
+
+        assert_eq!(
+            decrypt_data(&encrypted, &key)
+                .unwrap_or_else(|_| panic!("Decryption should succeed"))
+                .expose_secret(),
+            b"hi there"
+        );
+        assert!(matches!(
+            decrypt_data(&encrypted, &other_key)
+                .err()
+                .expect("Decryption should fail"),
+            Error::DecryptionFailure
+        ));
+
+        let (nonce, ciphertext) = encrypted.split_once('_').unwrap();
+        for value in [
+            "abc".to_string(),
+            format!("{}_{}", base64::encode(b"short"), ciphertext),
+            format!("{}_{}", nonce, base64::encode(b"short")),
+        ] {
+            assert!(matches!(
+                decrypt_data(&value, &key)
+                    .err()
+                    .expect("Decryption should fail"),
+                Error::InvalidCiphertext
+            ));
+        }
+    }
+
     #[test]
     fn test_base32_encode() {
         assert_eq!(encode("0000000000"), "AAAAAAAA");
@@ -24,17 +24,65 @@ pub enum Error {
         /// Underlying I/O error
         error: std::io::Error,
     },
+    /// An I/O operation other than reading or writing the storage file failed.
+    IoFailure {
+        /// Underlying I/O error
+        error: std::io::Error,
+    },
+    /// Database access failed.
+    DatabaseFailure {
+        /// Underlying database error message
+        error: String,
+    },
     /// Operation requires the storage to be initialized but it currently isn't.
     StorageNotInitialized,
     /// Storage file's format and version aren't supported.
     UnexpectedStorageFormat,
+    /// Storage file was written by a newer application version using an unsupported format.
+    NewerStorageFormat {
+        /// Format version of the storage file
+        format: u64,
+    },
+    /// Storage was opened read-only, changes cannot be saved.
+    ReadOnlyStorage,
+    /// Storage file is locked by another instance.
+    StorageLocked,
+    /// Storage file was modified by another application since it was loaded.
+    StorageChangedExternally,
+    /// The storage backend cannot discard unsaved changes.
+    ReloadUnsupported,
+    /// A transaction was started while another one is still in progress.
+    TransactionInProgress,
+    /// Operation requires a transaction to be in progress but there is none.
+    NoTransaction,
     /// Operation requires the passwords to be unlocked but they currently aren't.
     PasswordsLocked,
     /// The storage doesn't have the key (site or password) requested by the operation.
     KeyMissing,
+    /// The operation would overwrite an existing password.
+    PasswordExists,
+    /// The operation requires a generated password but the password is stored.
+    NotGeneratedPassword,
+    /// The operation is only possible for stored passwords.
+    NotStoredPassword,
+    /// Password tags cannot be empty.
+    InvalidTag,
+    /// Generated password length is outside the supported range.
+    InvalidLength {
+        /// The length requested
+        length: usize,
+    },
+    /// Key derivation parameters are invalid or unsupported.
+    InvalidKdfParams,
+    /// Generated password would have no characters to choose from.
+    EmptyCharacterSet,
+    /// Custom symbol set for a generated password is empty or contains invalid characters.
+    InvalidSymbols,
+    /// Password locator isn't in the expected `site/name@revision` format.
+    InvalidLocator,
     /// The operation was given unexpected data, e.g. JSON data that isn't an object.
     UnexpectedData,
-    /// Ciphertext isn't stored in the expected format.
+    /// Ciphertext isn't stored in the expected format, storage data is likely corrupt.
     InvalidCiphertext,
     /// Base64 decoding failed.
     InvalidBase64 {
@@ -57,8 +105,20 @@ pub enum Error {
     NoSuchAlias,
     /// Cannot alias a site to itself.
     AliasToSelf,
+    /// Alias chain contains a cycle or is too long.
+    AliasCycle,
     /// Cannot alias a site that already has passwords.
     SiteHasPasswords,
+    /// Some sites could not be made aliases, no aliases were added.
+    AliasFailures {
+        /// Site names along with the reason why they could not be made aliases
+        failures: Vec<(String, Error)>,
+    },
+    /// Some storage entries could not be decrypted or parsed.
+    UnreadableEntries {
+        /// Storage keys along with the reason why the entries could not be read
+        failures: Vec<(String, Error)>,
+    },
     /// Recovery code contains extra data.
     RecoveryCodeExtraData {
         /// First line to contain extra data
@@ -75,4 +135,179 @@ pub enum Error {
     RecoveryCodeWrongVersion,
     /// Recovery code encodes less data than expected.
     RecoveryCodeInsufficientData,
+    /// Recovery code text exceeds the maximal length.
+    RecoveryCodeTooLong,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::CreateDirFailure { .. } => write!(f, "failed creating storage directory"),
+            Error::FileReadFailure { .. } => write!(f, "failed reading storage file"),
+            Error::FileWriteFailure { .. } => write!(f, "failed writing storage file"),
+            Error::IoFailure { .. } => write!(f, "I/O operation failed"),
+            Error::DatabaseFailure { error } => write!(f, "database access failed: {}", error),
+            Error::StorageNotInitialized => write!(f, "storage is not initialized"),
+            Error::UnexpectedStorageFormat => write!(f, "unexpected storage format"),
+            Error::NewerStorageFormat { format } => {
+                write!(f, "storage format version {} is not supported", format)
+            }
+            Error::ReadOnlyStorage => write!(f, "storage is read-only"),
+            Error::StorageLocked => write!(f, "storage is locked by another instance"),
+            Error::StorageChangedExternally => write!(f, "storage was modified externally"),
+            Error::ReloadUnsupported => write!(f, "storage cannot discard unsaved changes"),
+            Error::TransactionInProgress => write!(f, "a transaction is already in progress"),
+            Error::NoTransaction => write!(f, "no transaction in progress"),
+            Error::PasswordsLocked => write!(f, "passwords are locked"),
+            Error::KeyMissing => write!(f, "no such value in storage"),
+            Error::PasswordExists => write!(f, "password already exists"),
+            Error::NotGeneratedPassword => write!(f, "password is not a generated password"),
+            Error::NotStoredPassword => write!(f, "password is not a stored password"),
+            Error::InvalidTag => write!(f, "tag is empty"),
+            Error::InvalidLength { length } => {
+                write!(f, "password length {} is not supported", length)
+            }
+            Error::InvalidKdfParams => write!(f, "unsupported key derivation parameters"),
+            Error::EmptyCharacterSet => write!(f, "character set is empty"),
+            Error::InvalidSymbols => write!(f, "invalid symbol set"),
+            Error::InvalidLocator => write!(f, "invalid password locator"),
+            Error::UnexpectedData => write!(f, "unexpected data"),
+            Error::InvalidCiphertext => write!(f, "invalid ciphertext"),
+            Error::InvalidBase64 { .. } => write!(f, "invalid Base64 data"),
+            Error::InvalidJson { .. } => write!(f, "invalid JSON data"),
+            Error::InvalidUtf8 { .. } => write!(f, "invalid UTF-8 data"),
+            Error::DecryptionFailure => write!(f, "decryption failed"),
+            Error::NoSuchAlias => write!(f, "site is not an alias"),
+            Error::AliasToSelf => write!(f, "cannot make a site an alias for itself"),
+            Error::AliasCycle => write!(f, "alias chain contains a cycle"),
+            Error::SiteHasPasswords => write!(f, "site has passwords"),
+            Error::AliasFailures { failures } => {
+                write!(f, "failed adding aliases:")?;
+                for (site, error) in failures {
+                    write!(f, " {} ({})", site, error)?;
+                }
+                Ok(())
+            }
+            Error::UnreadableEntries { failures } => {
+                write!(f, "unreadable storage entries:")?;
+                for (key, error) in failures {
+                    write!(f, " {} ({})", key, error)?;
+                }
+                Ok(())
+            }
+            Error::RecoveryCodeExtraData { line } => {
+                write!(
+                    f,
+                    "recovery code has extra data starting with line {}",
+                    line
+                )
+            }
+            Error::RecoveryCodeChecksumMismatch { line } => {
+                write!(f, "recovery code checksum mismatch in line {}", line)
+            }
+            Error::RecoveryCodeIncomplete => write!(f, "recovery code is incomplete"),
+            Error::RecoveryCodeWrongVersion => write!(f, "unsupported recovery code version"),
+            Error::RecoveryCodeInsufficientData => write!(f, "recovery code is too short"),
+            Error::RecoveryCodeTooLong => write!(f, "recovery code is too long"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::CreateDirFailure { error }
+            | Error::FileReadFailure { error }
+            | Error::FileWriteFailure { error }
+            | Error::IoFailure { error } => Some(error),
+            Error::InvalidBase64 { error } => Some(error),
+            Error::InvalidJson { error } => Some(error),
+            Error::InvalidUtf8 { error } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::IoFailure { error }
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(error: base64::DecodeError) -> Self {
+        Error::InvalidBase64 { error }
+    }
+}
+
+impl From<crate::json::Error> for Error {
+    fn from(error: crate::json::Error) -> Self {
+        Error::InvalidJson { error }
+    }
+}
+
+impl From<std::str::Utf8Error> for Error {
+    fn from(error: std::str::Utf8Error) -> Self {
+        Error::InvalidUtf8 { error }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn display() {
+        assert_eq!(Error::KeyMissing.to_string(), "no such value in storage");
+        assert_eq!(
+            Error::RecoveryCodeChecksumMismatch { line: 2 }.to_string(),
+            "recovery code checksum mismatch in line 2"
+        );
+    }
+
+    #[test]
+    fn source() {
+        assert!(Error::KeyMissing.source().is_none());
+
+        let error = Error::FileReadFailure {
+            error: std::io::Error::new(std::io::ErrorKind::NotFound, "missing"),
+        };
+        assert_eq!(
+            error
+                .source()
+                .expect("Source should be present")
+                .to_string(),
+            "missing"
+        );
+
+        let error = Error::InvalidBase64 {
+            error: base64::DecodeError::InvalidLength,
+        };
+        assert!(error
+            .source()
+            .expect("Source should be present")
+            .is::<base64::DecodeError>());
+    }
+
+    #[test]
+    fn conversions() {
+        assert!(matches!(
+            Error::from(base64::DecodeError::InvalidLength),
+            Error::InvalidBase64 { .. }
+        ));
+        let invalid_utf8 = vec![0xff];
+        assert!(matches!(
+            Error::from(std::str::from_utf8(&invalid_utf8).unwrap_err()),
+            Error::InvalidUtf8 { .. }
+        ));
+        assert!(matches!(
+            Error::from(crate::json::from_str::<u32>("x").unwrap_err()),
+            Error::InvalidJson { .. }
+        ));
+        assert!(matches!(
+            Error::from(std::io::Error::from(std::io::ErrorKind::Other)),
+            Error::IoFailure { .. }
+        ));
+    }
 }
@@ -72,7 +72,7 @@ macro_rules! enumset_serialization {
                     let mut result = Self::Value::empty();
 
                     loop {
-                        match map.next_key::<&str>()? {
+                        match map.next_key::<String>()?.as_deref() {
                             None => break,
                             $(
                                 Some($key) => {
@@ -81,14 +81,18 @@ macro_rules! enumset_serialization {
                                     }
                                 }
                             )*
-                            Some(_key) => continue,
+                            Some(_key) => {
+                                map.next_value::<crate::json::de::IgnoredAny>()?;
+                            }
                         }
                     }
                     Ok(result)
                 }
             }
 
-            deserializer.deserialize_map(Visitor {})
+            // Deserializing as a struct makes sure that only the known keys are consumed when
+            // this is used with flatten, leaving other keys to the remaining fields.
+            deserializer.deserialize_struct(stringify!($name), &[$($key),*], Visitor {})
         }
     }
 }
@@ -7,7 +7,10 @@
 pub use serde::de::Deserialize as Deserializable;
 pub use serde::ser::Serialize as Serializable;
 pub use serde::*;
-pub use serde_json::{from_slice, from_str, json, to_string, to_vec, Error, Value};
+pub use serde_json::{from_str, to_string, to_writer, Error, Value};
+
+#[cfg(test)]
+pub use serde_json::{from_slice, from_value, json, to_vec};
 
 mod macros;
 
@@ -15,4 +18,5 @@ mod macros;
 #[allow(unused_imports)]
 pub use macros::*;
 
+pub mod secret_deserializer;
 pub mod secret_serialization;
@@ -0,0 +1,364 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+//! Deserialization of decrypted storage data without leaving plaintext copies behind.
+//!
+//! serde_json unescapes strings containing escape sequences into an internal scratch buffer
+//! which is never zeroed. Here the top-level object is first split into raw field values,
+//! string values with escape sequences are then unescaped directly into the buffer that ends
+//! up owned by the deserialized value. Everything else is handed over to serde_json unchanged.
+
+use secrecy::zeroize::Zeroize;
+use serde::de::value::MapDeserializer;
+use serde::de::{self, Deserialize, Deserializer, IntoDeserializer, MapAccess, Visitor};
+use serde_json::value::RawValue;
+use serde_json::Error;
+use std::fmt;
+
+/// Deserializes a JSON object from decrypted data. With `allow_duplicates` set, the last
+/// occurrence of a duplicate field wins, otherwise duplicate fields produce an error.
+pub fn from_secret_slice<'de, T>(data: &'de [u8], allow_duplicates: bool) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    let RawObject(mut fields) = serde_json::from_slice(data)?;
+    if allow_duplicates {
+        let mut index = 0;
+        while index < fields.len() {
+            if fields[index + 1..]
+                .iter()
+                .any(|(key, _)| *key == fields[index].0)
+            {
+                fields.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    let entries = fields
+        .into_iter()
+        .map(|(key, value)| (key, RawField(value)));
+    T::deserialize(MapDeserializer::new(entries))
+}
+
+/// Deserializes a JSON string from decrypted data.
+pub fn string_from_secret_slice(data: &[u8]) -> Result<String, Error> {
+    let raw: &RawValue = serde_json::from_slice(data)?;
+    String::deserialize(RawField(raw))
+}
+
+struct RawObject<'de>(Vec<(String, &'de RawValue)>);
+
+impl<'de> Deserialize<'de> for RawObject<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawObjectVisitor;
+
+        impl<'de> Visitor<'de> for RawObjectVisitor {
+            type Value = RawObject<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a JSON object")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut fields = Vec::new();
+                while let Some(field) = map.next_entry()? {
+                    fields.push(field);
+                }
+                Ok(RawObject(fields))
+            }
+        }
+
+        deserializer.deserialize_map(RawObjectVisitor)
+    }
+}
+
+/// A single raw field value. Strings with escape sequences are unescaped here, all other
+/// values are deserialized by serde_json.
+struct RawField<'de>(&'de RawValue);
+
+impl<'de> RawField<'de> {
+    fn escaped_string(&self) -> Option<&'de str> {
+        let raw = self.0.get();
+        if raw.starts_with('"') && raw.contains('\\') {
+            Some(raw)
+        } else {
+            None
+        }
+    }
+
+    fn delegate<F, V>(self, f: F) -> Result<V, Error>
+    where
+        F: FnOnce(&mut serde_json::Deserializer<serde_json::de::StrRead<'de>>) -> Result<V, Error>,
+    {
+        let mut deserializer = serde_json::Deserializer::from_str(self.0.get());
+        let result = f(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(result)
+    }
+}
+
+macro_rules! deserialize_string_or_delegate {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                match self.escaped_string() {
+                    Some(raw) => visitor.visit_string(unescape(raw)?),
+                    None => self.delegate(|deserializer| deserializer.$method(visitor)),
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! delegate {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.delegate(|deserializer| deserializer.$method(visitor))
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for RawField<'de> {
+    type Error = Error;
+
+    deserialize_string_or_delegate! {
+        deserialize_any deserialize_str deserialize_string deserialize_identifier
+    }
+
+    delegate! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_bytes deserialize_byte_buf deserialize_unit
+        deserialize_seq deserialize_map deserialize_ignored_any
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.escaped_string() {
+            Some(_) => visitor.visit_some(self),
+            None => self.delegate(|deserializer| deserializer.deserialize_option(visitor)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.delegate(|deserializer| deserializer.deserialize_unit_struct(name, visitor))
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.delegate(|deserializer| deserializer.deserialize_tuple(len, visitor))
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.delegate(|deserializer| deserializer.deserialize_tuple_struct(name, len, visitor))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.delegate(|deserializer| deserializer.deserialize_struct(name, fields, visitor))
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.escaped_string() {
+            Some(raw) => visitor.visit_enum(unescape(raw)?.into_deserializer()),
+            None => {
+                self.delegate(|deserializer| deserializer.deserialize_enum(name, variants, visitor))
+            }
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for RawField<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Unescapes a JSON string literal including the quotation marks. The result is allocated
+/// upfront with sufficient capacity, so that it is never reallocated. On errors the partial
+/// result is zeroed.
+fn unescape(raw: &str) -> Result<String, Error> {
+    let mut result = String::with_capacity(raw.len());
+    if let Err(error) = unescape_into(raw, &mut result) {
+        result.zeroize();
+        return Err(error);
+    }
+    Ok(result)
+}
+
+fn unescape_into(raw: &str, result: &mut String) -> Result<(), Error> {
+    let inner = raw
+        .strip_prefix('"')
+        .and_then(|raw| raw.strip_suffix('"'))
+        .ok_or_else(|| de::Error::custom("invalid string literal"))?;
+
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        let c = match chars.next() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\x08',
+            Some('f') => '\x0C',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+                let first = read_hex(&mut chars)?;
+                let code = if (0xD800..0xDC00).contains(&first) {
+                    if chars.next() != Some('\\') || chars.next() != Some('u') {
+                        return Err(de::Error::custom("unpaired surrogate"));
+                    }
+                    let second = read_hex(&mut chars)?;
+                    if !(0xDC00..0xE000).contains(&second) {
+                        return Err(de::Error::custom("unpaired surrogate"));
+                    }
+                    0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+                } else {
+                    first
+                };
+                char::from_u32(code).ok_or_else(|| de::Error::custom("invalid unicode escape"))?
+            }
+            _ => return Err(de::Error::custom("invalid escape sequence")),
+        };
+        result.push(c);
+    }
+    Ok(())
+}
+
+fn read_hex(chars: &mut std::str::Chars) -> Result<u32, Error> {
+    let mut code = 0;
+    for _ in 0..4 {
+        let digit = chars
+            .next()
+            .and_then(|c| c.to_digit(16))
+            .ok_or_else(|| de::Error::custom("invalid unicode escape"))?;
+        code = code * 16 + digit;
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Test {
+        value: String,
+        number: u32,
+        optional: Option<String>,
+    }
+
+    #[test]
+    fn unescaping() {
+        assert_eq!(
+            unescape(r#""qu\"ote\\slash\/tab\tnew\nline""#).unwrap(),
+            "qu\"ote\\slash/tab\tnew\nline"
+        );
+        assert_eq!(unescape(r#""ä€😀""#).unwrap(), "ä€😀");
+        assert!(unescape(r#""\ud83d""#).is_err());
+        assert!(unescape(r#""\x""#).is_err());
+        assert!(unescape(r#""\u12""#).is_err());
+    }
+
+    #[test]
+    fn objects() {
+        let data = br#"{"value":"a\"b","number":12,"optional":"c\\d"}"#;
+        assert_eq!(
+            from_secret_slice::<Test>(data, false).unwrap(),
+            Test {
+                value: "a\"b".to_string(),
+                number: 12,
+                optional: Some("c\\d".to_string()),
+            }
+        );
+
+        let data = br#"{"value":"x","number":1,"optional":null,"value":"y"}"#;
+        assert!(from_secret_slice::<Test>(data, false).is_err());
+        assert_eq!(
+            from_secret_slice::<Test>(data, true).unwrap(),
+            Test {
+                value: "y".to_string(),
+                number: 1,
+                optional: None,
+            }
+        );
+
+        assert!(from_secret_slice::<Test>(br#""value""#, false).is_err());
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(string_from_secret_slice(br#""abc""#).unwrap(), "abc");
+        assert_eq!(string_from_secret_slice(br#""a\nc""#).unwrap(), "a\nc");
+        assert!(string_from_secret_slice(b"12").is_err());
+    }
+}
@@ -4,9 +4,13 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
+//! Serialization helpers for secret values, meant to be used with `#[serde(with = "...")]`.
+//! Values are serialized directly from the secret, without creating intermediate copies.
+
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 
+/// Serializes a secret string.
 pub fn serialize<S>(value: &SecretString, s: S) -> Result<S::Ok, S::Error>
 where
     S: serde::ser::Serializer,
@@ -14,6 +18,7 @@ where
     value.expose_secret().serialize(s)
 }
 
+/// Deserializes a secret string.
 pub fn deserialize<'de, D>(d: D) -> Result<SecretString, D::Error>
 where
     D: serde::de::Deserializer<'de>,
@@ -21,6 +26,32 @@ where
     Ok(SecretString::new(String::deserialize(d)?))
 }
 
+/// Produces an empty secret string, for use with `#[serde(default = "...")]`.
 pub fn default() -> SecretString {
     SecretString::new(String::new())
 }
+
+/// Serialization helpers for optional secret values.
+pub mod option {
+    use secrecy::{ExposeSecret, SecretString};
+    use serde::Deserialize;
+
+    /// Serializes an optional secret string.
+    pub fn serialize<S>(value: &Option<SecretString>, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        match value {
+            Some(value) => s.serialize_some(value.expose_secret()),
+            None => s.serialize_none(),
+        }
+    }
+
+    /// Deserializes an optional secret string.
+    pub fn deserialize<'de, D>(d: D) -> Result<Option<SecretString>, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        Ok(Option::<String>::deserialize(d)?.map(SecretString::new))
+    }
+}
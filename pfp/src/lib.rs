@@ -39,3 +39,6 @@ pub mod passwords;
 pub mod recovery_codes;
 pub mod storage_io;
 pub mod storage_types;
+
+pub use json::secret_deserializer;
+pub use json::secret_serialization;
@@ -12,11 +12,14 @@ use crate::recovery_codes;
 use crate::storage;
 use crate::storage_io;
 use crate::storage_types::{
-    CharacterSet, GeneratedPassword, Password, PasswordId, Site, StoredPassword,
+    CharacterSet, GeneratedPassword, KdfParams, Password, PasswordHistoryEntry, PasswordId,
+    PasswordPolicy, Site, StoredPassword,
 };
 
 use rand::Rng;
-use secrecy::{SecretString, SecretVec};
+use secrecy::{ExposeSecret, SecretString, SecretVec};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 /// Generates the storage data encryption key.
 ///
@@ -24,14 +27,176 @@ use secrecy::{SecretString, SecretVec};
 /// random value to prevent rainbow table attacks. The salt is not considered a secret and is
 /// stored as plain text in the storage file.
 pub fn get_encryption_key(primary_password: &SecretString, salt: &[u8]) -> SecretVec<u8> {
+    get_encryption_key_with_params(primary_password, salt, &KdfParams::default())
+        .expect("Key derivation with default parameters shouldn't fail")
+}
+
+/// Generates the storage data encryption key like [get_encryption_key()](fn.get_encryption_key.html)
+/// but with the given key derivation parameters instead of the default ones.
+///
+/// This produces
+/// [Error::InvalidKdfParams](../error/enum.Error.html#variant.InvalidKdfParams) if the key
+/// derivation function rejects the parameters or the salt.
+pub fn get_encryption_key_with_params(
+    primary_password: &SecretString,
+    salt: &[u8],
+    params: &KdfParams,
+) -> Result<SecretVec<u8>, Error> {
     // Replicate salt being converted to UTF-8 as done by JS code
     let salt_str = String::from_iter(salt.iter().map(|&byte| byte as char));
-    crypto::derive_key(primary_password, salt_str.as_bytes())
+    crypto::derive_key(primary_password, salt_str.as_bytes(), params)
+}
+
+/// Extracts the lower-case host name from a URL with a scheme like
+/// `https://user@www.example.com:8443/login`, this is how site names are normalized.
+///
+/// IPv6 addresses keep their brackets like `[::1]`, same as in the browser extension. `None` is
+/// returned if the URL has no valid scheme or no host name.
+pub fn url_host(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let mut chars = scheme.chars();
+    if !chars.next()?.is_ascii_alphabetic()
+        || !chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    {
+        return None;
+    }
+
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit('@').next()?;
+    let host = if host_port.starts_with('[') {
+        let end = host_port.find(']')? + 1;
+        if end == 2 || !(host_port[end..].is_empty() || host_port[end..].starts_with(':')) {
+            return None;
+        }
+        &host_port[..end]
+    } else {
+        host_port.split(':').next()?
+    };
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_lowercase())
+    }
+}
+
+/// The minimal length of generated passwords.
+pub const MIN_PASSWORD_LENGTH: usize = 4;
+
+/// The maximal length of generated passwords.
+pub const MAX_PASSWORD_LENGTH: usize = 24;
+
+/// The maximal number of previous values kept in the history of a stored password.
+pub const MAX_HISTORY_LENGTH: usize = 10;
+
+fn check_length(length: usize) -> Result<(), Error> {
+    if (MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&length) {
+        Ok(())
+    } else {
+        Err(Error::InvalidLength { length })
+    }
+}
+
+fn check_symbols(symbols: Option<&str>) -> Result<(), Error> {
+    match symbols {
+        Some(symbols) if !crypto::valid_symbols(symbols) => Err(Error::InvalidSymbols),
+        _ => Ok(()),
+    }
+}
+
+/// Identifies a derived password value: legacy derivation flag, salt, length, character set and
+/// custom symbols.
+type DerivationKey = (bool, String, usize, CharacterSet, Option<String>);
+
+fn system_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn revision_number(revision: &str) -> Option<u64> {
+    if revision.is_empty() {
+        Some(1)
+    } else {
+        revision.parse().ok()
+    }
+}
+
+/// Parameters of the key derivation function used for generated passwords. The storage encryption
+/// key uses the same parameters unless configured otherwise, see
+/// [Passwords::set_kdf_params](struct.Passwords.html#method.set_kdf_params).
+#[derive(Debug, Clone, Copy)]
+pub struct KeyDerivation {
+    /// Name of the algorithm
+    pub algorithm: &'static str,
+    /// CPU/memory cost parameter (N), the number of iterations
+    pub cost: u64,
+    /// Block size parameter (r)
+    pub block_size: u32,
+    /// Parallelization parameter (p)
+    pub parallelization: u32,
+}
+
+/// The key derivation parameters used by this library.
+pub const KEY_DERIVATION: KeyDerivation = KeyDerivation {
+    algorithm: "scrypt",
+    cost: 1 << crypto::SCRYPT_LOG_N,
+    block_size: crypto::SCRYPT_R,
+    parallelization: crypto::SCRYPT_P,
+};
+
+/// The state of a passwords storage, as returned by
+/// [Passwords::state](struct.Passwords.html#method.state).
+///
+/// [Passwords::reset](struct.Passwords.html#method.reset) moves storage in any state to
+/// `Unlocked`. [Passwords::unlock](struct.Passwords.html#method.unlock) moves `Locked` storage to
+/// `Unlocked`, [Passwords::lock](struct.Passwords.html#method.lock) moves `Unlocked` storage back
+/// to `Locked`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageState {
+    /// No storage data is present, storage needs to be reset first.
+    Uninitialized,
+    /// Storage data is present but the primary password isn't known.
+    Locked,
+    /// Storage data is present and passwords can be accessed.
+    Unlocked,
+}
+
+/// All data belonging to a single site, as returned by
+/// [Passwords::export_site](struct.Passwords.html#method.export_site).
+pub struct SiteExport {
+    /// The site entry
+    pub site: Site,
+    /// Names of the sites that are aliases for this site
+    pub aliases: Vec<String>,
+    /// The site's passwords along with their values
+    pub passwords: Vec<(Password, SecretString)>,
+}
+
+/// Summary of the storage contents, as returned by
+/// [Passwords::statistics](struct.Passwords.html#method.statistics).
+#[derive(Debug, Default)]
+pub struct Statistics {
+    /// Number of sites that aren't aliases
+    pub sites: usize,
+    /// Number of sites that are aliases for other sites
+    pub aliases: usize,
+    /// Number of generated passwords, including those in the legacy format
+    pub generated: usize,
+    /// Number of stored passwords
+    pub stored: usize,
+    /// Number of generated passwords for each password length
+    pub lengths: BTreeMap<usize, usize>,
+    /// Number of passwords that have notes
+    pub with_notes: usize,
 }
 
 /// The type providing access to the passwords storage, allowing to retrieve and manipulate its
 /// data.
 ///
+/// Site names passed to its methods are normalized: if a URL like `https://www.example.com/login`
+/// is given, only its host name is used. A `www.` prefix is removed in any case.
+///
 /// Typically, you will create a new `Passwords` instance using File I/O:
 ///
 /// ```no_run
@@ -52,7 +217,7 @@ pub fn get_encryption_key(primary_password: &SecretString, salt: &[u8]) -> Secre
 ///
 /// // At this point test.json file should exist.
 /// // Add a generated password for example.com
-/// passwords.set_generated("example.com", "me", "1", 16, CharacterSet::all()).unwrap();
+/// passwords.set_generated("example.com", "me", "1", 16, CharacterSet::all(), None).unwrap();
 ///
 /// // Get generated password
 /// assert_eq!(passwords.get("example.com", "me", "1").unwrap().expose_secret(), "sWEdAx<E<Gd_kaa2");
@@ -61,6 +226,10 @@ pub struct Passwords<IO: storage_io::StorageIO> {
     key: Option<SecretVec<u8>>,
     hmac_secret: Option<SecretVec<u8>>,
     primary_password: Option<SecretString>,
+    derivation_cache: Option<RefCell<HashMap<DerivationKey, SecretString>>>,
+    keep_history: bool,
+    clock: Box<dyn Fn() -> u64 + Send + Sync>,
+    last_timestamp: Cell<u64>,
 }
 
 impl<IO: storage_io::StorageIO> Passwords<IO> {
@@ -72,6 +241,155 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
             key: None,
             hmac_secret: None,
             primary_password: None,
+            derivation_cache: None,
+            keep_history: false,
+            clock: Box::new(system_time),
+            last_timestamp: Cell::new(0),
+        }
+    }
+
+    /// Enables or disables lenient parsing of storage entries, disabled by default.
+    ///
+    /// Storage entries containing the same field multiple times are normally rejected. In lenient
+    /// mode the last value of a duplicated field is used instead, which allows recovering data from
+    /// slightly malformed storage files.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.storage.set_lenient(lenient);
+    }
+
+    /// Enables or disables caching of generated password values, disabled by default.
+    ///
+    /// Deriving a generated password is deliberately slow. With the cache enabled, each value is
+    /// derived only once and subsequent calls to [get()](#method.get) return the cached value.
+    /// Cache entries are tied to the password's parameters, so modified passwords are derived
+    /// anew. The cache is emptied whenever passwords are locked or the primary password changes.
+    ///
+    /// Cached values are kept in memory as plain text (zeroed out when the cache is emptied), so
+    /// the cache should only be enabled for sessions where the responsiveness is worth it.
+    /// Disabling the cache discards all cached values.
+    pub fn set_derivation_cache(&mut self, enabled: bool) {
+        self.derivation_cache = if enabled {
+            Some(RefCell::new(HashMap::new()))
+        } else {
+            None
+        };
+    }
+
+    /// Enables or disables caching of decrypted site entries, disabled by default.
+    ///
+    /// Site entries are decrypted whenever a site name is resolved, so operations going through
+    /// many sites like [list_sites()](#method.list_sites) followed by [list()](#method.list) for
+    /// each site will decrypt each site entry repeatedly. With the cache enabled, each site entry
+    /// is decrypted only once. Cache entries are dropped when the site changes, and the cache is
+    /// emptied whenever passwords are locked or the storage is reloaded.
+    ///
+    /// Site entries contain no secrets but site names and aliases are kept in memory as long as
+    /// the cache is enabled. Disabling the cache discards all cached entries.
+    pub fn set_site_cache(&mut self, enabled: bool) {
+        self.storage.set_site_cache(enabled);
+    }
+
+    /// Enables or disables keeping previous values of stored passwords, disabled by default.
+    ///
+    /// With history enabled, [set_stored()](#method.set_stored) replacing an existing stored
+    /// password records the previous value along with the current time. At most
+    /// [MAX_HISTORY_LENGTH](constant.MAX_HISTORY_LENGTH.html) values are kept per password, the
+    /// oldest ones are dropped. History entries are encrypted along with the password. Values
+    /// recorded previously are kept regardless of this setting.
+    pub fn set_keep_history(&mut self, enabled: bool) {
+        self.keep_history = enabled;
+    }
+
+    /// Replaces the clock used for the creation and modification times of passwords as well as
+    /// password history, the system clock by default. The clock returns seconds since the Unix
+    /// epoch, replacing it is mostly useful to get deterministic results in tests.
+    ///
+    /// Timestamps never go backwards: if the clock returns an earlier time than a previously
+    /// recorded one, the previous time is used again.
+    pub fn set_clock(&mut self, clock: Box<dyn Fn() -> u64 + Send + Sync>) {
+        self.clock = clock;
+    }
+
+    fn timestamp(&self) -> u64 {
+        let timestamp = (self.clock)().max(self.last_timestamp.get());
+        self.last_timestamp.set(timestamp);
+        timestamp
+    }
+
+    /// Derives all generated passwords of a site and puts them into the cache enabled via
+    /// [set_derivation_cache()](#method.set_derivation_cache), so that retrieving them later is
+    /// instant. This is meant to be called in the background, e.g. before a user interface
+    /// displays the site.
+    ///
+    /// The `site` parameter will be normalized (`www.` prefix removed). If the site in question is
+    /// an alias, the passwords of the site it is an alias for will be derived.
+    ///
+    /// Returns the number of generated passwords for the site. If the cache isn't enabled,
+    /// nothing is derived and `0` is returned.
+    ///
+    /// Note that this keeps the plain text values of all the site's passwords in memory until
+    /// the passwords are locked, even if the user never looks at them.
+    pub fn prefetch(&self, site: &str) -> Result<usize, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+        let primary_password = self
+            .primary_password
+            .as_ref()
+            .ok_or(Error::PasswordsLocked)?;
+
+        if self.derivation_cache.is_none() {
+            return Ok(0);
+        }
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let mut count = 0;
+        for password in self
+            .storage
+            .list_passwords(&site_resolved, hmac_secret, key)
+        {
+            let (legacy, password) = match password {
+                Password::Generated(password) => (false, password),
+                Password::LegacyGenerated(password) => (true, password),
+                Password::Stored(_) => continue,
+            };
+            self.derive(primary_password, legacy, &password);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn clear_derivation_cache(&self) {
+        if let Some(cache) = &self.derivation_cache {
+            cache.borrow_mut().clear();
+        }
+    }
+
+    fn derive(
+        &self,
+        primary_password: &SecretString,
+        legacy: bool,
+        password: &GeneratedPassword,
+    ) -> SecretString {
+        let derive_func = if legacy {
+            crypto::derive_password_legacy
+        } else {
+            crypto::derive_password
+        };
+        let salt = password.salt();
+        let length = password.length();
+        let charset = password.charset();
+        let symbols = password.symbols();
+
+        if let Some(cache) = &self.derivation_cache {
+            let cache_key = (legacy, salt, length, charset, symbols.map(String::from));
+            if let Some(value) = cache.borrow().get(&cache_key) {
+                return value.clone();
+            }
+            let value = derive_func(primary_password, &cache_key.1, length, charset, symbols);
+            cache.borrow_mut().insert(cache_key, value.clone());
+            value
+        } else {
+            derive_func(primary_password, &salt, length, charset, symbols)
         }
     }
 
@@ -86,6 +404,27 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
         self.storage.initialized()
     }
 
+    /// Returns the length of the salt used for deriving the encryption key, in bytes. This is
+    /// available without unlocking the storage.
+    ///
+    /// Calling this method on uninitialized storage will result in
+    /// [Error::StorageNotInitialized](../error/enum.Error.html#variant.StorageNotInitialized).
+    pub fn salt_length(&self) -> Result<usize, Error> {
+        Ok(self.storage.get_salt()?.len())
+    }
+
+    /// Determines the current state of the passwords storage. This combines the results of
+    /// [initialized()](#method.initialized) and [unlocked()](#method.unlocked).
+    pub fn state(&self) -> StorageState {
+        if !self.initialized() {
+            StorageState::Uninitialized
+        } else if !self.unlocked() {
+            StorageState::Locked
+        } else {
+            StorageState::Unlocked
+        }
+    }
+
     /// Checks whether storage data is unlocked.
     ///
     /// This method returns `true` if the primary password is known and passwords can be accessed.
@@ -102,49 +441,353 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
     /// already initialized, all existing data will be removed. On success, passwords storage will
     /// be unlocked implicitly, calling `unlock()` isn't required.
     ///
+    /// The primary password is normalized to Unicode NFC form, so that it produces the same keys
+    /// regardless of how the input method composed accented characters.
+    ///
     /// This only produces errors related to writing out the storage data to disk.
     pub fn reset(&mut self, primary_password: SecretString) -> Result<(), Error> {
+        self.reset_with_kdf_params(primary_password, KdfParams::default())
+    }
+
+    /// Clears the passwords storage and sets a new primary password like
+    /// [reset()](#method.reset), deriving the storage encryption key with the given key
+    /// derivation parameters.
+    ///
+    /// In addition to the errors produced by `reset()`, this will produce
+    /// [Error::InvalidKdfParams](../error/enum.Error.html#variant.InvalidKdfParams) if the
+    /// parameters aren't supported.
+    pub fn reset_with_kdf_params(
+        &mut self,
+        primary_password: SecretString,
+        kdf_params: KdfParams,
+    ) -> Result<(), Error> {
+        kdf_params.validate()?;
+        let primary_password = crypto::normalize_password(&primary_password);
         let salt = crypto::get_rng().gen::<[u8; 16]>();
-        let key = get_encryption_key(&primary_password, &salt);
+        let key = get_encryption_key_with_params(&primary_password, &salt, &kdf_params)?;
         let hmac_secret = SecretVec::new(crypto::get_rng().gen::<[u8; 32]>().to_vec());
 
-        self.storage.clear(&salt, &hmac_secret, &key)?;
+        self.storage.clear(&salt, &kdf_params, &hmac_secret, &key)?;
         self.storage.flush()?;
 
+        self.clear_derivation_cache();
         self.key = Some(key);
         self.hmac_secret = Some(hmac_secret);
         self.primary_password = Some(primary_password);
         Ok(())
     }
 
+    /// Changes the primary password while keeping all data. The storage is re-encrypted with a
+    /// key derived from the new primary password and a new salt.
+    ///
+    /// Generated passwords are derived from the primary password, so their values will change.
+    /// Stored passwords and notes keep their values.
+    ///
+    /// This produces [Error::PasswordsLocked](../error/enum.Error.html#variant.PasswordsLocked)
+    /// if the passwords storage is locked.
+    pub fn change_primary(&mut self, new_password: SecretString) -> Result<(), Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let primary_password = crypto::normalize_password(&new_password);
+        let kdf_params = self.storage.get_kdf_params()?;
+        let salt = crypto::get_rng().gen::<[u8; 16]>();
+        let new_key = get_encryption_key_with_params(&primary_password, &salt, &kdf_params)?;
+
+        self.storage
+            .rekey(&salt, &kdf_params, hmac_secret, key, &new_key)?;
+        self.storage.flush()?;
+
+        self.clear_derivation_cache();
+        self.key = Some(new_key);
+        self.primary_password = Some(primary_password);
+        Ok(())
+    }
+
+    /// Retrieves the key derivation parameters used for the storage encryption key. This is
+    /// available without unlocking the storage.
+    ///
+    /// This produces
+    /// [Error::InvalidKdfParams](../error/enum.Error.html#variant.InvalidKdfParams) if the
+    /// parameters in storage aren't supported.
+    pub fn kdf_params(&self) -> Result<KdfParams, Error> {
+        self.storage.get_kdf_params()
+    }
+
+    /// Changes the key derivation parameters used for the storage encryption key. The storage is
+    /// re-encrypted with a key derived using the new parameters and a new salt. Generated
+    /// passwords aren't affected.
+    ///
+    /// This produces [Error::PasswordsLocked](../error/enum.Error.html#variant.PasswordsLocked)
+    /// if the passwords storage is locked.
+    pub fn set_kdf_params(&mut self, kdf_params: KdfParams) -> Result<(), Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+        let primary_password = self
+            .primary_password
+            .as_ref()
+            .ok_or(Error::PasswordsLocked)?;
+        kdf_params.validate()?;
+
+        let salt = crypto::get_rng().gen::<[u8; 16]>();
+        let new_key = get_encryption_key_with_params(primary_password, &salt, &kdf_params)?;
+
+        self.storage
+            .rekey(&salt, &kdf_params, hmac_secret, key, &new_key)?;
+        self.storage.flush()?;
+
+        self.key = Some(new_key);
+        Ok(())
+    }
+
     /// Unlocks the passwords storage with a given primary password.
     ///
     /// If successful, it will be possible to access and manipulate passwords data after this call.
     /// Calling this method on uninitialized storage will result in
     /// [Error::StorageNotInitialized](../error/enum.Error.html#variant.StorageNotInitialized).
     /// Calling this method with a wrong primary password will result in
-    /// [Error::DecryptionFailure](../error/enum.Error.html#variant.DecryptionFailure).
+    /// [Error::DecryptionFailure](../error/enum.Error.html#variant.DecryptionFailure). If the
+    /// encrypted data is malformed, the result will be
+    /// [Error::InvalidCiphertext](../error/enum.Error.html#variant.InvalidCiphertext) or
+    /// [Error::InvalidBase64](../error/enum.Error.html#variant.InvalidBase64) instead, entering a
+    /// different primary password won't help then.
+    ///
+    /// The primary password is normalized to Unicode NFC form first, same as in
+    /// [reset()](#method.reset). The browser extension doesn't normalize the primary password, so
+    /// if decryption fails with the normalized password the primary password is also tried
+    /// exactly as entered. This keeps storage initialized with a non-normalized primary password
+    /// accessible.
     pub fn unlock(&mut self, primary_password: SecretString) -> Result<(), Error> {
-        let salt = self.storage.get_salt()?;
-        let key = get_encryption_key(&primary_password, &salt);
-
-        let hmac_secret = self.storage.get_hmac_secret(&key)?;
+        let (primary_password, key, hmac_secret) = self.derive_keys(primary_password)?;
+        self.clear_derivation_cache();
         self.key = Some(key);
         self.hmac_secret = Some(hmac_secret);
         self.primary_password = Some(primary_password);
         Ok(())
     }
 
+    /// Unlocks the passwords storage with the primary password of another, already unlocked
+    /// `Passwords` instance, e.g. to read a backup created with the same primary password.
+    ///
+    /// This produces [Error::PasswordsLocked](../error/enum.Error.html#variant.PasswordsLocked)
+    /// if `other` is locked and
+    /// [Error::DecryptionFailure](../error/enum.Error.html#variant.DecryptionFailure) if its
+    /// primary password doesn't match.
+    pub fn unlock_from<OtherIO: storage_io::StorageIO>(
+        &mut self,
+        other: &Passwords<OtherIO>,
+    ) -> Result<(), Error> {
+        let primary_password = other
+            .primary_password
+            .as_ref()
+            .ok_or(Error::PasswordsLocked)?;
+        self.unlock(SecretString::new(primary_password.expose_secret().clone()))
+    }
+
+    fn derive_keys(
+        &self,
+        primary_password: SecretString,
+    ) -> Result<(SecretString, SecretVec<u8>, SecretVec<u8>), Error> {
+        let salt = self.storage.get_salt()?;
+        let kdf_params = self.storage.get_kdf_params()?;
+        let normalized = crypto::normalize_password(&primary_password);
+        let key = get_encryption_key_with_params(&normalized, &salt, &kdf_params)?;
+
+        match self.storage.get_hmac_secret(&key) {
+            Ok(hmac_secret) => Ok((normalized, key, hmac_secret)),
+            Err(Error::DecryptionFailure)
+                if normalized.expose_secret() != primary_password.expose_secret() =>
+            {
+                let key = get_encryption_key_with_params(&primary_password, &salt, &kdf_params)?;
+                let hmac_secret = self.storage.get_hmac_secret(&key)?;
+                Ok((primary_password, key, hmac_secret))
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Checks whether the given primary password is correct for the passwords storage without
+    /// unlocking it. Unlike [verify_primary()](#method.verify_primary) this works with locked
+    /// storage but performs the (slow) key derivation.
+    ///
+    /// Errors other than
+    /// [Error::DecryptionFailure](../error/enum.Error.html#variant.DecryptionFailure), e.g.
+    /// [Error::StorageNotInitialized](../error/enum.Error.html#variant.StorageNotInitialized),
+    /// are passed on.
+    pub fn verify_password(&self, primary_password: &SecretString) -> Result<bool, Error> {
+        match self.derive_keys(SecretString::new(primary_password.expose_secret().clone())) {
+            Ok(_) => Ok(true),
+            Err(Error::DecryptionFailure) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Checks whether the given primary password is the one the passwords storage was unlocked
+    /// with, e.g. to confirm the user's presence before revealing a password. No key derivation
+    /// is performed.
+    ///
+    /// This produces [Error::PasswordsLocked](../error/enum.Error.html#variant.PasswordsLocked)
+    /// if the passwords storage is locked.
+    pub fn verify_primary(&self, primary_password: &SecretString) -> Result<bool, Error> {
+        let current = self
+            .primary_password
+            .as_ref()
+            .ok_or(Error::PasswordsLocked)?;
+        let normalized = crypto::normalize_password(primary_password);
+        Ok(normalized.expose_secret() == current.expose_secret()
+            || primary_password.expose_secret() == current.expose_secret())
+    }
+
     /// Locks the passwords storage, forgetting anything it knows about the primary password.
     ///
     /// After this call, passwords will no longer be accessible until [unlock()](#method.unlock)
     /// is called again.
     pub fn lock(&mut self) {
+        self.clear_derivation_cache();
+        self.storage.clear_site_cache();
         self.key = None;
         self.hmac_secret = None;
         self.primary_password = None;
     }
 
+    /// Writes all changes to the storage file.
+    ///
+    /// Methods modifying data write their changes immediately, so outside of transactions this
+    /// is usually a no-op. During a transaction this saves the changes made so far as a
+    /// checkpoint: the transaction stays in progress, but
+    /// [rollback_transaction()](#method.rollback_transaction) will only discard changes made after
+    /// the checkpoint.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.storage.checkpoint()
+    }
+
+    /// Closes the passwords storage: commits a transaction that is still in progress, closes the
+    /// storage backend and locks the storage.
+    ///
+    /// Dropping a `Passwords` instance locks it as well, but with an open transaction its changes
+    /// are lost silently. This method reports any error writing the changes instead. Without a
+    /// transaction in progress, all changes have been written already and the storage file isn't
+    /// touched, unless the backend only writes its data on close like
+    /// [StdioIO](../storage_io/struct.StdioIO.html).
+    pub fn close(mut self) -> Result<(), Error> {
+        let result = if self.in_transaction() {
+            self.commit_transaction()
+        } else {
+            Ok(())
+        };
+        let result = result.and_then(|_| self.storage.io_mut().close());
+        self.lock();
+        result
+    }
+
+    /// Gives access to the underlying storage backend, e.g. to query
+    /// [MeteredIO](../storage_io/struct.MeteredIO.html) metrics.
+    ///
+    /// The backend only holds encrypted data, so this doesn't expose any secrets.
+    pub fn io(&self) -> &IO {
+        self.storage.io()
+    }
+
+    /// Gives mutable access to the underlying storage backend.
+    ///
+    /// Note that modifying storage entries directly bypasses encryption and consistency checks,
+    /// and `Passwords` won't be aware of such changes. This is only meant for backend-specific
+    /// operations like resetting [MeteredIO](../storage_io/struct.MeteredIO.html) metrics.
+    pub fn io_mut(&mut self) -> &mut IO {
+        self.storage.io_mut()
+    }
+
+    /// Runs `f` as a batch of changes: the storage file is written only once after `f` returns
+    /// rather than after every modification. This makes bulk operations like imports
+    /// considerably faster.
+    ///
+    /// Unlike with transactions, changes made before an error occurs are kept: the storage file
+    /// is written regardless of what `f` returns. Nested batches are merged into the outer one,
+    /// and within a transaction the changes are only written when the transaction is committed.
+    pub fn batch<T, F>(&mut self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        if self.storage.defer_flush(true) {
+            return Ok(f(self));
+        }
+
+        let result = f(self);
+        self.storage.defer_flush(false);
+        self.storage.flush()?;
+        Ok(result)
+    }
+
+    /// Checks whether a transaction is in progress.
+    pub fn in_transaction(&self) -> bool {
+        self.storage.in_transaction()
+    }
+
+    /// Starts a transaction. Until the transaction is finished via
+    /// [commit_transaction()](#method.commit_transaction) or
+    /// [rollback_transaction()](#method.rollback_transaction), changes are only kept in memory
+    /// and won't be written to the storage file.
+    ///
+    /// Transactions cannot be nested, calling this method while a transaction is in progress will
+    /// result in
+    /// [Error::TransactionInProgress](../error/enum.Error.html#variant.TransactionInProgress).
+    pub fn begin_transaction(&mut self) -> Result<(), Error> {
+        self.storage.begin_transaction()
+    }
+
+    /// Finishes the current transaction, writing all changes made during the transaction to the
+    /// storage file.
+    ///
+    /// Calling this method without a transaction in progress will result in
+    /// [Error::NoTransaction](../error/enum.Error.html#variant.NoTransaction).
+    pub fn commit_transaction(&mut self) -> Result<(), Error> {
+        self.storage.commit_transaction()
+    }
+
+    /// Finishes the current transaction, discarding all changes made during the transaction. The
+    /// data is read from the storage file again.
+    ///
+    /// The passwords stay unlocked unless the storage file was modified in the meantime in a way
+    /// that makes the known primary password invalid, in that case they are locked.
+    ///
+    /// Calling this method without a transaction in progress will result in
+    /// [Error::NoTransaction](../error/enum.Error.html#variant.NoTransaction).
+    pub fn rollback_transaction(&mut self) -> Result<(), Error> {
+        self.storage.rollback_transaction()?;
+
+        match self
+            .key
+            .as_ref()
+            .map(|key| self.storage.get_hmac_secret(key))
+        {
+            Some(Ok(hmac_secret)) => self.hmac_secret = Some(hmac_secret),
+            Some(Err(_)) => self.lock(),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Discards all unsaved changes and reads the data from the storage file again, e.g. after
+    /// flushing resulted in
+    /// [Error::StorageChangedExternally](../error/enum.Error.html#variant.StorageChangedExternally).
+    /// A transaction in progress is discarded as well.
+    ///
+    /// The passwords are unlocked again with the known primary password. If the storage file was
+    /// modified in a way that makes this primary password invalid, they are locked instead.
+    pub fn reload(&mut self) -> Result<(), Error> {
+        self.storage.reload()?;
+
+        match self.primary_password.take() {
+            Some(primary_password) => {
+                if self.unlock(primary_password).is_err() {
+                    self.lock();
+                }
+            }
+            None => self.lock(),
+        }
+        Ok(())
+    }
+
     /// Checks what `site` is an alias for.
     ///
     /// This will normalize `site` parameter (remove `www.` prefix). If `site` is an alias, it will
@@ -158,6 +801,21 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
         self.storage.get_alias(&site_normalized, hmac_secret, key)
     }
 
+    /// Retrieves the data of a single site.
+    ///
+    /// This will normalize `site` parameter (remove `www.` prefix). Unlike most other calls, an
+    /// alias is not resolved: the site entry itself is returned, so that its
+    /// [alias()](../storage_types/struct.Site.html#method.alias) can be checked. If there is no
+    /// data for the site this call will result in
+    /// [Error::KeyMissing](../error/enum.Error.html#variant.KeyMissing).
+    pub fn site_info(&self, site: &str) -> Result<Site, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let site_normalized = self.storage.normalize_site(site);
+        self.storage.get_site(&site_normalized, hmac_secret, key)
+    }
+
     /// Marks `site` and an alias for `alias`.
     ///
     /// This will normalize `site` parameter (remove `www.` prefix). If `alias` is itself marked as
@@ -169,7 +827,7 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
         let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
 
         let site_normalized = self.storage.normalize_site(site);
-        let alias_resolved = self.storage.resolve_site(alias, hmac_secret, key);
+        let alias_resolved = self.storage.resolve_site(alias, hmac_secret, key)?;
         if self
             .storage
             .list_passwords(&site_normalized, hmac_secret, key)
@@ -184,6 +842,106 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
         self.storage.flush()
     }
 
+    /// Marks `site` as an alias for `alias` like [set_alias()](#method.set_alias), moving any
+    /// passwords of `site` to the site it becomes an alias for.
+    ///
+    /// Like with [move_password()](#method.move_password), stored passwords keep their value
+    /// whereas generated passwords keep their generation parameters, meaning that their value
+    /// changes. Notes are kept for both password types. If any of the passwords already exists
+    /// for the target site, no changes are made and the call will result in
+    /// [Error::PasswordExists error](../error/enum.Error.html#variant.PasswordExists). Returns
+    /// the number of passwords moved.
+    pub fn set_alias_migrating(&mut self, site: &str, alias: &str) -> Result<usize, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let site_normalized = self.storage.normalize_site(site);
+        let alias_resolved = self.storage.resolve_site(alias, hmac_secret, key)?;
+        if site_normalized == alias_resolved {
+            return Err(Error::AliasToSelf);
+        }
+
+        let existing = self
+            .storage
+            .list_passwords(&site_normalized, hmac_secret, key)
+            .collect::<Vec<_>>();
+        let migrated = existing
+            .iter()
+            .map(|password| {
+                PasswordId::new(
+                    &alias_resolved,
+                    password.id().name(),
+                    password.id().revision(),
+                )
+            })
+            .collect::<Vec<_>>();
+        if migrated
+            .iter()
+            .any(|id| self.storage.has_password(id, hmac_secret))
+        {
+            return Err(Error::PasswordExists);
+        }
+
+        if !existing.is_empty() {
+            self.storage
+                .ensure_site_data(&alias_resolved, hmac_secret, key)?;
+        }
+        let count = existing.len();
+        let timestamp = self.timestamp();
+        for (mut password, destination) in existing.into_iter().zip(migrated) {
+            let source = password.id().clone();
+            password.set_id(destination);
+            password.touch(timestamp);
+            self.storage.set_password(password, hmac_secret, key)?;
+            self.storage.remove_password(&source, hmac_secret)?;
+        }
+
+        self.storage
+            .set_alias(&site_normalized, &alias_resolved, hmac_secret, key)?;
+        self.storage.flush()?;
+        Ok(count)
+    }
+
+    /// Marks a number of sites as aliases for `alias`, either all of them or none.
+    ///
+    /// This works like [set_alias()](#method.set_alias) but validates all sites first. If any of
+    /// them cannot be made an alias, no changes will be written and the call will result in
+    /// [Error::AliasFailures](../error/enum.Error.html#variant.AliasFailures) listing the
+    /// problematic sites.
+    pub fn set_aliases(&mut self, sites: &[&str], alias: &str) -> Result<(), Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let alias_resolved = self.storage.resolve_site(alias, hmac_secret, key)?;
+        let mut failures = Vec::new();
+        let mut sites_normalized = Vec::new();
+        for site in sites {
+            let site_normalized = self.storage.normalize_site(site);
+            if site_normalized == alias_resolved {
+                failures.push((site.to_string(), Error::AliasToSelf));
+            } else if self
+                .storage
+                .list_passwords(&site_normalized, hmac_secret, key)
+                .next()
+                .is_some()
+            {
+                failures.push((site.to_string(), Error::SiteHasPasswords));
+            } else {
+                sites_normalized.push(site_normalized);
+            }
+        }
+
+        if !failures.is_empty() {
+            return Err(Error::AliasFailures { failures });
+        }
+
+        for site in sites_normalized {
+            self.storage
+                .set_alias(&site, &alias_resolved, hmac_secret, key)?;
+        }
+        self.storage.flush()
+    }
+
     /// Turns `site` into a regular site, not an alias for another site any more.
     ///
     /// This will normalize `site` parameter (remove `www.` prefix). If `site` isn't marked as an
@@ -216,38 +974,119 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
         self.storage.flush()
     }
 
-    /// Adds a generated password or replaces an existing password.
-    ///
-    /// The `site` (site name), `name` (password name) and `revision` (password revision)
-    /// parameters identify a password, if a password with the same combination of these parameters
-    /// exists it will be replaced. While revisions are usually numerical, any string can be used.
-    /// The value `"1"` for revision is treated like an empty string.
+    /// Retrieves the password policy of a site if any.
     ///
     /// The `site` parameter will be normalized (`www.` prefix removed). If the site in question is
-    /// an alias, the password will be associated with the site it is an alias for.
+    /// an alias, the policy of the site it is an alias for is returned. Sites without any data
+    /// don't have a policy.
+    pub fn get_policy(&self, site: &str) -> Result<Option<PasswordPolicy>, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        match self.storage.get_site(&site_resolved, hmac_secret, key) {
+            Ok(site) => Ok(site.policy().cloned()),
+            Err(Error::KeyMissing) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Sets the password policy of a site, `None` removes an existing policy.
     ///
-    /// When the password is generated, it will have the length `length` and use the character sets
-    /// as determined by the `charset` parameter.
-    pub fn set_generated(
-        &mut self,
+    /// The `site` parameter will be normalized (`www.` prefix removed). If the site in question is
+    /// an alias, the policy will be associated with the site it is an alias for.
+    pub fn set_policy(&mut self, site: &str, policy: Option<PasswordPolicy>) -> Result<(), Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        self.storage
+            .ensure_site_data(&site_resolved, hmac_secret, key)?;
+        self.storage
+            .set_policy(&site_resolved, policy, hmac_secret, key)?;
+        self.storage.flush()
+    }
+
+    /// Calculates the value of a generated password without adding it to storage.
+    ///
+    /// The parameters have the same meaning as for [Passwords::set_generated]. The value returned
+    /// is identical to what [Passwords::get] would return after adding the password.
+    pub fn derive_generated(
+        &self,
+        site: &str,
+        name: &str,
+        revision: &str,
+        length: usize,
+        charset: CharacterSet,
+        symbols: Option<&str>,
+    ) -> Result<SecretString, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+        let primary_password = self
+            .primary_password
+            .as_ref()
+            .ok_or(Error::PasswordsLocked)?;
+        check_length(length)?;
+        check_symbols(symbols)?;
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let mut password = GeneratedPassword::new(&site_resolved, name, revision, length, charset);
+        password.set_symbols(symbols.map(String::from));
+        Ok(self.derive(primary_password, false, &password))
+    }
+
+    /// Adds a generated password or replaces an existing password.
+    ///
+    /// The `site` (site name), `name` (password name) and `revision` (password revision)
+    /// parameters identify a password, if a password with the same combination of these parameters
+    /// exists it will be replaced. While revisions are usually numerical, any string can be used.
+    /// The value `"1"` for revision is treated like an empty string.
+    ///
+    /// The `site` parameter will be normalized (`www.` prefix removed). If the site in question is
+    /// an alias, the password will be associated with the site it is an alias for.
+    ///
+    /// When the password is generated, it will have the length `length` and use the character sets
+    /// as determined by the `charset` parameter. It always contains at least one character of each
+    /// character type in `charset`, this doesn't affect its reproducibility. A length outside the
+    /// range [MIN_PASSWORD_LENGTH](constant.MIN_PASSWORD_LENGTH.html) to
+    /// [MAX_PASSWORD_LENGTH](constant.MAX_PASSWORD_LENGTH.html) will result in
+    /// [Error::InvalidLength](../error/enum.Error.html#variant.InvalidLength).
+    ///
+    /// If `symbols` is given, these characters are used instead of the default symbol set. They
+    /// have to be distinct ASCII punctuation characters, otherwise the call will result in
+    /// [Error::InvalidSymbols](../error/enum.Error.html#variant.InvalidSymbols).
+    ///
+    /// The password's modification time is set to the current time. When a password is replaced,
+    /// its creation time is kept.
+    pub fn set_generated(
+        &mut self,
         site: &str,
         name: &str,
         revision: &str,
         length: usize,
         charset: CharacterSet,
+        symbols: Option<&str>,
     ) -> Result<(), Error> {
         let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
         let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+        check_length(length)?;
+        check_symbols(symbols)?;
 
-        let site_resolved = self.storage.resolve_site(site, hmac_secret, key);
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
         self.storage
             .ensure_site_data(&site_resolved, hmac_secret, key)?;
 
-        self.storage.set_generated(
-            GeneratedPassword::new(&site_resolved, name, revision, length, charset),
-            hmac_secret,
-            key,
-        )?;
+        let mut password = GeneratedPassword::new(&site_resolved, name, revision, length, charset);
+        password.set_symbols(symbols.map(String::from));
+        let timestamp = self.timestamp();
+        password.set_created(
+            match self.storage.get_password(password.id(), hmac_secret, key) {
+                Ok(existing) => existing.created(),
+                Err(_) => Some(timestamp),
+            },
+        );
+        password.touch(timestamp);
+        self.storage.set_generated(password, hmac_secret, key)?;
         self.storage.flush()
     }
 
@@ -263,6 +1102,11 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
     ///
     /// The actual password value is supplied in the `password` parameter and will be encrypted
     /// along with all other data.
+    ///
+    /// When a stored password is replaced, its history is taken over. If keeping history is
+    /// enabled via [set_keep_history()](#method.set_keep_history), the previous value is added to
+    /// the history unless it is identical to the new one. The creation time of a replaced password
+    /// is kept as well while the modification time is set to the current time.
     pub fn set_stored(
         &mut self,
         site: &str,
@@ -273,18 +1117,356 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
         let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
         let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
 
-        let site_resolved = self.storage.resolve_site(site, hmac_secret, key);
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
         self.storage
             .ensure_site_data(&site_resolved, hmac_secret, key)?;
 
-        self.storage.set_stored(
-            StoredPassword::new(&site_resolved, name, revision, password),
+        let timestamp = self.timestamp();
+        let mut stored = StoredPassword::new(&site_resolved, name, revision, password);
+        stored.set_created(Some(timestamp));
+        if let Ok(existing) = self.storage.get_password(stored.id(), hmac_secret, key) {
+            stored.set_created(existing.created());
+            if let Password::Stored(mut existing) = existing {
+                stored.set_history(existing.take_history());
+                if self.keep_history
+                    && existing.password().expose_secret() != stored.password().expose_secret()
+                {
+                    stored.push_history(existing.password().clone(), timestamp, MAX_HISTORY_LENGTH);
+                }
+            }
+        }
+        stored.touch(timestamp);
+
+        self.storage.set_stored(stored, hmac_secret, key)?;
+        self.storage.flush()
+    }
+
+    /// Retrieves the previous values of a stored password, oldest first. The value `"1"` for
+    /// revision is treated like an empty string.
+    ///
+    /// The `site` parameter will be normalized (`www.` prefix removed). If the site in question is
+    /// an alias, the password of the site it is an alias for will be used.
+    ///
+    /// If the password does not exist, the call will result in
+    /// [Error::KeyMissing error](../error/enum.Error.html#variant.KeyMissing). Generated
+    /// passwords have no history and result in
+    /// [Error::NotStoredPassword error](../error/enum.Error.html#variant.NotStoredPassword).
+    pub fn history(
+        &self,
+        site: &str,
+        name: &str,
+        revision: &str,
+    ) -> Result<Vec<PasswordHistoryEntry>, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        match self.storage.get_password(
+            &PasswordId::new(&site_resolved, name, revision),
+            hmac_secret,
+            key,
+        )? {
+            Password::Stored(mut password) => Ok(password.take_history()),
+            _ => Err(Error::NotStoredPassword),
+        }
+    }
+
+    /// Restores a previous value of a stored password, `index` refers to the list returned by
+    /// [history()](#method.history). The restored value is removed from the history while the
+    /// current value is added to it, regardless of
+    /// [set_keep_history()](#method.set_keep_history), so that nothing is lost.
+    ///
+    /// If the password or the history entry does not exist, the call will result in
+    /// [Error::KeyMissing error](../error/enum.Error.html#variant.KeyMissing). Generated
+    /// passwords result in
+    /// [Error::NotStoredPassword error](../error/enum.Error.html#variant.NotStoredPassword).
+    pub fn restore_history(
+        &mut self,
+        site: &str,
+        name: &str,
+        revision: &str,
+        index: usize,
+    ) -> Result<(), Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let mut password = match self.storage.get_password(
+            &PasswordId::new(&site_resolved, name, revision),
+            hmac_secret,
+            key,
+        )? {
+            Password::Stored(password) => password,
+            _ => return Err(Error::NotStoredPassword),
+        };
+
+        let entry = password
+            .take_history_entry(index)
+            .ok_or(Error::KeyMissing)?;
+        let timestamp = self.timestamp();
+        password.replace_password(entry.password().clone(), timestamp, MAX_HISTORY_LENGTH);
+        password.touch(timestamp);
+        self.storage.set_stored(password, hmac_secret, key)?;
+        self.storage.flush()
+    }
+
+    /// Copies an existing password to a new identifier.
+    ///
+    /// For stored passwords the password value is copied, for generated passwords the generation
+    /// parameters (the generated value will differ as it depends on the identifier). Notes are
+    /// copied for both password types. Site names in both identifiers will be normalized and
+    /// aliases resolved.
+    ///
+    /// If the source password does not exist, the call will result in
+    /// [Error::KeyMissing error](../error/enum.Error.html#variant.KeyMissing). Unless `overwrite`
+    /// is `true`, an existing password at the destination will result in
+    /// [Error::PasswordExists error](../error/enum.Error.html#variant.PasswordExists).
+    pub fn duplicate(
+        &mut self,
+        source: &PasswordId,
+        destination: &PasswordId,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let source_site = self.storage.resolve_site(source.site(), hmac_secret, key)?;
+        let mut password = self.storage.get_password(
+            &PasswordId::new(&source_site, source.name(), source.revision()),
             hmac_secret,
             key,
         )?;
+
+        let destination_site = self
+            .storage
+            .resolve_site(destination.site(), hmac_secret, key)?;
+        let destination = PasswordId::new(
+            &destination_site,
+            destination.name(),
+            destination.revision(),
+        );
+        if !overwrite && self.storage.has_password(&destination, hmac_secret) {
+            return Err(Error::PasswordExists);
+        }
+
+        self.storage
+            .ensure_site_data(&destination_site, hmac_secret, key)?;
+        password.set_id(destination);
+        let timestamp = self.timestamp();
+        password.set_created(Some(timestamp));
+        password.touch(timestamp);
+        self.storage.set_password(password, hmac_secret, key)?;
+        self.storage.flush()
+    }
+
+    /// Moves an existing password to a new identifier within the same site.
+    ///
+    /// This works like [duplicate()](#method.duplicate) but removes the password under the old
+    /// identifier afterwards. Generated passwords keep their generation parameters, but their
+    /// value is derived from the user name and revision as well, so renaming a generated password
+    /// changes its value. Stored passwords keep their value. Notes are kept for both password
+    /// types.
+    ///
+    /// If the source password does not exist, the call will result in
+    /// [Error::KeyMissing error](../error/enum.Error.html#variant.KeyMissing). Unless `overwrite`
+    /// is `true`, an existing password at the destination will result in
+    /// [Error::PasswordExists error](../error/enum.Error.html#variant.PasswordExists).
+    pub fn rename(
+        &mut self,
+        site: &str,
+        name: &str,
+        revision: &str,
+        new_name: &str,
+        new_revision: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let source = PasswordId::new(&site_resolved, name, revision);
+        let mut password = self.storage.get_password(&source, hmac_secret, key)?;
+
+        let destination = PasswordId::new(&site_resolved, new_name, new_revision);
+        if destination == source {
+            return Ok(());
+        }
+        if !overwrite && self.storage.has_password(&destination, hmac_secret) {
+            return Err(Error::PasswordExists);
+        }
+
+        password.set_id(destination);
+        password.touch(self.timestamp());
+        self.storage.set_password(password, hmac_secret, key)?;
+        self.storage.remove_password(&source, hmac_secret)?;
+        self.storage.flush()
+    }
+
+    /// Moves an existing password to a different site, keeping its name and revision.
+    ///
+    /// Site names will be normalized and aliases resolved, site data for the destination site
+    /// is created if necessary. Stored passwords keep their value, generated passwords keep their
+    /// generation parameters, meaning that their value changes as it depends on the site name.
+    /// Notes are kept for both password types.
+    ///
+    /// If the source password does not exist, the call will result in
+    /// [Error::KeyMissing error](../error/enum.Error.html#variant.KeyMissing). Unless `overwrite`
+    /// is `true`, an existing password at the destination will result in
+    /// [Error::PasswordExists error](../error/enum.Error.html#variant.PasswordExists).
+    pub fn move_password(
+        &mut self,
+        site: &str,
+        name: &str,
+        revision: &str,
+        new_site: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let source = PasswordId::new(&site_resolved, name, revision);
+        let mut password = self.storage.get_password(&source, hmac_secret, key)?;
+
+        let new_site_resolved = self.storage.resolve_site(new_site, hmac_secret, key)?;
+        let destination = PasswordId::new(&new_site_resolved, name, revision);
+        if destination == source {
+            return Ok(());
+        }
+        if !overwrite && self.storage.has_password(&destination, hmac_secret) {
+            return Err(Error::PasswordExists);
+        }
+
+        self.storage
+            .ensure_site_data(&new_site_resolved, hmac_secret, key)?;
+        password.set_id(destination);
+        password.touch(self.timestamp());
+        self.storage.set_password(password, hmac_secret, key)?;
+        self.storage.remove_password(&source, hmac_secret)?;
         self.storage.flush()
     }
 
+    /// Merges sites and passwords into the storage, typically ones read from a backup.
+    ///
+    /// Alias entries are only added for sites that don't exist yet. Passwords are added under
+    /// the normalized and resolved site name, site data is created where necessary and policies
+    /// are taken over for sites that don't have one. If a password already exists, `overwrite` is
+    /// called to decide whether it should be replaced. Returns the number of passwords written.
+    pub fn import_entries<I, F>(&mut self, entries: I, mut overwrite: F) -> Result<usize, Error>
+    where
+        I: IntoIterator<Item = (Site, Vec<Password>)>,
+        F: FnMut(&Password) -> bool,
+    {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let mut imported = 0;
+        for (site, passwords) in entries {
+            let site_normalized = self.storage.normalize_site(site.name());
+            if let Some(alias) = site.alias() {
+                if self
+                    .storage
+                    .get_site(&site_normalized, hmac_secret, key)
+                    .is_err()
+                {
+                    let alias_resolved = self.storage.resolve_site(alias, hmac_secret, key)?;
+                    self.storage
+                        .set_alias(&site_normalized, &alias_resolved, hmac_secret, key)?;
+                }
+                continue;
+            }
+
+            let site_resolved = self
+                .storage
+                .resolve_site(&site_normalized, hmac_secret, key)?;
+            self.storage
+                .ensure_site_data(&site_resolved, hmac_secret, key)?;
+            if let Some(policy) = site.policy() {
+                let existing = self.storage.get_site(&site_resolved, hmac_secret, key)?;
+                if existing.policy().is_none() {
+                    self.storage.set_policy(
+                        &site_resolved,
+                        Some(policy.clone()),
+                        hmac_secret,
+                        key,
+                    )?;
+                }
+            }
+
+            for mut password in passwords {
+                let id = PasswordId::new(
+                    &site_resolved,
+                    password.id().name(),
+                    password.id().revision(),
+                );
+                if self.storage.has_password(&id, hmac_secret) && !overwrite(&password) {
+                    continue;
+                }
+                password.set_id(id);
+                self.storage.set_password(password, hmac_secret, key)?;
+                imported += 1;
+            }
+        }
+
+        self.storage.flush()?;
+        Ok(imported)
+    }
+
+    /// Converts all generated passwords in the legacy format of early PfP versions (type
+    /// `generated`) to a current password type, returning the number of passwords converted.
+    ///
+    /// By default, legacy passwords are converted into stored passwords holding their current
+    /// value, so the passwords themselves don't change. If `regenerate` is `true`, these are
+    /// converted into generated passwords with the same parameters instead. This produces a
+    /// different password value that has to be changed on the respective websites.
+    pub fn migrate_generated(&mut self, regenerate: bool) -> Result<usize, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+        let primary_password = self
+            .primary_password
+            .as_ref()
+            .ok_or(Error::PasswordsLocked)?;
+
+        let legacy = self
+            .storage
+            .list_sites(key)
+            .filter(|site| site.alias().is_none())
+            .flat_map(|site| {
+                self.storage
+                    .list_passwords(site.name(), hmac_secret, key)
+                    .filter_map(|password| match password {
+                        Password::LegacyGenerated(password) => Some(password),
+                        _ => None,
+                    })
+                    .collect::<Vec<GeneratedPassword>>()
+            })
+            .collect::<Vec<GeneratedPassword>>();
+
+        let count = legacy.len();
+        let timestamp = self.timestamp();
+        for password in legacy {
+            let mut migrated = if regenerate {
+                Password::Generated(password)
+            } else {
+                let value = crypto::derive_password_legacy(
+                    primary_password,
+                    &password.salt(),
+                    password.length(),
+                    password.charset(),
+                    password.symbols(),
+                );
+                Password::Stored(password.into_stored(value))
+            };
+            migrated.touch(timestamp);
+            self.storage.set_password(migrated, hmac_secret, key)?;
+        }
+
+        if count > 0 {
+            self.storage.flush()?;
+        }
+        Ok(count)
+    }
+
     /// Checks whether the password storage has a password with the given `site`, `name` and
     /// `revision` combination. The value `"1"` for revision is treated like an empty string.
     ///
@@ -294,7 +1476,7 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
         let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
         let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
 
-        let site_resolved = self.storage.resolve_site(site, hmac_secret, key);
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
         Ok(self.storage.has_password(
             &PasswordId::new(&site_resolved, name, revision),
             hmac_secret,
@@ -317,7 +1499,7 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
             .as_ref()
             .ok_or(Error::PasswordsLocked)?;
 
-        let site_resolved = self.storage.resolve_site(site, hmac_secret, key);
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
         let password = self.storage.get_password(
             &PasswordId::new(&site_resolved, name, revision),
             hmac_secret,
@@ -325,22 +1507,32 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
         )?;
 
         match password {
-            Password::Generated(password) => Ok(crypto::derive_password(
-                primary_password,
-                &password.salt(),
-                password.length(),
-                password.charset(),
-            )),
+            Password::Generated(password) => Ok(self.derive(primary_password, false, &password)),
+            Password::LegacyGenerated(password) => {
+                Ok(self.derive(primary_password, true, &password))
+            }
             Password::Stored(password) => Ok(password.password().clone()),
         }
     }
 
     /// Generates a human-readable recovery code for a stored password. With the correct primary
     /// password, the password can be decoded back from the recovery code.
+    ///
+    /// Recovery codes are always encrypted with a key derived using the default parameters, so
+    /// that they can be decoded independently of the storage.
     pub fn get_recovery_code(&self, password: &StoredPassword) -> Result<String, Error> {
         let salt = self.storage.get_salt()?;
         let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
-        recovery_codes::generate(password.password(), &salt, key)
+        if self.storage.get_kdf_params()? == KdfParams::default() {
+            recovery_codes::generate(password.password(), &salt, key)
+        } else {
+            let primary_password = self
+                .primary_password
+                .as_ref()
+                .ok_or(Error::PasswordsLocked)?;
+            let key = get_encryption_key(primary_password, &salt);
+            recovery_codes::generate(password.password(), &salt, &key)
+        }
     }
 
     /// Decodes a recovery code into a password value. Any invalid characters in the recovery code
@@ -364,7 +1556,7 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
         let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
         let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
 
-        let site_resolved = self.storage.resolve_site(site, hmac_secret, key);
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
         let password = self.storage.get_password(
             &PasswordId::new(&site_resolved, name, revision),
             hmac_secret,
@@ -387,17 +1579,62 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
         let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
         let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
 
-        let site_resolved = self.storage.resolve_site(site, hmac_secret, key);
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
         let mut password = self.storage.get_password(
             &PasswordId::new(&site_resolved, name, revision),
             hmac_secret,
             key,
         )?;
         password.set_notes(notes);
+        password.touch(self.timestamp());
         self.storage.set_password(password, hmac_secret, key)?;
         self.storage.flush()
     }
 
+    /// Adds and removes tags of the password with the given `site`, `name` and `revision`
+    /// combination, returning the resulting tags sorted alphabetically. Adding a tag the password
+    /// already has or removing one it doesn't have is not an error.
+    ///
+    /// Empty tags will result in
+    /// [Error::InvalidTag error](../error/enum.Error.html#variant.InvalidTag). This can also fail
+    /// if passwords are locked or the password doesn't exist.
+    pub fn update_tags(
+        &mut self,
+        site: &str,
+        name: &str,
+        revision: &str,
+        add: &[String],
+        remove: &[String],
+    ) -> Result<Vec<String>, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+        if add.iter().any(|tag| tag.is_empty()) {
+            return Err(Error::InvalidTag);
+        }
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let mut password = self.storage.get_password(
+            &PasswordId::new(&site_resolved, name, revision),
+            hmac_secret,
+            key,
+        )?;
+
+        let previous = password.tags().to_vec();
+        let mut tags = previous.clone();
+        tags.retain(|tag| !remove.contains(tag));
+        tags.extend(add.iter().cloned());
+        password.set_tags(tags);
+        let tags = password.tags().to_vec();
+        if tags == previous {
+            return Ok(tags);
+        }
+
+        password.touch(self.timestamp());
+        self.storage.set_password(password, hmac_secret, key)?;
+        self.storage.flush()?;
+        Ok(tags)
+    }
+
     /// Removes the password with the given `site`, `name` and `revision` combination. The value
     /// `"1"` for revision is treated like an empty string.
     ///
@@ -410,7 +1647,7 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
         let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
         let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
 
-        let site_resolved = self.storage.resolve_site(site, hmac_secret, key);
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
         self.storage.remove_password(
             &PasswordId::new(&site_resolved, name, revision),
             hmac_secret,
@@ -422,12 +1659,18 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
     /// parameter is a password name filter and can contain wildcards (see
     /// [wildmatch crate](https://docs.rs/wildmatch/latest/wildmatch/)). Passing `"*"` for `name`
     /// will list all passwords for the site.
+    ///
+    /// If site aliases form a cycle, the passwords of the site itself are listed. Use
+    /// [try_list()](#method.try_list) to detect this situation.
     pub fn list(&self, site: &str, name: &str) -> impl Iterator<Item = Password> + '_ {
         assert!(self.unlocked());
 
         let hmac_secret = self.hmac_secret.as_ref().unwrap();
         let key = self.key.as_ref().unwrap();
-        let site_resolved = self.storage.resolve_site(site, hmac_secret, key);
+        let site_resolved = self
+            .storage
+            .resolve_site(site, hmac_secret, key)
+            .unwrap_or_else(|_| self.storage.normalize_site(site));
         let matcher = wildmatch::WildMatch::new(name);
         self.storage
             .list_passwords(&site_resolved, hmac_secret, key)
@@ -436,58 +1679,476 @@ impl<IO: storage_io::StorageIO> Passwords<IO> {
             })
     }
 
-    /// Iterates over existing site entries. The `site` parameter is a site name filter and can
-    /// contain wildcards (see [wildmatch crate](https://docs.rs/wildmatch/latest/wildmatch/)).
-    /// Passing `"*"` for `site` will list all known sites.
+    /// Iterates over all passwords in the storage, regardless of the site they belong to.
     ///
-    /// This function will also return aliased sites, both if the site name matches the filter and
-    /// if the name of the site they are aliases for matches it.
-    pub fn list_sites(&self, site: &str) -> impl Iterator<Item = Site> + '_ {
+    /// This goes over the storage entries once, without listing the sites first. Passwords are
+    /// decrypted lazily and entries that cannot be decrypted are skipped, like with
+    /// [list()](#method.list). The order of the passwords is unspecified.
+    pub fn list_all(&self) -> impl Iterator<Item = Password> + '_ {
         assert!(self.unlocked());
 
         let key = self.key.as_ref().unwrap();
+        self.storage.list_all_passwords(key)
+    }
 
-        let matcher = wildmatch::WildMatch::new(site);
-        self.storage.list_sites(key).filter(move |site| {
-            return match site.alias() {
-                Some(alias) => matcher.matches(alias),
-                None => false,
-            } || matcher.matches(site.name());
+    /// Iterates over the passwords of all sites matching a site name filter, yielding each
+    /// password along with its site. Both `site` and `name` can contain wildcards (see
+    /// [wildmatch crate](https://docs.rs/wildmatch/latest/wildmatch/)).
+    ///
+    /// Site aliases matching the filter are resolved, so the passwords of the site they are an
+    /// alias for are included. Sites are processed in alphabetical order, each site only once.
+    /// Aliases that are part of a cycle are skipped.
+    pub fn find(&self, site: &str, name: &str) -> impl Iterator<Item = (Site, Password)> + '_ {
+        assert!(self.unlocked());
+
+        let hmac_secret = self.hmac_secret.as_ref().unwrap();
+        let key = self.key.as_ref().unwrap();
+        let site_names = self
+            .list_sites(site)
+            .filter_map(|site| match site.alias() {
+                Some(_) => self
+                    .storage
+                    .resolve_site(site.name(), hmac_secret, key)
+                    .ok(),
+                None => Some(site.name().to_string()),
+            })
+            .collect::<BTreeSet<String>>();
+        let matcher = wildmatch::WildMatch::new(name);
+        site_names.into_iter().flat_map(move |site_name| {
+            let site = self
+                .storage
+                .get_site(&site_name, hmac_secret, key)
+                .unwrap_or_else(|_| Site::new(&site_name, None));
+            let matcher = matcher.clone();
+            self.storage
+                .list_passwords(&site_name, hmac_secret, key)
+                .filter(move |password| matcher.matches(password.id().name()))
+                .map(move |password| (site.clone(), password))
         })
     }
-}
 
-impl<IO: storage_io::StorageIO> Drop for Passwords<IO> {
-    fn drop(&mut self) {
-        self.lock();
+    /// Lists the passwords for a given site like [list()](#method.list) does but fails on storage
+    /// entries that cannot be read.
+    ///
+    /// While [list()](#method.list) silently skips entries that fail to decrypt or parse, this
+    /// method returns the first such error, e.g.
+    /// [Error::DecryptionFailure](../error/enum.Error.html#variant.DecryptionFailure) or
+    /// [Error::InvalidJson](../error/enum.Error.html#variant.InvalidJson). This allows detecting
+    /// storage corruption.
+    pub fn try_list(&self, site: &str, name: &str) -> Result<Vec<Password>, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let matcher = wildmatch::WildMatch::new(name);
+        Ok(self
+            .storage
+            .try_list_passwords(&site_resolved, hmac_secret, key)?
+            .into_iter()
+            .filter(|password| matcher.matches(password.id().name()))
+            .collect())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Collects all data of a site: the site entry, the sites that are aliases for it and all its
+    /// passwords with their values.
+    ///
+    /// The `site` parameter will be normalized (`www.` prefix removed). If the site in question is
+    /// an alias, the data of the site it is an alias for is returned. If the site doesn't exist,
+    /// [Error::KeyMissing](../error/enum.Error.html#variant.KeyMissing) is returned.
+    pub fn export_site(&self, site: &str) -> Result<SiteExport, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
 
-    use secrecy::ExposeSecret;
-    use std::collections::HashMap;
-    use storage_io::MemoryIO;
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let site = self.storage.get_site(&site_resolved, hmac_secret, key)?;
 
-    const PRIMARY_PASSWORD: &str = "foobar";
+        let mut aliases = self
+            .storage
+            .list_sites(key)
+            .filter(|entry| entry.alias() == Some(site.name()))
+            .map(|entry| entry.name().to_string())
+            .collect::<Vec<String>>();
+        aliases.sort();
 
-    fn primary_pass() -> SecretString {
-        SecretString::new(PRIMARY_PASSWORD.to_owned())
+        let mut passwords = Vec::new();
+        for password in self
+            .storage
+            .list_passwords(&site_resolved, hmac_secret, key)
+        {
+            let id = password.id();
+            let value = self.get(id.site(), id.name(), id.revision())?;
+            passwords.push((password, value));
+        }
+        passwords.sort_by(|(a, _), (b, _)| {
+            (a.id().name(), a.id().revision()).cmp(&(b.id().name(), b.id().revision()))
+        });
+
+        Ok(SiteExport {
+            site,
+            aliases,
+            passwords,
+        })
     }
 
-    fn empty_data() -> HashMap<String, String> {
-        HashMap::from([
-            ("salt", "Y2Jh"),
-            (
-                "hmac-secret",
-                "YWJjZGVmZ2hpamts_Nosk0g9vPYtLPn9QzyFXLQ/1ZuAHVw==",
-            ),
-        ])
-        .iter()
-        .map(|(key, value)| (key.to_string(), value.to_string()))
-        .collect()
+    /// Retrieves all sites along with their passwords, sorted by site name. Sites that are aliases
+    /// are included with an empty password list. This is the format expected by
+    /// [import_entries()](#method.import_entries).
+    ///
+    /// This produces [Error::PasswordsLocked](../error/enum.Error.html#variant.PasswordsLocked)
+    /// if the passwords storage is locked.
+    pub fn export_all(&self) -> Result<Vec<(Site, Vec<Password>)>, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let mut sites = self.storage.list_sites(key).collect::<Vec<Site>>();
+        sites.sort_by(|a, b| a.name().cmp(b.name()));
+
+        Ok(sites
+            .into_iter()
+            .map(|site| {
+                let passwords = if site.alias().is_none() {
+                    self.storage
+                        .list_passwords(site.name(), hmac_secret, key)
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                (site, passwords)
+            })
+            .collect())
+    }
+
+    /// Summarizes the storage contents: number of sites, aliases and passwords. Only site entries
+    /// and password metadata are decrypted, no password values are derived.
+    ///
+    /// This produces [Error::PasswordsLocked](../error/enum.Error.html#variant.PasswordsLocked)
+    /// if the passwords storage is locked.
+    pub fn statistics(&self) -> Result<Statistics, Error> {
+        if !self.unlocked() {
+            return Err(Error::PasswordsLocked);
+        }
+
+        let mut result = Statistics::default();
+        for site in self.list_sites("*") {
+            if site.alias().is_some() {
+                result.aliases += 1;
+                continue;
+            }
+
+            result.sites += 1;
+            for password in self.list(site.name(), "*") {
+                match &password {
+                    Password::Generated(generated) | Password::LegacyGenerated(generated) => {
+                        result.generated += 1;
+                        *result.lengths.entry(generated.length()).or_insert(0) += 1;
+                    }
+                    Password::Stored(_) => result.stored += 1,
+                }
+                if !password.notes().expose_secret().is_empty() {
+                    result.with_notes += 1;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Finds passwords sharing the same value. Only stored passwords are considered unless
+    /// `include_generated` is `true`, in which case generated passwords are derived as well.
+    ///
+    /// Values are compared by their HMAC digests rather than directly, the decrypted values are
+    /// discarded right away. Only groups with more than one password are returned, each sorted
+    /// by site, name and revision.
+    ///
+    /// This produces [Error::PasswordsLocked](../error/enum.Error.html#variant.PasswordsLocked)
+    /// if the passwords storage is locked.
+    pub fn find_duplicates(&self, include_generated: bool) -> Result<Vec<Vec<PasswordId>>, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let mut groups: HashMap<Vec<u8>, Vec<PasswordId>> = HashMap::new();
+        for (_, passwords) in self.export_all()? {
+            for password in passwords {
+                if !include_generated && !matches!(password, Password::Stored(_)) {
+                    continue;
+                }
+
+                let id = password.id();
+                let value = self.get(id.site(), id.name(), id.revision())?;
+                let digest = crypto::get_secret_digest(hmac_secret, &value);
+                groups
+                    .entry(digest.into_bytes())
+                    .or_default()
+                    .push(id.clone());
+            }
+        }
+
+        fn sort_key(id: &PasswordId) -> (&str, &str, &str) {
+            (id.site(), id.name(), id.revision())
+        }
+
+        let mut result = groups
+            .into_values()
+            .filter(|ids| ids.len() > 1)
+            .map(|mut ids| {
+                ids.sort_by(|a, b| sort_key(a).cmp(&sort_key(b)));
+                ids
+            })
+            .collect::<Vec<_>>();
+        result.sort_by(|a, b| sort_key(&a[0]).cmp(&sort_key(&b[0])));
+        Ok(result)
+    }
+
+    /// Serializes the entire storage contents for a backup. Data is not decrypted, the result is
+    /// in the format of the storage file and of backups created by the PfP browser extension.
+    pub fn backup(&self) -> Result<String, Error> {
+        storage_io::FileIO::serialize(self.storage.io())
+    }
+
+    /// Checks whether all site and password entries in the storage can be decrypted and parsed,
+    /// returning the number of entries checked. Nothing is modified.
+    ///
+    /// Unlike other methods, this doesn't stop at the first entry that cannot be read. If there
+    /// are such entries, the call will result in
+    /// [Error::UnreadableEntries](../error/enum.Error.html#variant.UnreadableEntries) listing the
+    /// storage keys of all of them.
+    pub fn verify_entries(&self) -> Result<usize, Error> {
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let (count, failures) = self.storage.verify_entries(key);
+        if failures.is_empty() {
+            Ok(count)
+        } else {
+            Err(Error::UnreadableEntries { failures })
+        }
+    }
+
+    /// Returns the decrypted contents of the storage entry with the given key, e.g.
+    /// `site:<digest>:<digest>`, without parsing it.
+    ///
+    /// This is meant for diagnosing storage entries that fail to parse and is only available with
+    /// the `test-util` feature. It is unstable and can change or go away in any release.
+    #[cfg(feature = "test-util")]
+    pub fn raw_entry(&self, key: &str) -> Result<SecretVec<u8>, Error> {
+        let key_secret = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+        self.storage.get_raw(key, key_secret)
+    }
+
+    /// Lists the revisions present for a given site (site aliases will be resolved) and password
+    /// name. Unlike with [list()](#method.list), `name` is matched exactly and isn't a wildcard
+    /// pattern.
+    ///
+    /// Revisions are returned in the form they are stored in, meaning that revision `"1"` is
+    /// represented by an empty string. The default revision is listed first, other revisions
+    /// follow in alphabetical order.
+    pub fn revisions(&self, site: &str, name: &str) -> Result<Vec<String>, Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let mut revisions = self
+            .storage
+            .list_passwords(&site_resolved, hmac_secret, key)
+            .filter(|password| password.id().name() == name)
+            .map(|password| password.id().revision().to_string())
+            .collect::<Vec<String>>();
+        revisions.sort();
+        Ok(revisions)
+    }
+
+    /// Determines the next numerical revision for a given site (site aliases will be resolved) and
+    /// password name. The default revision counts as revision `"1"`, non-numerical revisions are
+    /// ignored. If there are no numerical revisions yet, `"1"` is returned.
+    pub fn next_revision(&self, site: &str, name: &str) -> Result<String, Error> {
+        let latest = self
+            .revisions(site, name)?
+            .iter()
+            .filter_map(|revision| revision_number(revision))
+            .max();
+        Ok(latest.map_or(1, |latest| latest + 1).to_string())
+    }
+
+    /// Replaces a generated password by a new one at the next revision, changing the password
+    /// value. The new password is added at the revision returned by
+    /// [next_revision()](#method.next_revision) and has the same length, character types, symbols
+    /// and notes as the password at the latest numerical revision. If `replace` is `true`, the old password
+    /// is removed. Returns the revision of the new password.
+    ///
+    /// If no password with a numerical revision exists, the call will result in
+    /// [Error::KeyMissing](../error/enum.Error.html#variant.KeyMissing). Stored passwords cannot
+    /// be rotated and will result in
+    /// [Error::NotGeneratedPassword](../error/enum.Error.html#variant.NotGeneratedPassword).
+    pub fn rotate(&mut self, site: &str, name: &str, replace: bool) -> Result<String, Error> {
+        let current = self
+            .revisions(site, name)?
+            .into_iter()
+            .filter_map(|revision| revision_number(&revision).map(|number| (number, revision)))
+            .max()
+            .map(|(_, revision)| revision)
+            .ok_or(Error::KeyMissing)?;
+        let next = self.next_revision(site, name)?;
+
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let current_id = PasswordId::new(&site_resolved, name, &current);
+        let password = match self.storage.get_password(&current_id, hmac_secret, key)? {
+            Password::Generated(password) | Password::LegacyGenerated(password) => password,
+            Password::Stored(_) => return Err(Error::NotGeneratedPassword),
+        };
+
+        let mut rotated = GeneratedPassword::new(
+            &site_resolved,
+            name,
+            &next,
+            password.length(),
+            password.charset(),
+        );
+        rotated.set_symbols(password.symbols().map(String::from));
+        rotated.set_notes(password.notes().clone());
+        rotated.set_tags(password.tags().to_vec());
+        let timestamp = self.timestamp();
+        rotated.set_created(Some(timestamp));
+        rotated.touch(timestamp);
+        self.storage.set_generated(rotated, hmac_secret, key)?;
+        if replace {
+            self.storage.remove_password(&current_id, hmac_secret)?;
+        }
+        self.storage.flush()?;
+        Ok(next)
+    }
+
+    /// Changes the length of a generated password, keeping its character types, symbols and notes.
+    /// This changes the password value. Passwords in the legacy format are converted to the
+    /// current generated password type.
+    ///
+    /// If the password does not exist, the call will result in
+    /// [Error::KeyMissing](../error/enum.Error.html#variant.KeyMissing). Stored passwords will
+    /// result in
+    /// [Error::NotGeneratedPassword](../error/enum.Error.html#variant.NotGeneratedPassword).
+    pub fn change_length(
+        &mut self,
+        site: &str,
+        name: &str,
+        revision: &str,
+        length: usize,
+    ) -> Result<(), Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+        check_length(length)?;
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let id = PasswordId::new(&site_resolved, name, revision);
+        let password = match self.storage.get_password(&id, hmac_secret, key)? {
+            Password::Generated(password) | Password::LegacyGenerated(password) => password,
+            Password::Stored(_) => return Err(Error::NotGeneratedPassword),
+        };
+
+        let mut changed =
+            GeneratedPassword::new(&site_resolved, name, revision, length, password.charset());
+        changed.set_symbols(password.symbols().map(String::from));
+        changed.set_notes(password.notes().clone());
+        changed.set_tags(password.tags().to_vec());
+        changed.set_created(password.created());
+        changed.touch(self.timestamp());
+        self.storage.set_generated(changed, hmac_secret, key)?;
+        self.storage.flush()
+    }
+
+    /// Changes the character types allowed in a generated password, keeping its length, symbols
+    /// and notes. This changes the password value. Passwords in the legacy format are converted to
+    /// the current generated password type.
+    ///
+    /// An empty `charset` will result in
+    /// [Error::EmptyCharacterSet](../error/enum.Error.html#variant.EmptyCharacterSet). If the
+    /// password does not exist, the call will result in
+    /// [Error::KeyMissing](../error/enum.Error.html#variant.KeyMissing). Stored passwords will
+    /// result in
+    /// [Error::NotGeneratedPassword](../error/enum.Error.html#variant.NotGeneratedPassword).
+    pub fn set_charset(
+        &mut self,
+        site: &str,
+        name: &str,
+        revision: &str,
+        charset: CharacterSet,
+    ) -> Result<(), Error> {
+        let hmac_secret = self.hmac_secret.as_ref().ok_or(Error::PasswordsLocked)?;
+        let key = self.key.as_ref().ok_or(Error::PasswordsLocked)?;
+        if charset.is_empty() {
+            return Err(Error::EmptyCharacterSet);
+        }
+
+        let site_resolved = self.storage.resolve_site(site, hmac_secret, key)?;
+        let id = PasswordId::new(&site_resolved, name, revision);
+        let password = match self.storage.get_password(&id, hmac_secret, key)? {
+            Password::Generated(password) | Password::LegacyGenerated(password) => password,
+            Password::Stored(_) => return Err(Error::NotGeneratedPassword),
+        };
+
+        let mut changed =
+            GeneratedPassword::new(&site_resolved, name, revision, password.length(), charset);
+        changed.set_symbols(password.symbols().map(String::from));
+        changed.set_notes(password.notes().clone());
+        changed.set_tags(password.tags().to_vec());
+        changed.set_created(password.created());
+        changed.touch(self.timestamp());
+        self.storage.set_generated(changed, hmac_secret, key)?;
+        self.storage.flush()
+    }
+
+    /// Iterates over existing site entries. The `site` parameter is a site name filter and can
+    /// contain wildcards (see [wildmatch crate](https://docs.rs/wildmatch/latest/wildmatch/)).
+    /// Passing `"*"` for `site` will list all known sites.
+    ///
+    /// This function will also return aliased sites, both if the site name matches the filter and
+    /// if the name of the site they are aliases for matches it.
+    pub fn list_sites(&self, site: &str) -> impl Iterator<Item = Site> + '_ {
+        assert!(self.unlocked());
+
+        let key = self.key.as_ref().unwrap();
+
+        let matcher = wildmatch::WildMatch::new(site);
+        self.storage.list_sites(key).filter(move |site| {
+            return match site.alias() {
+                Some(alias) => matcher.matches(alias),
+                None => false,
+            } || matcher.matches(site.name());
+        })
+    }
+}
+
+impl<IO: storage_io::StorageIO> Drop for Passwords<IO> {
+    fn drop(&mut self) {
+        // Errors can only be reported by close(), backends that already closed won't write again
+        let _ = self.storage.io_mut().close();
+        self.lock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use secrecy::ExposeSecret;
+    use std::collections::HashMap;
+    use storage_io::MemoryIO;
+
+    const PRIMARY_PASSWORD: &str = "foobar";
+
+    fn primary_pass() -> SecretString {
+        SecretString::new(PRIMARY_PASSWORD.to_owned())
+    }
+
+    fn empty_data() -> HashMap<String, String> {
+        HashMap::from([
+            ("salt", "Y2Jh"),
+            (
+                "hmac-secret",
+                "YWJjZGVmZ2hpamts_Nosk0g9vPYtLPn9QzyFXLQ/1ZuAHVw==",
+            ),
+        ])
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
     }
 
     fn default_data() -> HashMap<String, String> {
@@ -530,6 +2191,13 @@ mod tests {
     mod initialization {
         use super::*;
 
+        #[test]
+        fn send() {
+            fn assert_send<T: Send>() {}
+            assert_send::<Passwords<MemoryIO>>();
+            assert_send::<Passwords<storage_io::FileIO>>();
+        }
+
         #[test]
         fn read_empty_data() {
             let io = MemoryIO::new(HashMap::new());
@@ -563,6 +2231,25 @@ mod tests {
                 .unlock(primary_pass())
                 .expect("Passwords should unlock");
         }
+
+        #[test]
+        fn state() {
+            let mut passwords = Passwords::new(MemoryIO::new(HashMap::new()));
+            assert_eq!(passwords.state(), StorageState::Uninitialized);
+
+            passwords
+                .reset(primary_pass())
+                .expect("Reset should succeed");
+            assert_eq!(passwords.state(), StorageState::Unlocked);
+
+            passwords.lock();
+            assert_eq!(passwords.state(), StorageState::Locked);
+
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+            assert_eq!(passwords.state(), StorageState::Unlocked);
+        }
     }
 
     mod reset {
@@ -698,399 +2385,2893 @@ mod tests {
                 PRIMARY_PASSWORD
             );
         }
-    }
-
-    mod retrieval {
-        use super::*;
-
-        fn list_sites(passwords: &Passwords<MemoryIO>, site: &str) -> Vec<String> {
-            let mut vec = passwords
-                .list_sites(site)
-                .map(|site| site.name().to_string())
-                .collect::<Vec<String>>();
-            vec.sort();
-            vec
-        }
-
-        fn list_passwords(passwords: &Passwords<MemoryIO>, site: &str, name: &str) -> Vec<String> {
-            let mut vec = passwords
-                .list(site, name)
-                .map(password_name)
-                .collect::<Vec<String>>();
-            vec.sort();
-            vec
-        }
 
         #[test]
-        fn list_sites_wildcards() {
+        fn change_primary() {
             let io = MemoryIO::new(default_data());
             let mut passwords = Passwords::new(io);
+            let new_primary = || SecretString::new("new primary".to_owned());
+
+            assert!(matches!(
+                passwords
+                    .change_primary(new_primary())
+                    .expect_err("Changing primary password should fail"),
+                Error::PasswordsLocked
+            ));
+
             passwords
                 .unlock(primary_pass())
                 .expect("Passwords should unlock");
+            let sites = passwords.list_sites("*").count();
+            passwords
+                .change_primary(new_primary())
+                .expect("Changing primary password should succeed");
+            assert_eq!(passwords.list_sites("*").count(), sites);
+
+            passwords.lock();
+            assert!(matches!(
+                passwords
+                    .unlock(primary_pass())
+                    .expect_err("Old primary password should fail"),
+                Error::DecryptionFailure
+            ));
+            passwords
+                .unlock(new_primary())
+                .expect("New primary password should unlock");
 
+            assert_eq!(passwords.list_sites("*").count(), sites);
             assert_eq!(
-                list_sites(&passwords, "*"),
-                vec!["example.com", "example.info", "example.org"]
+                passwords
+                    .get_alias("example.org")
+                    .expect("Alias should be present"),
+                "example.com"
             );
             assert_eq!(
-                list_sites(&passwords, "ex*"),
-                vec!["example.com", "example.info", "example.org"]
+                passwords
+                    .get("example.com", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
             );
             assert_eq!(
-                list_sites(&passwords, "*.com"),
-                vec!["example.com", "example.org"]
+                passwords
+                    .get_notes("example.info", "test", "yet another")
+                    .expect("Getting notes should succeed")
+                    .expose_secret(),
+                "nothing here"
             );
-            assert_eq!(list_sites(&passwords, "*am*i*"), vec!["example.info"]);
-            assert_eq!(list_sites(&passwords, "example.info"), vec!["example.info"]);
-            assert_eq!(list_sites(&passwords, "example.net").len(), 0);
-            assert_eq!(list_sites(&passwords, "blub*").len(), 0);
         }
 
         #[test]
-        fn list_passwords_wildcards() {
+        fn kdf_params() {
             let io = MemoryIO::new(default_data());
             let mut passwords = Passwords::new(io);
-            passwords
-                .unlock(primary_pass())
-                .expect("Passwords should unlock");
+            let kdf_params = KdfParams::scrypt(1 << 10).expect("Parameters should be valid");
 
             assert_eq!(
-                list_passwords(&passwords, "example.com", "*"),
-                vec!["blabber", "blubber"]
+                passwords
+                    .kdf_params()
+                    .expect("Getting parameters should succeed"),
+                KdfParams::default()
             );
+            assert!(matches!(
+                passwords
+                    .set_kdf_params(kdf_params)
+                    .expect_err("Changing parameters should fail"),
+                Error::PasswordsLocked
+            ));
+
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+            let generated = passwords
+                .get("example.com", "blubber", "")
+                .expect("Retrieval should succeed");
+            passwords
+                .set_kdf_params(kdf_params)
+                .expect("Changing parameters should succeed");
             assert_eq!(
-                list_passwords(&passwords, "www.example.com", "*"),
-                vec!["blabber", "blubber"]
+                passwords
+                    .kdf_params()
+                    .expect("Getting parameters should succeed"),
+                kdf_params
             );
             assert_eq!(
-                list_passwords(&passwords, "example.org", "*"),
-                vec!["blabber", "blubber"]
+                passwords.storage.io().file_data().get("kdf-params"),
+                Some(&r#"{"algorithm":"scrypt","cost":1024}"#.to_owned())
             );
+
+            let mut reopened =
+                Passwords::new(MemoryIO::new(passwords.storage.io().file_data().clone()));
+            reopened
+                .unlock(primary_pass())
+                .expect("Passwords should unlock with new parameters");
             assert_eq!(
-                list_passwords(&passwords, "www.example.org", "*"),
-                vec!["blabber", "blubber"]
+                reopened
+                    .get("example.com", "blubber", "")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                generated.expose_secret()
             );
             assert_eq!(
-                list_passwords(&passwords, "example.info", "*"),
-                vec!["test"]
+                reopened
+                    .get("example.com", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
             );
+
+            // Recovery codes don't depend on storage parameters
+            let code = reopened
+                .get_recovery_code(&StoredPassword::new(
+                    "example.com",
+                    "blabber",
+                    "2",
+                    SecretString::new("asdf".to_owned()),
+                ))
+                .expect("Generating recovery code should succeed");
             assert_eq!(
-                list_passwords(&passwords, "www.example.info", "*"),
-                vec!["test"]
+                recovery_codes::decode(&code, &primary_pass())
+                    .expect("Decoding recovery code should succeed")
+                    .expose_secret(),
+                "asdf"
             );
 
+            reopened
+                .set_kdf_params(KdfParams::default())
+                .expect("Changing parameters should succeed");
+            assert!(!reopened.storage.io().file_data().contains_key("kdf-params"));
+        }
+
+        #[test]
+        fn legacy_kdf() {
+            // default_data() was encrypted with a key derived via scrypt and has no kdf-params
+            let mut passwords = Passwords::new(MemoryIO::new(default_data()));
             assert_eq!(
-                list_passwords(&passwords, "example.com", "b*"),
-                vec!["blabber", "blubber"]
+                passwords
+                    .kdf_params()
+                    .expect("Getting parameters should succeed"),
+                KdfParams::default()
             );
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
             assert_eq!(
-                list_passwords(&passwords, "www.example.com", "b*"),
-                vec!["blabber", "blubber"]
+                passwords
+                    .get("example.com", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
             );
+        }
+
+        #[test]
+        fn argon2id() {
+            let kdf_params = KdfParams::argon2id(8192, 1, 1).expect("Parameters should be valid");
+
+            let mut passwords = Passwords::new(MemoryIO::new(HashMap::new()));
+            assert!(matches!(
+                passwords
+                    .reset_with_kdf_params(primary_pass(), KdfParams::Scrypt { cost: 5 })
+                    .expect_err("Reset should fail"),
+                Error::InvalidKdfParams
+            ));
+            passwords
+                .reset_with_kdf_params(primary_pass(), kdf_params)
+                .expect("Reset should succeed");
+            passwords
+                .set_stored(
+                    "example.com",
+                    "blabber",
+                    "2",
+                    SecretString::new("asdf".to_owned()),
+                )
+                .expect("Adding password should succeed");
             assert_eq!(
-                list_passwords(&passwords, "example.org", "b*"),
-                vec!["blabber", "blubber"]
+                passwords.storage.io().file_data().get("kdf-params"),
+                Some(
+                    &r#"{"algorithm":"argon2id","memory":8192,"iterations":1,"parallelism":1}"#
+                        .to_owned()
+                )
             );
+
+            let mut reopened =
+                Passwords::new(MemoryIO::new(passwords.storage.io().file_data().clone()));
             assert_eq!(
-                list_passwords(&passwords, "www.example.org", "b*"),
-                vec!["blabber", "blubber"]
+                reopened
+                    .kdf_params()
+                    .expect("Getting parameters should succeed"),
+                kdf_params
             );
-            assert_eq!(list_passwords(&passwords, "example.info", "b*").len(), 0);
+            assert!(matches!(
+                reopened
+                    .unlock(SecretString::new("wrong".to_owned()))
+                    .expect_err("Wrong primary password should fail"),
+                Error::DecryptionFailure
+            ));
+            reopened
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
             assert_eq!(
-                list_passwords(&passwords, "www.example.info", "b*").len(),
+                reopened
+                    .get("example.com", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
+            );
+        }
+    }
+
+    mod verification {
+        use super::*;
+
+        #[test]
+        fn verify_primary() {
+            let mut passwords = Passwords::new(MemoryIO::new(default_data()));
+            assert!(matches!(
+                passwords.verify_primary(&primary_pass()),
+                Err(Error::PasswordsLocked)
+            ));
+
+            passwords
+                .unlock(primary_pass())
+                .expect("Unlock should succeed");
+            assert!(passwords
+                .verify_primary(&primary_pass())
+                .expect("Verification should succeed"));
+            assert!(!passwords
+                .verify_primary(&SecretString::new("foobaz".to_owned()))
+                .expect("Verification should succeed"));
+        }
+
+        #[test]
+        fn verify_entries() {
+            use storage_io::StorageIO;
+
+            let mut passwords = Passwords::new(MemoryIO::new(default_data()));
+            assert!(matches!(
+                passwords.verify_entries(),
+                Err(Error::PasswordsLocked)
+            ));
+
+            passwords
+                .unlock(primary_pass())
+                .expect("Unlock should succeed");
+            assert_eq!(
+                passwords
+                    .verify_entries()
+                    .expect("Verification should succeed"),
+                6
+            );
+
+            let invalid_json = crypto::encrypt_data(
+                &SecretVec::new(b"{}".to_vec()),
+                passwords.key.as_ref().expect("Should be unlocked"),
+            );
+            passwords
+                .io_mut()
+                .set("site:abc:def".to_string(), invalid_json);
+            passwords
+                .io_mut()
+                .set("site:abc".to_string(), "garbage".to_string());
+            match passwords
+                .verify_entries()
+                .expect_err("Verification should fail")
+            {
+                Error::UnreadableEntries { failures } => {
+                    assert_eq!(failures.len(), 2);
+                    assert_eq!(failures[0].0, "site:abc");
+                    assert!(matches!(failures[0].1, Error::InvalidCiphertext));
+                    assert_eq!(failures[1].0, "site:abc:def");
+                    assert!(matches!(failures[1].1, Error::InvalidJson { .. }));
+                }
+                error => panic!("Unexpected error {:?}", error),
+            }
+        }
+
+        #[test]
+        fn verify_password() {
+            let passwords = Passwords::new(MemoryIO::new(HashMap::new()));
+            assert!(matches!(
+                passwords.verify_password(&primary_pass()),
+                Err(Error::StorageNotInitialized)
+            ));
+
+            let passwords = Passwords::new(MemoryIO::new(default_data()));
+            assert!(passwords
+                .verify_password(&primary_pass())
+                .expect("Verification should succeed"));
+            assert!(!passwords
+                .verify_password(&SecretString::new("foobaz".to_owned()))
+                .expect("Verification should succeed"));
+            assert!(!passwords.unlocked());
+        }
+    }
+
+    mod normalization {
+        use super::*;
+
+        const COMPOSED: &str = "caf\u{e9}";
+        const DECOMPOSED: &str = "cafe\u{301}";
+
+        fn password(value: &str) -> SecretString {
+            SecretString::new(value.to_owned())
+        }
+
+        fn stored_primary_password(passwords: &Passwords<MemoryIO>) -> String {
+            passwords
+                .primary_password
+                .as_ref()
+                .expect("Primary password should be present")
+                .expose_secret()
+                .to_owned()
+        }
+
+        #[test]
+        fn normalized_on_reset() {
+            let mut passwords = Passwords::new(MemoryIO::new(HashMap::new()));
+            passwords
+                .reset(password(DECOMPOSED))
+                .expect("Reset should succeed");
+            assert_eq!(stored_primary_password(&passwords), COMPOSED);
+
+            passwords.lock();
+            passwords
+                .unlock(password(COMPOSED))
+                .expect("Unlocking with composed form should succeed");
+
+            passwords.lock();
+            passwords
+                .unlock(password(DECOMPOSED))
+                .expect("Unlocking with decomposed form should succeed");
+            assert_eq!(stored_primary_password(&passwords), COMPOSED);
+        }
+
+        #[test]
+        fn non_normalized_fallback() {
+            let mut passwords = Passwords::new(MemoryIO::new(HashMap::new()));
+            let salt = b"abcdefghijklmnop";
+            let key = get_encryption_key(&password(DECOMPOSED), salt);
+            let hmac_secret = SecretVec::new(b"abcdefghijklmnopqrstuvwxyz{|}~\x7F\x80".to_vec());
+            passwords
+                .storage
+                .clear(salt, &KdfParams::default(), &hmac_secret, &key)
+                .expect("Clearing storage should succeed");
+
+            passwords
+                .unlock(password(DECOMPOSED))
+                .expect("Unlocking with original form should succeed");
+            assert_eq!(stored_primary_password(&passwords), DECOMPOSED);
+
+            passwords.lock();
+            assert!(matches!(
+                passwords.unlock(password(COMPOSED)),
+                Err(Error::DecryptionFailure)
+            ));
+        }
+    }
+
+    mod derivation_cache {
+        use super::*;
+
+        fn cached_count(passwords: &Passwords<MemoryIO>) -> usize {
+            passwords
+                .derivation_cache
+                .as_ref()
+                .expect("Cache should be enabled")
+                .borrow()
+                .len()
+        }
+
+        #[test]
+        fn caching() {
+            let mut passwords = Passwords::new(MemoryIO::new(default_data()));
+            passwords.set_derivation_cache(true);
+            passwords
+                .unlock(primary_pass())
+                .expect("Unlock should succeed");
+
+            let value = passwords
+                .get("example.com", "blubber", "1")
+                .expect("Retrieval should succeed");
+            assert_eq!(cached_count(&passwords), 1);
+            assert_eq!(
+                passwords
+                    .get("www.example.com", "blubber", "1")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                value.expose_secret()
+            );
+            assert_eq!(cached_count(&passwords), 1);
+
+            passwords
+                .set_generated("example.com", "blubber", "1", 8, CharacterSet::all(), None)
+                .expect("Overwriting password should succeed");
+            let modified = passwords
+                .get("example.com", "blubber", "1")
+                .expect("Retrieval should succeed");
+            assert_eq!(modified.expose_secret().len(), 8);
+            assert_eq!(cached_count(&passwords), 2);
+
+            passwords.lock();
+            assert_eq!(cached_count(&passwords), 0);
+        }
+
+        #[test]
+        fn prefetching() {
+            let mut passwords = Passwords::new(MemoryIO::new(default_data()));
+            passwords
+                .unlock(primary_pass())
+                .expect("Unlock should succeed");
+            assert_eq!(
+                passwords
+                    .prefetch("example.com")
+                    .expect("Prefetching should succeed"),
                 0
             );
 
-            assert_eq!(
-                list_passwords(&passwords, "example.com", "blu*"),
-                vec!["blubber"]
-            );
-            assert_eq!(
-                list_passwords(&passwords, "www.example.com", "blu*"),
-                vec!["blubber"]
-            );
-            assert_eq!(
-                list_passwords(&passwords, "example.org", "blu*"),
-                vec!["blubber"]
-            );
-            assert_eq!(
-                list_passwords(&passwords, "www.example.org", "blu*"),
-                vec!["blubber"]
-            );
-            assert_eq!(list_passwords(&passwords, "example.info", "blu*").len(), 0);
-            assert_eq!(
-                list_passwords(&passwords, "www.example.info", "blu*").len(),
-                0
-            );
+            passwords.set_derivation_cache(true);
+            let count = passwords
+                .prefetch("www.example.com")
+                .expect("Prefetching should succeed");
+            assert!(count > 0);
+            assert_eq!(cached_count(&passwords), count);
+
+            passwords
+                .get("example.com", "blubber", "1")
+                .expect("Retrieval should succeed");
+            assert_eq!(cached_count(&passwords), count);
+
+            passwords.lock();
+            assert_eq!(cached_count(&passwords), 0);
+            assert!(matches!(
+                passwords.prefetch("example.com"),
+                Err(Error::PasswordsLocked)
+            ));
+        }
+
+        #[test]
+        fn disabled_by_default() {
+            let mut passwords = Passwords::new(MemoryIO::new(default_data()));
+            passwords
+                .unlock(primary_pass())
+                .expect("Unlock should succeed");
+            passwords
+                .get("example.com", "blubber", "1")
+                .expect("Retrieval should succeed");
+            assert!(passwords.derivation_cache.is_none());
+        }
+    }
+
+    mod retrieval {
+        use super::*;
+
+        fn list_sites(passwords: &Passwords<MemoryIO>, site: &str) -> Vec<String> {
+            let mut vec = passwords
+                .list_sites(site)
+                .map(|site| site.name().to_string())
+                .collect::<Vec<String>>();
+            vec.sort();
+            vec
+        }
+
+        fn list_passwords(passwords: &Passwords<MemoryIO>, site: &str, name: &str) -> Vec<String> {
+            let mut vec = passwords
+                .list(site, name)
+                .map(password_name)
+                .collect::<Vec<String>>();
+            vec.sort();
+            vec
+        }
+
+        #[test]
+        fn list_sites_wildcards() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            assert_eq!(
+                list_sites(&passwords, "*"),
+                vec!["example.com", "example.info", "example.org"]
+            );
+            assert_eq!(
+                list_sites(&passwords, "ex*"),
+                vec!["example.com", "example.info", "example.org"]
+            );
+            assert_eq!(
+                list_sites(&passwords, "*.com"),
+                vec!["example.com", "example.org"]
+            );
+            assert_eq!(list_sites(&passwords, "*am*i*"), vec!["example.info"]);
+            assert_eq!(list_sites(&passwords, "example.info"), vec!["example.info"]);
+            assert_eq!(list_sites(&passwords, "example.net").len(), 0);
+            assert_eq!(list_sites(&passwords, "blub*").len(), 0);
+        }
+
+        #[test]
+        fn find_wildcards() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            let find = |site: &str, name: &str| {
+                let mut vec = passwords
+                    .find(site, name)
+                    .map(|(site, password)| format!("{}/{}", site.name(), password_name(password)))
+                    .collect::<Vec<String>>();
+                vec.sort();
+                vec
+            };
+
+            assert_eq!(
+                find("*", "*"),
+                vec![
+                    "example.com/blabber",
+                    "example.com/blubber",
+                    "example.info/test"
+                ]
+            );
+            assert_eq!(
+                find("*.org", "*"),
+                vec!["example.com/blabber", "example.com/blubber"]
+            );
+            assert_eq!(
+                find("*", "bl?bber"),
+                vec!["example.com/blabber", "example.com/blubber"]
+            );
+            assert_eq!(find("*.info", "b*").len(), 0);
+            assert_eq!(find("example.net", "*").len(), 0);
+        }
+
+        #[test]
+        fn list_all() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            let mut vec = passwords
+                .list_all()
+                .map(|password| password.id().to_string())
+                .collect::<Vec<String>>();
+            vec.sort();
+            assert_eq!(
+                vec,
+                vec![
+                    "example.com/blabber@2",
+                    "example.com/blubber",
+                    "example.info/test@yet another"
+                ]
+            );
+        }
+
+        #[test]
+        fn try_list_corrupt() {
+            let mut data = default_data();
+            let io = MemoryIO::new(data.clone());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            let mut names = passwords
+                .try_list("www.example.com", "*")
+                .expect("Listing should succeed")
+                .into_iter()
+                .map(password_name)
+                .collect::<Vec<String>>();
+            names.sort();
+            assert_eq!(names, vec!["blabber", "blubber"]);
+
+            data.insert(
+                "site:fRTOldDD+lTwIBS8G+eUkrIzvNsfdGRSWQXrXqszDHM=:corrupt".to_string(),
+                "YWJjZGVmZ2hpamts_Zm9vYmFy".to_string(),
+            );
+            let io = MemoryIO::new(data);
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            assert_eq!(list_passwords(&passwords, "example.com", "*").len(), 2);
+            assert!(passwords.try_list("example.com", "*").is_err());
+        }
+
+        #[test]
+        fn list_passwords_wildcards() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            assert_eq!(
+                list_passwords(&passwords, "example.com", "*"),
+                vec!["blabber", "blubber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "www.example.com", "*"),
+                vec!["blabber", "blubber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "example.org", "*"),
+                vec!["blabber", "blubber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "www.example.org", "*"),
+                vec!["blabber", "blubber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "example.info", "*"),
+                vec!["test"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "www.example.info", "*"),
+                vec!["test"]
+            );
+
+            assert_eq!(
+                list_passwords(&passwords, "example.com", "b*"),
+                vec!["blabber", "blubber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "www.example.com", "b*"),
+                vec!["blabber", "blubber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "example.org", "b*"),
+                vec!["blabber", "blubber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "www.example.org", "b*"),
+                vec!["blabber", "blubber"]
+            );
+            assert_eq!(list_passwords(&passwords, "example.info", "b*").len(), 0);
+            assert_eq!(
+                list_passwords(&passwords, "www.example.info", "b*").len(),
+                0
+            );
+
+            assert_eq!(
+                list_passwords(&passwords, "example.com", "blu*"),
+                vec!["blubber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "www.example.com", "blu*"),
+                vec!["blubber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "example.org", "blu*"),
+                vec!["blubber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "www.example.org", "blu*"),
+                vec!["blubber"]
+            );
+            assert_eq!(list_passwords(&passwords, "example.info", "blu*").len(), 0);
+            assert_eq!(
+                list_passwords(&passwords, "www.example.info", "blu*").len(),
+                0
+            );
+
+            assert_eq!(
+                list_passwords(&passwords, "example.com", "*a*"),
+                vec!["blabber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "www.example.com", "*a*"),
+                vec!["blabber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "example.org", "*a*"),
+                vec!["blabber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "www.example.org", "*a*"),
+                vec!["blabber"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "example.info", "t*t"),
+                vec!["test"]
+            );
+            assert_eq!(
+                list_passwords(&passwords, "www.example.info", "t*t"),
+                vec!["test"]
+            );
+        }
+
+        #[test]
+        fn query_passwords() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            assert!(passwords
+                .has("example.com", "blubber", "")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("example.com", "blubber", "")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "SUDJjn&%:nBe}cr8"
+            );
+            assert!(passwords
+                .has("www.example.com", "blubber", "")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("www.example.com", "blubber", "")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "SUDJjn&%:nBe}cr8"
+            );
+            assert!(passwords
+                .has("example.org", "blubber", "")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("example.org", "blubber", "")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "SUDJjn&%:nBe}cr8"
+            );
+            assert!(passwords
+                .has("www.example.org", "blubber", "")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("www.example.org", "blubber", "")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "SUDJjn&%:nBe}cr8"
+            );
+
+            assert!(passwords
+                .has("example.com", "blabber", "2")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("example.com", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
+            );
+            assert!(passwords
+                .has("www.example.com", "blabber", "2")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("www.example.com", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
+            );
+            assert!(passwords
+                .has("example.org", "blabber", "2")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("example.org", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
+            );
+            assert!(passwords
+                .has("www.example.org", "blabber", "2")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("www.example.org", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
+            );
+
+            assert!(passwords
+                .has("example.info", "test", "yet another")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("example.info", "test", "yet another")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "rjtfxqf4"
+            );
+            assert!(passwords
+                .has("www.example.info", "test", "yet another")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("www.example.info", "test", "yet another")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "rjtfxqf4"
+            );
+            assert!(!passwords
+                .has("example.info", "blubber", "")
+                .expect("Check should succeed"));
+            assert!(matches!(
+                passwords
+                    .get("example.info", "blubber", "")
+                    .expect_err("Retrieval should fail"),
+                Error::KeyMissing { .. }
+            ));
+            assert!(!passwords
+                .has("www.example.info", "blubber", "")
+                .expect("Check should succeed"));
+            assert!(matches!(
+                passwords
+                    .get("www.example.info", "blubber", "")
+                    .expect_err("Retrieval should fail"),
+                Error::KeyMissing { .. }
+            ));
+        }
+    }
+
+    mod addition {
+        use super::*;
+        use crate::storage_types::CharacterType;
+
+        #[test]
+        fn invalid_length() {
+            let io = MemoryIO::new(empty_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            for length in [0, MIN_PASSWORD_LENGTH - 1, MAX_PASSWORD_LENGTH + 1, 1000] {
+                assert!(matches!(
+                    passwords
+                        .set_generated(
+                            "example.com",
+                            "blubber",
+                            "1",
+                            length,
+                            CharacterSet::all(),
+                            None,
+                        )
+                        .expect_err("Adding password should fail"),
+                    Error::InvalidLength { length: l } if l == length
+                ));
+                assert!(matches!(
+                    passwords
+                        .derive_generated(
+                            "example.com",
+                            "blubber",
+                            "1",
+                            length,
+                            CharacterSet::all(),
+                            None,
+                        )
+                        .expect_err("Deriving password should fail"),
+                    Error::InvalidLength { .. }
+                ));
+            }
+            assert_eq!(passwords.list("example.com", "*").count(), 0);
+
+            for length in [MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH] {
+                passwords
+                    .set_generated(
+                        "example.com",
+                        "blubber",
+                        "1",
+                        length,
+                        CharacterSet::all(),
+                        None,
+                    )
+                    .expect("Adding password should succeed");
+            }
+        }
+
+        #[test]
+        fn custom_symbols() {
+            let io = MemoryIO::new(empty_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            for symbols in ["", "ab!", "!!", "ä"] {
+                assert!(matches!(
+                    passwords
+                        .set_generated(
+                            "example.com",
+                            "blubber",
+                            "1",
+                            16,
+                            CharacterSet::all(),
+                            Some(symbols)
+                        )
+                        .expect_err("Adding password should fail"),
+                    Error::InvalidSymbols
+                ));
+            }
+
+            let derived = passwords
+                .derive_generated(
+                    "example.com",
+                    "blubber",
+                    "1",
+                    16,
+                    CharacterType::Symbol.into(),
+                    Some("!@"),
+                )
+                .expect("Deriving password should succeed");
+            assert_eq!(derived.expose_secret(), "!!!@!@!@@@!!!!!!");
+
+            passwords
+                .set_generated(
+                    "example.com",
+                    "blubber",
+                    "1",
+                    16,
+                    CharacterType::Symbol.into(),
+                    Some("!@"),
+                )
+                .expect("Adding password should succeed");
+            assert_eq!(
+                passwords
+                    .get("example.com", "blubber", "1")
+                    .expect("Password should be present")
+                    .expose_secret(),
+                derived.expose_secret()
+            );
+
+            passwords
+                .change_length("example.com", "blubber", "1", 20)
+                .expect("Changing length should succeed");
+            let changed = passwords
+                .get("example.com", "blubber", "1")
+                .expect("Password should be present");
+            assert_eq!(changed.expose_secret().len(), 20);
+            assert!(changed
+                .expose_secret()
+                .chars()
+                .all(|char| char == '!' || char == '@'));
+
+            passwords
+                .set_generated("example.com", "blubber", "1", 16, CharacterSet::all(), None)
+                .expect("Adding password should succeed");
+            assert_ne!(
+                passwords
+                    .get("example.com", "blubber", "1")
+                    .expect("Password should be present")
+                    .expose_secret(),
+                derived.expose_secret()
+            );
+        }
+
+        #[test]
+        fn add_passwords() {
+            let io = MemoryIO::new(empty_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            assert!(matches!(
+                passwords
+                    .get_alias("example.org")
+                    .expect_err("Alias should not be present"),
+                Error::NoSuchAlias { .. }
+            ));
+            assert!(matches!(
+                passwords
+                    .set_alias("www.example.org", "example.org")
+                    .expect_err("Adding alias should fail"),
+                Error::AliasToSelf { .. }
+            ));
+            passwords
+                .set_alias("www.example.org", "www.example.com")
+                .expect("Adding alias should succeed");
+            assert!(matches!(
+                passwords
+                    .set_alias("www.example.com", "example.org")
+                    .expect_err("Adding alias should fail"),
+                Error::AliasToSelf { .. }
+            ));
+            assert_eq!(
+                passwords
+                    .get_alias("example.org")
+                    .expect("Alias should be present"),
+                "example.com"
+            );
+
+            passwords
+                .set_generated("example.com", "blubber", "", 16, CharacterSet::all(), None)
+                .expect("Adding password should succeed");
+            passwords
+                .set_stored(
+                    "example.com",
+                    "blabber",
+                    "2",
+                    SecretString::new("asdf".to_owned()),
+                )
+                .expect("Adding password should succeed");
+            passwords
+                .set_generated(
+                    "example.info",
+                    "test",
+                    "yet another",
+                    8,
+                    CharacterType::Lower | CharacterType::Digit,
+                    None,
+                )
+                .expect("Adding password should succeed");
+
+            assert!(matches!(
+                passwords
+                    .set_alias("www.example.com", "example.info")
+                    .expect_err("Adding alias should fail"),
+                Error::SiteHasPasswords { .. }
+            ));
+
+            assert_eq!(
+                passwords
+                    .get("example.com", "blubber", "")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "SUDJjn&%:nBe}cr8"
+            );
+            assert_eq!(
+                passwords
+                    .get("example.org", "blubber", "")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "SUDJjn&%:nBe}cr8"
+            );
+
+            assert_eq!(
+                passwords
+                    .get("www.example.com", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
+            );
+            assert_eq!(
+                passwords
+                    .get("www.example.org", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
+            );
+
+            assert_eq!(
+                passwords
+                    .get("example.info", "test", "yet another")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "rjtfxqf4"
+            );
+            assert!(matches!(
+                passwords
+                    .get("example.info", "blubber", "")
+                    .expect_err("Retrieval should fail"),
+                Error::KeyMissing { .. }
+            ));
+        }
+    }
+
+    mod aliases {
+        use super::*;
+
+        #[test]
+        fn set_aliases() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            match passwords
+                .set_aliases(
+                    &["example.net", "www.example.com", "example.info"],
+                    "example.org",
+                )
+                .expect_err("Adding aliases should fail")
+            {
+                Error::AliasFailures { failures } => {
+                    assert_eq!(failures.len(), 2);
+                    assert_eq!(failures[0].0, "www.example.com");
+                    assert!(matches!(failures[0].1, Error::AliasToSelf));
+                    assert_eq!(failures[1].0, "example.info");
+                    assert!(matches!(failures[1].1, Error::SiteHasPasswords));
+                }
+                error => panic!("Unexpected error {:?}", error),
+            }
+            assert!(matches!(
+                passwords
+                    .get_alias("example.net")
+                    .expect_err("Alias should not be present"),
+                Error::NoSuchAlias
+            ));
+
+            passwords
+                .set_aliases(&["example.net", "www.example.de"], "example.org")
+                .expect("Adding aliases should succeed");
+            for site in ["example.net", "example.de"] {
+                assert_eq!(
+                    passwords.get_alias(site).expect("Alias should be present"),
+                    "example.com"
+                );
+            }
+        }
+
+        #[test]
+        fn set_alias_migrating() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            assert!(matches!(
+                passwords
+                    .set_alias_migrating("www.example.com", "example.org")
+                    .expect_err("Making a site an alias for itself should fail"),
+                Error::AliasToSelf
+            ));
+
+            passwords
+                .set_stored(
+                    "example.net",
+                    "blubber",
+                    "",
+                    SecretString::new("xyz".to_owned()),
+                )
+                .expect("Adding password should succeed");
+            assert!(matches!(
+                passwords
+                    .set_alias_migrating("example.net", "example.org")
+                    .expect_err("Migrating should fail due to an existing password"),
+                Error::PasswordExists
+            ));
+            assert!(passwords
+                .has("example.net", "blubber", "")
+                .expect("Check should succeed"));
+
+            assert_eq!(
+                passwords
+                    .set_alias_migrating("www.example.info", "example.org")
+                    .expect("Migrating should succeed"),
+                1
+            );
+            assert_eq!(
+                passwords
+                    .get_alias("example.info")
+                    .expect("Alias should be present"),
+                "example.com"
+            );
+            assert!(passwords
+                .has("example.com", "test", "yet another")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get_notes("example.com", "test", "yet another")
+                    .expect("Notes should be present")
+                    .expose_secret(),
+                "nothing here"
+            );
+
+            assert_eq!(
+                passwords
+                    .set_alias_migrating("example.de", "example.com")
+                    .expect("Migrating should succeed"),
+                0
+            );
+            assert_eq!(
+                passwords
+                    .get_alias("example.de")
+                    .expect("Alias should be present"),
+                "example.com"
+            );
+        }
+
+        #[test]
+        fn cycles() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            // Bypass the checks of set_alias(), imported data could contain cycles as well
+            let hmac_secret = passwords.hmac_secret.as_ref().expect("Should be unlocked");
+            let key = passwords.key.as_ref().expect("Should be unlocked");
+            passwords
+                .storage
+                .set_alias("example.net", "example.org", hmac_secret, key)
+                .expect("Adding alias should succeed");
+            passwords
+                .storage
+                .set_alias("example.org", "example.net", hmac_secret, key)
+                .expect("Adding alias should succeed");
+
+            assert!(matches!(
+                passwords
+                    .get("example.org", "blubber", "")
+                    .expect_err("Retrieving password should fail"),
+                Error::AliasCycle
+            ));
+            assert!(matches!(
+                passwords
+                    .try_list("example.net", "*")
+                    .expect_err("Listing passwords should fail"),
+                Error::AliasCycle
+            ));
+            assert_eq!(passwords.list("example.net", "*").count(), 0);
+            assert_eq!(
+                passwords
+                    .find("example.*", "*")
+                    .map(|(site, _)| site.name().to_string())
+                    .collect::<BTreeSet<_>>(),
+                BTreeSet::from(["example.com".to_string(), "example.info".to_string()])
+            );
+        }
+
+        #[test]
+        fn site_info() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            assert!(matches!(
+                passwords
+                    .site_info("example.com")
+                    .expect_err("Passwords should be locked"),
+                Error::PasswordsLocked
+            ));
+
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            let site = passwords
+                .site_info("www.example.com")
+                .expect("Site should be present");
+            assert_eq!(site.name(), "example.com");
+            assert_eq!(site.alias(), None);
+
+            let site = passwords
+                .site_info("www.example.org")
+                .expect("Site should be present");
+            assert_eq!(site.name(), "example.org");
+            assert_eq!(site.alias(), Some("example.com"));
+
+            assert!(matches!(
+                passwords
+                    .site_info("example.net")
+                    .expect_err("Site should be missing"),
+                Error::KeyMissing
+            ));
+        }
+    }
+
+    mod duplication {
+        use super::*;
+        use crate::storage_types::CharacterType;
+
+        #[test]
+        fn duplicate_passwords() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            passwords
+                .duplicate(
+                    &PasswordId::new("www.example.org", "blabber", "2"),
+                    &PasswordId::new("example.net", "blabber", "1"),
+                    false,
+                )
+                .expect("Duplicating password should succeed");
+            assert_eq!(
+                passwords
+                    .get("example.net", "blabber", "")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
+            );
+            assert_eq!(
+                passwords
+                    .get_notes("example.net", "blabber", "")
+                    .expect("Getting notes should succeed")
+                    .expose_secret(),
+                "hi there!"
+            );
+
+            passwords
+                .duplicate(
+                    &PasswordId::new("example.info", "test", "yet another"),
+                    &PasswordId::new("example.info", "test", "2"),
+                    false,
+                )
+                .expect("Duplicating password should succeed");
+            let duplicate = passwords
+                .list("example.info", "test")
+                .find(|password| password.id().revision() == "2")
+                .expect("Duplicate should be listed");
+            if let Password::Generated(duplicate) = &duplicate {
+                assert_eq!(duplicate.length(), 8);
+                assert_eq!(
+                    duplicate.charset(),
+                    CharacterType::Lower | CharacterType::Digit
+                );
+                assert_eq!(duplicate.notes().expose_secret(), "nothing here");
+            } else {
+                panic!("Duplicate should be a generated password");
+            }
+
+            assert!(matches!(
+                passwords
+                    .duplicate(
+                        &PasswordId::new("example.com", "blubber", ""),
+                        &PasswordId::new("example.net", "blabber", ""),
+                        false,
+                    )
+                    .expect_err("Duplicating password should fail"),
+                Error::PasswordExists
+            ));
+            assert!(matches!(
+                passwords
+                    .duplicate(
+                        &PasswordId::new("example.com", "nonexistent", ""),
+                        &PasswordId::new("example.net", "nonexistent", ""),
+                        true,
+                    )
+                    .expect_err("Duplicating password should fail"),
+                Error::KeyMissing
+            ));
+
+            passwords
+                .duplicate(
+                    &PasswordId::new("example.com", "blubber", ""),
+                    &PasswordId::new("example.net", "blabber", ""),
+                    true,
+                )
+                .expect("Duplicating password should succeed");
+            assert_eq!(
+                passwords
+                    .get("example.net", "blabber", "")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "CMfue2.8T&/eE>RX"
+            );
+        }
+    }
+
+    mod renaming {
+        use super::*;
+        use crate::storage_types::CharacterType;
+
+        #[test]
+        fn rename_passwords() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            passwords
+                .rename("www.example.org", "blabber", "2", "blubbel", "1", false)
+                .expect("Renaming password should succeed");
+            assert!(!passwords
+                .has("example.com", "blabber", "2")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("example.com", "blubbel", "1")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
+            );
+            assert_eq!(
+                passwords
+                    .get_notes("example.com", "blubbel", "1")
+                    .expect("Getting notes should succeed")
+                    .expose_secret(),
+                "hi there!"
+            );
+
+            passwords
+                .rename("example.info", "test", "yet another", "test2", "3", false)
+                .expect("Renaming password should succeed");
+            let renamed = passwords
+                .list("example.info", "*")
+                .collect::<Vec<Password>>();
+            assert_eq!(renamed.len(), 1);
+            if let Password::Generated(renamed) = &renamed[0] {
+                assert_eq!(renamed.id(), &PasswordId::new("example.info", "test2", "3"));
+                assert_eq!(renamed.length(), 8);
+                assert_eq!(
+                    renamed.charset(),
+                    CharacterType::Lower | CharacterType::Digit
+                );
+                assert_eq!(renamed.notes().expose_secret(), "nothing here");
+            } else {
+                panic!("Renamed password should be a generated password");
+            }
+        }
+
+        #[test]
+        fn rename_errors() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            assert!(matches!(
+                passwords
+                    .rename("example.com", "blubber", "1", "blabber", "2", false)
+                    .expect_err("Renaming should fail"),
+                Error::PasswordExists
+            ));
+            assert!(matches!(
+                passwords
+                    .rename("example.com", "nonexistent", "1", "blabber", "3", false)
+                    .expect_err("Renaming should fail"),
+                Error::KeyMissing
+            ));
+
+            // Renaming to the same identifier keeps the password
+            passwords
+                .rename("example.com", "blubber", "", "blubber", "1", false)
+                .expect("Renaming password should succeed");
+            assert!(passwords
+                .has("example.com", "blubber", "1")
+                .expect("Check should succeed"));
+
+            passwords
+                .rename("example.com", "blubber", "1", "blabber", "2", true)
+                .expect("Renaming password should succeed");
+            assert_eq!(
+                passwords
+                    .list("example.com", "*")
+                    .collect::<Vec<Password>>()
+                    .len(),
+                1
+            );
+        }
+    }
+
+    mod moving {
+        use super::*;
+
+        #[test]
+        fn move_passwords() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            passwords
+                .move_password("www.example.org", "blabber", "2", "example.net", false)
+                .expect("Moving password should succeed");
+            assert!(!passwords
+                .has("example.com", "blabber", "2")
+                .expect("Check should succeed"));
+            assert_eq!(
+                passwords
+                    .get("example.net", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
+            );
+            assert_eq!(
+                passwords
+                    .get_notes("example.net", "blabber", "2")
+                    .expect("Getting notes should succeed")
+                    .expose_secret(),
+                "hi there!"
+            );
+            assert_eq!(
+                passwords
+                    .list_sites("example.net")
+                    .collect::<Vec<Site>>()
+                    .len(),
+                1
+            );
+
+            assert!(matches!(
+                passwords
+                    .move_password("example.com", "nonexistent", "1", "example.net", false)
+                    .expect_err("Moving should fail"),
+                Error::KeyMissing
+            ));
+
+            passwords
+                .set_stored(
+                    "example.net",
+                    "blubber",
+                    "1",
+                    SecretString::from("x".to_owned()),
+                )
+                .expect("Adding password should succeed");
+            assert!(matches!(
+                passwords
+                    .move_password("example.com", "blubber", "1", "example.net", false)
+                    .expect_err("Moving should fail"),
+                Error::PasswordExists
+            ));
+            passwords
+                .move_password("example.com", "blubber", "1", "example.net", true)
+                .expect("Moving password should succeed");
+            assert_eq!(passwords.list("example.com", "*").count(), 0);
+            assert_eq!(passwords.list("example.net", "*").count(), 2);
+        }
+    }
+
+    mod timestamps {
+        use super::*;
+        use crate::storage_types::CharacterType;
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::Arc;
+
+        fn timestamps(passwords: &Passwords<MemoryIO>, name: &str) -> (Option<u64>, Option<u64>) {
+            let password = passwords
+                .list("example.com", name)
+                .next()
+                .expect("Password should exist");
+            (password.created(), password.modified())
+        }
+
+        #[test]
+        fn timestamps_recorded() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            let time = Arc::new(AtomicU64::new(1000));
+            let clock_time = time.clone();
+            passwords.set_clock(Box::new(move || clock_time.load(Ordering::SeqCst)));
+
+            assert_eq!(timestamps(&passwords, "blubber"), (None, None));
+
+            passwords
+                .set_generated("example.com", "new", "1", 16, CharacterSet::all(), None)
+                .expect("Adding password should succeed");
+            assert_eq!(timestamps(&passwords, "new"), (Some(1000), Some(1000)));
+
+            time.store(2000, Ordering::SeqCst);
+            passwords
+                .set_stored(
+                    "example.com",
+                    "new",
+                    "1",
+                    SecretString::from("x".to_owned()),
+                )
+                .expect("Overwriting password should succeed");
+            assert_eq!(timestamps(&passwords, "new"), (Some(1000), Some(2000)));
+
+            time.store(3000, Ordering::SeqCst);
+            passwords
+                .set_notes(
+                    "example.com",
+                    "blubber",
+                    "1",
+                    SecretString::from("x".to_owned()),
+                )
+                .expect("Setting notes should succeed");
+            assert_eq!(timestamps(&passwords, "blubber"), (None, Some(3000)));
+
+            // Time going backwards doesn't produce earlier timestamps
+            time.store(500, Ordering::SeqCst);
+            passwords
+                .set_charset("example.com", "blubber", "1", CharacterType::Digit.into())
+                .expect("Changing character set should succeed");
+            assert_eq!(timestamps(&passwords, "blubber"), (None, Some(3000)));
+
+            time.store(4000, Ordering::SeqCst);
+            passwords
+                .duplicate(
+                    &PasswordId::new("example.com", "new", "1"),
+                    &PasswordId::new("example.com", "copy", "1"),
+                    false,
+                )
+                .expect("Duplicating password should succeed");
+            assert_eq!(timestamps(&passwords, "copy"), (Some(4000), Some(4000)));
+            assert_eq!(timestamps(&passwords, "new"), (Some(1000), Some(2000)));
+
+            time.store(5000, Ordering::SeqCst);
+            passwords
+                .rename("example.com", "copy", "1", "renamed", "1", false)
+                .expect("Renaming password should succeed");
+            assert_eq!(timestamps(&passwords, "renamed"), (Some(4000), Some(5000)));
+        }
+    }
+
+    mod tags {
+        use super::*;
+
+        fn tags(add: &[&str]) -> Vec<String> {
+            add.iter().map(|tag| tag.to_string()).collect()
+        }
+
+        #[test]
+        fn update_tags() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            assert_eq!(
+                passwords
+                    .update_tags("example.org", "blubber", "1", &tags(&["work", "mail"]), &[])
+                    .expect("Updating tags should succeed"),
+                vec!["mail", "work"]
+            );
+            assert_eq!(
+                passwords
+                    .update_tags(
+                        "example.com",
+                        "blubber",
+                        "1",
+                        &tags(&["work", "old"]),
+                        &tags(&["mail", "nonexistent"])
+                    )
+                    .expect("Updating tags should succeed"),
+                vec!["old", "work"]
+            );
+            assert_eq!(
+                passwords
+                    .update_tags("example.com", "blubber", "1", &[], &[])
+                    .expect("Updating tags should succeed"),
+                vec!["old", "work"]
+            );
+
+            let password = passwords
+                .list("example.com", "blubber")
+                .next()
+                .expect("Password should exist");
+            assert!(password.has_tag("work"));
+            assert!(!password.has_tag("mail"));
+            assert!(passwords
+                .list("example.com", "blabber")
+                .all(|password| password.tags().is_empty()));
+
+            // Tags are kept when the password is changed
+            passwords
+                .change_length("example.com", "blubber", "1", 10)
+                .expect("Changing length should succeed");
+            assert_eq!(
+                passwords
+                    .list("example.com", "blubber")
+                    .next()
+                    .expect("Password should exist")
+                    .tags(),
+                ["old", "work"]
+            );
+
+            assert!(matches!(
+                passwords
+                    .update_tags("example.com", "blubber", "1", &tags(&[""]), &[])
+                    .expect_err("Updating tags should fail"),
+                Error::InvalidTag
+            ));
+            assert!(matches!(
+                passwords
+                    .update_tags("example.com", "nonexistent", "1", &tags(&["work"]), &[])
+                    .expect_err("Updating tags should fail"),
+                Error::KeyMissing
+            ));
+        }
+    }
+
+    mod history {
+        use super::*;
+
+        fn history_values(passwords: &Passwords<MemoryIO>) -> Vec<String> {
+            passwords
+                .history("example.com", "blabber", "2")
+                .expect("Retrieving history should succeed")
+                .iter()
+                .map(|entry| entry.password().expose_secret().to_owned())
+                .collect()
+        }
+
+        #[test]
+        fn keep_history() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            passwords
+                .set_stored(
+                    "example.com",
+                    "blabber",
+                    "2",
+                    SecretString::from("x".to_owned()),
+                )
+                .expect("Overwriting password should succeed");
+            assert!(history_values(&passwords).is_empty());
+
+            passwords.set_keep_history(true);
+            passwords
+                .set_stored(
+                    "www.example.org",
+                    "blabber",
+                    "2",
+                    SecretString::from("y".to_owned()),
+                )
+                .expect("Overwriting password should succeed");
+            passwords
+                .set_stored(
+                    "example.com",
+                    "blabber",
+                    "2",
+                    SecretString::from("y".to_owned()),
+                )
+                .expect("Overwriting password should succeed");
+            assert_eq!(history_values(&passwords), vec!["x"]);
+            let history = passwords
+                .history("example.com", "blabber", "2")
+                .expect("Retrieving history should succeed");
+            assert!(history[0].timestamp() > 0);
+
+            for i in 0..MAX_HISTORY_LENGTH {
+                passwords
+                    .set_stored(
+                        "example.com",
+                        "blabber",
+                        "2",
+                        SecretString::from(format!("value{}", i)),
+                    )
+                    .expect("Overwriting password should succeed");
+            }
+            let values = history_values(&passwords);
+            assert_eq!(values.len(), MAX_HISTORY_LENGTH);
+            assert_eq!(values[0], "y");
+            assert_eq!(
+                values[MAX_HISTORY_LENGTH - 1],
+                format!("value{}", MAX_HISTORY_LENGTH - 2)
+            );
+
+            passwords.set_keep_history(false);
+            passwords
+                .set_stored(
+                    "example.com",
+                    "blabber",
+                    "2",
+                    SecretString::from("z".to_owned()),
+                )
+                .expect("Overwriting password should succeed");
+            assert_eq!(history_values(&passwords), values);
+
+            assert!(matches!(
+                passwords
+                    .history("example.com", "blubber", "1")
+                    .expect_err("Retrieving history should fail"),
+                Error::NotStoredPassword
+            ));
+            assert!(matches!(
+                passwords
+                    .history("example.com", "nonexistent", "1")
+                    .expect_err("Retrieving history should fail"),
+                Error::KeyMissing
+            ));
+        }
+
+        #[test]
+        fn restore_history() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            passwords.set_keep_history(true);
+            for value in ["x", "y"] {
+                passwords
+                    .set_stored(
+                        "example.com",
+                        "blabber",
+                        "2",
+                        SecretString::from(value.to_owned()),
+                    )
+                    .expect("Overwriting password should succeed");
+            }
+            assert_eq!(history_values(&passwords), vec!["asdf", "x"]);
+
+            passwords.set_keep_history(false);
+            passwords
+                .restore_history("example.org", "blabber", "2", 0)
+                .expect("Restoring should succeed");
+            assert_eq!(
+                passwords
+                    .get("example.com", "blabber", "2")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "asdf"
+            );
+            assert_eq!(history_values(&passwords), vec!["x", "y"]);
+
+            assert!(matches!(
+                passwords
+                    .restore_history("example.com", "blabber", "2", 2)
+                    .expect_err("Restoring should fail"),
+                Error::KeyMissing
+            ));
+            assert!(matches!(
+                passwords
+                    .restore_history("example.com", "blubber", "1", 0)
+                    .expect_err("Restoring should fail"),
+                Error::NotStoredPassword
+            ));
+        }
+    }
+
+    mod revisions {
+        use super::*;
+        use crate::storage_types::CharacterType;
+
+        #[test]
+        fn list_revisions() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+
+            assert!(matches!(
+                passwords
+                    .revisions("example.com", "blabber")
+                    .expect_err("Passwords should be locked"),
+                Error::PasswordsLocked
+            ));
+
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            assert_eq!(
+                passwords
+                    .revisions("example.com", "blabber")
+                    .expect("Listing revisions should succeed"),
+                vec!["2"]
+            );
+            assert_eq!(
+                passwords
+                    .revisions("www.example.org", "blubber")
+                    .expect("Listing revisions should succeed"),
+                vec![""]
+            );
+            assert_eq!(
+                passwords
+                    .revisions("example.com", "blub*")
+                    .expect("Listing revisions should succeed"),
+                Vec::<String>::new()
+            );
+            assert_eq!(
+                passwords
+                    .revisions("example.net", "blabber")
+                    .expect("Listing revisions should succeed"),
+                Vec::<String>::new()
+            );
+
+            passwords
+                .set_generated(
+                    "example.com",
+                    "blabber",
+                    "1",
+                    16,
+                    CharacterType::Lower | CharacterType::Digit,
+                    None,
+                )
+                .expect("Adding password should succeed");
+            passwords
+                .set_stored(
+                    "example.com",
+                    "blabber",
+                    "10",
+                    SecretString::new("xyz".to_owned()),
+                )
+                .expect("Adding password should succeed");
+            assert_eq!(
+                passwords
+                    .revisions("example.com", "blabber")
+                    .expect("Listing revisions should succeed"),
+                vec!["", "10", "2"]
+            );
+
+            let revisions = passwords
+                .revisions("example.com", "blabber")
+                .expect("Listing revisions should succeed");
+            for revision in revisions {
+                passwords
+                    .get("example.com", "blabber", &revision)
+                    .expect("Retrieval should succeed");
+            }
+        }
+    }
+
+    mod closing {
+        use super::*;
+        use storage_io::FileIO;
+
+        #[test]
+        fn io_access() {
+            use storage_io::StorageIO;
+
+            let mut passwords =
+                Passwords::new(storage_io::MeteredIO::new(MemoryIO::new(default_data())));
+            passwords
+                .unlock(primary_pass())
+                .expect("Unlock should succeed");
+            assert!(passwords.io().metrics().gets > 0);
+
+            passwords.io_mut().reset_metrics();
+            assert_eq!(passwords.io().metrics().gets, 0);
+            assert!(passwords.io().inner().contains_key("salt"));
+        }
+
+        #[test]
+        fn batch() {
+            let mut passwords =
+                Passwords::new(storage_io::MeteredIO::new(MemoryIO::new(default_data())));
+            passwords
+                .unlock(primary_pass())
+                .expect("Unlock should succeed");
+            passwords.io_mut().reset_metrics();
+
+            let result = passwords
+                .batch(|passwords| {
+                    passwords.set_generated(
+                        "example.com",
+                        "blubber",
+                        "1",
+                        16,
+                        CharacterSet::all(),
+                        None,
+                    )?;
+                    passwords.batch(|passwords| {
+                        passwords.set_stored(
+                            "example.com",
+                            "blabber",
+                            "1",
+                            SecretString::new("asdf".to_string()),
+                        )
+                    })??;
+                    assert_eq!(passwords.io().metrics().flushes, 0);
+                    passwords.remove("example.com", "nonexistent", "1")
+                })
+                .expect("Batch should succeed");
+            assert!(matches!(result, Err(Error::KeyMissing)));
+            assert_eq!(passwords.io().metrics().flushes, 1);
+
+            assert!(passwords
+                .has("example.com", "blubber", "1")
+                .expect("Check should succeed"));
+            assert!(passwords
+                .has("example.com", "blabber", "1")
+                .expect("Check should succeed"));
+
+            passwords
+                .remove("example.com", "blubber", "1")
+                .expect("Removing password should succeed");
+            assert_eq!(passwords.io().metrics().flushes, 2);
+        }
+
+        #[test]
+        fn close_commits_transaction() {
+            let file = tempfile::NamedTempFile::new()
+                .expect("Creating temporary file should succeed")
+                .into_temp_path();
+
+            let mut passwords = Passwords::new(FileIO::new(&file));
+            passwords
+                .reset(primary_pass())
+                .expect("Initialization should succeed");
+            passwords
+                .begin_transaction()
+                .expect("Starting transaction should succeed");
+            passwords
+                .set_generated("example.com", "blubber", "1", 16, CharacterSet::all(), None)
+                .expect("Adding password should succeed");
+            passwords.close().expect("Closing should succeed");
+
+            let mut passwords =
+                Passwords::new(FileIO::load(&file).expect("Loading storage should succeed"));
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+            assert!(passwords
+                .has("example.com", "blubber", "1")
+                .expect("Check should succeed"));
+            passwords.close().expect("Closing should succeed");
+        }
+
+        #[test]
+        fn flush_checkpoint() {
+            let file = tempfile::NamedTempFile::new()
+                .expect("Creating temporary file should succeed")
+                .into_temp_path();
+
+            let mut passwords = Passwords::new(FileIO::new(&file));
+            passwords
+                .reset(primary_pass())
+                .expect("Initialization should succeed");
+            passwords
+                .begin_transaction()
+                .expect("Starting transaction should succeed");
+            passwords
+                .set_generated("example.com", "blubber", "1", 16, CharacterSet::all(), None)
+                .expect("Adding password should succeed");
+            passwords.flush().expect("Flush should succeed");
+            assert!(passwords.in_transaction());
+
+            passwords
+                .set_generated("example.com", "blabber", "1", 16, CharacterSet::all(), None)
+                .expect("Adding password should succeed");
+            passwords
+                .rollback_transaction()
+                .expect("Rollback should succeed");
+
+            assert!(passwords
+                .has("example.com", "blubber", "1")
+                .expect("Check should succeed"));
+            assert!(!passwords
+                .has("example.com", "blabber", "1")
+                .expect("Check should succeed"));
+        }
+
+        #[test]
+        fn reload() {
+            let file = tempfile::NamedTempFile::new()
+                .expect("Creating temporary file should succeed")
+                .into_temp_path();
+
+            let mut other = Passwords::new(FileIO::new(&file));
+            other
+                .reset(primary_pass())
+                .expect("Initialization should succeed");
+            other
+                .set_generated("example.com", "blubber", "1", 16, CharacterSet::all(), None)
+                .expect("Adding password should succeed");
+
+            let mut passwords =
+                Passwords::new(FileIO::load(&file).expect("Loading storage should succeed"));
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            other
+                .reset(primary_pass())
+                .expect("Initialization should succeed");
+            other
+                .set_generated("example.com", "blabber", "1", 16, CharacterSet::all(), None)
+                .expect("Adding password should succeed");
+
+            passwords
+                .begin_transaction()
+                .expect("Starting transaction should succeed");
+            passwords
+                .set_generated("example.com", "test", "1", 16, CharacterSet::all(), None)
+                .expect("Adding password should succeed");
+            assert!(matches!(
+                passwords.commit_transaction(),
+                Err(Error::StorageChangedExternally)
+            ));
+
+            passwords.reload().expect("Reload should succeed");
+            assert!(!passwords.in_transaction());
+            assert!(passwords.unlocked());
+            assert!(passwords
+                .has("example.com", "blabber", "1")
+                .expect("Check should succeed"));
+            assert!(!passwords
+                .has("example.com", "blubber", "1")
+                .expect("Check should succeed"));
+            assert!(!passwords
+                .has("example.com", "test", "1")
+                .expect("Check should succeed"));
+            passwords
+                .set_generated("example.com", "test", "1", 16, CharacterSet::all(), None)
+                .expect("Adding password should succeed");
+
+            Passwords::new(FileIO::new(&file))
+                .reset(SecretString::new("other".to_string()))
+                .expect("Initialization should succeed");
+            passwords.reload().expect("Reload should succeed");
+            assert!(!passwords.unlocked());
+        }
+    }
+
+    mod length_change {
+        use super::*;
+        use crate::storage_types::CharacterType;
+
+        #[test]
+        fn change_length() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            passwords
+                .change_length("example.info", "test", "yet another", 12)
+                .expect("Changing length should succeed");
+            let changed = passwords
+                .list("example.info", "test")
+                .collect::<Vec<Password>>();
+            assert_eq!(changed.len(), 1);
+            if let Password::Generated(changed) = &changed[0] {
+                assert_eq!(
+                    changed.id(),
+                    &PasswordId::new("example.info", "test", "yet another")
+                );
+                assert_eq!(changed.length(), 12);
+                assert_eq!(
+                    changed.charset(),
+                    CharacterType::Lower | CharacterType::Digit
+                );
+                assert_eq!(changed.notes().expose_secret(), "nothing here");
+            } else {
+                panic!("Changed password should be a generated password");
+            }
+
+            assert!(matches!(
+                passwords
+                    .change_length("example.com", "blabber", "2", 12)
+                    .expect_err("Changing length should fail"),
+                Error::NotGeneratedPassword
+            ));
+            assert!(matches!(
+                passwords
+                    .change_length("example.com", "nonexistent", "1", 12)
+                    .expect_err("Changing length should fail"),
+                Error::KeyMissing
+            ));
+        }
+
+        #[test]
+        fn set_charset() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            passwords
+                .set_charset(
+                    "example.info",
+                    "test",
+                    "yet another",
+                    CharacterType::Upper | CharacterType::Symbol,
+                )
+                .expect("Changing character set should succeed");
+            let changed = passwords
+                .list("example.info", "test")
+                .collect::<Vec<Password>>();
+            assert_eq!(changed.len(), 1);
+            if let Password::Generated(changed) = &changed[0] {
+                assert_eq!(changed.length(), 8);
+                assert_eq!(
+                    changed.charset(),
+                    CharacterType::Upper | CharacterType::Symbol
+                );
+                assert_eq!(changed.notes().expose_secret(), "nothing here");
+            } else {
+                panic!("Changed password should be a generated password");
+            }
+
+            assert!(matches!(
+                passwords
+                    .set_charset("example.info", "test", "yet another", CharacterSet::empty())
+                    .expect_err("Changing character set should fail"),
+                Error::EmptyCharacterSet
+            ));
+            assert!(matches!(
+                passwords
+                    .set_charset("example.com", "blabber", "2", CharacterSet::all())
+                    .expect_err("Changing character set should fail"),
+                Error::NotGeneratedPassword
+            ));
+            assert!(matches!(
+                passwords
+                    .set_charset("example.com", "nonexistent", "1", CharacterSet::all())
+                    .expect_err("Changing character set should fail"),
+                Error::KeyMissing
+            ));
+        }
+    }
+
+    mod rotation {
+        use super::*;
+
+        #[test]
+        fn next_revision() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+
+            assert!(matches!(
+                passwords
+                    .next_revision("example.com", "blubber")
+                    .expect_err("Passwords should be locked"),
+                Error::PasswordsLocked
+            ));
+
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
 
             assert_eq!(
-                list_passwords(&passwords, "example.com", "*a*"),
-                vec!["blabber"]
-            );
-            assert_eq!(
-                list_passwords(&passwords, "www.example.com", "*a*"),
-                vec!["blabber"]
-            );
-            assert_eq!(
-                list_passwords(&passwords, "example.org", "*a*"),
-                vec!["blabber"]
+                passwords
+                    .next_revision("example.com", "blubber")
+                    .expect("Determining revision should succeed"),
+                "2"
             );
             assert_eq!(
-                list_passwords(&passwords, "www.example.org", "*a*"),
-                vec!["blabber"]
+                passwords
+                    .next_revision("www.example.org", "blabber")
+                    .expect("Determining revision should succeed"),
+                "3"
             );
             assert_eq!(
-                list_passwords(&passwords, "example.info", "t*t"),
-                vec!["test"]
+                passwords
+                    .next_revision("example.info", "test")
+                    .expect("Determining revision should succeed"),
+                "1"
             );
             assert_eq!(
-                list_passwords(&passwords, "www.example.info", "t*t"),
-                vec!["test"]
+                passwords
+                    .next_revision("example.net", "test")
+                    .expect("Determining revision should succeed"),
+                "1"
             );
         }
 
         #[test]
-        fn query_passwords() {
+        fn rotate() {
             let io = MemoryIO::new(default_data());
             let mut passwords = Passwords::new(io);
             passwords
                 .unlock(primary_pass())
                 .expect("Passwords should unlock");
 
-            assert!(passwords
-                .has("example.com", "blubber", "")
-                .expect("Check should succeed"));
+            passwords
+                .set_notes(
+                    "example.com",
+                    "blubber",
+                    "1",
+                    SecretString::new("some notes".to_owned()),
+                )
+                .expect("Setting notes should succeed");
+
             assert_eq!(
                 passwords
-                    .get("example.com", "blubber", "")
-                    .expect("Retrieval should succeed")
-                    .expose_secret(),
-                "SUDJjn&%:nBe}cr8"
+                    .rotate("www.example.org", "blubber", false)
+                    .expect("Rotating should succeed"),
+                "2"
             );
-            assert!(passwords
-                .has("www.example.com", "blubber", "")
-                .expect("Check should succeed"));
             assert_eq!(
                 passwords
-                    .get("www.example.com", "blubber", "")
+                    .get("example.com", "blubber", "1")
                     .expect("Retrieval should succeed")
                     .expose_secret(),
                 "SUDJjn&%:nBe}cr8"
             );
-            assert!(passwords
-                .has("example.org", "blubber", "")
-                .expect("Check should succeed"));
+            let rotated = passwords
+                .get("example.com", "blubber", "2")
+                .expect("Retrieval should succeed");
+            assert_ne!(rotated.expose_secret(), "SUDJjn&%:nBe}cr8");
+            assert_eq!(rotated.expose_secret().len(), 16);
             assert_eq!(
                 passwords
-                    .get("example.org", "blubber", "")
+                    .get_notes("example.com", "blubber", "2")
                     .expect("Retrieval should succeed")
                     .expose_secret(),
-                "SUDJjn&%:nBe}cr8"
+                "some notes"
             );
-            assert!(passwords
-                .has("www.example.org", "blubber", "")
-                .expect("Check should succeed"));
+
             assert_eq!(
                 passwords
-                    .get("www.example.org", "blubber", "")
-                    .expect("Retrieval should succeed")
-                    .expose_secret(),
-                "SUDJjn&%:nBe}cr8"
+                    .rotate("example.com", "blubber", true)
+                    .expect("Rotating should succeed"),
+                "3"
+            );
+            assert_eq!(
+                passwords
+                    .revisions("example.com", "blubber")
+                    .expect("Listing revisions should succeed"),
+                vec!["", "3"]
             );
 
-            assert!(passwords
-                .has("example.com", "blabber", "2")
-                .expect("Check should succeed"));
+            assert!(matches!(
+                passwords
+                    .rotate("example.com", "blabber", false)
+                    .expect_err("Stored passwords shouldn't rotate"),
+                Error::NotGeneratedPassword
+            ));
+            assert!(matches!(
+                passwords
+                    .rotate("example.com", "nothing", false)
+                    .expect_err("Missing passwords shouldn't rotate"),
+                Error::KeyMissing
+            ));
+        }
+    }
+
+    mod policies {
+        use super::*;
+        use crate::storage_types::CharacterType;
+
+        #[test]
+        fn set_and_get() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+
+            assert!(matches!(
+                passwords
+                    .get_policy("example.com")
+                    .expect_err("Passwords should be locked"),
+                Error::PasswordsLocked
+            ));
+
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
             assert_eq!(
                 passwords
-                    .get("example.com", "blabber", "2")
-                    .expect("Retrieval should succeed")
-                    .expose_secret(),
-                "asdf"
+                    .get_policy("example.com")
+                    .expect("Retrieving policy should succeed"),
+                None
             );
-            assert!(passwords
-                .has("www.example.com", "blabber", "2")
-                .expect("Check should succeed"));
             assert_eq!(
                 passwords
-                    .get("www.example.com", "blabber", "2")
-                    .expect("Retrieval should succeed")
-                    .expose_secret(),
-                "asdf"
+                    .get_policy("example.net")
+                    .expect("Retrieving policy should succeed"),
+                None
             );
-            assert!(passwords
-                .has("example.org", "blabber", "2")
-                .expect("Check should succeed"));
+
+            let policy = PasswordPolicy::new(
+                Some(8),
+                Some(12),
+                CharacterType::Lower | CharacterType::Digit,
+                "0O",
+            );
+            passwords
+                .set_policy("www.example.org", Some(policy.clone()))
+                .expect("Setting policy should succeed");
             assert_eq!(
                 passwords
-                    .get("example.org", "blabber", "2")
-                    .expect("Retrieval should succeed")
-                    .expose_secret(),
-                "asdf"
+                    .get_policy("example.com")
+                    .expect("Retrieving policy should succeed"),
+                Some(policy.clone())
             );
-            assert!(passwords
-                .has("www.example.org", "blabber", "2")
-                .expect("Check should succeed"));
             assert_eq!(
                 passwords
-                    .get("www.example.org", "blabber", "2")
-                    .expect("Retrieval should succeed")
-                    .expose_secret(),
-                "asdf"
+                    .get_alias("example.org")
+                    .expect("Alias should still be present"),
+                "example.com"
+            );
+
+            passwords
+                .set_policy("example.net", Some(policy.clone()))
+                .expect("Setting policy should succeed");
+            assert_eq!(
+                passwords
+                    .get_policy("example.net")
+                    .expect("Retrieving policy should succeed"),
+                Some(policy)
             );
 
-            assert!(passwords
-                .has("example.info", "test", "yet another")
-                .expect("Check should succeed"));
+            passwords
+                .set_policy("example.com", None)
+                .expect("Removing policy should succeed");
             assert_eq!(
                 passwords
-                    .get("example.info", "test", "yet another")
-                    .expect("Retrieval should succeed")
-                    .expose_secret(),
-                "rjtfxqf4"
+                    .get_policy("example.com")
+                    .expect("Retrieving policy should succeed"),
+                None
             );
-            assert!(passwords
-                .has("www.example.info", "test", "yet another")
+        }
+
+        #[test]
+        fn clamp_length() {
+            let policy = PasswordPolicy::new(Some(8), Some(12), CharacterSet::all(), "");
+            assert_eq!(policy.clamp_length(4), 8);
+            assert_eq!(policy.clamp_length(10), 10);
+            assert_eq!(policy.clamp_length(16), 12);
+
+            let policy = PasswordPolicy::new(None, None, CharacterSet::all(), "<>");
+            assert_eq!(policy.clamp_length(16), 16);
+            assert!(policy.has_forbidden("a<b"));
+            assert!(!policy.has_forbidden("ab"));
+        }
+
+        #[test]
+        fn derive_generated() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            let charset = CharacterType::Lower | CharacterType::Digit;
+            let value = passwords
+                .derive_generated("www.example.org", "test", "1", 10, charset, None)
+                .expect("Deriving password should succeed");
+            assert!(!passwords
+                .has("example.com", "test", "1")
                 .expect("Check should succeed"));
+
+            passwords
+                .set_generated("example.com", "test", "1", 10, charset, None)
+                .expect("Adding password should succeed");
             assert_eq!(
                 passwords
-                    .get("www.example.info", "test", "yet another")
+                    .get("example.com", "test", "1")
                     .expect("Retrieval should succeed")
                     .expose_secret(),
-                "rjtfxqf4"
+                value.expose_secret()
             );
-            assert!(!passwords
-                .has("example.info", "blubber", "")
-                .expect("Check should succeed"));
+        }
+    }
+
+    mod export {
+        use super::*;
+
+        #[test]
+        fn export_site() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+
             assert!(matches!(
                 passwords
-                    .get("example.info", "blubber", "")
-                    .expect_err("Retrieval should fail"),
-                Error::KeyMissing { .. }
+                    .export_site("example.com")
+                    .err()
+                    .expect("Passwords should be locked"),
+                Error::PasswordsLocked
             ));
-            assert!(!passwords
-                .has("www.example.info", "blubber", "")
-                .expect("Check should succeed"));
+
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
             assert!(matches!(
                 passwords
-                    .get("www.example.info", "blubber", "")
-                    .expect_err("Retrieval should fail"),
-                Error::KeyMissing { .. }
+                    .export_site("example.net")
+                    .err()
+                    .expect("Unknown site should fail"),
+                Error::KeyMissing
             ));
-        }
-    }
 
-    mod addition {
-        use super::*;
-        use crate::storage_types::CharacterType;
+            let export = passwords
+                .export_site("www.example.org")
+                .unwrap_or_else(|_| panic!("Export should succeed"));
+            assert_eq!(export.site.name(), "example.com");
+            assert_eq!(export.aliases, vec!["example.org"]);
+
+            let exported = export
+                .passwords
+                .iter()
+                .map(|(password, value)| {
+                    (
+                        password.id().name().to_string(),
+                        password.id().revision().to_string(),
+                        value.expose_secret().to_string(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                exported,
+                vec![
+                    ("blabber".to_string(), "2".to_string(), "asdf".to_string()),
+                    (
+                        "blubber".to_string(),
+                        "".to_string(),
+                        "SUDJjn&%:nBe}cr8".to_string()
+                    ),
+                ]
+            );
+
+            let export = passwords
+                .export_site("example.info")
+                .unwrap_or_else(|_| panic!("Export should succeed"));
+            assert_eq!(export.site.name(), "example.info");
+            assert!(export.aliases.is_empty());
+            assert_eq!(export.passwords.len(), 1);
+        }
 
         #[test]
-        fn add_passwords() {
-            let io = MemoryIO::new(empty_data());
+        fn export_all() {
+            let io = MemoryIO::new(default_data());
             let mut passwords = Passwords::new(io);
+
+            assert!(matches!(
+                passwords
+                    .export_all()
+                    .expect_err("Passwords should be locked"),
+                Error::PasswordsLocked
+            ));
+
             passwords
                 .unlock(primary_pass())
                 .expect("Passwords should unlock");
 
+            let export = passwords
+                .export_all()
+                .unwrap_or_else(|_| panic!("Export should succeed"));
+            assert_eq!(
+                export
+                    .iter()
+                    .map(|(site, list)| {
+                        let mut names = list
+                            .iter()
+                            .map(|password| password.id().name())
+                            .collect::<Vec<&str>>();
+                        names.sort();
+                        (site.name(), site.alias(), names)
+                    })
+                    .collect::<Vec<_>>(),
+                vec![
+                    ("example.com", None, vec!["blabber", "blubber"]),
+                    ("example.info", None, vec!["test"]),
+                    ("example.org", Some("example.com"), Vec::new()),
+                ]
+            );
+        }
+
+        #[test]
+        fn backup() {
+            let io = MemoryIO::new(default_data());
+            let passwords = Passwords::new(io);
+
+            let backup: crate::json::Value =
+                crate::json::from_str(&passwords.backup().expect("Backup should succeed"))
+                    .expect("Backup should be valid JSON");
+            assert_eq!(backup["application"], "pfp");
+            assert_eq!(backup["format"], 3);
+
+            let data = backup["data"]
+                .as_object()
+                .expect("Backup should contain data");
+            assert_eq!(data.len(), default_data().len());
+            for (key, value) in default_data() {
+                assert_eq!(data[&key], value);
+            }
+        }
+    }
+
+    mod statistics {
+        use super::*;
+
+        #[test]
+        fn statistics() {
+            let mut passwords = Passwords::new(MemoryIO::new(default_data()));
             assert!(matches!(
                 passwords
-                    .get_alias("example.org")
-                    .expect_err("Alias should not be present"),
-                Error::NoSuchAlias { .. }
+                    .statistics()
+                    .expect_err("Passwords should be locked"),
+                Error::PasswordsLocked
             ));
+
+            passwords
+                .unlock(primary_pass())
+                .expect("Unlock should succeed");
+            let statistics = passwords
+                .statistics()
+                .expect("Statistics should be available");
+            assert_eq!(statistics.sites, 2);
+            assert_eq!(statistics.aliases, 1);
+            assert_eq!(statistics.generated, 2);
+            assert_eq!(statistics.stored, 1);
+            assert_eq!(statistics.lengths, BTreeMap::from([(8, 1), (16, 1)]));
+            assert_eq!(statistics.with_notes, 2);
+        }
+
+        #[test]
+        fn duplicates() {
+            let mut passwords = Passwords::new(MemoryIO::new(default_data()));
             assert!(matches!(
                 passwords
-                    .set_alias("www.example.org", "example.org")
-                    .expect_err("Adding alias should fail"),
-                Error::AliasToSelf { .. }
+                    .find_duplicates(false)
+                    .expect_err("Passwords should be locked"),
+                Error::PasswordsLocked
             ));
+
             passwords
-                .set_alias("www.example.org", "www.example.com")
-                .expect("Adding alias should succeed");
-            assert!(matches!(
+                .unlock(primary_pass())
+                .expect("Unlock should succeed");
+            assert!(passwords
+                .find_duplicates(true)
+                .expect("Search should succeed")
+                .is_empty());
+
+            passwords
+                .set_stored(
+                    "example.net",
+                    "x",
+                    "1",
+                    SecretString::new("asdf".to_string()),
+                )
+                .expect("Adding password should succeed");
+            passwords
+                .set_stored(
+                    "example.net",
+                    "y",
+                    "1",
+                    SecretString::new("other".to_string()),
+                )
+                .expect("Adding password should succeed");
+            let generated = passwords
+                .get("example.com", "blubber", "1")
+                .expect("Retrieval should succeed");
+            passwords
+                .set_stored("example.net", "z", "1", generated)
+                .expect("Adding password should succeed");
+
+            assert_eq!(
                 passwords
-                    .set_alias("www.example.com", "example.org")
-                    .expect_err("Adding alias should fail"),
-                Error::AliasToSelf { .. }
-            ));
+                    .find_duplicates(false)
+                    .expect("Search should succeed"),
+                vec![vec![
+                    PasswordId::new("example.com", "blabber", "2"),
+                    PasswordId::new("example.net", "x", "1"),
+                ]]
+            );
             assert_eq!(
                 passwords
-                    .get_alias("example.org")
-                    .expect("Alias should be present"),
-                "example.com"
+                    .find_duplicates(true)
+                    .expect("Search should succeed"),
+                vec![
+                    vec![
+                        PasswordId::new("example.com", "blabber", "2"),
+                        PasswordId::new("example.net", "x", "1"),
+                    ],
+                    vec![
+                        PasswordId::new("example.com", "blubber", "1"),
+                        PasswordId::new("example.net", "z", "1"),
+                    ],
+                ]
             );
+        }
+    }
+
+    mod importing {
+        use super::*;
+
+        #[test]
+        fn unlock_from() {
+            let mut passwords = Passwords::new(MemoryIO::new(default_data()));
+            let mut backup = Passwords::new(MemoryIO::new(default_data()));
+
+            assert!(matches!(
+                backup
+                    .unlock_from(&passwords)
+                    .expect_err("Unlocking should fail"),
+                Error::PasswordsLocked
+            ));
 
             passwords
-                .set_generated("example.com", "blubber", "", 16, CharacterSet::all())
-                .expect("Adding password should succeed");
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+            backup
+                .unlock_from(&passwords)
+                .expect("Backup should unlock");
+            assert!(backup.unlocked());
+        }
+
+        #[test]
+        fn import_entries() {
+            let mut passwords = Passwords::new(MemoryIO::new(HashMap::new()));
+            passwords
+                .reset(primary_pass())
+                .expect("Reset should succeed");
             passwords
                 .set_stored(
                     "example.com",
                     "blabber",
                     "2",
-                    SecretString::new("asdf".to_owned()),
-                )
-                .expect("Adding password should succeed");
-            passwords
-                .set_generated(
-                    "example.info",
-                    "test",
-                    "yet another",
-                    8,
-                    CharacterType::Lower | CharacterType::Digit,
+                    SecretString::new("existing".to_owned()),
                 )
                 .expect("Adding password should succeed");
 
-            assert!(matches!(
-                passwords
-                    .set_alias("www.example.com", "example.info")
-                    .expect_err("Adding alias should fail"),
-                Error::SiteHasPasswords { .. }
-            ));
+            let mut backup = Passwords::new(MemoryIO::new(default_data()));
+            backup
+                .unlock_from(&passwords)
+                .expect("Backup should unlock");
 
+            let mut conflicts = Vec::new();
+            let imported = passwords
+                .import_entries(
+                    backup.export_all().expect("Export should succeed"),
+                    |password| {
+                        conflicts.push(password.id().clone());
+                        false
+                    },
+                )
+                .expect("Import should succeed");
+            assert_eq!(imported, 2);
+            assert_eq!(
+                conflicts,
+                vec![PasswordId::new("example.com", "blabber", "2")]
+            );
             assert_eq!(
                 passwords
-                    .get("example.com", "blubber", "")
+                    .get("example.com", "blabber", "2")
                     .expect("Retrieval should succeed")
                     .expose_secret(),
-                "SUDJjn&%:nBe}cr8"
+                "existing"
             );
             assert_eq!(
                 passwords
-                    .get("example.org", "blubber", "")
-                    .expect("Retrieval should succeed")
+                    .get_alias("example.org")
+                    .expect("Alias should be imported"),
+                "example.com"
+            );
+            assert_eq!(
+                passwords
+                    .get_notes("example.info", "test", "yet another")
+                    .expect("Getting notes should succeed")
                     .expose_secret(),
-                "SUDJjn&%:nBe}cr8"
+                "nothing here"
             );
 
+            let imported = passwords
+                .import_entries(backup.export_all().expect("Export should succeed"), |_| {
+                    true
+                })
+                .expect("Import should succeed");
+            assert_eq!(imported, 3);
             assert_eq!(
                 passwords
-                    .get("www.example.com", "blabber", "2")
+                    .get("example.com", "blabber", "2")
                     .expect("Retrieval should succeed")
                     .expose_secret(),
                 "asdf"
             );
+        }
+    }
+
+    mod migration {
+        use super::*;
+        use crate::storage_types::CharacterType;
+
+        fn legacy_data() -> HashMap<String, String> {
+            let mut data = default_data();
+            data.insert(
+                // example.com\x00legacy\x00 (hmac-sha256)
+                "site:fRTOldDD+lTwIBS8G+eUkrIzvNsfdGRSWQXrXqszDHM=:qWhfZOFEdqACexKf3dxNh+lGAaN/LCf6xELNB7I9Ts8=".to_string(),
+                // {"type":"generated","site":"example.com","name":"legacy","revision":"","length":12,"lower":true,"upper":true,"number":true,"symbol":false,"notes":"old one"} encrypted
+                "YWJjZGVmZ2hpamts_b/AH4RUorsFjuRRJBYJzOBc4M+sHMG+8u1ZPdAegGgJ0WRr0DnFfptHwCQdRh4sfyWuuGm0+fQIJSFaVrprvKt0Z3jZz1OmtAJyvMwQiOARVxSYbhStzi6fdhdfNvxjQpCuvvxVdq7CVNk6GnjNZTtaNQdFaQYFqX0yjiKgwLCeI3/Jo1PMzdycTfv8IVUC9clyEESkKQV4JzNZyCnMts9Vzgg3/dZGrUMPGmA==".to_string(),
+            );
+            data
+        }
+
+        fn get_legacy(passwords: &Passwords<MemoryIO>) -> Password {
+            passwords
+                .list("example.com", "legacy")
+                .next()
+                .expect("Password should be present")
+        }
+
+        #[test]
+        fn migrate_to_stored() {
+            let io = MemoryIO::new(legacy_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            let password = get_legacy(&passwords);
+            assert!(matches!(password, Password::LegacyGenerated(_)));
+            assert_eq!(password.notes().expose_secret(), "old one");
+            if let Password::LegacyGenerated(password) = password {
+                assert_eq!(password.length(), 12);
+                assert_eq!(
+                    password.charset(),
+                    CharacterType::Lower | CharacterType::Upper | CharacterType::Digit
+                );
+            }
             assert_eq!(
                 passwords
-                    .get("www.example.org", "blabber", "2")
+                    .get("example.com", "legacy", "1")
                     .expect("Retrieval should succeed")
                     .expose_secret(),
-                "asdf"
+                "E24gxYNZA3pv"
             );
 
             assert_eq!(
                 passwords
-                    .get("example.info", "test", "yet another")
+                    .migrate_generated(false)
+                    .expect("Migration should succeed"),
+                1
+            );
+            let password = get_legacy(&passwords);
+            assert!(matches!(password, Password::Stored(_)));
+            assert_eq!(password.notes().expose_secret(), "old one");
+            assert_eq!(
+                passwords
+                    .get("example.com", "legacy", "1")
                     .expect("Retrieval should succeed")
                     .expose_secret(),
-                "rjtfxqf4"
+                "E24gxYNZA3pv"
+            );
+
+            assert_eq!(
+                passwords
+                    .migrate_generated(false)
+                    .expect("Migration should succeed"),
+                0
+            );
+        }
+
+        #[test]
+        fn regenerate() {
+            let io = MemoryIO::new(legacy_data());
+            let mut passwords = Passwords::new(io);
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+
+            assert_eq!(
+                passwords
+                    .migrate_generated(true)
+                    .expect("Migration should succeed"),
+                1
+            );
+            let password = get_legacy(&passwords);
+            assert!(matches!(password, Password::Generated(_)));
+            assert_eq!(password.notes().expose_secret(), "old one");
+            assert_eq!(
+                passwords
+                    .get("example.com", "legacy", "1")
+                    .expect("Retrieval should succeed")
+                    .expose_secret(),
+                "weFH5sPBNdRH"
+            );
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    mod raw_entry {
+        use super::*;
+
+        #[test]
+        fn get_raw_entry() {
+            let io = MemoryIO::new(default_data());
+            let mut passwords = Passwords::new(io);
+
+            const KEY: &str = "site:fRTOldDD+lTwIBS8G+eUkrIzvNsfdGRSWQXrXqszDHM=:h2pnx6RFyNbAUBLcuQYz9w79/vnf4fgJlY/c+EP44d8=";
+            assert!(matches!(
+                passwords
+                    .raw_entry(KEY)
+                    .err()
+                    .expect("Passwords should be locked"),
+                Error::PasswordsLocked
+            ));
+
+            passwords
+                .unlock(primary_pass())
+                .expect("Passwords should unlock");
+            assert_eq!(
+                passwords
+                    .raw_entry(KEY)
+                    .unwrap_or_else(|_| panic!("Entry should be present"))
+                    .expose_secret(),
+                br#"{"type":"stored","site":"example.com","name":"blabber","revision":"2","password":"asdf","notes":"hi there!"}"#
             );
             assert!(matches!(
                 passwords
-                    .get("example.info", "blubber", "")
-                    .expect_err("Retrieval should fail"),
-                Error::KeyMissing { .. }
+                    .raw_entry("site:missing")
+                    .err()
+                    .expect("Entry should be missing"),
+                Error::KeyMissing
             ));
         }
     }
@@ -19,6 +19,10 @@ const SALT_SIZE: usize = 16;
 const NONCE_SIZE: usize = 12;
 const TAG_SIZE: usize = 16;
 
+/// Maximal length of the recovery code text accepted by [decode()](fn.decode.html), including
+/// whitespace and punctuation. This is sufficient for passwords of several kilobytes.
+pub const MAX_CODE_LENGTH: usize = 16384;
+
 /// Generates a new recovery code for the password.
 ///
 /// The password is encrypted and can only be decrypted if the right primary password is known.
@@ -77,13 +81,12 @@ pub fn generate(
     let (nonce_base64, ciphertext_base64) =
         encrypted.split_once('_').ok_or(Error::InvalidCiphertext)?;
 
-    let nonce = base64::decode(nonce_base64).map_err(|error| Error::InvalidBase64 { error })?;
+    let nonce = base64::decode(nonce_base64)?;
     if nonce.len() != NONCE_SIZE {
         return Err(Error::UnexpectedData);
     }
 
-    let ciphertext =
-        base64::decode(ciphertext_base64).map_err(|error| Error::InvalidBase64 { error })?;
+    let ciphertext = base64::decode(ciphertext_base64)?;
     if ciphertext.len() != password_len + fill_bytes + TAG_SIZE {
         return Err(Error::UnexpectedData);
     }
@@ -171,7 +174,10 @@ pub fn format_code(code: &[u8], insert_punctuation: bool) -> String {
 /// [Error::RecoveryCodeIncomplete](../error/enum.Error.html#variant.RecoveryCodeIncomplete) for
 /// recovery codes that are missing part of their data and
 /// [Error::DecryptionFailure](../error/enum.Error.html#variant.DecryptionFailure) in case of a
-/// wrong primary password.
+/// wrong primary password. Text longer than [MAX_CODE_LENGTH](constant.MAX_CODE_LENGTH.html)
+/// is rejected with
+/// [Error::RecoveryCodeTooLong](../error/enum.Error.html#variant.RecoveryCodeTooLong) without
+/// attempting to decode it.
 ///
 /// ```
 /// use pfp::recovery_codes;
@@ -186,6 +192,10 @@ pub fn format_code(code: &[u8], insert_punctuation: bool) -> String {
 /// }
 /// ```
 pub fn decode(code: &str, primary_password: &SecretString) -> Result<SecretString, Error> {
+    if code.len() > MAX_CODE_LENGTH {
+        return Err(Error::RecoveryCodeTooLong);
+    }
+
     let decoded = validate(code)?;
 
     let without_checksums = decoded
@@ -232,8 +242,7 @@ pub fn decode(code: &str, primary_password: &SecretString) -> Result<SecretStrin
         SecretVec::<u8>::new(decrypted.expose_secret()[..end_pos].to_vec())
     };
 
-    let decrypted_str = std::str::from_utf8(stripped.expose_secret())
-        .map_err(|error| Error::InvalidUtf8 { error })?;
+    let decrypted_str = std::str::from_utf8(stripped.expose_secret())?;
     Ok(SecretString::new(decrypted_str.to_owned()))
 }
 
@@ -454,4 +463,41 @@ mod tests {
             Error::RecoveryCodeIncomplete { .. }
         ));
     }
+
+    #[test]
+    fn too_long() {
+        assert!(matches!(
+            decode(&"A".repeat(MAX_CODE_LENGTH + 1), &primary_pass())
+                .expect_err("Password recovery should fail"),
+            Error::RecoveryCodeTooLong
+        ));
+
+        // Long passwords still fit
+        let encryption_key = passwords::get_encryption_key(&primary_pass(), SALT);
+        let value = "x".repeat(4096);
+        let code = generate(&SecretString::new(value.clone()), SALT, &encryption_key)
+            .expect("Generating code should succeed");
+        assert_eq!(
+            decode(&code, &primary_pass())
+                .expect("Password recovery should succeed")
+                .expose_secret(),
+            &value
+        );
+    }
+
+    #[test]
+    fn whitespace_roundtrip() {
+        let encryption_key = passwords::get_encryption_key(&primary_pass(), SALT);
+        for value in ["  asdf  ", "asdf ", "\tasdf\n", " "] {
+            let code = generate(&SecretString::new(value.to_owned()), SALT, &encryption_key)
+                .expect("Generating code should succeed");
+            assert_eq!(
+                decode(&code, &primary_pass())
+                    .expect("Password recovery should succeed")
+                    .expose_secret()
+                    .as_bytes(),
+                value.as_bytes()
+            );
+        }
+    }
 }
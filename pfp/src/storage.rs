@@ -7,41 +7,198 @@
 use crate::crypto;
 use crate::error::Error;
 use crate::json;
+use crate::passwords;
 use crate::storage_io;
-use crate::storage_types::{GeneratedPassword, Password, PasswordId, Site, StoredPassword};
+use crate::storage_types::{
+    GeneratedPassword, KdfParams, Password, PasswordId, PasswordPolicy, Site, StoredPassword,
+};
 
+use secrecy::zeroize::Zeroize;
 use secrecy::{ExposeSecret, SecretString, SecretVec};
+use std::cell::RefCell;
+use std::collections::HashMap;
 
 const SALT_KEY: &str = "salt";
+const KDF_PARAMS_KEY: &str = "kdf-params";
 const HMAC_SECRET_KEY: &str = "hmac-secret";
 const STORAGE_PREFIX: &str = "site:";
 
+/// Maximal number of aliases followed when resolving a site. Aliases are normally set up to
+/// point to a regular site directly, longer chains only occur in hand-edited or imported data.
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// Serializes a value into a buffer allocated with its final size. Growing the buffer while
+/// serializing would leave partial copies of the data behind in freed memory.
+fn to_secret_vec<T>(value: &T) -> Result<SecretVec<u8>, Error>
+where
+    T: json::Serializable + ?Sized,
+{
+    struct Counter(usize);
+
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    json::to_writer(&mut counter, value)?;
+    let mut buffer = Vec::with_capacity(counter.0);
+    if let Err(error) = json::to_writer(&mut buffer, value) {
+        buffer.zeroize();
+        return Err(error.into());
+    }
+    Ok(SecretVec::new(buffer))
+}
+
 #[derive(Debug)]
 pub struct Storage<IO> {
     io: IO,
+    lenient: bool,
+    in_transaction: bool,
+    flush_deferred: bool,
+    site_cache: Option<RefCell<HashMap<String, Site>>>,
 }
 
 impl<IO: storage_io::StorageIO> Storage<IO> {
     pub fn new(io: IO) -> Self {
-        Self { io }
+        Self {
+            io,
+            lenient: false,
+            in_transaction: false,
+            flush_deferred: false,
+            site_cache: None,
+        }
+    }
+
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    /// Enables or disables caching of decrypted site entries. Cache entries are dropped whenever
+    /// the corresponding storage entry changes.
+    pub fn set_site_cache(&mut self, enabled: bool) {
+        self.site_cache = if enabled {
+            Some(RefCell::new(HashMap::new()))
+        } else {
+            None
+        };
+    }
+
+    pub fn clear_site_cache(&self) {
+        if let Some(cache) = &self.site_cache {
+            cache.borrow_mut().clear();
+        }
     }
 
     pub fn clear(
         &mut self,
         salt: &[u8],
+        kdf_params: &KdfParams,
         hmac_secret: &SecretVec<u8>,
         encryption_key: &SecretVec<u8>,
     ) -> Result<(), Error> {
+        self.clear_site_cache();
         self.io.clear();
         self.set_salt(salt);
+        self.set_kdf_params(kdf_params)?;
         self.set_hmac_secret(hmac_secret, encryption_key)?;
         Ok(())
     }
 
+    pub fn rekey(
+        &mut self,
+        salt: &[u8],
+        kdf_params: &KdfParams,
+        hmac_secret: &SecretVec<u8>,
+        old_key: &SecretVec<u8>,
+        new_key: &SecretVec<u8>,
+    ) -> Result<(), Error> {
+        // Decrypt everything before making changes, so that failures leave the data untouched
+        let mut entries = Vec::new();
+        for key in self.io.keys_with_prefix(STORAGE_PREFIX) {
+            let decrypted = crypto::decrypt_data(self.io.get(key)?, old_key)?;
+            entries.push((key.to_string(), decrypted));
+        }
+
+        self.clear_site_cache();
+        for (key, decrypted) in entries {
+            self.io.set(key, crypto::encrypt_data(&decrypted, new_key));
+        }
+        self.set_salt(salt);
+        self.set_kdf_params(kdf_params)?;
+        self.set_hmac_secret(hmac_secret, new_key)
+    }
+
     pub fn flush(&mut self) -> Result<(), Error> {
+        if self.in_transaction || self.flush_deferred {
+            Ok(())
+        } else {
+            self.io.flush()
+        }
+    }
+
+    /// Turns deferring of flushes on or off, returns the previous state.
+    pub fn defer_flush(&mut self, defer: bool) -> bool {
+        std::mem::replace(&mut self.flush_deferred, defer)
+    }
+
+    pub fn checkpoint(&mut self) -> Result<(), Error> {
         self.io.flush()
     }
 
+    pub fn io(&self) -> &IO {
+        &self.io
+    }
+
+    pub fn io_mut(&mut self) -> &mut IO {
+        self.clear_site_cache();
+        &mut self.io
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.in_transaction
+    }
+
+    pub fn begin_transaction(&mut self) -> Result<(), Error> {
+        if self.in_transaction {
+            return Err(Error::TransactionInProgress);
+        }
+        self.in_transaction = true;
+        Ok(())
+    }
+
+    pub fn commit_transaction(&mut self) -> Result<(), Error> {
+        if !self.in_transaction {
+            return Err(Error::NoTransaction);
+        }
+        self.io.flush()?;
+        self.in_transaction = false;
+        Ok(())
+    }
+
+    pub fn rollback_transaction(&mut self) -> Result<(), Error> {
+        if !self.in_transaction {
+            return Err(Error::NoTransaction);
+        }
+        self.clear_site_cache();
+        self.io.reload()?;
+        self.in_transaction = false;
+        Ok(())
+    }
+
+    pub fn reload(&mut self) -> Result<(), Error> {
+        self.clear_site_cache();
+        self.io.reload()?;
+        self.in_transaction = false;
+        Ok(())
+    }
+
     pub fn initialized(&self) -> bool {
         self.io.contains_key(SALT_KEY) && self.io.contains_key(HMAC_SECRET_KEY)
     }
@@ -54,17 +211,41 @@ impl<IO: storage_io::StorageIO> Storage<IO> {
     where
         T: for<'de> json::Deserializable<'de>,
     {
+        let decrypted = self.get_raw(key, encryption_key)?;
+        json::secret_deserializer::from_secret_slice(decrypted.expose_secret(), self.lenient)
+            .map_err(Error::from)
+    }
+
+    fn get_site_entry(&self, key: &str, encryption_key: &SecretVec<u8>) -> Result<Site, Error> {
+        if let Some(cache) = &self.site_cache {
+            if let Some(site) = cache.borrow().get(key) {
+                return Ok(site.clone());
+            }
+            let site: Site = self.get(key, encryption_key)?;
+            cache.borrow_mut().insert(key.to_string(), site.clone());
+            Ok(site)
+        } else {
+            self.get(key, encryption_key)
+        }
+    }
+
+    pub fn get_raw(
+        &self,
+        key: &str,
+        encryption_key: &SecretVec<u8>,
+    ) -> Result<SecretVec<u8>, Error> {
         let value = self.io.get(key)?;
-        let decrypted = crypto::decrypt_data(value, encryption_key)?;
-        json::from_slice(decrypted.expose_secret()).map_err(|error| Error::InvalidJson { error })
+        crypto::decrypt_data(value, encryption_key)
     }
 
     fn set<T>(&mut self, key: &str, value: &T, encryption_key: &SecretVec<u8>) -> Result<(), Error>
     where
         T: json::Serializable,
     {
-        let serialized =
-            SecretVec::new(json::to_vec(value).map_err(|error| Error::InvalidJson { error })?);
+        let serialized = to_secret_vec(value)?;
+        if let Some(cache) = &self.site_cache {
+            cache.borrow_mut().remove(key);
+        }
         self.io.set(
             key.to_string(),
             crypto::encrypt_data(&serialized, encryption_key),
@@ -73,6 +254,9 @@ impl<IO: storage_io::StorageIO> Storage<IO> {
     }
 
     fn remove(&mut self, key: &str) -> Result<(), Error> {
+        if let Some(cache) = &self.site_cache {
+            cache.borrow_mut().remove(key);
+        }
         self.io.remove(key)
     }
 
@@ -81,26 +265,48 @@ impl<IO: storage_io::StorageIO> Storage<IO> {
             .io
             .get(SALT_KEY)
             .map_err(|_| Error::StorageNotInitialized)?;
-        base64::decode(encoded.as_bytes()).map_err(|error| Error::InvalidBase64 { error })
+        base64::decode(encoded.as_bytes()).map_err(Error::from)
     }
 
     fn set_salt(&mut self, salt: &[u8]) {
         self.io.set(SALT_KEY.to_string(), base64::encode(salt));
     }
 
+    /// Retrieves the key derivation parameters, storage without any uses the defaults.
+    pub fn get_kdf_params(&self) -> Result<KdfParams, Error> {
+        if !self.contains(KDF_PARAMS_KEY) {
+            return Ok(KdfParams::default());
+        }
+        let params: KdfParams = json::from_str(self.io.get(KDF_PARAMS_KEY)?)?;
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Stores the key derivation parameters. Default parameters aren't stored explicitly, so that
+    /// such storage stays readable by older versions.
+    fn set_kdf_params(&mut self, params: &KdfParams) -> Result<(), Error> {
+        if *params == KdfParams::default() {
+            if self.contains(KDF_PARAMS_KEY) {
+                self.io.remove(KDF_PARAMS_KEY)?;
+            }
+        } else {
+            self.io
+                .set(KDF_PARAMS_KEY.to_string(), json::to_string(params)?);
+        }
+        Ok(())
+    }
+
     pub fn get_hmac_secret(&self, encryption_key: &SecretVec<u8>) -> Result<SecretVec<u8>, Error> {
         let ciphertext = self
             .io
             .get(HMAC_SECRET_KEY)
             .map_err(|_| Error::StorageNotInitialized)?;
         let decrypted = crypto::decrypt_data(ciphertext, encryption_key)?;
-        let hmac_secret = SecretString::new(
-            json::from_slice::<String>(decrypted.expose_secret())
-                .map_err(|error| Error::InvalidJson { error })?,
-        );
+        let hmac_secret = SecretString::new(json::secret_deserializer::string_from_secret_slice(
+            decrypted.expose_secret(),
+        )?);
 
-        let decoded = base64::decode(hmac_secret.expose_secret())
-            .map_err(|error| Error::InvalidBase64 { error })?;
+        let decoded = base64::decode(hmac_secret.expose_secret())?;
         Ok(SecretVec::new(decoded))
     }
 
@@ -110,9 +316,7 @@ impl<IO: storage_io::StorageIO> Storage<IO> {
         encryption_key: &SecretVec<u8>,
     ) -> Result<(), Error> {
         let encoded = SecretString::new(base64::encode(hmac_secret.expose_secret()));
-        let stringified = SecretVec::new(
-            json::to_vec(encoded.expose_secret()).map_err(|error| Error::InvalidJson { error })?,
-        );
+        let stringified = to_secret_vec(encoded.expose_secret())?;
         let encrypted = crypto::encrypt_data(&stringified, encryption_key);
         self.io.set(HMAC_SECRET_KEY.to_string(), encrypted);
         Ok(())
@@ -158,7 +362,8 @@ impl<IO: storage_io::StorageIO> Storage<IO> {
     }
 
     pub fn normalize_site(&self, site: &str) -> String {
-        site.strip_prefix("www.").unwrap_or(site).to_string()
+        let host = passwords::url_host(site).unwrap_or_else(|| site.to_string());
+        host.strip_prefix("www.").unwrap_or(&host).to_string()
     }
 
     pub fn resolve_site(
@@ -166,10 +371,15 @@ impl<IO: storage_io::StorageIO> Storage<IO> {
         site: &str,
         hmac_secret: &SecretVec<u8>,
         encryption_key: &SecretVec<u8>,
-    ) -> String {
-        let normalized = self.normalize_site(site);
-        self.get_alias(&normalized, hmac_secret, encryption_key)
-            .unwrap_or(normalized)
+    ) -> Result<String, Error> {
+        let mut resolved = self.normalize_site(site);
+        for _ in 0..MAX_ALIAS_DEPTH {
+            match self.get_alias(&resolved, hmac_secret, encryption_key) {
+                Ok(alias) => resolved = alias,
+                Err(_) => return Ok(resolved),
+            }
+        }
+        Err(Error::AliasCycle)
     }
 
     pub fn ensure_site_data(
@@ -179,7 +389,7 @@ impl<IO: storage_io::StorageIO> Storage<IO> {
         encryption_key: &SecretVec<u8>,
     ) -> Result<(), Error> {
         let key = self.get_site_key(site, hmac_secret);
-        if self.get::<Site>(&key, encryption_key).is_err() {
+        if self.get_site_entry(&key, encryption_key).is_err() {
             self.set(&key, &Site::new(site, None), encryption_key)
         } else {
             Ok(())
@@ -193,7 +403,7 @@ impl<IO: storage_io::StorageIO> Storage<IO> {
         encryption_key: &SecretVec<u8>,
     ) -> Result<Site, Error> {
         let key = self.get_site_key(site, hmac_secret);
-        self.get(&key, encryption_key)
+        self.get_site_entry(&key, encryption_key)
     }
 
     pub fn set_alias(
@@ -218,7 +428,9 @@ impl<IO: storage_io::StorageIO> Storage<IO> {
         encryption_key: &SecretVec<u8>,
     ) -> Result<(), Error> {
         let key = self.get_site_key(site, hmac_secret);
-        let site: Site = self.get(&key, encryption_key).or(Err(Error::NoSuchAlias))?;
+        let site = self
+            .get_site_entry(&key, encryption_key)
+            .or(Err(Error::NoSuchAlias))?;
         if site.alias().is_none() {
             Err(Error::NoSuchAlias)
         } else {
@@ -226,6 +438,19 @@ impl<IO: storage_io::StorageIO> Storage<IO> {
         }
     }
 
+    pub fn set_policy(
+        &mut self,
+        site: &str,
+        policy: Option<PasswordPolicy>,
+        hmac_secret: &SecretVec<u8>,
+        encryption_key: &SecretVec<u8>,
+    ) -> Result<(), Error> {
+        let key = self.get_site_key(site, hmac_secret);
+        let mut site = self.get_site_entry(&key, encryption_key)?;
+        site.set_policy(policy);
+        self.set(&key, &site, encryption_key)
+    }
+
     pub fn remove_site(&mut self, site: &str, hmac_secret: &SecretVec<u8>) -> Result<(), Error> {
         let key = self.get_site_key(site, hmac_secret);
         self.remove(&key)
@@ -292,26 +517,70 @@ impl<IO: storage_io::StorageIO> Storage<IO> {
         encryption_key: &'a SecretVec<u8>,
     ) -> impl Iterator<Item = Password> + 'a {
         let prefix = self.get_site_prefix(site, hmac_secret);
-        self.io.keys().filter_map(move |key| {
-            if key.starts_with(&prefix) {
-                self.get(key, encryption_key).ok()
+        self.io
+            .keys_with_prefix(&prefix)
+            .filter_map(move |key| self.get(key, encryption_key).ok())
+    }
+
+    pub fn try_list_passwords(
+        &self,
+        site: &str,
+        hmac_secret: &SecretVec<u8>,
+        encryption_key: &SecretVec<u8>,
+    ) -> Result<Vec<Password>, Error> {
+        let prefix = self.get_site_prefix(site, hmac_secret);
+        self.io
+            .keys_with_prefix(&prefix)
+            .map(|key| self.get(key, encryption_key))
+            .collect()
+    }
+
+    pub fn list_all_passwords<'a>(
+        &'a self,
+        encryption_key: &'a SecretVec<u8>,
+    ) -> impl Iterator<Item = Password> + 'a {
+        self.io
+            .keys_with_prefix(STORAGE_PREFIX)
+            .filter_map(move |key| {
+                if key[STORAGE_PREFIX.len()..].find(':').is_some() {
+                    self.get(key, encryption_key).ok()
+                } else {
+                    None
+                }
+            })
+    }
+
+    pub fn verify_entries(&self, encryption_key: &SecretVec<u8>) -> (usize, Vec<(String, Error)>) {
+        let mut count = 0;
+        let mut failures = Vec::new();
+        for key in self.io.keys_with_prefix(STORAGE_PREFIX) {
+            count += 1;
+            let result = if key[STORAGE_PREFIX.len()..].contains(':') {
+                self.get::<Password>(key, encryption_key).map(|_| ())
             } else {
-                None
+                self.get::<Site>(key, encryption_key).map(|_| ())
+            };
+            if let Err(error) = result {
+                failures.push((key.to_string(), error));
             }
-        })
+        }
+        failures.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
+        (count, failures)
     }
 
     pub fn list_sites<'a>(
         &'a self,
         encryption_key: &'a SecretVec<u8>,
     ) -> impl Iterator<Item = Site> + 'a {
-        self.io.keys().filter_map(move |key| {
-            if key.starts_with(STORAGE_PREFIX) && key[STORAGE_PREFIX.len()..].find(':').is_none() {
-                self.get(key, encryption_key).ok()
-            } else {
-                None
-            }
-        })
+        self.io
+            .keys_with_prefix(STORAGE_PREFIX)
+            .filter_map(move |key| {
+                if key[STORAGE_PREFIX.len()..].find(':').is_none() {
+                    self.get_site_entry(key, encryption_key).ok()
+                } else {
+                    None
+                }
+            })
     }
 }
 
@@ -459,6 +728,37 @@ mod tests {
                 HMAC_SECRET
             );
         }
+
+        #[test]
+        fn read_kdf_params() {
+            let mut data = default_data();
+            assert_eq!(
+                Storage::new(MemoryIO::new(data.clone()))
+                    .get_kdf_params()
+                    .expect("Missing parameters should fall back to defaults"),
+                KdfParams::default()
+            );
+
+            data.insert(
+                "kdf-params".to_string(),
+                r#"{"algorithm":"scrypt","cost":2048}"#.to_string(),
+            );
+            assert_eq!(
+                Storage::new(MemoryIO::new(data.clone()))
+                    .get_kdf_params()
+                    .expect("Parameters should be read"),
+                KdfParams::Scrypt { cost: 2048 }
+            );
+
+            data.insert(
+                "kdf-params".to_string(),
+                r#"{"algorithm":"scrypt","cost":2000}"#.to_string(),
+            );
+            assert!(matches!(
+                Storage::new(MemoryIO::new(data)).get_kdf_params(),
+                Err(Error::InvalidKdfParams)
+            ));
+        }
     }
 
     mod clear {
@@ -471,14 +771,14 @@ mod tests {
             assert_eq!(storage.initialized(), false);
 
             storage
-                .clear(b"cba", &hmac_secret(), &enc_key())
+                .clear(b"cba", &KdfParams::default(), &hmac_secret(), &enc_key())
                 .expect("Clearing storage should succeed");
             assert_eq!(storage.initialized(), true);
             assert_eq!(storage.list_sites(&enc_key()).count(), 0);
 
             storage.flush().expect("Flush should succeed");
 
-            assert_eq!(storage.io.data(), &empty_data());
+            assert_eq!(storage.io.file_data(), &empty_data());
         }
 
         #[test]
@@ -487,14 +787,111 @@ mod tests {
             let mut storage = Storage::new(io);
 
             storage
-                .clear(b"cba", &hmac_secret(), &enc_key())
+                .clear(b"cba", &KdfParams::default(), &hmac_secret(), &enc_key())
                 .expect("Clearing storage should succeed");
             assert_eq!(storage.initialized(), true);
             assert_eq!(storage.list_sites(&enc_key()).count(), 0);
 
             storage.flush().expect("Flush should succeed");
 
-            assert_eq!(storage.io.data(), &empty_data());
+            assert_eq!(storage.io.file_data(), &empty_data());
+        }
+    }
+
+    mod transactions {
+        use super::*;
+
+        fn add_password(storage: &mut Storage<MemoryIO>) {
+            storage
+                .set_stored(
+                    StoredPassword::new(
+                        "example.com",
+                        "test",
+                        "",
+                        SecretString::new("asdf".to_owned()),
+                    ),
+                    &hmac_secret(),
+                    &enc_key(),
+                )
+                .expect("Adding password should succeed");
+            storage.flush().expect("Flush should succeed");
+        }
+
+        #[test]
+        fn commit() {
+            let io = MemoryIO::new(default_data());
+            let mut storage = Storage::new(io);
+
+            assert!(matches!(
+                storage
+                    .commit_transaction()
+                    .expect_err("Commit without transaction should fail"),
+                Error::NoTransaction
+            ));
+
+            storage
+                .begin_transaction()
+                .expect("Starting transaction should succeed");
+            assert!(storage.in_transaction());
+            assert!(matches!(
+                storage
+                    .begin_transaction()
+                    .expect_err("Nested transaction should fail"),
+                Error::TransactionInProgress
+            ));
+
+            add_password(&mut storage);
+            assert_eq!(storage.io.file_data(), &default_data());
+
+            storage.commit_transaction().expect("Commit should succeed");
+            assert!(!storage.in_transaction());
+            assert_ne!(storage.io.file_data(), &default_data());
+            assert_eq!(
+                storage
+                    .list_passwords("example.com", &hmac_secret(), &enc_key())
+                    .count(),
+                3
+            );
+        }
+
+        #[test]
+        fn rollback() {
+            let io = MemoryIO::new(default_data());
+            let mut storage = Storage::new(io);
+
+            assert!(matches!(
+                storage
+                    .rollback_transaction()
+                    .expect_err("Rollback without transaction should fail"),
+                Error::NoTransaction
+            ));
+
+            storage
+                .begin_transaction()
+                .expect("Starting transaction should succeed");
+            add_password(&mut storage);
+            assert_eq!(
+                storage
+                    .list_passwords("example.com", &hmac_secret(), &enc_key())
+                    .count(),
+                3
+            );
+
+            storage
+                .rollback_transaction()
+                .expect("Rollback should succeed");
+            assert!(!storage.in_transaction());
+            assert_eq!(storage.io.file_data(), &default_data());
+            assert_eq!(
+                storage
+                    .list_passwords("example.com", &hmac_secret(), &enc_key())
+                    .count(),
+                2
+            );
+
+            // Without a transaction changes are written immediately
+            add_password(&mut storage);
+            assert_ne!(storage.io.file_data(), &default_data());
         }
     }
 
@@ -736,13 +1133,17 @@ mod tests {
 
             assert_eq!(storage.normalize_site("example.com"), "example.com");
             assert_eq!(
-                storage.resolve_site("example.com", &hmac_secret(), &enc_key()),
+                storage
+                    .resolve_site("example.com", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve"),
                 "example.com"
             );
 
             assert_eq!(storage.normalize_site("www.example.com"), "example.com");
             assert_eq!(
-                storage.resolve_site("www.example.com", &hmac_secret(), &enc_key()),
+                storage
+                    .resolve_site("www.example.com", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve"),
                 "example.com"
             );
 
@@ -751,28 +1152,244 @@ mod tests {
                 "www2.example.com"
             );
             assert_eq!(
-                storage.resolve_site("www2.example.com", &hmac_secret(), &enc_key()),
+                storage
+                    .resolve_site("www2.example.com", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve"),
                 "www2.example.com"
             );
 
             assert_eq!(storage.normalize_site("www.example.net"), "example.net");
             assert_eq!(
-                storage.resolve_site("www.example.net", &hmac_secret(), &enc_key()),
+                storage
+                    .resolve_site("www.example.net", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve"),
                 "example.net"
             );
 
             assert_eq!(storage.normalize_site("example.org"), "example.org");
             assert_eq!(
-                storage.resolve_site("example.org", &hmac_secret(), &enc_key()),
+                storage
+                    .resolve_site("example.org", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve"),
                 "example.com"
             );
 
             assert_eq!(storage.normalize_site("www.example.org"), "example.org");
             assert_eq!(
-                storage.resolve_site("www.example.org", &hmac_secret(), &enc_key()),
+                storage
+                    .resolve_site("www.example.org", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve"),
                 "example.com"
             );
         }
+
+        #[test]
+        fn alias_chains() {
+            let io = MemoryIO::new(default_data());
+            let mut storage = Storage::new(io);
+
+            storage
+                .set_alias("example.net", "example.org", &hmac_secret(), &enc_key())
+                .expect("Adding alias should succeed");
+            assert_eq!(
+                storage
+                    .resolve_site("www.example.net", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve"),
+                "example.com"
+            );
+
+            storage
+                .set_alias("example.com", "example.net", &hmac_secret(), &enc_key())
+                .expect("Adding alias should succeed");
+            for site in ["example.com", "example.net", "example.org"] {
+                assert!(matches!(
+                    storage
+                        .resolve_site(site, &hmac_secret(), &enc_key())
+                        .expect_err("Resolving cyclic alias should fail"),
+                    Error::AliasCycle
+                ));
+            }
+            assert_eq!(
+                storage
+                    .resolve_site("example.info", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve"),
+                "example.info"
+            );
+        }
+
+        #[test]
+        fn normalize_urls() {
+            let io = MemoryIO::new(default_data());
+            let storage = Storage::new(io);
+
+            assert_eq!(
+                storage.normalize_site("https://example.com/foo"),
+                "example.com"
+            );
+            assert_eq!(
+                storage.normalize_site("http://www.example.com"),
+                "example.com"
+            );
+            assert_eq!(
+                storage.normalize_site("https://user@WWW.Example.com:8443/login?x=1#y"),
+                "example.com"
+            );
+            assert_eq!(
+                storage
+                    .resolve_site("https://www.example.org/", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve"),
+                "example.com"
+            );
+
+            assert_eq!(
+                storage.normalize_site("example.com:8443"),
+                "example.com:8443"
+            );
+            assert_eq!(storage.normalize_site("my note site"), "my note site");
+            assert_eq!(
+                storage.normalize_site("not a url://example.com"),
+                "not a url://example.com"
+            );
+            assert_eq!(storage.normalize_site("https://"), "https://");
+
+            assert_eq!(storage.normalize_site("http://[::1]:8080/login"), "[::1]");
+            assert_eq!(
+                storage.normalize_site("https://user@[2001:DB8::1]/"),
+                "[2001:db8::1]"
+            );
+            assert_eq!(storage.normalize_site("https://[::1"), "https://[::1");
+            assert_eq!(storage.normalize_site("https://[::1]x"), "https://[::1]x");
+        }
+    }
+
+    mod site_cache {
+        use super::*;
+        use storage_io::StorageIO;
+
+        #[test]
+        fn caching() {
+            let io = MemoryIO::new(default_data());
+            let mut storage = Storage::new(io);
+            storage.set_site_cache(true);
+
+            let resolve = |storage: &Storage<MemoryIO>| {
+                storage
+                    .resolve_site("example.org", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve")
+            };
+            assert_eq!(resolve(&storage), "example.com");
+
+            // Cached entry is used even if the data is gone
+            let key = storage.get_site_key("example.org", &hmac_secret());
+            storage
+                .io
+                .remove(&key)
+                .expect("Removing entry should succeed");
+            assert_eq!(resolve(&storage), "example.com");
+
+            // Modifications drop cache entries
+            storage
+                .set_alias("example.org", "example.info", &hmac_secret(), &enc_key())
+                .expect("Adding alias should succeed");
+            assert_eq!(resolve(&storage), "example.info");
+            storage
+                .remove_alias("example.org", &hmac_secret(), &enc_key())
+                .expect("Removing alias should succeed");
+            assert_eq!(resolve(&storage), "example.org");
+
+            // Disabling the cache discards cached entries
+            storage
+                .set_alias("example.org", "example.com", &hmac_secret(), &enc_key())
+                .expect("Adding alias should succeed");
+            assert_eq!(resolve(&storage), "example.com");
+            storage
+                .io
+                .remove(&key)
+                .expect("Removing entry should succeed");
+            storage.set_site_cache(false);
+            assert_eq!(resolve(&storage), "example.org");
+        }
+
+        #[test]
+        fn clearing() {
+            let io = MemoryIO::new(default_data());
+            let mut storage = Storage::new(io);
+            storage.set_site_cache(true);
+
+            assert_eq!(
+                storage
+                    .resolve_site("example.org", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve"),
+                "example.com"
+            );
+            let key = storage.get_site_key("example.org", &hmac_secret());
+            storage
+                .io
+                .remove(&key)
+                .expect("Removing entry should succeed");
+            storage.clear_site_cache();
+            assert_eq!(
+                storage
+                    .resolve_site("example.org", &hmac_secret(), &enc_key())
+                    .expect("Site should resolve"),
+                "example.org"
+            );
+        }
+    }
+
+    mod lenient {
+        use super::*;
+        use json::json;
+        use storage_io::StorageIO;
+
+        #[test]
+        fn duplicate_fields() {
+            let io = MemoryIO::new(empty_data());
+            let mut storage = Storage::new(io);
+
+            let id = PasswordId::new("example.com", "blabber", "2");
+            let key = storage.get_password_key(&id, &hmac_secret());
+            let data = br#"{"type":"stored","site":"example.com","name":"blabber","revision":"2","password":"asdf","notes":"hi","password":"yxcv"}"#;
+            storage.io.set(
+                key,
+                crypto::encrypt_data(&SecretVec::new(data.to_vec()), &enc_key()),
+            );
+
+            assert!(matches!(
+                storage
+                    .get_password(&id, &hmac_secret(), &enc_key())
+                    .expect_err("Duplicate fields should be rejected"),
+                Error::InvalidJson { .. }
+            ));
+            assert_eq!(
+                storage
+                    .list_passwords("example.com", &hmac_secret(), &enc_key())
+                    .count(),
+                0
+            );
+
+            storage.set_lenient(true);
+            let password = storage
+                .get_password(&id, &hmac_secret(), &enc_key())
+                .expect("Password should be read in lenient mode");
+            assert_eq!(
+                to_json_value(&password),
+                json!({
+                    "type": "stored",
+                    "site": "example.com",
+                    "name": "blabber",
+                    "revision": "2",
+                    "password": "yxcv",
+                    "notes": "hi",
+                })
+            );
+            assert_eq!(
+                storage
+                    .list_passwords("example.com", &hmac_secret(), &enc_key())
+                    .count(),
+                1
+            );
+        }
     }
 
     mod addition {
@@ -907,7 +1524,49 @@ mod tests {
 
             storage.flush().expect("Flush should succeed");
 
-            compare_storage_data(storage.io.data(), &default_data());
+            compare_storage_data(storage.io.file_data(), &default_data());
+        }
+    }
+
+    mod renaming {
+        use super::*;
+        use storage_io::StorageIO;
+
+        #[test]
+        fn rename_key() {
+            let io = MemoryIO::new(default_data());
+            let mut storage = Storage::new(io);
+
+            let old_key = storage.get_site_key("example.info", &hmac_secret());
+            let new_key = storage.get_site_key("example.net", &hmac_secret());
+            let encrypted = storage
+                .io
+                .get(&old_key)
+                .expect("Entry should exist")
+                .clone();
+
+            assert!(matches!(
+                storage
+                    .io
+                    .rename_key(&new_key, old_key.clone())
+                    .expect_err("Renaming missing key should fail"),
+                Error::KeyMissing
+            ));
+
+            storage
+                .io
+                .rename_key(&old_key, new_key.clone())
+                .expect("Renaming should succeed");
+            assert!(!storage.contains(&old_key));
+            assert_eq!(
+                storage.io.get(&new_key).expect("Entry should exist"),
+                &encrypted
+            );
+
+            let site: Site = storage
+                .get(&new_key, &enc_key())
+                .expect("Renamed entry should decrypt");
+            assert_eq!(site.name(), "example.info");
         }
     }
 
@@ -972,7 +1631,7 @@ mod tests {
 
             storage.flush().expect("Flush should succeed");
 
-            compare_storage_data(storage.io.data(), &empty_data());
+            compare_storage_data(storage.io.file_data(), &empty_data());
         }
     }
 }
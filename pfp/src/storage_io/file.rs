@@ -6,19 +6,71 @@
 
 use crate::error::Error;
 use crate::json::{const_serializable, Deserialize, Serialize};
-use std::collections::HashMap;
+use fs2::FileExt;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::io::Write;
 use std::path;
 
+pub(super) const FORMAT_VERSION: u8 = 3;
+
 const_serializable!(ApplicationName: String = "pfp");
 const_serializable!(Format: u8 = 3);
 
+#[derive(Deserialize)]
+/// Fields common to all storage format versions, used to determine the version before parsing
+/// the actual data.
+struct Header {
+    #[serde(rename = "application")]
+    _application: ApplicationName,
+    format: u64,
+}
+
+#[derive(Deserialize)]
+/// Storage data of a newer format version, unknown fields are ignored.
+struct NewerFormat {
+    data: HashMap<String, String>,
+}
+
+#[derive(Serialize)]
+/// Storage data in the storage file format, used to back up data of arbitrary storage.
+struct Backup<'a> {
+    application: ApplicationName,
+    format: Format,
+    data: BTreeMap<&'a str, &'a str>,
+}
+
+#[derive(Debug, PartialEq)]
+/// Metadata used to recognize external modifications of the storage file
+struct FileState {
+    modified: Option<std::time::SystemTime>,
+    len: u64,
+}
+
+impl FileState {
+    fn get(path: &path::Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 /// File-based I/O implementation
 pub struct FileIO {
     #[serde(skip)]
     path: path::PathBuf,
+    #[serde(skip)]
+    read_only: bool,
+    #[serde(skip)]
+    newer_format: Option<u64>,
+    #[serde(skip)]
+    lock: Option<fs::File>,
+    #[serde(skip)]
+    file_state: Option<FileState>,
     application: ApplicationName,
     format: Format,
     data: HashMap<String, String>,
@@ -31,6 +83,10 @@ impl FileIO {
     pub fn new(path: &path::Path) -> Self {
         Self {
             path: path.to_path_buf(),
+            read_only: false,
+            newer_format: None,
+            lock: None,
+            file_state: None,
             application: ApplicationName,
             format: Format,
             data: HashMap::new(),
@@ -38,15 +94,200 @@ impl FileIO {
     }
 
     /// Creates a `FileIO` instance by loading data from disk.
+    ///
+    /// If the file is modified externally after loading, flushing will result in
+    /// [Error::StorageChangedExternally](../error/enum.Error.html#variant.StorageChangedExternally)
+    /// rather than overwriting these changes. Calling `reload()` will reset this state.
+    ///
+    /// This acquires an advisory lock on the storage file which is held for as long as the
+    /// instance exists. If another instance holds the lock already, this will result in
+    /// [Error::StorageLocked](../error/enum.Error.html#variant.StorageLocked).
+    ///
+    /// If the file was written by a newer application version using a format that isn't
+    /// supported, this will result in
+    /// [Error::NewerStorageFormat](../error/enum.Error.html#variant.NewerStorageFormat).
     pub fn load(path: &path::Path) -> Result<Self, Error> {
+        Self::load_impl(path, false)
+    }
+
+    /// Creates a `FileIO` instance by loading data from disk, accepting files written in a newer
+    /// format.
+    ///
+    /// Newer formats are parsed on a best-effort basis, ignoring any unknown fields. To prevent
+    /// data loss, such storage is read-only: flushing it will result in
+    /// [Error::ReadOnlyStorage](../error/enum.Error.html#variant.ReadOnlyStorage). Files in the
+    /// current format are loaded normally.
+    pub fn load_allow_newer(path: &path::Path) -> Result<Self, Error> {
+        Self::load_impl(path, true)
+    }
+
+    /// Creates a read-only `FileIO` instance by loading data from disk, accepting files written
+    /// in a newer format.
+    ///
+    /// This doesn't acquire a lock on the storage file, so it will succeed even if another
+    /// instance has the file open. Flushing the storage will result in
+    /// [Error::ReadOnlyStorage](../error/enum.Error.html#variant.ReadOnlyStorage).
+    pub fn load_read_only(path: &path::Path) -> Result<Self, Error> {
+        let mut result = Self::read_impl(path, true)?;
+        result.read_only = true;
+        Ok(result)
+    }
+
+    /// Creates a `FileIO` instance from the contents of a storage file rather than loading it
+    /// from disk. Files in a newer format are accepted like with
+    /// [load_allow_newer()](#method.load_allow_newer).
+    ///
+    /// This is meant for fuzzing the storage file parser and is only available with the
+    /// `test-util` feature. It is unstable and can change or go away in any release.
+    #[cfg(feature = "test-util")]
+    pub fn parse(path: &path::Path, contents: &str) -> Result<Self, Error> {
+        Self::parse_impl(path, contents, true)
+    }
+
+    fn load_impl(path: &path::Path, allow_newer: bool) -> Result<Self, Error> {
+        // Don't leave a lock file behind if there is no storage file to be locked
+        fs::metadata(path).map_err(|error| Error::FileReadFailure { error })?;
+        let lock = lock_file(path)?;
+
+        let mut result = Self::read_impl(path, allow_newer)?;
+        result.lock = Some(lock);
+        Ok(result)
+    }
+
+    fn read_impl(path: &path::Path, allow_newer: bool) -> Result<Self, Error> {
+        // Get file state before reading, so that any concurrent changes will be noticed
+        let file_state = FileState::get(path);
         let contents =
             fs::read_to_string(path).map_err(|error| Error::FileReadFailure { error })?;
+        let mut result = Self::parse_impl(path, &contents, allow_newer)?;
+        result.file_state = file_state;
+        Ok(result)
+    }
+
+    fn parse_impl(path: &path::Path, contents: &str, allow_newer: bool) -> Result<Self, Error> {
+        // Only check the format version here, errors will be reported by the actual parsing
+        if let Ok(header) = crate::json::from_str::<Header>(contents) {
+            if header.format > FORMAT_VERSION.into() {
+                if !allow_newer {
+                    return Err(Error::NewerStorageFormat {
+                        format: header.format,
+                    });
+                }
 
-        let mut result = crate::json::from_str::<Self>(&contents)
-            .map_err(|error| Error::InvalidJson { error })?;
+                let newer = crate::json::from_str::<NewerFormat>(contents)?;
+                let mut result = Self::new(path);
+                result.data = newer.data;
+                result.read_only = true;
+                result.newer_format = Some(header.format);
+                return Ok(result);
+            }
+        }
+
+        let mut result = crate::json::from_str::<Self>(contents)?;
         result.path = path.to_path_buf();
         Ok(result)
     }
+
+    /// Parses the contents of a storage file in the current format, returning its data and
+    /// format version.
+    pub(super) fn parse_data(contents: &str) -> Result<(HashMap<String, String>, u64), Error> {
+        let result = Self::parse_impl(path::Path::new(""), contents, false)?;
+        let format = result.format();
+        Ok((result.data, format))
+    }
+
+    /// Serializes the data of any storage in the storage file format. This is also the format of
+    /// backups created by the PfP browser extension, so that the result can be restored there.
+    pub fn serialize(io: &impl super::StorageIO) -> Result<String, Error> {
+        let mut data = BTreeMap::new();
+        for key in io.keys() {
+            data.insert(key.as_str(), io.get(key)?.as_str());
+        }
+        Self::serialize_data(data)
+    }
+
+    pub(super) fn serialize_data(data: BTreeMap<&str, &str>) -> Result<String, Error> {
+        Ok(crate::json::to_string(&Backup {
+            application: ApplicationName,
+            format: Format,
+            data,
+        })?)
+    }
+
+    /// Checks whether the storage was loaded read-only, either explicitly or because of a newer
+    /// file format.
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Checks whether the storage file was written in a newer format than the current one.
+    pub fn newer_format(&self) -> bool {
+        self.newer_format.is_some()
+    }
+
+    /// Retrieves the format version of the storage file.
+    pub fn format(&self) -> u64 {
+        self.newer_format.unwrap_or_else(|| FORMAT_VERSION.into())
+    }
+}
+
+/// How long to wait for another instance to release the storage lock
+const LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Acquires an exclusive advisory lock on a file next to the storage file. The storage file
+/// itself cannot be locked because flushing replaces it by a different file. If the lock is
+/// held by another instance, a short wait gives an instance that is about to exit the chance to
+/// release it.
+fn lock_file(path: &path::Path) -> Result<fs::File, Error> {
+    let mut lock_name = path.file_name().unwrap_or_default().to_os_string();
+    lock_name.push(".lock");
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path.with_file_name(lock_name))
+        .map_err(|error| Error::FileReadFailure { error })?;
+
+    let start = std::time::Instant::now();
+    loop {
+        match file.try_lock_exclusive() {
+            Ok(()) => return Ok(file),
+            Err(error) if error.kind() == fs2::lock_contended_error().kind() => {
+                if start.elapsed() >= LOCK_TIMEOUT {
+                    return Err(Error::StorageLocked);
+                }
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+            Err(error) => return Err(Error::IoFailure { error }),
+        }
+    }
+}
+
+fn replace_file(path: &path::Path, temp_path: &path::Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = fs::File::create(temp_path)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(temp_path, path)
+}
+
+/// Writes data to a temporary file next to the target and renames it over the target then, so
+/// that a crash while writing cannot leave a truncated file behind. The rename is atomic on the
+/// same filesystem, on Windows it replaces an existing file as well.
+fn write_atomically(path: &path::Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = replace_file(path, &temp_path, contents);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
 }
 
 impl super::StorageIO for FileIO {
@@ -66,6 +307,12 @@ impl super::StorageIO for FileIO {
         self.data.remove(key).map(|_| ()).ok_or(Error::KeyMissing)
     }
 
+    fn rename_key(&mut self, old: &str, new: String) -> Result<(), Error> {
+        let value = self.data.remove(old).ok_or(Error::KeyMissing)?;
+        self.data.insert(new, value);
+        Ok(())
+    }
+
     fn keys(&self) -> Box<dyn Iterator<Item = &String> + '_> {
         Box::new(self.data.keys())
     }
@@ -75,13 +322,171 @@ impl super::StorageIO for FileIO {
     }
 
     fn flush(&mut self) -> Result<(), Error> {
-        let contents =
-            crate::json::to_string(self).map_err(|error| Error::InvalidJson { error })?;
+        if self.read_only {
+            return Err(Error::ReadOnlyStorage);
+        }
+
+        if self.file_state.is_some() && self.file_state != FileState::get(&self.path) {
+            return Err(Error::StorageChangedExternally);
+        }
+
+        let contents = crate::json::to_string(self)?;
 
         let parent = self.path.parent();
         if let Some(parent) = parent {
             fs::create_dir_all(parent).map_err(|error| Error::CreateDirFailure { error })?;
         }
-        fs::write(&self.path, &contents).map_err(|error| Error::FileWriteFailure { error })
+        write_atomically(&self.path, contents.as_bytes())
+            .map_err(|error| Error::FileWriteFailure { error })?;
+        self.file_state = FileState::get(&self.path);
+        Ok(())
+    }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        // Keep holding the existing lock rather than acquiring it again
+        let mut result = Self::read_impl(&self.path, self.read_only)?;
+        result.read_only |= self.read_only;
+        result.lock = self.lock.take();
+        *self = result;
+        Ok(())
+    }
+
+    fn format_version(&self) -> Option<u64> {
+        Some(self.format())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage_io::StorageIO;
+
+    #[test]
+    fn atomic_flush() {
+        let dir = tempfile::tempdir().expect("Creating temporary directory should succeed");
+        let path = dir.path().join("storage.json");
+        let temp_path = dir.path().join("storage.json.tmp");
+
+        let mut io = FileIO::new(&path);
+        io.set("salt".to_string(), "Y2Jh".to_string());
+        io.flush().expect("Flush should succeed");
+        assert!(!temp_path.exists());
+        let original = fs::read_to_string(&path).expect("Reading file should succeed");
+
+        // Writing the temporary file fails if there is a directory in its place
+        fs::create_dir(&temp_path).expect("Creating directory should succeed");
+        io.set("site:a".to_string(), "1".to_string());
+        assert!(matches!(
+            io.flush().expect_err("Flush should fail"),
+            Error::FileWriteFailure { .. }
+        ));
+        assert_eq!(
+            fs::read_to_string(&path).expect("Reading file should succeed"),
+            original
+        );
+
+        fs::remove_dir(&temp_path).expect("Removing directory should succeed");
+        io.flush().expect("Flush should succeed");
+        assert!(!temp_path.exists());
+        let io = FileIO::load(&path).expect("Loading file should succeed");
+        assert_eq!(io.get("site:a").expect("Key should exist"), "1");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn flush_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().expect("Creating temporary directory should succeed");
+        let path = dir.path().join("storage.json");
+
+        let mut io = FileIO::new(&path);
+        io.flush().expect("Flush should succeed");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+            .expect("Setting permissions should succeed");
+
+        io.set("salt".to_string(), "Y2Jh".to_string());
+        io.flush().expect("Flush should succeed");
+        let metadata = fs::metadata(&path).expect("File should exist");
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn locking() {
+        let dir = tempfile::tempdir().expect("Creating temporary directory should succeed");
+        let path = dir.path().join("storage.json");
+
+        assert!(matches!(
+            FileIO::load(&path).expect_err("Loading missing file should fail"),
+            Error::FileReadFailure { .. }
+        ));
+        assert!(!dir.path().join("storage.json.lock").exists());
+
+        let mut io = FileIO::new(&path);
+        io.set("salt".to_string(), "Y2Jh".to_string());
+        io.flush().expect("Flush should succeed");
+
+        let mut io = FileIO::load(&path).expect("Loading file should succeed");
+        assert!(matches!(
+            FileIO::load(&path).expect_err("Loading locked file should fail"),
+            Error::StorageLocked
+        ));
+        assert!(matches!(
+            FileIO::load_allow_newer(&path).expect_err("Loading locked file should fail"),
+            Error::StorageLocked
+        ));
+
+        // Reloading keeps the lock
+        io.reload().expect("Reload should succeed");
+        assert!(matches!(
+            FileIO::load(&path).expect_err("Loading locked file should fail"),
+            Error::StorageLocked
+        ));
+
+        // Read-only access doesn't require the lock
+        let mut read_only = FileIO::load_read_only(&path).expect("Loading file should succeed");
+        assert_eq!(read_only.get("salt").expect("Key should exist"), "Y2Jh");
+        assert!(read_only.read_only());
+        assert!(matches!(
+            read_only.flush().expect_err("Flush should fail"),
+            Error::ReadOnlyStorage
+        ));
+
+        drop(io);
+        FileIO::load(&path).expect("Loading file should succeed");
+    }
+
+    #[test]
+    fn external_modification() {
+        let dir = tempfile::tempdir().expect("Creating temporary directory should succeed");
+        let path = dir.path().join("storage.json");
+
+        let mut io = FileIO::new(&path);
+        io.set("salt".to_string(), "Y2Jh".to_string());
+        io.flush().expect("Flush should succeed");
+
+        let mut io = FileIO::load(&path).expect("Loading file should succeed");
+        io.set("site:a".to_string(), "1".to_string());
+        io.flush().expect("Flush should succeed");
+
+        let mut other = FileIO::new(&path);
+        other.set("salt".to_string(), "ZGVm".to_string());
+        other.set("site:b".to_string(), "2".to_string());
+        other.flush().expect("Flush should succeed");
+
+        io.set("site:c".to_string(), "3".to_string());
+        assert!(matches!(
+            io.flush().expect_err("Flush should fail"),
+            Error::StorageChangedExternally
+        ));
+        let loaded = FileIO::load_read_only(&path).expect("Loading file should succeed");
+        assert!(loaded.contains_key("site:b"));
+        assert!(!loaded.contains_key("site:c"));
+
+        io.reload().expect("Reload should succeed");
+        assert_eq!(io.get("salt").expect("Key should exist"), "ZGVm");
+        assert!(!io.contains_key("site:c"));
+        io.set("site:c".to_string(), "3".to_string());
+        io.flush().expect("Flush should succeed");
     }
 }
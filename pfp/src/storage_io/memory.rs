@@ -8,24 +8,48 @@ use crate::error::Error;
 use std::collections::HashMap;
 
 #[derive(Debug)]
-/// In-memory I/O implementation (for tests)
+/// In-memory I/O implementation, only available with the `memory-io` feature
+///
+/// This is meant for embedding, when the storage file contents are kept elsewhere rather than in
+/// a file. Flushing doesn't write anything, it only updates the "file" data that
+/// [file_data()](#method.file_data) and [to_json()](#method.to_json) return and
+/// [reload()](trait.StorageIO.html#tymethod.reload) reverts to.
 pub struct MemoryIO {
     file_data: HashMap<String, String>,
     data: HashMap<String, String>,
 }
 
 impl MemoryIO {
-    #[cfg(test)]
     /// Creates a new `MemoryIO` instance with some initial "file" data.
     pub fn new(data: HashMap<String, String>) -> Self {
         Self {
             file_data: data.clone(),
-            data: data.clone(),
+            data,
         }
     }
 
+    /// Creates a new `MemoryIO` instance from the contents of a storage file.
+    ///
+    /// Like with [FileIO::load()](struct.FileIO.html#method.load), a newer storage format will
+    /// result in
+    /// [Error::NewerStorageFormat](../error/enum.Error.html#variant.NewerStorageFormat).
+    pub fn from_json(contents: &str) -> Result<Self, Error> {
+        Ok(Self::new(super::FileIO::parse_data(contents)?.0))
+    }
+
+    /// Serializes the "file" data in the storage file format, changes that weren't flushed are
+    /// not included.
+    pub fn to_json(&self) -> Result<String, Error> {
+        super::FileIO::serialize_data(
+            self.file_data
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect(),
+        )
+    }
+
     /// Retrieves the data stored in the "file".
-    pub fn data(&self) -> &HashMap<String, String> {
+    pub fn file_data(&self) -> &HashMap<String, String> {
         &self.file_data
     }
 }
@@ -47,6 +71,12 @@ impl super::StorageIO for MemoryIO {
         self.data.remove(key).map(|_| ()).ok_or(Error::KeyMissing)
     }
 
+    fn rename_key(&mut self, old: &str, new: String) -> Result<(), Error> {
+        let value = self.data.remove(old).ok_or(Error::KeyMissing)?;
+        self.data.insert(new, value);
+        Ok(())
+    }
+
     fn keys(&self) -> Box<dyn Iterator<Item = &String> + '_> {
         Box::new(self.data.keys())
     }
@@ -59,4 +89,45 @@ impl super::StorageIO for MemoryIO {
         self.file_data = self.data.clone();
         Ok(())
     }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        self.data = self.file_data.clone();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage_io::StorageIO;
+
+    #[test]
+    fn json() {
+        let mut io = MemoryIO::from_json(
+            r#"{"application":"pfp","format":3,"data":{"salt":"Y2Jh","site:a":"1"}}"#,
+        )
+        .expect("Parsing should succeed");
+        assert_eq!(io.get("salt").expect("Key should exist"), "Y2Jh");
+
+        io.set("site:b".to_string(), "2".to_string());
+        assert_eq!(
+            io.to_json().expect("Serializing should succeed"),
+            r#"{"application":"pfp","format":3,"data":{"salt":"Y2Jh","site:a":"1"}}"#
+        );
+        io.flush().expect("Flush should succeed");
+        assert_eq!(
+            io.to_json().expect("Serializing should succeed"),
+            r#"{"application":"pfp","format":3,"data":{"salt":"Y2Jh","site:a":"1","site:b":"2"}}"#
+        );
+
+        assert!(matches!(
+            MemoryIO::from_json(r#"{"application":"pfp","format":4,"data":{}}"#)
+                .expect_err("Parsing should fail"),
+            Error::NewerStorageFormat { format: 4 }
+        ));
+        assert!(matches!(
+            MemoryIO::from_json("[]").expect_err("Parsing should fail"),
+            Error::InvalidJson { .. }
+        ));
+    }
 }
@@ -0,0 +1,173 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::StorageIO;
+use crate::error::Error;
+use std::cell::Cell;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// Operation counters collected by [MeteredIO](struct.MeteredIO.html).
+pub struct IoMetrics {
+    /// Number of values retrieved
+    pub gets: usize,
+    /// Number of values added or replaced
+    pub sets: usize,
+    /// Number of values removed
+    pub removes: usize,
+    /// Number of times changes were saved
+    pub flushes: usize,
+    /// Total length of the values retrieved
+    pub bytes_read: usize,
+    /// Total length of the values added or replaced
+    pub bytes_written: usize,
+}
+
+/// I/O wrapper counting the operations performed on another I/O implementation, for diagnostic
+/// purposes. Only the number of operations and the length of the values are recorded, values
+/// themselves are never inspected.
+///
+/// ```
+/// use pfp::passwords::Passwords;
+/// use pfp::storage_io::{FileIO, MeteredIO};
+/// use std::path::Path;
+///
+/// let io = MeteredIO::new(FileIO::new(Path::new("test.json")));
+/// let passwords = Passwords::new(io);
+/// ```
+pub struct MeteredIO<IO: StorageIO> {
+    inner: IO,
+    metrics: Cell<IoMetrics>,
+}
+
+impl<IO: StorageIO> MeteredIO<IO> {
+    /// Wraps an I/O implementation, all counters start at zero.
+    pub fn new(inner: IO) -> Self {
+        Self {
+            inner,
+            metrics: Cell::new(IoMetrics::default()),
+        }
+    }
+
+    /// Retrieves the operation counters collected so far.
+    pub fn metrics(&self) -> IoMetrics {
+        self.metrics.get()
+    }
+
+    /// Resets all operation counters to zero.
+    pub fn reset_metrics(&self) {
+        self.metrics.set(IoMetrics::default());
+    }
+
+    /// Retrieves the wrapped I/O implementation.
+    pub fn inner(&self) -> &IO {
+        &self.inner
+    }
+
+    fn update(&self, callback: impl FnOnce(&mut IoMetrics)) {
+        let mut metrics = self.metrics.get();
+        callback(&mut metrics);
+        self.metrics.set(metrics);
+    }
+}
+
+impl<IO: StorageIO> StorageIO for MeteredIO<IO> {
+    fn contains_key(&self, key: &str) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    fn get(&self, key: &str) -> Result<&String, Error> {
+        let value = self.inner.get(key)?;
+        self.update(|metrics| {
+            metrics.gets += 1;
+            metrics.bytes_read += value.len();
+        });
+        Ok(value)
+    }
+
+    fn set(&mut self, key: String, value: String) {
+        self.update(|metrics| {
+            metrics.sets += 1;
+            metrics.bytes_written += value.len();
+        });
+        self.inner.set(key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), Error> {
+        self.inner.remove(key)?;
+        self.update(|metrics| metrics.removes += 1);
+        Ok(())
+    }
+
+    fn rename_key(&mut self, old: &str, new: String) -> Result<(), Error> {
+        self.inner.rename_key(old, new)
+    }
+
+    fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &String> + '_> {
+        self.inner.keys()
+    }
+
+    fn keys_with_prefix(&self, prefix: &str) -> Box<dyn Iterator<Item = &String> + '_> {
+        self.inner.keys_with_prefix(prefix)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.update(|metrics| metrics.flushes += 1);
+        self.inner.flush()
+    }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        self.inner.reload()
+    }
+
+    fn close(&mut self) -> Result<(), Error> {
+        self.inner.close()
+    }
+
+    fn format_version(&self) -> Option<u64> {
+        self.inner.format_version()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage_io::MemoryIO;
+    use std::collections::HashMap;
+
+    #[test]
+    fn counting() {
+        let mut io = MeteredIO::new(MemoryIO::new(HashMap::new()));
+        assert_eq!(io.metrics(), IoMetrics::default());
+
+        io.set("a".to_string(), "abc".to_string());
+        io.set("b".to_string(), "de".to_string());
+        io.get("a").expect("Key should exist");
+        io.get("c").expect_err("Key shouldn't exist");
+        io.remove("b").expect("Removal should succeed");
+        io.remove("b").expect_err("Removal should fail");
+        io.flush().expect("Flush should succeed");
+
+        assert_eq!(
+            io.metrics(),
+            IoMetrics {
+                gets: 1,
+                sets: 2,
+                removes: 1,
+                flushes: 1,
+                bytes_read: 3,
+                bytes_written: 5,
+            }
+        );
+        assert_eq!(io.inner().file_data().len(), 1);
+
+        io.reset_metrics();
+        assert_eq!(io.metrics(), IoMetrics::default());
+    }
+}
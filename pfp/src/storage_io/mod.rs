@@ -21,9 +21,20 @@
 mod file;
 pub use file::FileIO;
 
-#[cfg(test)]
+mod metered;
+pub use metered::{IoMetrics, MeteredIO};
+
+mod stdio;
+pub use stdio::StdioIO;
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteIO;
+
+#[cfg(any(test, feature = "memory-io"))]
 mod memory;
-#[cfg(test)]
+#[cfg(any(test, feature = "memory-io"))]
 pub use memory::MemoryIO;
 
 use crate::error::Error;
@@ -40,10 +51,43 @@ pub trait StorageIO {
     /// Removes the value associated with a particlar key or returns `Error::KeyMissing` if the key
     /// isn't found.
     fn remove(&mut self, key: &str) -> Result<(), Error>;
+    /// Moves the value associated with a particular key to a new key, overwriting any value
+    /// already present there. Returns `Error::KeyMissing` if the original key isn't found.
+    ///
+    /// The value is moved as is, so this is only suitable for renames where the encrypted data
+    /// doesn't change. The default implementation uses `get`, `set` and `remove`, backends can
+    /// override it with something more efficient.
+    fn rename_key(&mut self, old: &str, new: String) -> Result<(), Error> {
+        let value = self.get(old)?.clone();
+        self.set(new, value);
+        self.remove(old)
+    }
     /// Removes all data from the file.
     fn clear(&mut self);
     /// Iterates over keys contained in the data.
     fn keys(&self) -> Box<dyn Iterator<Item = &String> + '_>;
+    /// Iterates over keys starting with a particular prefix. The default implementation filters
+    /// the results of `keys`, backends can override it to look up matching keys more efficiently.
+    fn keys_with_prefix(&self, prefix: &str) -> Box<dyn Iterator<Item = &String> + '_> {
+        let prefix = prefix.to_string();
+        Box::new(self.keys().filter(move |key| key.starts_with(&prefix)))
+    }
     /// Saves the changes back to the storage file if necessary.
     fn flush(&mut self) -> Result<(), Error>;
+    /// Discards any changes that haven't been flushed, reading the data from the storage file
+    /// again. The default implementation returns `Error::ReloadUnsupported`, rolling back
+    /// transactions isn't possible then.
+    fn reload(&mut self) -> Result<(), Error> {
+        Err(Error::ReloadUnsupported)
+    }
+    /// Called when the storage is closed. Backends that cannot write their data more than once
+    /// should write it here rather than in `flush`. The default implementation does nothing.
+    fn close(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    /// Retrieves the format version of the storage data if it uses the storage file format. The
+    /// default implementation returns `None`.
+    fn format_version(&self) -> Option<u64> {
+        None
+    }
 }
@@ -0,0 +1,238 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use super::StorageIO;
+use crate::error::Error;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path;
+
+const FORMAT_VERSION: u64 = 1;
+
+fn database_error(error: rusqlite::Error) -> Error {
+    Error::DatabaseFailure {
+        error: error.to_string(),
+    }
+}
+
+/// SQLite-based I/O implementation, only available with the `sqlite` feature.
+///
+/// The storage keys and their encrypted values are kept as is in a key/value table, the
+/// encryption is identical to [FileIO](struct.FileIO.html). The data is loaded into memory when
+/// the database is opened, changes are written back in a single database transaction on flush.
+pub struct SqliteIO {
+    connection: rusqlite::Connection,
+    data: BTreeMap<String, String>,
+    changed: BTreeSet<String>,
+    cleared: bool,
+}
+
+impl SqliteIO {
+    /// Opens the database at `path`, creating it if necessary.
+    ///
+    /// If the database was written by a newer application version using a format that isn't
+    /// supported, this will result in
+    /// [Error::NewerStorageFormat](../error/enum.Error.html#variant.NewerStorageFormat).
+    pub fn open(path: &path::Path) -> Result<Self, Error> {
+        let connection = rusqlite::Connection::open(path).map_err(database_error)?;
+
+        let format: u64 = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(database_error)?;
+        if format > FORMAT_VERSION {
+            return Err(Error::NewerStorageFormat { format });
+        }
+
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS data (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+            )
+            .map_err(database_error)?;
+        connection
+            .pragma_update(None, "user_version", FORMAT_VERSION)
+            .map_err(database_error)?;
+
+        let mut result = Self {
+            connection,
+            data: BTreeMap::new(),
+            changed: BTreeSet::new(),
+            cleared: false,
+        };
+        result.reload()?;
+        Ok(result)
+    }
+
+    /// Replaces all data with the data from another storage, e.g. a
+    /// [FileIO](struct.FileIO.html) instance, and saves it to the database.
+    pub fn migrate_from<IO: StorageIO>(&mut self, source: &IO) -> Result<(), Error> {
+        self.clear();
+        for key in source.keys() {
+            self.set(key.clone(), source.get(key)?.clone());
+        }
+        self.flush()
+    }
+}
+
+impl StorageIO for SqliteIO {
+    fn contains_key(&self, key: &str) -> bool {
+        self.data.contains_key(key)
+    }
+
+    fn get(&self, key: &str) -> Result<&String, Error> {
+        self.data.get(key).ok_or(Error::KeyMissing)
+    }
+
+    fn set(&mut self, key: String, value: String) {
+        self.changed.insert(key.clone());
+        self.data.insert(key, value);
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), Error> {
+        self.data.remove(key).ok_or(Error::KeyMissing)?;
+        self.changed.insert(key.to_string());
+        Ok(())
+    }
+
+    fn rename_key(&mut self, old: &str, new: String) -> Result<(), Error> {
+        let value = self.data.remove(old).ok_or(Error::KeyMissing)?;
+        self.changed.insert(old.to_string());
+        self.set(new, value);
+        Ok(())
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &String> + '_> {
+        Box::new(self.data.keys())
+    }
+
+    fn keys_with_prefix(&self, prefix: &str) -> Box<dyn Iterator<Item = &String> + '_> {
+        // Keys are sorted, so the matching keys form a contiguous range.
+        let prefix = prefix.to_string();
+        Box::new(
+            self.data
+                .range(prefix.clone()..)
+                .map(|(key, _)| key)
+                .take_while(move |key| key.starts_with(&prefix)),
+        )
+    }
+
+    fn clear(&mut self) {
+        self.data.clear();
+        self.changed.clear();
+        self.cleared = true;
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        let transaction = self.connection.transaction().map_err(database_error)?;
+        if self.cleared {
+            transaction
+                .execute("DELETE FROM data", [])
+                .map_err(database_error)?;
+        }
+        for key in &self.changed {
+            match self.data.get(key) {
+                Some(value) => transaction.execute(
+                    "INSERT OR REPLACE INTO data (key, value) VALUES (?1, ?2)",
+                    [key, value],
+                ),
+                None => transaction.execute("DELETE FROM data WHERE key = ?1", [key]),
+            }
+            .map_err(database_error)?;
+        }
+        transaction.commit().map_err(database_error)?;
+
+        self.changed.clear();
+        self.cleared = false;
+        Ok(())
+    }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT key, value FROM data")
+            .map_err(database_error)?;
+        let data = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(database_error)?
+            .collect::<Result<BTreeMap<String, String>, _>>()
+            .map_err(database_error)?;
+        drop(statement);
+
+        self.data = data;
+        self.changed.clear();
+        self.cleared = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(path: &path::Path) -> SqliteIO {
+        SqliteIO::open(path).unwrap_or_else(|_| panic!("Opening database should succeed"))
+    }
+
+    #[test]
+    fn persistence() {
+        let file = tempfile::NamedTempFile::new().expect("Creating temporary file should succeed");
+
+        let mut io = open(file.path());
+        assert_eq!(io.keys().count(), 0);
+        io.set("salt".to_string(), "Y2Jh".to_string());
+        io.set("site:a".to_string(), "1".to_string());
+        io.set("site:a:b".to_string(), "2".to_string());
+        io.set("site:c".to_string(), "3".to_string());
+        io.flush().expect("Flush should succeed");
+
+        io.remove("site:c").expect("Removal should succeed");
+        io.rename_key("site:a:b", "site:a:d".to_string())
+            .expect("Renaming should succeed");
+        io.flush().expect("Flush should succeed");
+
+        io.set("site:e".to_string(), "4".to_string());
+        drop(io);
+
+        let mut io = open(file.path());
+        assert_eq!(
+            io.keys().cloned().collect::<Vec<_>>(),
+            vec!["salt", "site:a", "site:a:d"]
+        );
+        assert_eq!(
+            io.keys_with_prefix("site:a:").cloned().collect::<Vec<_>>(),
+            vec!["site:a:d"]
+        );
+        assert_eq!(io.get("site:a:d").expect("Key should exist"), "2");
+
+        io.set("site:e".to_string(), "4".to_string());
+        io.reload().expect("Reload should succeed");
+        assert!(!io.contains_key("site:e"));
+
+        io.clear();
+        io.flush().expect("Flush should succeed");
+        drop(io);
+        assert_eq!(open(file.path()).keys().count(), 0);
+    }
+
+    #[test]
+    fn migration() {
+        let file = tempfile::NamedTempFile::new().expect("Creating temporary file should succeed");
+        let mut source = super::super::FileIO::new(file.path());
+        source.set("salt".to_string(), "Y2Jh".to_string());
+        source.set("site:a".to_string(), "1".to_string());
+
+        let database =
+            tempfile::NamedTempFile::new().expect("Creating temporary file should succeed");
+        let mut io = open(database.path());
+        io.set("site:b".to_string(), "2".to_string());
+        io.migrate_from(&source).expect("Migration should succeed");
+        drop(io);
+
+        let io = open(database.path());
+        assert_eq!(
+            io.keys().cloned().collect::<Vec<_>>(),
+            vec!["salt", "site:a"]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use crate::error::Error;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+
+#[derive(Debug)]
+/// I/O implementation reading the storage data from stdin and writing it to stdout
+///
+/// This allows passing the storage data through other tools, e.g. decrypting it with GnuPG
+/// first. All of stdin is consumed when loading, so anything else has to be read from the
+/// terminal directly.
+///
+/// Flushing only records the changes, the data is written to stdout once when the storage is
+/// closed and only if it changed. Nothing else should be written to stdout in this mode.
+pub struct StdioIO {
+    file_data: HashMap<String, String>,
+    data: HashMap<String, String>,
+    changed: bool,
+    format: u64,
+}
+
+impl StdioIO {
+    /// Creates a `StdioIO` instance by reading the contents of a storage file from stdin. Empty
+    /// input results in a `StdioIO` instance without any data.
+    pub fn load() -> Result<Self, Error> {
+        Self::read_from(&mut std::io::stdin().lock())
+    }
+
+    fn read_from(input: &mut impl Read) -> Result<Self, Error> {
+        let mut contents = String::new();
+        input
+            .read_to_string(&mut contents)
+            .map_err(|error| Error::FileReadFailure { error })?;
+
+        // Empty input will be written in the current format
+        let (data, format) = if contents.trim().is_empty() {
+            (HashMap::new(), super::file::FORMAT_VERSION.into())
+        } else {
+            super::FileIO::parse_data(&contents)?
+        };
+        Ok(Self {
+            file_data: data.clone(),
+            data,
+            changed: false,
+            format,
+        })
+    }
+
+    fn write_to(&mut self, output: &mut impl Write) -> Result<(), Error> {
+        // Only flushed data is written, changes of a transaction that wasn't committed are lost
+        let contents = super::FileIO::serialize_data(
+            self.file_data
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect::<BTreeMap<_, _>>(),
+        )?;
+        output
+            .write_all(contents.as_bytes())
+            .and_then(|_| output.write_all(b"\n"))
+            .and_then(|_| output.flush())
+            .map_err(|error| Error::FileWriteFailure { error })?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+impl super::StorageIO for StdioIO {
+    fn contains_key(&self, key: &str) -> bool {
+        self.data.contains_key(key)
+    }
+
+    fn get(&self, key: &str) -> Result<&String, Error> {
+        self.data.get(key).ok_or(Error::KeyMissing)
+    }
+
+    fn set(&mut self, key: String, value: String) {
+        self.data.insert(key, value);
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), Error> {
+        self.data.remove(key).map(|_| ()).ok_or(Error::KeyMissing)
+    }
+
+    fn rename_key(&mut self, old: &str, new: String) -> Result<(), Error> {
+        let value = self.data.remove(old).ok_or(Error::KeyMissing)?;
+        self.data.insert(new, value);
+        Ok(())
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &String> + '_> {
+        Box::new(self.data.keys())
+    }
+
+    fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.data != self.file_data {
+            self.file_data = self.data.clone();
+            self.changed = true;
+        }
+        Ok(())
+    }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        self.data = self.file_data.clone();
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), Error> {
+        if self.changed {
+            self.write_to(&mut std::io::stdout().lock())?;
+        }
+        Ok(())
+    }
+
+    fn format_version(&self) -> Option<u64> {
+        Some(self.format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage_io::StorageIO;
+
+    #[test]
+    fn read_write() {
+        let io = StdioIO::read_from(&mut "".as_bytes()).expect("Reading should succeed");
+        assert_eq!(io.keys().count(), 0);
+        assert_eq!(io.format_version(), Some(3));
+
+        let mut io = StdioIO::read_from(
+            &mut r#"{"application":"pfp","format":3,"data":{"salt":"Y2Jh","site:a":"1"}}"#
+                .as_bytes(),
+        )
+        .expect("Reading should succeed");
+        assert_eq!(io.get("salt").expect("Key should exist"), "Y2Jh");
+        assert_eq!(io.format_version(), Some(3));
+
+        io.set("site:b".to_string(), "2".to_string());
+        io.remove("site:a").expect("Removal should succeed");
+        assert!(!io.changed);
+        io.flush().expect("Flush should succeed");
+        assert!(io.changed);
+        io.flush().expect("Flush should succeed");
+        let mut output = Vec::new();
+        io.write_to(&mut output).expect("Writing should succeed");
+        assert_eq!(
+            String::from_utf8(output).expect("Output should be valid UTF-8"),
+            "{\"application\":\"pfp\",\"format\":3,\"data\":{\"salt\":\"Y2Jh\",\"site:b\":\"2\"}}\n"
+        );
+        assert!(!io.changed);
+
+        io.set("site:c".to_string(), "3".to_string());
+        io.reload().expect("Reload should succeed");
+        assert!(!io.contains_key("site:c"));
+        assert!(io.contains_key("site:b"));
+
+        assert!(matches!(
+            StdioIO::read_from(&mut "{}".as_bytes()).expect_err("Reading should fail"),
+            Error::InvalidJson { .. }
+        ));
+    }
+}
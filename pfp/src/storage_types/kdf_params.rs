@@ -0,0 +1,243 @@
+/*
+ * This Source Code is subject to the terms of the Mozilla Public License
+ * version 2.0 (the "License"). You can obtain a copy of the License at
+ * http://mozilla.org/MPL/2.0/.
+ */
+
+use crate::crypto;
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "algorithm", rename_all = "lowercase", deny_unknown_fields)]
+/// Parameters of the key derivation function producing the storage encryption key from the
+/// primary password.
+pub enum KdfParams {
+    /// scrypt with the given CPU/memory cost parameter (N), the number of iterations
+    Scrypt {
+        /// Number of iterations, a power of two
+        cost: u64,
+    },
+    /// Argon2id with the given memory size, number of iterations and degree of parallelism
+    Argon2id {
+        /// Memory size in KiB
+        memory: u32,
+        /// Number of iterations
+        iterations: u32,
+        /// Degree of parallelism
+        parallelism: u32,
+    },
+}
+
+impl KdfParams {
+    /// The minimal number of scrypt iterations.
+    pub const MIN_SCRYPT_COST: u64 = 1 << 10;
+
+    /// The maximal number of scrypt iterations.
+    pub const MAX_SCRYPT_COST: u64 = 1 << 20;
+
+    /// The minimal Argon2id memory size in KiB.
+    pub const MIN_ARGON2_MEMORY: u32 = 8 * 1024;
+
+    /// The maximal Argon2id memory size in KiB.
+    pub const MAX_ARGON2_MEMORY: u32 = 4 * 1024 * 1024;
+
+    /// The maximal number of Argon2id iterations.
+    pub const MAX_ARGON2_ITERATIONS: u32 = 64;
+
+    /// The maximal Argon2id degree of parallelism.
+    pub const MAX_ARGON2_PARALLELISM: u32 = 16;
+
+    /// The recommended Argon2id memory size in KiB.
+    pub const DEFAULT_ARGON2_MEMORY: u32 = 64 * 1024;
+
+    /// The recommended number of Argon2id iterations.
+    pub const DEFAULT_ARGON2_ITERATIONS: u32 = 3;
+
+    /// The recommended Argon2id degree of parallelism.
+    pub const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+
+    /// Creates scrypt parameters with the given number of iterations. This will produce
+    /// [Error::InvalidKdfParams](../error/enum.Error.html#variant.InvalidKdfParams) if the number
+    /// of iterations isn't a power of two or outside the supported range.
+    pub fn scrypt(cost: u64) -> Result<KdfParams, Error> {
+        let params = KdfParams::Scrypt { cost };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Creates Argon2id parameters. This will produce
+    /// [Error::InvalidKdfParams](../error/enum.Error.html#variant.InvalidKdfParams) if any of the
+    /// values is outside the supported range.
+    pub fn argon2id(memory: u32, iterations: u32, parallelism: u32) -> Result<KdfParams, Error> {
+        let params = KdfParams::Argon2id {
+            memory,
+            iterations,
+            parallelism,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Creates the recommended Argon2id parameters.
+    pub fn argon2id_default() -> KdfParams {
+        KdfParams::Argon2id {
+            memory: Self::DEFAULT_ARGON2_MEMORY,
+            iterations: Self::DEFAULT_ARGON2_ITERATIONS,
+            parallelism: Self::DEFAULT_ARGON2_PARALLELISM,
+        }
+    }
+
+    /// Retrieves the name of the algorithm.
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            KdfParams::Scrypt { .. } => "scrypt",
+            KdfParams::Argon2id { .. } => "argon2id",
+        }
+    }
+
+    /// Retrieves the number of iterations.
+    pub fn cost(&self) -> u64 {
+        match self {
+            KdfParams::Scrypt { cost } => *cost,
+            KdfParams::Argon2id { iterations, .. } => u64::from(*iterations),
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        match self {
+            KdfParams::Scrypt { cost } => {
+                if cost.is_power_of_two()
+                    && (Self::MIN_SCRYPT_COST..=Self::MAX_SCRYPT_COST).contains(cost)
+                {
+                    Ok(())
+                } else {
+                    Err(Error::InvalidKdfParams)
+                }
+            }
+            KdfParams::Argon2id {
+                memory,
+                iterations,
+                parallelism,
+            } => {
+                if (1..=Self::MAX_ARGON2_PARALLELISM).contains(parallelism)
+                    && (1..=Self::MAX_ARGON2_ITERATIONS).contains(iterations)
+                    && (Self::MIN_ARGON2_MEMORY..=Self::MAX_ARGON2_MEMORY).contains(memory)
+                {
+                    Ok(())
+                } else {
+                    Err(Error::InvalidKdfParams)
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for KdfParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KdfParams::Scrypt { cost } => write!(
+                f,
+                "scrypt (N = {}, r = {}, p = {})",
+                cost,
+                crypto::SCRYPT_R,
+                crypto::SCRYPT_P
+            ),
+            KdfParams::Argon2id {
+                memory,
+                iterations,
+                parallelism,
+            } => write!(
+                f,
+                "argon2id (m = {} KiB, t = {}, p = {})",
+                memory, iterations, parallelism
+            ),
+        }
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams::Scrypt {
+            cost: 1 << crypto::SCRYPT_LOG_N,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn validation() {
+        assert_eq!(
+            KdfParams::scrypt(1 << 16).expect("Parameters should be valid"),
+            KdfParams::Scrypt { cost: 65536 }
+        );
+        assert!(matches!(
+            KdfParams::scrypt(50000),
+            Err(Error::InvalidKdfParams)
+        ));
+        assert!(matches!(
+            KdfParams::scrypt(1 << 8),
+            Err(Error::InvalidKdfParams)
+        ));
+        assert!(matches!(
+            KdfParams::scrypt(1 << 24),
+            Err(Error::InvalidKdfParams)
+        ));
+
+        assert_eq!(
+            KdfParams::argon2id(65536, 3, 1).expect("Parameters should be valid"),
+            KdfParams::argon2id_default()
+        );
+        assert!(matches!(
+            KdfParams::argon2id(1024, 3, 1),
+            Err(Error::InvalidKdfParams)
+        ));
+        assert!(matches!(
+            KdfParams::argon2id(65536, 0, 1),
+            Err(Error::InvalidKdfParams)
+        ));
+        assert!(matches!(
+            KdfParams::argon2id(65536, 3, 0),
+            Err(Error::InvalidKdfParams)
+        ));
+    }
+
+    #[test]
+    fn serialization() {
+        let params = KdfParams::Scrypt { cost: 65536 };
+        let serialized = json::to_string(&params).expect("Serialization should succeed");
+        assert_eq!(serialized, r#"{"algorithm":"scrypt","cost":65536}"#);
+        assert_eq!(
+            json::from_str::<KdfParams>(&serialized).expect("Deserialization should succeed"),
+            params
+        );
+        assert!(json::from_str::<KdfParams>(r#"{"algorithm":"bcrypt","cost":10}"#).is_err());
+
+        let params = KdfParams::argon2id_default();
+        let serialized = json::to_string(&params).expect("Serialization should succeed");
+        assert_eq!(
+            serialized,
+            r#"{"algorithm":"argon2id","memory":65536,"iterations":3,"parallelism":1}"#
+        );
+        assert_eq!(
+            json::from_str::<KdfParams>(&serialized).expect("Deserialization should succeed"),
+            params
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            KdfParams::default().to_string(),
+            "scrypt (N = 32768, r = 8, p = 1)"
+        );
+        assert_eq!(
+            KdfParams::argon2id_default().to_string(),
+            "argon2id (m = 65536 KiB, t = 3, p = 1)"
+        );
+    }
+}
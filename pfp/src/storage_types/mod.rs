@@ -9,8 +9,11 @@
 mod character_set;
 pub use character_set::{CharacterSet, CharacterType};
 
+mod kdf_params;
+pub use kdf_params::KdfParams;
+
 mod password;
-pub use password::{GeneratedPassword, Password, PasswordId, StoredPassword};
+pub use password::{GeneratedPassword, Password, PasswordHistoryEntry, PasswordId, StoredPassword};
 
 mod site;
-pub use site::Site;
+pub use site::{PasswordPolicy, Site};
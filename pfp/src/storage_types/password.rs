@@ -5,14 +5,16 @@
  */
 
 use super::CharacterSet;
-use crate::json::{Deserialize, Serialize};
+use crate::error::Error;
+use crate::json::{Deserialize, Serialize, Value};
 use secrecy::{ExposeSecret, SecretString};
+use std::collections::BTreeMap;
 
 fn empty_secret(str: &SecretString) -> bool {
     str.expose_secret().is_empty()
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 /// A password identifier, no two passwords with identical identifiers are allowed in storage.
 pub struct PasswordId {
     site: String,
@@ -51,6 +53,62 @@ impl PasswordId {
     pub fn revision(&self) -> &str {
         &self.revision
     }
+
+    /// Parses a password locator like `example.com/me@2` into a password identifier.
+    ///
+    /// The revision part (`@2`) is optional, revision `"1"` is treated like an empty string as
+    /// usual. A backslash escapes the following character, so `\/`, `\@` and `\\` can be used
+    /// for site or password names containing these characters. Locators without a password name
+    /// will result in
+    /// [Error::InvalidLocator](../error/enum.Error.html#variant.InvalidLocator).
+    pub fn parse(locator: &str) -> Result<PasswordId, Error> {
+        let mut parts = vec![String::new()];
+        let mut chars = locator.chars();
+        while let Some(char) = chars.next() {
+            match char {
+                '\\' => parts
+                    .last_mut()
+                    .unwrap()
+                    .push(chars.next().ok_or(Error::InvalidLocator)?),
+                '/' if parts.len() == 1 => parts.push(String::new()),
+                '@' if parts.len() == 2 => parts.push(String::new()),
+                char => parts.last_mut().unwrap().push(char),
+            }
+        }
+
+        if parts.len() < 2 || parts[0].is_empty() || parts[1].is_empty() {
+            return Err(Error::InvalidLocator);
+        }
+        let revision = parts.get(2).map(|revision| revision.as_str()).unwrap_or("");
+        Ok(PasswordId::new(&parts[0], &parts[1], revision))
+    }
+}
+
+impl TryFrom<&str> for PasswordId {
+    type Error = Error;
+
+    fn try_from(locator: &str) -> Result<Self, Self::Error> {
+        PasswordId::parse(locator)
+    }
+}
+
+impl std::fmt::Display for PasswordId {
+    /// Formats the password identifier as a locator that
+    /// [PasswordId::parse()](#method.parse) will accept.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn escape(value: &str) -> String {
+            value
+                .replace('\\', "\\\\")
+                .replace('/', "\\/")
+                .replace('@', "\\@")
+        }
+
+        write!(f, "{}/{}", escape(&self.site), escape(&self.name))?;
+        if !self.revision.is_empty() {
+            write!(f, "@{}", escape(&self.revision))?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -62,12 +120,22 @@ pub struct GeneratedPassword {
     length: usize,
     #[serde(with = "super::character_set", flatten)]
     charset: CharacterSet,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symbols: Option<String>,
     #[serde(
         skip_serializing_if = "empty_secret",
         default = "crate::json::secret_serialization::default",
         with = "crate::json::secret_serialization"
     )]
     notes: SecretString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(flatten)]
+    extra: BTreeMap<String, Value>,
 }
 
 impl GeneratedPassword {
@@ -84,7 +152,12 @@ impl GeneratedPassword {
             id: PasswordId::new(site, name, revision),
             length,
             charset,
+            symbols: None,
             notes: SecretString::new(String::new()),
+            created: None,
+            modified: None,
+            tags: Vec::new(),
+            extra: BTreeMap::new(),
         }
     }
 
@@ -103,6 +176,27 @@ impl GeneratedPassword {
         self.charset
     }
 
+    /// Retrieves the symbols used instead of the default symbol set if any.
+    pub fn symbols(&self) -> Option<&str> {
+        self.symbols.as_deref()
+    }
+
+    /// Sets the symbols to be used instead of the default symbol set, `None` restores the default.
+    /// This changes the password value if symbols are among the character types used.
+    pub fn set_symbols(&mut self, symbols: Option<String>) {
+        self.symbols = symbols;
+    }
+
+    /// Calculates the password's entropy in bits from its length and the number of characters it
+    /// can be generated from. The password value isn't needed for this.
+    pub fn entropy_bits(&self) -> f64 {
+        let charset_size = crate::crypto::charset_size(self.charset, self.symbols());
+        if charset_size == 0 {
+            return 0.0;
+        }
+        self.length as f64 * (charset_size as f64).log2()
+    }
+
     /// Retrieves the password-specific salt used when deriving data from the primary password for
     /// password generation.
     pub fn salt(&self) -> String {
@@ -125,6 +219,75 @@ impl GeneratedPassword {
     pub fn set_notes(&mut self, notes: SecretString) {
         self.notes = notes;
     }
+
+    /// Retrieves the time the password was added, in seconds since the Unix epoch. This is
+    /// `None` for passwords added by versions that didn't record it.
+    pub fn created(&self) -> Option<u64> {
+        self.created
+    }
+
+    /// Retrieves the time the password was last changed, in seconds since the Unix epoch. This
+    /// is `None` for passwords that weren't changed since timestamps are recorded.
+    pub fn modified(&self) -> Option<u64> {
+        self.modified
+    }
+
+    /// Retrieves the tags assigned to the password, sorted alphabetically.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Sets the tags assigned to the password. Tags are sorted and duplicates removed.
+    pub fn set_tags(&mut self, mut tags: Vec<String>) {
+        tags.sort();
+        tags.dedup();
+        self.tags = tags;
+    }
+
+    /// Sets the creation time, e.g. to keep it when a password is replaced by a new one.
+    pub(crate) fn set_created(&mut self, created: Option<u64>) {
+        self.created = created;
+    }
+
+    /// Marks the password as changed at the given time.
+    pub(crate) fn touch(&mut self, timestamp: u64) {
+        self.modified = Some(timestamp);
+    }
+
+    /// Converts into a stored password with the given value, keeping identifier, notes and any
+    /// unknown fields.
+    pub(crate) fn into_stored(self, password: SecretString) -> StoredPassword {
+        StoredPassword {
+            id: self.id,
+            password,
+            notes: self.notes,
+            created: self.created,
+            modified: self.modified,
+            tags: self.tags,
+            history: Vec::new(),
+            extra: self.extra,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// A previous value of a stored password, kept when the password is overwritten.
+pub struct PasswordHistoryEntry {
+    timestamp: u64,
+    #[serde(with = "crate::json::secret_serialization")]
+    password: SecretString,
+}
+
+impl PasswordHistoryEntry {
+    /// Retrieves the time the value was replaced, in seconds since the Unix epoch.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Retrieves the previous password value.
+    pub fn password(&self) -> &SecretString {
+        &self.password
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -140,6 +303,16 @@ pub struct StoredPassword {
         with = "crate::json::secret_serialization"
     )]
     notes: SecretString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    history: Vec<PasswordHistoryEntry>,
+    #[serde(flatten)]
+    extra: BTreeMap<String, Value>,
 }
 
 impl StoredPassword {
@@ -150,6 +323,11 @@ impl StoredPassword {
             id: PasswordId::new(site, name, revision),
             password,
             notes: SecretString::new(String::new()),
+            created: None,
+            modified: None,
+            tags: Vec::new(),
+            history: Vec::new(),
+            extra: BTreeMap::new(),
         }
     }
 
@@ -163,6 +341,58 @@ impl StoredPassword {
         &self.password
     }
 
+    /// Retrieves the previous values of the password, oldest first.
+    pub fn history(&self) -> &[PasswordHistoryEntry] {
+        &self.history
+    }
+
+    /// Removes all history entries and returns them.
+    pub(crate) fn take_history(&mut self) -> Vec<PasswordHistoryEntry> {
+        std::mem::take(&mut self.history)
+    }
+
+    /// Takes over the history of another password, e.g. the one being overwritten.
+    pub(crate) fn set_history(&mut self, history: Vec<PasswordHistoryEntry>) {
+        self.history = history;
+    }
+
+    /// Removes the history entry with the given index and returns it.
+    pub(crate) fn take_history_entry(&mut self, index: usize) -> Option<PasswordHistoryEntry> {
+        if index < self.history.len() {
+            Some(self.history.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Adds a previous password value to the history. If the history grows beyond `max_length`
+    /// entries, the oldest entries are dropped.
+    pub(crate) fn push_history(
+        &mut self,
+        password: SecretString,
+        timestamp: u64,
+        max_length: usize,
+    ) {
+        self.history.push(PasswordHistoryEntry {
+            timestamp,
+            password,
+        });
+        if self.history.len() > max_length {
+            self.history.drain(..self.history.len() - max_length);
+        }
+    }
+
+    /// Replaces the password value, adding the previous value to the history.
+    pub(crate) fn replace_password(
+        &mut self,
+        password: SecretString,
+        timestamp: u64,
+        max_length: usize,
+    ) {
+        let previous = std::mem::replace(&mut self.password, password);
+        self.push_history(previous, timestamp, max_length);
+    }
+
     /// Retrieves the notes stored with the password if any.
     pub fn notes(&self) -> &SecretString {
         &self.notes
@@ -172,6 +402,40 @@ impl StoredPassword {
     pub fn set_notes(&mut self, notes: SecretString) {
         self.notes = notes;
     }
+
+    /// Retrieves the time the password was added, in seconds since the Unix epoch. This is
+    /// `None` for passwords added by versions that didn't record it.
+    pub fn created(&self) -> Option<u64> {
+        self.created
+    }
+
+    /// Retrieves the time the password was last changed, in seconds since the Unix epoch. This
+    /// is `None` for passwords that weren't changed since timestamps are recorded.
+    pub fn modified(&self) -> Option<u64> {
+        self.modified
+    }
+
+    /// Retrieves the tags assigned to the password, sorted alphabetically.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Sets the tags assigned to the password. Tags are sorted and duplicates removed.
+    pub fn set_tags(&mut self, mut tags: Vec<String>) {
+        tags.sort();
+        tags.dedup();
+        self.tags = tags;
+    }
+
+    /// Sets the creation time, e.g. to keep it when a password is replaced by a new one.
+    pub(crate) fn set_created(&mut self, created: Option<u64>) {
+        self.created = created;
+    }
+
+    /// Marks the password as changed at the given time.
+    pub(crate) fn touch(&mut self, timestamp: u64) {
+        self.modified = Some(timestamp);
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -181,6 +445,11 @@ pub enum Password {
     #[serde(rename = "generated2")]
     /// Contains a generated password
     Generated(GeneratedPassword),
+    #[serde(rename = "generated")]
+    /// Contains a generated password in the legacy format of early PfP versions. The fields are
+    /// identical to the current format but the password is derived via PBKDF2-HMAC-SHA1 rather
+    /// than scrypt, so the same parameters produce a different password value.
+    LegacyGenerated(GeneratedPassword),
     /// Contains a stored password
     #[serde(rename = "stored")]
     Stored(StoredPassword),
@@ -190,15 +459,23 @@ impl Password {
     /// Retrieves the password's identifier.
     pub fn id(&self) -> &PasswordId {
         match self {
-            Self::Generated(password) => password.id(),
+            Self::Generated(password) | Self::LegacyGenerated(password) => password.id(),
             Self::Stored(password) => password.id(),
         }
     }
 
+    /// Changes the password's identifier, keeping all other data.
+    pub(crate) fn set_id(&mut self, id: PasswordId) {
+        match self {
+            Self::Generated(password) | Self::LegacyGenerated(password) => password.id = id,
+            Self::Stored(password) => password.id = id,
+        }
+    }
+
     /// Retrieves the notes stored with the password if any.
     pub fn notes(&self) -> &SecretString {
         match self {
-            Self::Generated(password) => password.notes(),
+            Self::Generated(password) | Self::LegacyGenerated(password) => password.notes(),
             Self::Stored(password) => password.notes(),
         }
     }
@@ -206,8 +483,347 @@ impl Password {
     /// Sets the notes for the password.
     pub fn set_notes(&mut self, notes: SecretString) {
         match self {
-            Self::Generated(password) => password.set_notes(notes),
+            Self::Generated(password) | Self::LegacyGenerated(password) => {
+                password.set_notes(notes)
+            }
             Self::Stored(password) => password.set_notes(notes),
         }
     }
+
+    /// Retrieves the time the password was added if known, in seconds since the Unix epoch.
+    pub fn created(&self) -> Option<u64> {
+        match self {
+            Self::Generated(password) | Self::LegacyGenerated(password) => password.created(),
+            Self::Stored(password) => password.created(),
+        }
+    }
+
+    /// Retrieves the time the password was last changed if known, in seconds since the Unix
+    /// epoch.
+    pub fn modified(&self) -> Option<u64> {
+        match self {
+            Self::Generated(password) | Self::LegacyGenerated(password) => password.modified(),
+            Self::Stored(password) => password.modified(),
+        }
+    }
+
+    /// Retrieves the tags assigned to the password, sorted alphabetically.
+    pub fn tags(&self) -> &[String] {
+        match self {
+            Self::Generated(password) | Self::LegacyGenerated(password) => password.tags(),
+            Self::Stored(password) => password.tags(),
+        }
+    }
+
+    /// Checks whether the password has the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags().iter().any(|existing| existing == tag)
+    }
+
+    /// Sets the tags assigned to the password. Tags are sorted and duplicates removed.
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        match self {
+            Self::Generated(password) | Self::LegacyGenerated(password) => password.set_tags(tags),
+            Self::Stored(password) => password.set_tags(tags),
+        }
+    }
+
+    /// Sets the creation time, e.g. to keep it when a password is replaced by a new one.
+    pub(crate) fn set_created(&mut self, created: Option<u64>) {
+        match self {
+            Self::Generated(password) | Self::LegacyGenerated(password) => {
+                password.set_created(created)
+            }
+            Self::Stored(password) => password.set_created(created),
+        }
+    }
+
+    /// Marks the password as changed at the given time.
+    pub(crate) fn touch(&mut self, timestamp: u64) {
+        match self {
+            Self::Generated(password) | Self::LegacyGenerated(password) => {
+                password.touch(timestamp)
+            }
+            Self::Stored(password) => password.touch(timestamp),
+        }
+    }
+
+    /// Lists the names of fields present in the storage entry that this version doesn't know
+    /// about. These fields are kept as is and written back when the password is saved.
+    pub fn unknown_fields(&self) -> impl Iterator<Item = &str> {
+        let extra = match self {
+            Self::Generated(password) | Self::LegacyGenerated(password) => &password.extra,
+            Self::Stored(password) => &password.extra,
+        };
+        extra.keys().map(|key| key.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn parse_locator() {
+        assert_eq!(
+            PasswordId::parse("example.com/me@2").expect("Parsing should succeed"),
+            PasswordId::new("example.com", "me", "2")
+        );
+        assert_eq!(
+            PasswordId::parse("example.com/me").expect("Parsing should succeed"),
+            PasswordId::new("example.com", "me", "")
+        );
+        assert_eq!(
+            PasswordId::parse("example.com/me@1").expect("Parsing should succeed"),
+            PasswordId::new("example.com", "me", "")
+        );
+        assert_eq!(
+            PasswordId::try_from("example.com/me@work@home").expect("Parsing should succeed"),
+            PasswordId::new("example.com", "me", "work@home")
+        );
+        assert_eq!(
+            PasswordId::parse(r"example.com/me\/you\@home@a\\b").expect("Parsing should succeed"),
+            PasswordId::new("example.com", "me/you@home", r"a\b")
+        );
+
+        for locator in ["", "example.com", "example.com/", "/me", "example.com/me\\"] {
+            assert!(matches!(
+                PasswordId::parse(locator).expect_err("Parsing should fail"),
+                Error::InvalidLocator
+            ));
+        }
+    }
+
+    #[test]
+    fn display_locator() {
+        for (id, locator) in [
+            (
+                PasswordId::new("example.com", "me", "2"),
+                "example.com/me@2",
+            ),
+            (PasswordId::new("example.com", "me", "1"), "example.com/me"),
+            (
+                PasswordId::new("example.com", "me/you@home", r"a\b"),
+                r"example.com/me\/you\@home@a\\b",
+            ),
+        ] {
+            assert_eq!(id.to_string(), locator);
+            assert_eq!(
+                PasswordId::parse(locator).expect("Parsing should succeed"),
+                id
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_fields() {
+        for value in [
+            json::json!({
+                "type": "generated2",
+                "site": "example.com",
+                "name": "me",
+                "revision": "",
+                "length": 16,
+                "lower": true,
+                "upper": false,
+                "number": true,
+                "symbol": false,
+                "favorite": true,
+                "icon": "key",
+            }),
+            json::json!({
+                "type": "stored",
+                "site": "example.com",
+                "name": "me",
+                "revision": "2",
+                "password": "asdf",
+                "notes": "hi there!",
+                "favorite": true,
+                "icon": "key",
+            }),
+        ] {
+            let password: Password =
+                json::from_value(value.clone()).expect("Deserializing should succeed");
+            assert_eq!(
+                password.unknown_fields().collect::<Vec<&str>>(),
+                vec!["favorite", "icon"]
+            );
+
+            let serialized: json::Value =
+                json::from_slice(&json::to_vec(&password).expect("Serializing should succeed"))
+                    .expect("Serialized data should be valid JSON");
+            assert_eq!(serialized, value);
+        }
+
+        let password = Password::Stored(StoredPassword::new(
+            "example.com",
+            "me",
+            "",
+            SecretString::new("asdf".to_owned()),
+        ));
+        assert_eq!(password.unknown_fields().count(), 0);
+    }
+
+    #[test]
+    fn symbols() {
+        let value = json::json!({
+            "type": "generated2",
+            "site": "example.com",
+            "name": "me",
+            "revision": "",
+            "length": 10,
+            "lower": false,
+            "upper": false,
+            "number": false,
+            "symbol": true,
+            "symbols": "!@#$",
+        });
+        let password: Password =
+            json::from_value(value.clone()).expect("Deserializing should succeed");
+        match &password {
+            Password::Generated(password) => {
+                assert_eq!(password.symbols(), Some("!@#$"));
+                assert_eq!(password.entropy_bits(), 20.0);
+            }
+            _ => panic!("Unexpected password type"),
+        }
+        assert_eq!(password.unknown_fields().count(), 0);
+
+        let serialized: json::Value =
+            json::from_slice(&json::to_vec(&password).expect("Serializing should succeed"))
+                .expect("Serialized data should be valid JSON");
+        assert_eq!(serialized, value);
+
+        let mut password = GeneratedPassword::new("example.com", "me", "", 16, CharacterSet::all());
+        assert_eq!(password.symbols(), None);
+        password.set_symbols(Some("-_".to_string()));
+        assert_eq!(password.symbols(), Some("-_"));
+    }
+
+    #[test]
+    fn timestamps() {
+        let value = json::json!({
+            "type": "generated2",
+            "site": "example.com",
+            "name": "me",
+            "revision": "",
+            "length": 16,
+            "lower": true,
+            "upper": false,
+            "number": true,
+            "symbol": false,
+            "created": 1000,
+            "modified": 2000,
+        });
+        let mut password: Password =
+            json::from_value(value.clone()).expect("Deserializing should succeed");
+        assert_eq!(password.created(), Some(1000));
+        assert_eq!(password.modified(), Some(2000));
+        assert_eq!(password.unknown_fields().count(), 0);
+        let serialized: json::Value =
+            json::from_slice(&json::to_vec(&password).expect("Serializing should succeed"))
+                .expect("Serialized data should be valid JSON");
+        assert_eq!(serialized, value);
+
+        password.touch(3000);
+        assert_eq!(password.created(), Some(1000));
+        assert_eq!(password.modified(), Some(3000));
+
+        let password = StoredPassword::new("example.com", "me", "", SecretString::new("x".into()));
+        assert_eq!(password.created(), None);
+        assert_eq!(password.modified(), None);
+        let serialized: json::Value = json::from_slice(
+            &json::to_vec(&Password::Stored(password)).expect("Serializing should succeed"),
+        )
+        .expect("Serialized data should be valid JSON");
+        assert!(serialized.get("created").is_none());
+        assert!(serialized.get("modified").is_none());
+    }
+
+    #[test]
+    fn tags() {
+        let value = json::json!({
+            "type": "stored",
+            "site": "example.com",
+            "name": "me",
+            "revision": "",
+            "password": "asdf",
+            "tags": ["mail", "work"],
+        });
+        let mut password: Password =
+            json::from_value(value.clone()).expect("Deserializing should succeed");
+        assert_eq!(password.tags(), ["mail", "work"]);
+        assert!(password.has_tag("work"));
+        assert!(!password.has_tag("private"));
+        assert_eq!(password.unknown_fields().count(), 0);
+        let serialized: json::Value =
+            json::from_slice(&json::to_vec(&password).expect("Serializing should succeed"))
+                .expect("Serialized data should be valid JSON");
+        assert_eq!(serialized, value);
+
+        password.set_tags(vec![
+            "work".to_string(),
+            "a".to_string(),
+            "work".to_string(),
+        ]);
+        assert_eq!(password.tags(), ["a", "work"]);
+        password.set_tags(Vec::new());
+        let serialized: json::Value =
+            json::from_slice(&json::to_vec(&password).expect("Serializing should succeed"))
+                .expect("Serialized data should be valid JSON");
+        assert!(serialized.get("tags").is_none());
+    }
+
+    #[test]
+    fn history() {
+        let value = json::json!({
+            "type": "stored",
+            "site": "example.com",
+            "name": "me",
+            "revision": "",
+            "password": "asdf",
+            "history": [
+                {"timestamp": 1000, "password": "old"},
+                {"timestamp": 2000, "password": "older"},
+            ],
+        });
+        let password: Password =
+            json::from_value(value.clone()).expect("Deserializing should succeed");
+        assert_eq!(password.unknown_fields().count(), 0);
+        let serialized: json::Value =
+            json::from_slice(&json::to_vec(&password).expect("Serializing should succeed"))
+                .expect("Serialized data should be valid JSON");
+        assert_eq!(serialized, value);
+
+        let mut password = match password {
+            Password::Stored(password) => password,
+            _ => panic!("Unexpected password type"),
+        };
+        password.replace_password(SecretString::new("new".to_string()), 3000, 2);
+        assert_eq!(password.password().expose_secret(), "new");
+        assert_eq!(
+            password
+                .history()
+                .iter()
+                .map(|entry| (entry.timestamp(), entry.password().expose_secret().as_str()))
+                .collect::<Vec<_>>(),
+            vec![(2000, "older"), (3000, "asdf")]
+        );
+    }
+
+    #[test]
+    fn entropy() {
+        use crate::storage_types::CharacterType;
+
+        let password = GeneratedPassword::new("example.com", "me", "", 16, CharacterSet::all());
+        assert_eq!(password.entropy_bits().round(), 102.0);
+
+        let password =
+            GeneratedPassword::new("example.com", "me", "", 10, CharacterType::Digit.into());
+        assert_eq!(password.entropy_bits(), 30.0);
+
+        let password = GeneratedPassword::new("example.com", "me", "", 10, CharacterSet::empty());
+        assert_eq!(password.entropy_bits(), 0.0);
+    }
 }
@@ -4,16 +4,94 @@
  * http://mozilla.org/MPL/2.0/.
  */
 
-use crate::json::{Deserialize, Serialize};
+use super::CharacterSet;
+use crate::json::{Deserialize, Serialize, Value};
+use std::collections::BTreeMap;
 
-#[derive(Serialize, Deserialize, Debug)]
+fn all_characters() -> CharacterSet {
+    CharacterSet::all()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
+/// Password requirements of a website, used as defaults and constraints when adding passwords.
+pub struct PasswordPolicy {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    min_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    max_length: Option<usize>,
+    #[serde(with = "super::character_set", default = "all_characters")]
+    charset: CharacterSet,
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    forbidden: String,
+}
+
+impl PasswordPolicy {
+    /// Creates a new password policy from minimal and maximal password length, the character types
+    /// allowed and a list of characters that passwords shouldn't contain.
+    pub fn new(
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        charset: CharacterSet,
+        forbidden: &str,
+    ) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length,
+            max_length,
+            charset,
+            forbidden: forbidden.to_string(),
+        }
+    }
+
+    /// Retrieves the minimal password length if any.
+    pub fn min_length(&self) -> Option<usize> {
+        self.min_length
+    }
+
+    /// Retrieves the maximal password length if any.
+    pub fn max_length(&self) -> Option<usize> {
+        self.max_length
+    }
+
+    /// Retrieves the character types passwords are allowed to use.
+    pub fn charset(&self) -> CharacterSet {
+        self.charset
+    }
+
+    /// Retrieves the characters that passwords shouldn't contain.
+    pub fn forbidden(&self) -> &str {
+        &self.forbidden
+    }
+
+    /// Returns the password length closest to `length` that satisfies the policy.
+    pub fn clamp_length(&self, length: usize) -> usize {
+        let mut result = length;
+        if let Some(max_length) = self.max_length {
+            result = result.min(max_length);
+        }
+        if let Some(min_length) = self.min_length {
+            result = result.max(min_length);
+        }
+        result
+    }
+
+    /// Checks whether a password value contains any of the forbidden characters.
+    pub fn has_forbidden(&self, password: &str) -> bool {
+        password.chars().any(|c| self.forbidden.contains(c))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 /// A website entry in storage.
 pub struct Site {
     #[serde(rename = "site")]
     name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     alias: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    policy: Option<PasswordPolicy>,
+    #[serde(flatten)]
+    extra: BTreeMap<String, Value>,
 }
 
 impl Site {
@@ -22,6 +100,8 @@ impl Site {
         Site {
             name: name.to_string(),
             alias: alias.map(|alias| alias.to_string()),
+            policy: None,
+            extra: BTreeMap::new(),
         }
     }
 
@@ -37,4 +117,57 @@ impl Site {
             None => None,
         }
     }
+
+    /// Retrieves the website's password policy if any.
+    pub fn policy(&self) -> Option<&PasswordPolicy> {
+        self.policy.as_ref()
+    }
+
+    /// Sets or removes the website's password policy.
+    pub fn set_policy(&mut self, policy: Option<PasswordPolicy>) {
+        self.policy = policy;
+    }
+
+    /// Lists the names of fields present in the storage entry that this version doesn't know
+    /// about. These fields are kept as is and written back when the site is saved.
+    pub fn unknown_fields(&self) -> impl Iterator<Item = &str> {
+        self.extra.keys().map(|key| key.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn unknown_fields() {
+        let value = json::json!({
+            "site": "example.com",
+            "policy": {
+                "min_length": 8,
+                "charset": {
+                    "lower": true,
+                    "upper": true,
+                    "number": true,
+                    "symbol": false,
+                },
+            },
+            "blocked": true,
+            "notes": ["a", "b"],
+        });
+        let site: Site = json::from_value(value.clone()).expect("Deserializing should succeed");
+        assert_eq!(
+            site.unknown_fields().collect::<Vec<&str>>(),
+            vec!["blocked", "notes"]
+        );
+
+        let serialized: json::Value =
+            json::from_slice(&json::to_vec(&site).expect("Serializing should succeed"))
+                .expect("Serialized data should be valid JSON");
+        assert_eq!(serialized, value);
+
+        let site = Site::new("example.com", Some("example.info"));
+        assert_eq!(site.unknown_fields().count(), 0);
+    }
 }